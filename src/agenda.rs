@@ -0,0 +1,74 @@
+// Plain-text agenda listing used by the terminal-facing commands.
+use chrono::NaiveDate;
+use regex::Regex;
+use crate::text::pad_to_width;
+use crate::{cmp_tasks, time, Task};
+
+const TIME_COLUMN: usize = 20;
+
+fn format_times(task: &Task) -> String {
+    if let (Some(start), Some(span)) = (task.start_time, task.span) {
+        return format!("{} -- {} {}", time::format_time(start), time::format_short_date(span.last), time::format_time(span.end));
+    }
+    return match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => format!("{} -- {}", time::format_time(start), time::format_time(end)),
+        _ if task.all_day => "All day".to_string(),
+        _ => "".to_string(),
+    };
+}
+
+// Lists the given tasks grouped under a line per date, one task per line with
+// its time range, details, and tags.
+pub fn format_agenda(tasks: &[&Task]) -> String {
+    let mut sorted: Vec<&Task> = tasks.to_vec();
+    sorted.sort_by(|a, b| cmp_tasks(a, b));
+    let mut out = String::new();
+    let mut last_date = None;
+    for task in sorted {
+        if last_date != Some(task.date) {
+            out.push_str(&time::format_date(task.date));
+            out.push('\n');
+            last_date = Some(task.date);
+        }
+        let mut line = format!("  {}{}", pad_to_width(&format_times(task), TIME_COLUMN), task.details);
+        for tag in &task.tags {
+            line.push_str(" +");
+            line.push_str(tag);
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    return out;
+}
+
+// What `wtd list` looks for. Every part given must match.
+#[derive(Default)]
+pub struct Filter {
+    // Tags the task must all carry, with or without the '+'.
+    pub tags: Vec<String>,
+    // The first and last days, inclusive; a task spanning days matches if
+    // any of its days are between them.
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    // Found in the task's description, notes, checklist, or location.
+    pub grep: Option<Regex>,
+}
+
+impl Filter {
+    pub fn matches(&self, task: &Task) -> bool {
+        let last = task.span.map_or(task.date, |span| span.last);
+        if self.from.is_some_and(|from| last < from) || self.to.is_some_and(|to| task.date > to) {
+            return false;
+        }
+        if !self.tags.iter().all(|wanted| task.tags.iter().any(|tag| tag == wanted.trim_start_matches('+'))) {
+            return false;
+        }
+        return match &self.grep {
+            Some(pattern) => pattern.is_match(&task.details)
+                || task.notes.iter().any(|paragraph| pattern.is_match(paragraph))
+                || task.subtasks.iter().any(|subtask| pattern.is_match(&subtask.text))
+                || task.location.as_ref().is_some_and(|place| pattern.is_match(place)),
+            None => true,
+        };
+    }
+}
@@ -0,0 +1,63 @@
+// Files attached to tasks with '+file:PATH' tokens. Paths are relative to the
+// directory holding wtd.md, and are copied to the same relative path next to
+// the written calendars so the links in the task details keep working when
+// the output directory is published on its own.
+use std::path::{Component, Path};
+use crate::staged::Staged;
+use crate::{CalendarPrivacy, Task};
+
+// Attachments must stay inside the wtd.md directory, so that publishing can't
+// be tricked into copying arbitrary files next to the calendar.
+fn is_contained(path: &Path) -> bool {
+    return path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+}
+
+// Whether a path or URL names an image, ignoring any query or fragment.
+pub fn is_image(path: &str) -> bool {
+    let path = path.split(['?', '#']).next().unwrap_or("").to_lowercase();
+    return [".png", ".jpg", ".jpeg", ".gif", ".webp", ".svg"].iter().any(|ext| path.ends_with(ext));
+}
+
+// Drops (with a warning) attachments that are missing or point outside the
+// input directory.
+pub fn check(tasks: &mut [Task], input_dir: &Path) {
+    for task in tasks.iter_mut() {
+        let date = task.date;
+        task.attachments.retain(|attachment| {
+            let path = Path::new(attachment);
+            if !is_contained(path) {
+                eprintln!("Ignoring attachment {} on {}: it must be a relative path inside the wtd.md directory",
+                          attachment, date);
+                return false;
+            }
+            if !input_dir.join(path).is_file() {
+                eprintln!("Ignoring attachment {} on {}: no such file", attachment, date);
+                return false;
+            }
+            return true;
+        });
+    }
+}
+
+// Whether a task's attachments appear on the given calendar.
+pub fn visible(task: &Task, privacy: &CalendarPrivacy) -> bool {
+    return match privacy {
+        CalendarPrivacy::Public => task.tags.iter().any(|t| t == "public"),
+        CalendarPrivacy::Private => true,
+    };
+}
+
+// Stages copies of the attachments shown on a calendar in its output
+// directory; files already up to date are left alone when it's committed.
+pub fn publish(tasks: &[Task], input_dir: &Path, output_dir: &Path, privacy: &CalendarPrivacy,
+               staged: &mut Staged) -> Result<(), String> {
+    for task in tasks.iter().filter(|t| visible(t, privacy)) {
+        for attachment in &task.attachments {
+            let source = input_dir.join(attachment);
+            let contents = std::fs::read(&source)
+                .map_err(|why| format!("Couldn't read {}: {}", source.display(), why))?;
+            staged.write(&output_dir.join(attachment), contents);
+        }
+    }
+    return Ok(());
+}
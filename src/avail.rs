@@ -0,0 +1,162 @@
+// `wtd avail`: the free stretches of the working hours over the next few
+// working days, written to paste into a reply to "when are you free?", e.g.
+//
+//   Tue 3/19: free 10–12, 2–4
+//   Wed 3/20: free after 3
+//
+// Weekends and days out of office are skipped, and so is the part of today
+// that has already gone by. Times are on the 12-hour clock without AM or PM,
+// as they'd be written in an email about working hours. For someone in
+// another timezone they can be given in theirs instead (or as well), with AM
+// and PM, since their side of your working day may well be the night:
+//
+//   Wed 3/20: free 2AM–4AM, 6AM–8AM JST
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Weekday};
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+use crate::protect::free_stretches;
+use crate::{is_out_of_office, split_days, time, OutOfOffice, Task};
+
+pub struct AvailOptions {
+    // Working days listed.
+    pub days: usize,
+    // Working hours as (start, end) in 24-hour time.
+    pub work_hours: (u32, u32),
+    // Free stretches shorter than this aren't offered.
+    pub min_free: Duration,
+}
+
+pub struct Availability {
+    pub date: NaiveDate,
+    pub free: Vec<(NaiveTime, NaiveTime)>,
+}
+
+// The free stretches of each of the next `options.days` working days from
+// `now`'s, today's starting from the next quarter hour.
+pub fn availability(tasks: &[Task], out_of_office: &[OutOfOffice], now: NaiveDateTime,
+                    options: &AvailOptions) -> Vec<Availability> {
+    let by_day = split_days(tasks);
+    let next_quarter = (now.time().num_seconds_from_midnight() as i64 / 60 + 14) / 15 * 15;
+    let mut days = Vec::new();
+    // Out of office for over a year means there's nothing to offer.
+    for date in (0..366).map(|offset| now.date() + Duration::days(offset)) {
+        if days.len() >= options.days {
+            break;
+        }
+        if matches!(date.weekday(), Weekday::Sat | Weekday::Sun) || is_out_of_office(out_of_office, date) {
+            continue;
+        }
+        let mut free = free_stretches(&by_day, date, options.work_hours);
+        if date == now.date() {
+            // After 11:45PM, the next quarter hour is tomorrow.
+            let from = time::time_of_day(next_quarter);
+            free = free.into_iter()
+                .filter(|_| next_quarter < 24 * 60)
+                .map(|(start, end)| (start.max(from), end))
+                .filter(|(start, end)| start < end)
+                .collect();
+        }
+        free.retain(|(start, end)| *end - *start >= options.min_free);
+        days.push(Availability { date, free });
+    }
+    return days;
+}
+
+fn short_time(t: NaiveTime) -> String {
+    // Working hours ending at midnight end at the end of the day.
+    if t == time::end_of_day() {
+        return "12".to_string();
+    }
+    let (_, hour) = t.hour12();
+    return if t.minute() == 0 { hour.to_string() } else { format!("{}:{:02}", hour, t.minute()) };
+}
+
+// Whose clock the text is written on. Converting to theirs needs mine, the
+// timezone the task file's times are in.
+#[derive(Clone, Copy)]
+pub enum Zones {
+    Mine,
+    Theirs { mine: Tz, theirs: Tz },
+    // Mine, with theirs after each day.
+    Both { mine: Tz, theirs: Tz },
+}
+
+// A free stretch's start and end on someone else's clock.
+type TheirStretch = (DateTime<Tz>, DateTime<Tz>);
+
+// A free stretch on clocks in `mine` as it reads on clocks in `theirs`.
+fn convert(date: NaiveDate, (start, end): (NaiveTime, NaiveTime), mine: Tz, theirs: Tz) -> TheirStretch {
+    // Free until the end of the day is free until midnight.
+    let end = match end == time::end_of_day() {
+        true => time::in_timezone(mine, date + Duration::days(1), time::midnight(), theirs),
+        false => time::in_timezone(mine, date, end, theirs),
+    };
+    return (time::in_timezone(mine, date, start, theirs), end);
+}
+
+// The stretches of `days` on clocks in `theirs`, grouped by the day they
+// start on there, as e.g. "Wed 3/20: free 2AM–4AM, 6AM–8AM JST".
+fn their_days(days: &[&Availability], mine: Tz, theirs: Tz) -> Vec<String> {
+    let mut grouped: Vec<(NaiveDate, Vec<TheirStretch>)> = Vec::new();
+    for day in days {
+        for stretch in &day.free {
+            let (start, end) = convert(day.date, *stretch, mine, theirs);
+            match grouped.last_mut() {
+                Some((date, stretches)) if *date == start.date_naive() => stretches.push((start, end)),
+                _ => grouped.push((start.date_naive(), vec![(start, end)])),
+            }
+        }
+    }
+    return grouped.iter().map(|(date, stretches)| {
+        let free: Vec<String> = stretches.iter()
+            .map(|(start, end)| format!("{}–{}", time::format_compact_time(start.time()), time::format_compact_time(end.time())))
+            .collect();
+        return format!("{}: free {} {}", time::format_short_day(*date), free.join(", "), time::format_zone(&stretches[0].0));
+    }).collect();
+}
+
+// A line per day, e.g. "Tue 3/19: free 10–12, 2–4". On their clock alone,
+// days without a free stretch are left out, as theirs needn't line up with
+// mine.
+pub fn format_text(days: &[Availability], work_hours: (u32, u32), zones: Zones) -> String {
+    if let Zones::Theirs { mine, theirs } = zones {
+        let days: Vec<&Availability> = days.iter().collect();
+        return their_days(&days, mine, theirs).iter().map(|line| format!("{}\n", line)).collect();
+    }
+    let day_start = time::time_of_day(work_hours.0 as i64 * 60);
+    let day_end = time::hms(work_hours.1, 0, 0).unwrap_or_else(time::end_of_day);
+    let mut out = String::new();
+    for day in days {
+        let stretches: Vec<String> = day.free.iter().map(|(start, end)| match (*start == day_start, *end == day_end) {
+            (true, true) => "all day".to_string(),
+            (true, false) => format!("until {}", short_time(*end)),
+            (false, true) => format!("after {}", short_time(*start)),
+            (false, false) => format!("{}–{}", short_time(*start), short_time(*end)),
+        }).collect();
+        let free = if stretches.is_empty() { "busy".to_string() } else { format!("free {}", stretches.join(", ")) };
+        out.push_str(&format!("{}: {}", time::format_short_day(day.date), free));
+        if let (Zones::Both { mine, theirs }, false) = (zones, stretches.is_empty()) {
+            // "(your Wed 3/20: free 2AM–4AM JST)"
+            out.push_str(&format!(" (your {})", their_days(&[day], mine, theirs).join("; your ")));
+        }
+        out.push('\n');
+    }
+    return out;
+}
+
+// Unless `zones` is Mine, each stretch also has their_start and their_end:
+// the date and time it starts and ends on their clock.
+pub fn to_json(days: &[Availability], zones: Zones) -> Value {
+    return Value::Array(days.iter().map(|day| json!({
+        "date": time::format_iso_date(day.date),
+        "free": day.free.iter().map(|stretch| {
+            let mut free = json!({ "start": time::format_hhmm(stretch.0), "end": time::format_hhmm(stretch.1) });
+            if let Zones::Theirs { mine, theirs } | Zones::Both { mine, theirs } = zones {
+                let (start, end) = convert(day.date, *stretch, mine, theirs);
+                free["their_start"] = json!(time::format_iso_datetime(start.naive_local()));
+                free["their_end"] = json!(time::format_iso_datetime(end.naive_local()));
+            }
+            return free;
+        }).collect::<Vec<Value>>(),
+    })).collect());
+}
@@ -0,0 +1,113 @@
+// `wtd bench --synthetic N`: times each phase of turning a task file into
+// output, over a generated schedule of N tasks, so that a change that slows
+// the parser or the grid down shows up before it ships. The schedule is the
+// same on every run for a given N, so timings can be compared across builds.
+use std::hint::black_box;
+use std::time::{Duration as StdDuration, Instant};
+use chrono::{Duration, NaiveDate};
+use crate::{format, ics, json, parse_notes, parse_tasks, render_html, time, CalendarPrivacy, ParseOptions, RenderOptions, View};
+
+pub struct BenchOptions {
+    // Tasks in the generated schedule.
+    pub tasks: usize,
+    // Times each phase is run; the fastest is reported.
+    pub runs: usize,
+}
+
+const DAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+const TAGS: [&str; 6] = ["", " +busy", " +public", " +self +tentative", " +meetings &4", " +rough +public"];
+const TASKS_PER_DAY: usize = 8;
+
+// A small linear congruential generator; the schedule only has to look
+// varied, and has to be the same every time.
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, n: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        return (self.0 >> 33) % n;
+    }
+}
+
+fn first_day() -> NaiveDate {
+    return time::ymd(2024, 1, 1).expect("a Monday");
+}
+
+// A task file of `tasks` tasks, TASKS_PER_DAY a day from the first Monday of
+// 2024, with the mix of times, tags, checklists, and notes of a real one.
+pub fn synthetic_schedule(tasks: usize) -> String {
+    let mut random = Random(tasks as u64);
+    let mut out = String::from("A generated schedule for `wtd bench`.\n\n");
+    let mut written = 0;
+    let mut date = first_day();
+    while written < tasks {
+        out.push_str(&format!("# {}\nWeek {} notes.\n", time::format_header_date(date), written / (7 * TASKS_PER_DAY) + 1));
+        for day in DAYS.iter() {
+            out.push_str(&format!("## {}\n", day));
+            for _ in 0..TASKS_PER_DAY.min(tasks - written) {
+                let done = if random.below(3) == 0 { "X" } else { " " };
+                let tags = TAGS[random.below(TAGS.len() as u64) as usize];
+                let when = match random.below(8) {
+                    0 => String::new(),
+                    _ => {
+                        let hour = 7 + random.below(13);
+                        let minute = 15 * random.below(4);
+                        let length = 15 * (1 + random.below(8));
+                        let (hour, half) = if hour < 12 { (hour, "AM") } else { (if hour == 12 { 12 } else { hour - 12 }, "PM") };
+                        format!(" @{}:{:02}{}+{}m", hour, minute, half, length)
+                    },
+                };
+                out.push_str(&format!("- [{}] Task {} with a description of ordinary length{}{}\n", done, written + 1, when, tags));
+                if random.below(5) == 0 {
+                    out.push_str("  - [x] Prepare\n  - [ ] Follow up\n");
+                }
+                written += 1;
+            }
+        }
+        date += Duration::weeks(1);
+    }
+    return out;
+}
+
+fn fastest(runs: usize, mut phase: impl FnMut()) -> StdDuration {
+    return (0..runs.max(1)).map(|_| {
+        let start = Instant::now();
+        phase();
+        return start.elapsed();
+    }).min().unwrap_or_default();
+}
+
+pub fn run(options: &BenchOptions) -> Result<String, String> {
+    let source = synthetic_schedule(options.tasks);
+    let tasks = parse_tasks(&source).map_err(|errors| format!("the generated schedule didn't parse: {}", errors[0]))?;
+    let notes = parse_notes(&source);
+    let grid = RenderOptions { start: Some(first_day()), days: 365, ..RenderOptions::default() };
+    let compare = RenderOptions { start: Some(first_day() + Duration::weeks(8)), view: View::Compare, weeks: 8, ..RenderOptions::default() };
+
+    let runs = options.runs;
+    let phases: Vec<(&str, StdDuration)> = vec![
+        ("generate", fastest(runs, || { black_box(synthetic_schedule(options.tasks)); })),
+        ("parse", fastest(runs, || { black_box(parse_tasks(&source).ok()); })),
+        ("parse notes", fastest(runs, || { black_box(parse_notes(&source)); })),
+        ("fmt", fastest(runs, || { black_box(format::format_source(&source, &ParseOptions::default()).ok()); })),
+        ("render grid, 365 days", fastest(runs, || {
+            black_box(render_html(&tasks, &notes, CalendarPrivacy::Private, &grid));
+        })),
+        ("render compare, 8 weeks", fastest(runs, || {
+            black_box(render_html(&tasks, &notes, CalendarPrivacy::Public, &compare));
+        })),
+        ("export ics", fastest(runs, || {
+            black_box(ics::tasks_to_ics(&tasks, &notes.out_of_office, ics::ShowAs::Busy, &grid.public_tags));
+        })),
+        ("export json", fastest(runs, || { black_box(json::tasks_to_json(&tasks, true, &grid.public_tags).to_string()); })),
+    ];
+
+    let mut out = match runs {
+        0 | 1 => format!("{} tasks ({} KB), one run:\n", tasks.len(), source.len() / 1024),
+        runs => format!("{} tasks ({} KB), fastest of {} runs:\n", tasks.len(), source.len() / 1024, runs),
+    };
+    for (name, elapsed) in phases {
+        out.push_str(&format!("  {:<24} {:>9.2} ms\n", name, elapsed.as_secs_f64() * 1000.0));
+    }
+    return Ok(out);
+}
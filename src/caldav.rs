@@ -0,0 +1,174 @@
+// Just enough of CalDAV (RFC 4791) for `wtd sync`: listing the events in a
+// calendar collection with their ETags, and getting, putting and deleting
+// single events. Multistatus responses are picked apart with string searches
+// rather than a full XML parser, since only <href> and <getetag> are needed.
+use base64::Engine;
+use ureq::http;
+
+pub struct Resource {
+    // As the server gives it, usually an absolute path.
+    pub href: String,
+    pub etag: String,
+}
+
+pub struct Client {
+    agent: ureq::Agent,
+    // The calendar collection, ending in '/'.
+    url: String,
+    authorization: Option<String>,
+}
+
+struct Response {
+    status: u16,
+    etag: Option<String>,
+    body: String,
+}
+
+fn unescape_xml(s: &str) -> String {
+    return s.replace("&quot;", "\"").replace("&apos;", "'").replace("&lt;", "<").replace("&gt;", ">")
+        .replace("&amp;", "&");
+}
+
+// The contents of every element named `name`, whatever its namespace prefix.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let tag_end = match rest.find('>') {
+            Some(tag_end) => tag_end,
+            None => break,
+        };
+        let tag = &rest[..tag_end];
+        let tag_name = tag.split_whitespace().next().unwrap_or("");
+        let local = tag_name.rsplit(':').next().unwrap_or("");
+        if tag.starts_with('/') || tag.ends_with('/') || local != name {
+            continue;
+        }
+        let contents = &rest[tag_end + 1..];
+        let close = format!("</{}>", tag_name);
+        if let Some(end) = contents.find(&close) {
+            found.push(&contents[..end]);
+            rest = &contents[end + close.len()..];
+        }
+    }
+    return found;
+}
+
+impl Client {
+    pub fn new(url: &str, username: Option<&str>, password: Option<&str>) -> Client {
+        let config = ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .allow_non_standard_methods(true)
+            .build();
+        let authorization = username.map(|username| {
+            let credentials = format!("{}:{}", username, password.unwrap_or(""));
+            return format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(credentials));
+        });
+        return Client {
+            agent: config.new_agent(),
+            url: format!("{}/", url.trim_end_matches('/')),
+            authorization,
+        };
+    }
+
+    // Where a resource of the collection named `name` lives.
+    pub fn href_for(&self, name: &str) -> String {
+        let path = self.url.split_once("://").and_then(|(_, rest)| rest.find('/').map(|i| &rest[i..]));
+        return format!("{}{}", path.unwrap_or("/"), name);
+    }
+
+    fn url_for(&self, href: &str) -> String {
+        if href.contains("://") {
+            return href.to_string();
+        }
+        let origin_end = self.url.find("://").map(|i| i + 3)
+            .and_then(|start| self.url[start..].find('/').map(|i| start + i))
+            .unwrap_or(self.url.len());
+        return format!("{}{}", &self.url[..origin_end], href);
+    }
+
+    fn request(&self, method: &str, url: &str, headers: &[(&str, &str)], body: &str) -> Result<Response, String> {
+        let mut request = http::Request::builder().method(method).uri(url);
+        if let Some(authorization) = &self.authorization {
+            request = request.header("Authorization", authorization);
+        }
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        // An empty body is left off entirely, since some servers don't expect
+        // one with GET or DELETE.
+        let sent = if body.is_empty() {
+            request.body(()).map_err(ureq::Error::from).and_then(|request| self.agent.run(request))
+        } else {
+            request.body(body.to_string()).map_err(ureq::Error::from).and_then(|request| self.agent.run(request))
+        };
+        let mut response = sent.map_err(|why| format!("{} {}: {}", method, url, why))?;
+        let etag = response.headers().get("ETag").and_then(|etag| etag.to_str().ok()).map(|etag| etag.to_string());
+        let status = response.status().as_u16();
+        let body = response.body_mut().read_to_string().unwrap_or_default();
+        if status == 401 || status == 403 {
+            return Err(format!("{} {}: the server refused the credentials ({})", method, url, status));
+        }
+        return Ok(Response { status, etag, body });
+    }
+
+    // Every event in the collection.
+    pub fn list(&self) -> Result<Vec<Resource>, String> {
+        let body = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+                    <d:propfind xmlns:d=\"DAV:\"><d:prop><d:getetag/></d:prop></d:propfind>";
+        let response = self.request("PROPFIND", &self.url,
+                                    &[("Depth", "1"), ("Content-Type", "application/xml; charset=utf-8")], body)?;
+        if response.status != 207 {
+            return Err(format!("PROPFIND {}: unexpected status {}", self.url, response.status));
+        }
+        let mut resources = Vec::new();
+        for entry in elements(&response.body, "response") {
+            let href = elements(entry, "href").first().map(|href| unescape_xml(href.trim()));
+            let etag = elements(entry, "getetag").first().map(|etag| unescape_xml(etag.trim()));
+            if let (Some(href), Some(etag)) = (href, etag) {
+                if href.ends_with(".ics") {
+                    resources.push(Resource { href, etag });
+                }
+            }
+        }
+        return Ok(resources);
+    }
+
+    pub fn get(&self, href: &str) -> Result<String, String> {
+        let url = self.url_for(href);
+        let response = self.request("GET", &url, &[], "")?;
+        if response.status != 200 {
+            return Err(format!("GET {}: unexpected status {}", url, response.status));
+        }
+        return Ok(response.body);
+    }
+
+    // Creates the event, or replaces it if it still has the given ETag. The
+    // new ETag is returned when the server sends one.
+    pub fn put(&self, href: &str, event: &str, etag: Option<&str>) -> Result<Option<String>, String> {
+        let url = self.url_for(href);
+        let condition = match etag {
+            Some(etag) => ("If-Match", etag),
+            None => ("If-None-Match", "*"),
+        };
+        let response = self.request("PUT", &url, &[("Content-Type", "text/calendar; charset=utf-8"), condition], event)?;
+        return match response.status {
+            200..=299 => Ok(response.etag),
+            412 => Err(format!("PUT {}: the event was changed on the server meanwhile", url)),
+            status => Err(format!("PUT {}: unexpected status {}", url, status)),
+        };
+    }
+
+    // Deletes the event if it still has the given ETag; one that's already
+    // gone counts as deleted.
+    pub fn delete(&self, href: &str, etag: &str) -> Result<(), String> {
+        let url = self.url_for(href);
+        let response = self.request("DELETE", &url, &[("If-Match", etag)], "")?;
+        return match response.status {
+            200..=299 | 404 => Ok(()),
+            412 => Err(format!("DELETE {}: the event was changed on the server meanwhile", url)),
+            status => Err(format!("DELETE {}: unexpected status {}", url, status)),
+        };
+    }
+}
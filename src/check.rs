@@ -0,0 +1,80 @@
+// `wtd check --published`: a last look over a generated public calendar
+// before it's uploaded, for anything the public view shouldn't contain.
+use std::collections::HashMap;
+use crate::{escape_html, Task};
+
+// Everything tasks_to_html emits; anything else came from user content.
+const ELEMENTS: [&str; 19] = ["html", "head", "meta", "title", "link", "body", "table", "tr", "th", "td",
+                              "a", "b", "ul", "li", "img", "br", "p", "div", "style"];
+
+// Shorter strings turn up by coincidence too often to be worth reporting.
+const MIN_SECRET_LEN: usize = 4;
+
+fn is_public(task: &Task) -> bool {
+    return task.tags.iter().any(|t| t == "public");
+}
+
+// Text from private tasks that shouldn't be on the page, raw or escaped.
+fn private_details(html: &str, tasks: &[Task]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for task in tasks.iter().filter(|t| !is_public(t)) {
+        // Line by line, since hard breaks are written as <br>.
+        let mut secrets: Vec<&str> = task.details.lines().collect();
+        secrets.extend(task.notes.iter().map(|n| n.as_str()));
+        secrets.extend(task.location.as_deref());
+        secrets.extend(task.subtasks.iter().flat_map(|s| s.text.lines()));
+        secrets.extend(task.attachments.iter().map(|a| a.as_str()));
+        for secret in secrets.into_iter().filter(|s| s.chars().count() >= MIN_SECRET_LEN) {
+            if html.contains(secret) || html.contains(&escape_html(secret)) {
+                problems.push(format!("private text from {} appears: {:?}", task.date, secret));
+            }
+        }
+    }
+    return problems;
+}
+
+// Tags named in cell classes or the details list that aren't public tags.
+fn unknown_tags(html: &str, public_tags: &HashMap<String, String>) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    for (i, _) in html.match_indices(" tag-") {
+        let rest = &html[i + 5..];
+        let end = rest.find(|c: char| c == '"' || c.is_whitespace()).unwrap_or(rest.len());
+        found.push(rest[..end].to_string());
+    }
+    for (i, _) in html.match_indices("Tagged <b>") {
+        let rest = &html[i + 10..];
+        found.push(rest[..rest.find(":</b>").unwrap_or(0)].to_string());
+    }
+    found.sort();
+    found.dedup();
+    return found.into_iter()
+        .filter(|tag| !public_tags.contains_key(tag.as_str()))
+        .map(|tag| format!("unknown tag: {:?}", tag))
+        .collect();
+}
+
+// Markup that tasks_to_html wouldn't have written itself: other elements,
+// event handler attributes, and javascript: URLs.
+fn unescaped_content(html: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (i, _) in html.match_indices('<') {
+        let rest = &html[i + 1..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        let name: String = tag.trim_start_matches('/').chars()
+            .take_while(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase();
+        let lower = tag.to_lowercase();
+        if !ELEMENTS.contains(&name.as_str()) {
+            problems.push(format!("unexpected markup: <{}>", tag));
+        } else if lower.contains("javascript:") || lower.split_whitespace().skip(1).any(|attr| attr.starts_with("on")) {
+            problems.push(format!("script in markup: <{}>", tag));
+        }
+    }
+    return problems;
+}
+
+pub fn check_published(html: &str, tasks: &[Task], public_tags: &HashMap<String, String>) -> Vec<String> {
+    let mut problems = private_details(html, tasks);
+    problems.extend(unknown_tags(html, public_tags));
+    problems.extend(unescaped_content(html));
+    return problems;
+}
@@ -0,0 +1,492 @@
+// Settings read from wtd.toml in the current directory, or else from the
+// user-wide config.toml in the XDG config directory. Anything given on the
+// command line takes precedence over the file.
+//
+// String values may reference environment variables as `${NAME}` (write `$$`
+// for a literal `$`), and a file may pull in other files with a top-level
+// `include = ["secrets.toml"]`. Included files are resolved relative to the
+// file including them, must live inside the main config's directory, and are
+// merged underneath the including file's own settings. This lets secrets live
+// outside a config that is checked into dotfiles. Values of the form
+// `keyring:NAME` are read from the OS keyring (see credentials.rs).
+//
+// Named profiles bundle overrides for any of these settings, e.g.
+//
+//   [profiles.work]
+//   input = "work.md"
+//   output = { public = "work.html", audiences = ["public"] }
+//
+// and are applied on top of the rest of the file with `wtd --profile work`.
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use toml::{Table, Value};
+use crate::{credentials, dirs, ics::ShowAs, policy::Enforce, redact::Privacy, CellOverflow, MapLinks, TimeFormat, View};
+
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+// Bumped whenever a setting is renamed or changes meaning, for scripts that
+// write config files (see `wtd --capabilities`).
+pub const SCHEMA_VERSION: u32 = 1;
+
+// The top-level keys a config file may have.
+pub const KEYS: [&str; 18] = [
+    "include", "profiles", "input", "merge_ics", "plugins", "output", "export", "hooks", "journal", "parse", "policies",
+    "redact", "render", "serve", "stats", "sync", "tags", "gcal",
+];
+
+// Written by `wtd config init`.
+pub const STARTER: &str = r#"# wtd settings. Command-line flags take precedence over these.
+
+# input = "wtd.md"
+# merge_ics = ["meetings.ics"]
+# plugins = ["plugins/csv.wasm"]
+
+# [[policies]]
+# tag = "work"
+# hours = "9-18"
+# enforce = "flag"
+
+# The tags shown (and explained) on the public calendar, replacing the
+# built-in busy, rough, tentative, join-me, and self.
+# [tags]
+# busy = "I will be genuinely busy, e.g., a meeting with others."
+# focus = "Heads-down time; please don't book over it."
+
+[output]
+# public = "public.html"
+# private = "private.html"
+# audiences = ["public", "private"]
+# public_template = "index.template.html"
+# ics = "calendar.ics"
+# json = "calendar.json"
+# year = "year.svg"
+
+[export]
+# out_of_office = "busy"
+
+[journal]
+# dir = "journal"
+# template = "journal.template.md"
+
+[parse]
+# lenient = false
+# date_formats = ["%d.%m.%Y"]
+# Tags --strict accepts besides those mentioned elsewhere in this file.
+# tags = ["focus", "errand"]
+# The timezone times are on clocks in; unset, clocks never change.
+# timezone = "America/New_York"
+
+[render]
+# cell_max_chars = 20
+# cell_overflow = "ellipsis"
+# time_format = "12h"
+# week_start = "monday"
+# full_weeks = false
+# hide_done = false
+# meeting_costs = false
+# increment = 15
+# days = 14
+# hours = "7-22"
+# stylesheet = "calendar_style.css"
+# embed_css = false
+# map_links = "openstreetmap"
+# tag_priority = ["busy", "tentative"]
+
+# A week's grid followed by a month, on each page.
+# [[render.sections]]
+# view = "grid"
+# days = 7
+# [[render.sections]]
+# view = "month"
+# weeks = 4
+
+# Colors for tagged tasks, taking precedence over the stylesheet's.
+# [render.colors]
+# busy = "crimson"
+# focus = "rebeccapurple"
+
+[serve]
+# bind = "127.0.0.1:8080"
+# privacy_mode = true
+# refresh_minutes = 15
+# A compact dashboard of the next few days, for a small screen.
+# days = 3
+# hours = "8-20"
+
+[stats]
+# work_hours = "9-17"
+# hourly_rate = 85
+
+[sync]
+# url = "https://caldav.fastmail.com/dav/calendars/user/you@fastmail.com/Default/"
+# username = "you@fastmail.com"
+# password = "keyring:caldav"
+
+[gcal]
+# client_id = "1234-abcd.apps.googleusercontent.com"
+# client_secret = "keyring:gcal-client-secret"
+# calendar = "primary"
+# days = 14
+
+# Commands given the calendar as JSON on standard input; see the README.
+[hooks]
+# pre_parse = ["./expand-shorthand"]
+# post_render = ["./add-banner"]
+# pre_publish = ["./check-nothing-secret"]
+"#;
+
+pub fn user_config_path() -> PathBuf {
+    return dirs::config_dir().join("config.toml");
+}
+
+// The config file to use: an explicitly given one, else wtd.toml in the
+// current directory, else the user-wide one if it exists.
+pub fn find(explicit: Option<&Path>) -> Option<PathBuf> {
+    if let Some(path) = explicit {
+        return Some(path.to_path_buf());
+    }
+    return vec![PathBuf::from("wtd.toml"), user_config_path()].into_iter().find(|candidate| candidate.exists());
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub input: Option<PathBuf>,
+    /// iCalendar files whose events are drawn on the rendered calendars.
+    pub merge_ics: Vec<PathBuf>,
+    /// WebAssembly plugins adding output formats and lint rules (see plugin.rs).
+    pub plugins: Vec<PathBuf>,
+    pub output: OutputConfig,
+    pub export: ExportConfig,
+    pub hooks: HooksConfig,
+    pub journal: JournalConfig,
+    pub parse: ParseConfig,
+    /// Per-tag working hours, as [[policies]] tables.
+    pub policies: Vec<PolicyConfig>,
+    pub redact: RedactConfig,
+    pub render: RenderConfig,
+    /// The tags described on the public calendar, replacing the built-in ones.
+    pub tags: Option<HashMap<String, String>>,
+    pub serve: ServeConfig,
+    pub stats: StatsConfig,
+    pub sync: SyncConfig,
+    pub gcal: GcalConfig,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Audience {
+    Public,
+    Private,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    pub public: PathBuf,
+    pub private: PathBuf,
+    /// Which of the two calendars to write.
+    pub audiences: Vec<Audience>,
+    /// Templates of pages of your own to put the calendars in (at {{ calendar }}
+    /// or a <!-- wtd:calendar --> comment), instead of pages of their own.
+    pub public_template: Option<PathBuf>,
+    pub private_template: Option<PathBuf>,
+    /// Where to also write the public calendar as iCalendar, as JSON, and as
+    /// this year's SVG poster, if anywhere.
+    pub ics: Option<PathBuf>,
+    pub json: Option<PathBuf>,
+    pub year: Option<PathBuf>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> OutputConfig {
+        return OutputConfig {
+            public: PathBuf::from("public.html"),
+            private: PathBuf::from("private.html"),
+            audiences: vec![Audience::Public, Audience::Private],
+            public_template: None,
+            private_template: None,
+            ics: None,
+            json: None,
+            year: None,
+        };
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportConfig {
+    /// How out-of-office days show in the exported calendar, "busy" or "free" [default: busy].
+    pub out_of_office: Option<ShowAs>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksConfig {
+    /// Commands run on each task file before it's read (see hooks.rs).
+    pub pre_parse: Vec<String>,
+    /// Commands run on each rendered calendar page.
+    pub post_render: Vec<String>,
+    /// Commands run before the rendered files are written, any of which can
+    /// stop them being written by exiting nonzero.
+    pub pre_publish: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JournalConfig {
+    /// Where the journal's dated entries are kept, relative to the task
+    /// file's directory [default: "journal"].
+    pub dir: Option<PathBuf>,
+    /// A minijinja template for new entries, in place of the built-in one.
+    pub template: Option<PathBuf>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParseConfig {
+    pub lenient: bool,
+    /// chrono formats for the dates in week headers and OOO lines, tried
+    /// before the built-in ones, e.g. "%d.%m.%Y".
+    pub date_formats: Vec<String>,
+    /// Tags --strict accepts besides those the rest of the config mentions.
+    pub tags: Vec<String>,
+    /// The IANA timezone the file's times are in, e.g. "America/New_York".
+    /// Unset, clocks are taken never to change, so the file reads the same
+    /// on every machine.
+    pub timezone: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyConfig {
+    pub tag: String,
+    /// The hours the tag's tasks belong in, e.g. "9-18".
+    pub hours: String,
+    /// "busy" to show the tag's tasks publicly only as busy and only within
+    /// the hours, or "flag" to have `wtd check` report them outside the hours.
+    pub enforce: Enforce,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// Words scrubbed from public output, matched whole and ignoring case.
+    pub keywords: Vec<String>,
+    /// Regular expressions scrubbed from public output.
+    pub patterns: Vec<String>,
+    /// What redacted text is replaced with [default: "[redacted]"].
+    pub replacement: Option<String>,
+    /// How much of the schedule public output shows: "none" (the default),
+    /// "busy-only", or "full".
+    pub privacy: Option<Privacy>,
+    /// The same as privacy = "busy-only": private timed tasks as busy blocks,
+    /// with back-to-back and overlapping ones merged so that where one ends
+    /// and the next begins is hidden.
+    pub merge_private: bool,
+    /// Move the start and end of private timed tasks out to a multiple of
+    /// this many minutes, e.g. 30, so exact times aren't given away.
+    pub round_minutes: Option<i64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderConfig {
+    pub cell_max_chars: Option<usize>,
+    pub cell_overflow: Option<CellOverflow>,
+    /// "12h" or "24h", the clock the calendars' times are shown on.
+    pub time_format: Option<TimeFormat>,
+    /// First day of the week, e.g. "sunday"; defaults to the locale's.
+    pub week_start: Option<String>,
+    /// Show whole weeks in the grid, from the start of the current one.
+    pub full_weeks: bool,
+    /// Leave tasks that are checked off out of the public calendar.
+    pub hide_done: bool,
+    /// Show the estimated cost of meetings with an &N count on the private calendar.
+    pub meeting_costs: bool,
+    /// Minutes per row of the grid [default: 15].
+    pub increment: Option<i64>,
+    /// Days shown by the grid view [default: 14].
+    pub days: Option<i64>,
+    /// Hours of the day the grid shows, as START-END in 24-hour time, e.g. "8-20" [default: all of them].
+    pub hours: Option<String>,
+    /// The stylesheet the pages link to [default: "calendar_style.css"].
+    pub stylesheet: Option<String>,
+    /// Write the stylesheet into each page instead of linking it.
+    pub embed_css: bool,
+    /// Where task locations link: "openstreetmap" or "google" [default: "openstreetmap"].
+    pub map_links: Option<MapLinks>,
+    /// Tags in the order a cell lists them, the first of a cell's setting its color, e.g. ["busy", "tentative"].
+    pub tag_priority: Vec<String>,
+    /// Calendars to show one after another on each page, in place of --view.
+    pub sections: Vec<SectionConfig>,
+    /// CSS colors for tasks with a tag, by tag, e.g. busy = "#d33".
+    pub colors: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SectionConfig {
+    /// "grid", "compare", or "month".
+    pub view: View,
+    /// Days shown by a grid [default: days above].
+    pub days: Option<i64>,
+    /// Weeks shown by a compare or month view [default: 4].
+    pub weeks: Option<i64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    /// Working hours for the idle report, e.g. "9-17".
+    pub work_hours: Option<String>,
+    /// Hourly cost of one meeting attendee, for `stats meetings`.
+    pub hourly_rate: Option<f64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// The CalDAV calendar collection to sync with.
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Where to remember what was synced; defaults to the data directory.
+    pub state: Option<PathBuf>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GcalConfig {
+    /// The OAuth client wtd authorizes as, of type "TVs and Limited Input devices".
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// The calendar to push to [default: "primary"].
+    pub calendar: Option<String>,
+    /// How many days, starting today, to push [default: 14].
+    pub days: Option<i64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServeConfig {
+    pub bind: Option<String>,
+    pub meeting_tag: Option<String>,
+    pub expose_private: bool,
+    pub privacy_mode: bool,
+    /// Minutes between fetches of the merged calendars [default: 15].
+    pub refresh_minutes: Option<u64>,
+    /// Days the served grid shows, in place of days under [render].
+    pub days: Option<i64>,
+    /// Hours of the day the served grid shows, in place of hours under [render].
+    pub hours: Option<String>,
+}
+
+fn expand_env(s: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            out.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let end = after.find('}').ok_or_else(|| format!("unterminated '${{' in \"{}\"", s))?;
+            let name = &after[..end];
+            let value = std::env::var(name)
+                .map_err(|_| format!("environment variable {} is not set", name))?;
+            out.push_str(&value);
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    return Ok(out);
+}
+
+fn expand_value(value: &mut Value) -> Result<(), String> {
+    match value {
+        Value::String(s) => *s = credentials::resolve(&expand_env(s)?)?,
+        Value::Array(values) => {
+            for v in values {
+                expand_value(v)?;
+            }
+        },
+        Value::Table(table) => {
+            for (_, v) in table.iter_mut() {
+                expand_value(v)?;
+            }
+        },
+        _ => (),
+    }
+    return Ok(());
+}
+
+// Merges `overlay` into `base`, recursing into tables present in both.
+fn merge(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) => merge(base_table, overlay_table),
+            (_, value) => { base.insert(key, value); },
+        }
+    }
+}
+
+fn load_table(path: &Path, sandbox: &Path, stack: &mut Vec<PathBuf>) -> Result<Table, String> {
+    let canonical = path.canonicalize().map_err(|why| format!("{}: {}", path.display(), why))?;
+    if !canonical.starts_with(sandbox) {
+        return Err(format!("{} is outside of the config directory {}", path.display(), sandbox.display()));
+    }
+    if stack.contains(&canonical) {
+        return Err(format!("{} includes itself", path.display()));
+    }
+    if stack.len() > MAX_INCLUDE_DEPTH {
+        return Err(format!("includes nested more than {} deep at {}", MAX_INCLUDE_DEPTH, path.display()));
+    }
+    let text = std::fs::read_to_string(&canonical).map_err(|why| format!("{}: {}", path.display(), why))?;
+    let mut table: Table = text.parse().map_err(|why| format!("{}: {}", path.display(), why))?;
+    for (_, value) in table.iter_mut() {
+        expand_value(value).map_err(|why| format!("{}: {}", path.display(), why))?;
+    }
+
+    let mut merged = Table::new();
+    if let Some(includes) = table.remove("include") {
+        let includes = match includes {
+            Value::String(s) => vec![Value::String(s)],
+            Value::Array(values) => values,
+            _ => return Err(format!("{}: include must be a file name or a list of them", path.display())),
+        };
+        stack.push(canonical.clone());
+        for include in includes {
+            let name = include.as_str()
+                .ok_or_else(|| format!("{}: include must be a file name or a list of them", path.display()))?;
+            let included = canonical.parent().unwrap_or(sandbox).join(name);
+            merge(&mut merged, load_table(&included, sandbox, stack)?);
+        }
+        stack.pop();
+    }
+    merge(&mut merged, table);
+    return Ok(merged);
+}
+
+pub fn load(path: &Path, profile: Option<&str>) -> Result<Config, String> {
+    let canonical = path.canonicalize().map_err(|why| format!("{}: {}", path.display(), why))?;
+    let sandbox = canonical.parent().unwrap_or(Path::new("/")).to_path_buf();
+    let mut table = load_table(&canonical, &sandbox, &mut Vec::new())?;
+    let mut profiles = match table.remove("profiles") {
+        Some(Value::Table(profiles)) => profiles,
+        Some(_) => return Err(format!("{}: profiles must be a table", path.display())),
+        None => Table::new(),
+    };
+    if let Some(name) = profile {
+        match profiles.remove(name) {
+            Some(Value::Table(overrides)) => merge(&mut table, overrides),
+            _ => return Err(format!("{}: no profile named {}", path.display(), name)),
+        }
+    }
+    return Value::Table(table).try_into().map_err(|why| format!("{}: {}", path.display(), why));
+}
@@ -0,0 +1,111 @@
+// Secrets (OAuth tokens, CalDAV passwords) kept in the OS keyring instead of
+// plaintext config. Config values of the form `keyring:NAME` are looked up
+// here; anything else is used as written. The platform's own tools are used
+// to reach the keyring: `secret-tool` (libsecret) on Linux, `security` on
+// macOS, and the Credential Locker through PowerShell on Windows.
+use std::io::prelude::*;
+use std::process::{Command, Stdio};
+
+const SERVICE: &str = "wtd";
+
+fn run(command: &mut Command, input: Option<&str>) -> Result<String, String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|why| format!("couldn't run {}: {}", program, why))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes()).map_err(|why| format!("couldn't talk to {}: {}", program, why))?;
+    }
+    let output = child.wait_with_output().map_err(|why| format!("{} failed: {}", program, why))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    return Ok(String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_string());
+}
+
+#[cfg(target_os = "macos")]
+pub fn get(name: &str) -> Result<String, String> {
+    return run(Command::new("security").args(["find-generic-password", "-s", SERVICE, "-a", name, "-w"]), None);
+}
+
+// The secret is handed to `security` on standard input, as a command for its
+// interactive mode, rather than as an argument that any local user could read
+// with `ps`.
+#[cfg(target_os = "macos")]
+pub fn set(name: &str, secret: &str) -> Result<(), String> {
+    if secret.contains(['\n', '\r']) {
+        return Err("the keyring can't be handed a secret with a line break".to_string());
+    }
+    let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+    let command = format!("add-generic-password -U -s {} -a {} -w {}\n", quote(SERVICE), quote(name), quote(secret));
+    run(Command::new("security").arg("-i"), Some(&command))?;
+    // Interactive mode carries on past a command that fails, so the secret
+    // is read back to be sure it was stored.
+    return match get(name) {
+        Ok(stored) if stored == secret => Ok(()),
+        _ => Err("security didn't store the secret".to_string()),
+    };
+}
+
+#[cfg(target_os = "macos")]
+pub fn delete(name: &str) -> Result<(), String> {
+    return run(Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", name]), None).map(|_| ());
+}
+
+// On Windows the secret's name is passed through the environment rather than
+// spliced into the script, so it needs no quoting.
+#[cfg(windows)]
+fn powershell(script: &str, name: &str, input: Option<&str>) -> Result<String, String> {
+    let vault = "[void][Windows.Security.Credentials.PasswordVault,Windows.Security.Credentials,ContentType=WindowsRuntime]; \
+                 $vault = New-Object Windows.Security.Credentials.PasswordVault; ";
+    return run(Command::new("powershell")
+               .args(["-NoProfile", "-NonInteractive", "-Command", &format!("{}{}", vault, script)])
+               .env("WTD_SERVICE", SERVICE)
+               .env("WTD_SECRET_NAME", name), input);
+}
+
+#[cfg(windows)]
+pub fn get(name: &str) -> Result<String, String> {
+    return powershell("$c = $vault.Retrieve($env:WTD_SERVICE, $env:WTD_SECRET_NAME); $c.RetrievePassword(); $c.Password",
+                      name, None);
+}
+
+#[cfg(windows)]
+pub fn set(name: &str, secret: &str) -> Result<(), String> {
+    return powershell("$s = [Console]::In.ReadToEnd(); \
+                       $vault.Add((New-Object Windows.Security.Credentials.PasswordCredential($env:WTD_SERVICE, $env:WTD_SECRET_NAME, $s)))",
+                      name, Some(secret)).map(|_| ());
+}
+
+#[cfg(windows)]
+pub fn delete(name: &str) -> Result<(), String> {
+    return powershell("$vault.Remove($vault.Retrieve($env:WTD_SERVICE, $env:WTD_SECRET_NAME))", name, None).map(|_| ());
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn get(name: &str) -> Result<String, String> {
+    return run(Command::new("secret-tool").args(["lookup", "service", SERVICE, "account", name]), None);
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn set(name: &str, secret: &str) -> Result<(), String> {
+    let label = format!("wtd: {}", name);
+    return run(Command::new("secret-tool")
+               .args(["store", "--label", &label, "service", SERVICE, "account", name]), Some(secret)).map(|_| ());
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn delete(name: &str) -> Result<(), String> {
+    return run(Command::new("secret-tool").args(["clear", "service", SERVICE, "account", name]), None).map(|_| ());
+}
+
+// Resolves a secret-valued config setting.
+pub fn resolve(value: &str) -> Result<String, String> {
+    return match value.strip_prefix("keyring:") {
+        Some(name) => get(name).map_err(|why| format!("couldn't read {} from the keyring: {}", name, why)),
+        None => Ok(value.to_string()),
+    };
+}
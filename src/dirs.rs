@@ -0,0 +1,35 @@
+// Standard locations for wtd's files, following the XDG base directory spec
+// (or the usual AppData folders on Windows).
+use std::env;
+use std::path::PathBuf;
+
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    if let Some(dir) = env::var_os(var).filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir).join("wtd");
+    }
+    let home = env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    return home.join(fallback).join("wtd");
+}
+
+#[cfg(windows)]
+fn known_dir(var: &str) -> PathBuf {
+    return env::var_os(var).map(PathBuf::from).unwrap_or_default().join("wtd");
+}
+
+// Where the user-wide config.toml lives.
+pub fn config_dir() -> PathBuf {
+    #[cfg(windows)]
+    if env::var_os("XDG_CONFIG_HOME").is_none() {
+        return known_dir("APPDATA");
+    }
+    return xdg_dir("XDG_CONFIG_HOME", ".config");
+}
+
+// Where caches and logs written by wtd live.
+pub fn data_dir() -> PathBuf {
+    #[cfg(windows)]
+    if env::var_os("XDG_DATA_HOME").is_none() {
+        return known_dir("LOCALAPPDATA");
+    }
+    return xdg_dir("XDG_DATA_HOME", ".local/share");
+}
@@ -0,0 +1,56 @@
+// `wtd done TASK`: checks a task off in the task file, finding it as `wtd
+// open` does, by its '#id:' or words of its description. With --time it also
+// records when, as a '+done:HH:MM' tag, which `wtd stats done` compares with
+// the time the task was planned to end.
+use std::collections::BTreeSet;
+use std::path::Path;
+use chrono::{NaiveDate, NaiveTime};
+use crate::edit::check_off;
+use crate::{check_editable, find_tasks, lock, parse_tasks_with, read_file, time, ParseOptions, Task};
+
+pub struct DoneOptions {
+    // Check off the task on this day, rather than the nearest.
+    pub on: Option<NaiveDate>,
+    // The time to record it was done at, if any.
+    pub at: Option<NaiveTime>,
+    pub parse: ParseOptions,
+}
+
+// The time a task was checked off at, from its '+done:HH:MM' tag.
+pub fn done_at(task: &Task) -> Option<NaiveTime> {
+    return task.tags.iter().find_map(|tag| tag.strip_prefix("done:").and_then(time::parse_hhmm));
+}
+
+// Checks off the task `query` names in the task file at `path`: of those
+// not yet done, the one on `on` if given, else today, else the latest before
+// today, else the soonest after. Returns the task as it now is.
+pub fn done(path: &Path, query: &str, options: &DoneOptions) -> Result<Task, String> {
+    check_editable(path)?;
+    let _lock = lock::lock(path)?;
+    let describe = |errors: Vec<crate::ParseError>| {
+        return errors.iter().map(|e| format!("{}:{}", path.display(), e)).collect::<Vec<String>>().join("\n");
+    };
+    let original = read_file(path)?;
+    let tasks = parse_tasks_with(&original, &options.parse).map_err(describe)?;
+    let matching = find_tasks(&tasks, query);
+    if matching.is_empty() {
+        return Err(format!("No task's id or description matches {:?}", query));
+    }
+    let open: Vec<usize> = matching.iter().copied().filter(|&i| !tasks[i].done).collect();
+    if open.is_empty() {
+        let task = &tasks[matching[0]];
+        return Err(format!("{:?} on {} is already done", task.details, time::format_date(task.date)));
+    }
+    let dates: BTreeSet<NaiveDate> = open.iter().map(|&i| tasks[i].date).collect();
+    let date = match options.on {
+        Some(on) if dates.contains(&on) => on,
+        Some(on) => return Err(format!("Nothing {:?} names is left to do on {}; there is on {}", query, time::format_date(on),
+                                       dates.iter().map(|date| time::format_date(*date)).collect::<Vec<String>>().join(", "))),
+        None => time::nearest_day(&dates, time::today()).expect("an open task has a date"),
+    };
+    let index = *open.iter().find(|&&i| tasks[i].date == date).expect("the date is one of theirs");
+    let source = check_off(&original, index, options.at, &options.parse)?;
+    let checked = parse_tasks_with(&source, &options.parse).map_err(describe)?;
+    lock::write_checked(path, &original, &source)?;
+    return Ok(checked[index].clone());
+}
@@ -0,0 +1,191 @@
+// Edits to a task file that find tasks the way the parser does, and leave
+// every other line (comments, notes, formatting) as it was. Used by commands
+// that change wtd.md on the user's behalf.
+use std::str::FromStr;
+use chrono::{Duration, NaiveDate, NaiveTime, Weekday};
+use crate::{blank_comments, closest_weekday, format, is_task_line, normalize_source, parse_date_line, resolve_day};
+use crate::{strip_hard_break, time, ParseOptions, Task};
+
+// What a task file line is, for finding tasks in the file without disturbing
+// anything else on the way.
+enum Line {
+    Week(Option<NaiveDate>),
+    Day(Option<Weekday>),
+    // With the number of tasks on the line.
+    Task(usize),
+    Indented,
+    Blank,
+    Other,
+}
+
+fn classify(raw: &str, options: &ParseOptions) -> Line {
+    let (l, _) = blank_comments(&normalize_source(raw));
+    if l.starts_with("# ") {
+        return Line::Week(parse_date_line(&l, options));
+    } else if let Some(day) = l.strip_prefix("## ") {
+        // As parse_day_line reads it, without repeating its warnings.
+        let day = day.trim();
+        return Line::Day(Weekday::from_str(day).ok()
+                         .or_else(|| closest_weekday(day).filter(|_| options.lenient).map(|(day, _)| day)));
+    } else if is_task_line(&l) {
+        let (details, _) = strip_hard_break(&l[5..]);
+        return Line::Task(details.split(';').filter(|part| !part.trim().is_empty()).count().max(1));
+    } else if l.starts_with(' ') {
+        return Line::Indented;
+    } else if l.trim().is_empty() {
+        return Line::Blank;
+    }
+    return Line::Other;
+}
+
+// The lines [start, end) of each task line's block, with the number of tasks
+// on it, in the order the parser reads them.
+fn task_blocks(lines: &[&str], options: &ParseOptions) -> Vec<(usize, usize, usize)> {
+    let mut blocks = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Line::Task(count) = classify(line, options) {
+            let mut end = i + 1;
+            for (j, next) in lines.iter().enumerate().skip(i + 1) {
+                match classify(next, options) {
+                    Line::Indented => end = j + 1,
+                    Line::Blank => {},
+                    _ => break,
+                }
+            }
+            blocks.push((i, end, count));
+        }
+    }
+    return blocks;
+}
+
+// The 1-based line each task starts on, in the order the parser reads them;
+// tasks sharing a line each get it.
+pub fn task_lines(source: &str, options: &ParseOptions) -> Vec<usize> {
+    let lines: Vec<&str> = source.split('\n').collect();
+    return task_blocks(&lines, options).into_iter()
+        .flat_map(|(start, _, count)| std::iter::repeat_n(start + 1, count))
+        .collect();
+}
+
+// The block [start, end) holding the `index`th task, with the number of
+// tasks on its line.
+fn find_block(lines: &[&str], index: usize, options: &ParseOptions) -> Result<(usize, usize, usize), String> {
+    let mut first = 0;
+    for (start, end, count) in task_blocks(lines, options) {
+        if index < first + count {
+            return Ok((start, end, count));
+        }
+        first += count;
+    }
+    return Err(format!("couldn't find task {} in the file", index + 1));
+}
+
+// Replaces the lines of the `index`th task with `replacement`.
+pub fn splice_task(source: &str, index: usize, replacement: &str, options: &ParseOptions) -> Result<String, String> {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let (start, end, count) = find_block(&lines, index, options)?;
+    if count > 1 {
+        return Err(format!("line {} holds several tasks; split it to sync them", start + 1));
+    }
+    let mut out = lines[..start].join("\n");
+    if start > 0 {
+        out.push('\n');
+    }
+    out.push_str(replacement);
+    out.push_str(&lines[end..].join("\n"));
+    return Ok(out);
+}
+
+// Checks off the `index`th task, changing its '- [ ]' to '- [X]' and, if
+// given `at`, adding a '+done:HH:MM' token after its first line's words, so
+// the rest of the line (a hard break, comments) stays as it was.
+pub fn check_off(source: &str, index: usize, at: Option<NaiveTime>, options: &ParseOptions) -> Result<String, String> {
+    let mut lines: Vec<&str> = source.split('\n').collect();
+    let (start, _, count) = find_block(&lines, index, options)?;
+    if count > 1 {
+        return Err(format!("line {} holds several tasks; split it to check one off", start + 1));
+    }
+    let line = lines[start];
+    // Past the byte-order mark the first line may start with.
+    let marker = line.len() - line.trim_start_matches('\u{feff}').len();
+    if !line[marker..].starts_with("- [ ]") {
+        return Err(format!("line {} is already checked off", start + 1));
+    }
+    let mut checked = format!("{}- [X]{}", &line[..marker], &line[marker + 5..]);
+    if let Some(at) = at {
+        let (blanked, _) = blank_comments(&checked);
+        let words = blanked.trim_end();
+        let words = words.strip_suffix('\\').unwrap_or(words).trim_end();
+        checked.insert_str(words.len(), &format!(" +done:{}", time::format_hhmm(at)));
+    }
+    lines[start] = &checked;
+    return Ok(lines.join("\n"));
+}
+
+// Inserts lines after the last non-blank line before `before`.
+fn insert_lines(lines: &[&str], before: usize, text: &str) -> String {
+    let at = (0..before).rev().find(|&i| !lines[i].trim().is_empty()).map_or(0, |i| i + 1);
+    let mut out = lines[..at].join("\n");
+    if at > 0 {
+        out.push('\n');
+        out.push_str(text);
+    } else {
+        out.push_str(text.trim_start_matches('\n'));
+    }
+    out.push_str(&lines[at..].join("\n"));
+    return out;
+}
+
+// Adds a task under its day: at the end of the day's block if there is one,
+// else in a new day block at the end of its week, else in a new week block
+// placed among the others by date.
+pub fn insert_task(source: &str, task: &Task, options: &ParseOptions, week_start: Weekday) -> String {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let entry = format::format_task(task);
+    let mut week = None;
+    let mut day_end = None;
+    let mut week_end = None;
+    let mut later_week = None;
+    for (i, line) in lines.iter().enumerate() {
+        match classify(line, options) {
+            Line::Week(start) => {
+                if day_end.is_some() || week_end.is_some() {
+                    break;
+                }
+                week = start;
+                if start.is_some_and(|start| start > task.date) {
+                    later_week = Some(i);
+                    break;
+                }
+            },
+            Line::Day(day) => {
+                if day_end.is_some() {
+                    break;
+                }
+                let date = week.zip(day).map(|(start, day)| resolve_day(start, day));
+                if date == Some(task.date) {
+                    day_end = Some(lines.len());
+                }
+            },
+            _ => {},
+        }
+        if day_end.is_some() {
+            day_end = Some(i + 1);
+        }
+        if week.is_some_and(|start| start <= task.date && task.date < start + Duration::days(7)) {
+            week_end = Some(i + 1);
+        }
+    }
+    if let Some(end) = day_end {
+        return insert_lines(&lines, end, &entry);
+    }
+    let day_block = format!("\n## {}\n{}", time::format_weekday(task.date), entry);
+    if let Some(end) = week_end {
+        return insert_lines(&lines, end, &day_block);
+    }
+    let week_block = format!("# {}{}\n", time::format_short_date(time::week_start(task.date, week_start)), day_block);
+    return match later_week {
+        Some(at) => insert_lines(&lines, at, &format!("\n{}", week_block)),
+        None => format!("{}\n\n{}", source.trim_end(), week_block),
+    };
+}
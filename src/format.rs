@@ -0,0 +1,430 @@
+// `wtd fmt`: rewrites a task file in a canonical form so that diffs of the
+// schedule only show real changes. Within each task, words come first, then
+// the time (or '@allday'), location, attendee count, priority, id, tags in
+// the order written, and attachments. Times are written like 9AM or 9:30PM,
+// durations like 1h30m, and spans of days like 6PM--+2d10AM. Continuation
+// lines, notes ('> '), and checklist items are indented by two spaces, and
+// lines continuing a checklist item by four; hard breaks are a trailing ' \';
+// day headers use the full name; comments go at the end of their line.
+// Anything the parser ignores is kept as is.
+use chrono::{Datelike, Duration, NaiveTime, Timelike, Weekday};
+use crate::{indentation, nest_item, normalize_source, parse_day_line, parse_duration, parse_tasks_with, parse_time};
+use crate::{attendee_count, blank_comments, detail_tokens, is_done, is_task_line, location, priority_level, strip_hard_break, task_id, weekday_name};
+use crate::{time, ParseOptions, Span, Subtask, Task};
+
+fn format_time(time: NaiveTime) -> String {
+    let hour = match time.hour() % 12 { 0 => 12, h => h };
+    let suffix = if time.hour() < 12 { "AM" } else { "PM" };
+    if time.minute() == 0 {
+        return format!("{}{}", hour, suffix);
+    }
+    return format!("{}:{:02}{}", hour, time.minute(), suffix);
+}
+
+fn format_duration(duration: Duration) -> String {
+    let (hours, minutes) = (duration.num_minutes() / 60, duration.num_minutes() % 60);
+    return match (hours, minutes) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    };
+}
+
+fn format_time_token(timestr: &str) -> Result<String, String> {
+    if let Some((start, rest)) = timestr.split_once("--+") {
+        if let Some((days, end)) = rest.split_once(['d', 'D']).and_then(|(days, end)| Some((days.parse::<i64>().ok()?, end))) {
+            return Ok(format!("@{}--+{}d{}", format_time(parse_time(start)?), days, format_time(parse_time(end)?)));
+        }
+    }
+    if let [start, duration] = timestr.split('+').collect::<Vec<_>>()[..] {
+        return Ok(format!("@{}+{}", format_time(parse_time(start)?), format_duration(parse_duration(duration)?)));
+    }
+    if let [start, end] = timestr.split("--").collect::<Vec<_>>()[..] {
+        return Ok(format!("@{}--{}", format_time(parse_time(start)?), format_time(parse_time(end)?)));
+    }
+    return Err(format!("'{}' is not of the form Start+Duration or Start--End", timestr));
+}
+
+// A location token, quoted if the place has spaces or would end the line in a
+// hard break.
+fn format_location(place: &str) -> String {
+    if place.contains(char::is_whitespace) || place.ends_with('\\') {
+        return format!("@loc:\"{}\"", place);
+    }
+    return format!("@loc:{}", place);
+}
+
+// Puts the tokens of a task (or continuation line) in canonical order.
+fn format_tokens(text: &str) -> Result<String, String> {
+    let (mut words, mut times, mut places, mut counts) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let (mut priorities, mut ids, mut tags, mut files) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for tok in detail_tokens(text) {
+        if let Some(place) = location(tok) {
+            places.push(format_location(&place?));
+        } else if attendee_count(tok).is_some() {
+            counts.push(tok.to_string());
+        } else if let Some(level) = priority_level(tok) {
+            priorities.push(format!("!{}", level?));
+        } else if let Some(id) = task_id(tok) {
+            ids.push(format!("#id:{}", id?));
+        } else if tok.starts_with("+file:") {
+            files.push(tok.to_string());
+        } else if tok.starts_with('+') {
+            tags.push(tok.to_string());
+        } else if tok.eq_ignore_ascii_case("@allday") {
+            times.push("@allday".to_string());
+        } else if let Some(timestr) = tok.strip_prefix('@') {
+            times.push(format_time_token(timestr)?);
+        } else {
+            words.push(tok.to_string());
+        }
+    }
+    return Ok([words, times, places, counts, priorities, ids, tags, files].concat().join(" "));
+}
+
+// Done tasks are written '- [X]', even if checked off as '- [x]'.
+fn marker(done: bool) -> &'static str {
+    return if done { "- [X]" } else { "- [ ]" };
+}
+
+fn with_hard_break(line: String, breaks: bool) -> String {
+    let line = line.trim_end().to_string();
+    return if breaks { format!("{} \\", line) } else { line };
+}
+
+// `items` follows the parser's: the indentation of the open checklist items
+// (see nest_item), while lines deeper than the last still continue it.
+fn format_line(l: &str, options: &ParseOptions, items: &mut Vec<usize>) -> Result<String, String> {
+    let (text, comments) = blank_comments(l);
+    if comments.is_empty() {
+        return format_text(l, options, items);
+    }
+    if text.trim().is_empty() {
+        return Ok(l.trim_end().to_string());
+    }
+    return Ok(format!("{} {}", format_text(&text, options, items)?, comments.join(" ")));
+}
+
+fn format_text(l: &str, options: &ParseOptions, items: &mut Vec<usize>) -> Result<String, String> {
+    if l.starts_with("# ") || l.starts_with("## ") || is_task_line(l) {
+        items.clear();
+    }
+    if let Some(rest) = l.strip_prefix("# ") {
+        return Ok(format!("# {}", rest.trim()));
+    } else if l.starts_with("## OOO") {
+        return Ok(l.trim_end().to_string());
+    } else if l.starts_with("## ") {
+        return Ok(format!("## {}", weekday_name(parse_day_line(l, options)?)));
+    } else if is_task_line(l) {
+        let (details, breaks) = strip_hard_break(&l[5..]);
+        let parts = details.split(';')
+            .filter(|part| !part.trim().is_empty())
+            .map(format_tokens)
+            .collect::<Result<Vec<String>, String>>()?;
+        return Ok(with_hard_break(format!("{} {}", marker(is_done(l)), parts.join("; ")), breaks));
+    } else if is_task_line(l.trim_start()) {
+        let level = nest_item(items, indentation(l));
+        let item = l.trim_start();
+        let (text, breaks) = strip_hard_break(&item[5..]);
+        return Ok(with_hard_break(format!("{}{} {}", item_indent(level), marker(is_done(item)), text.trim()), breaks));
+    } else if l.starts_with(' ') {
+        let (text, breaks) = strip_hard_break(l);
+        if text.trim().is_empty() {
+            return Ok(String::new());
+        }
+        if items.last().is_some_and(|indent| indentation(l) <= *indent) {
+            items.clear();
+        }
+        if let (true, Some(note)) = (items.is_empty(), l.trim_start().strip_prefix('>')) {
+            // Notes are prose, so like checklist items they keep their words.
+            let words = note.split_whitespace().collect::<Vec<&str>>().join(" ");
+            return Ok(format!("  > {}", words).trim_end().to_string());
+        }
+        if !items.is_empty() {
+            // Checklist items are plain text, so their words stay in order.
+            let words = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+            return Ok(with_hard_break(format!("{}  {}", item_indent(items.len() - 1), words), breaks));
+        }
+        return Ok(with_hard_break(format!("  {}", format_tokens(text)?), breaks));
+    }
+    return Ok(l.trim_end().to_string());
+}
+
+// Two spaces per level of a checklist item, under the task's own two.
+fn item_indent(level: usize) -> String {
+    return " ".repeat(2 + 2 * level);
+}
+
+// Lines of text joined with hard breaks, each after the first indented by
+// `indent` spaces.
+fn with_hard_breaks(first: String, rest: std::str::Lines, indent: usize) -> String {
+    let mut out = first;
+    for line in rest {
+        out = format!("{} \\\n{}{}", out.trim_end(), " ".repeat(indent), line.trim());
+    }
+    return format!("{}\n", out.trim_end());
+}
+
+// A word the parser would read as something other than a word of text: a
+// tag, attachment, time, attendee count, priority, or id.
+fn is_token(word: &str) -> bool {
+    return word.starts_with('+') || word.starts_with('@') || attendee_count(word).is_some() || priority_level(word).is_some()
+        || task_id(word).is_some();
+}
+
+// Takes apart every '<!--', including any that removing one would leave.
+fn without_comment_openers(text: &str) -> String {
+    let mut text = text.to_string();
+    while text.contains("<!--") {
+        text = text.replace("<!--", "<!-");
+    }
+    return text;
+}
+
+// A line of details (`tokens`) or checklist text as words the parser reads
+// back as the same words: a token, or a word that would start a '//' comment
+// or end the line in a hard break, goes in backticks, and a line that would
+// start a checklist item gets its '-' in backticks too.
+fn plain_line(line: &str, tokens: bool) -> String {
+    let line = without_comment_openers(line);
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let mut out = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        let misread = (tokens && is_token(word)) || word.starts_with("//") || (i + 1 == words.len() && word.ends_with('\\'));
+        out.push(if misread { format!("`{}`", word) } else { word.to_string() });
+    }
+    let out = out.join(" ");
+    if is_task_line(&out) {
+        return format!("`-`{}", &out[1..]);
+    }
+    return out;
+}
+
+// Text as the lines of plain_line, leaving out blank ones, which a task file
+// can't hold. Lines of details after the first that would be read as notes get
+// their '>' in backticks.
+fn plain_text(text: &str, tokens: bool) -> String {
+    return text.lines()
+        .map(|line| plain_line(line, tokens))
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(i, line)| match line.strip_prefix('>') {
+            Some(rest) if tokens && i > 0 => format!("`>`{}", rest),
+            _ => line,
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+}
+
+// A place as a location token holds it: without quotes, with ';' as ',' as in
+// tags, runs of whitespace as single spaces, and no word starting with a
+// '//' comment or a comment opener.
+fn plain_place(place: &str) -> String {
+    let place = without_comment_openers(&place.replace('"', "").replace(';', ","));
+    return place.split_whitespace()
+        .map(|word| if word.starts_with("//") { word.trim_start_matches('/') } else { word })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+}
+
+// A tag or attachment path as a single token: whitespace in it becomes '-',
+// ';' becomes ',' as in the first line of details, and a trailing '\', which
+// would be read as a hard break, is dropped.
+fn plain_name(name: &str) -> String {
+    let name = name.replace(';', ",").split_whitespace().collect::<Vec<&str>>().join("-");
+    let name = without_comment_openers(&name);
+    return name.trim_end_matches('\\').to_string();
+}
+
+// A checklist as plain text, with no item nested deeper than just under the
+// one above it, as the parser can only read them.
+fn nested(subtasks: &[Subtask]) -> Vec<Subtask> {
+    let mut out: Vec<Subtask> = Vec::new();
+    for subtask in subtasks {
+        let deepest = out.last().map_or(0, |above| above.level + 1);
+        out.push(Subtask { text: plain_text(&subtask.text, false), done: subtask.done, level: subtask.level.min(deepest) });
+    }
+    return out;
+}
+
+// The task as a task file can hold it, which is what parsing format_task's
+// line for it gives back: for any task, parsing `format_task(task)` under the
+// header of its day yields `normalize(task)`, and normalizing that changes
+// nothing more, so that a tool can parse a file, change its tasks, and write
+// them back without the file drifting. The rules are:
+//  - times are kept only if both are set, the end isn't before the start,
+//    and the task isn't all day, and to the minute; a span is kept if the
+//    task has a start time and the span ends on a later day, and then the
+//    end time is the end of the first day,
+//  - details and checklist text lose blank lines and runs of whitespace,
+//    words that would be read as tokens or comments are put in backticks,
+//    and a ';' in the first line of details, which would start another task,
+//    becomes ',',
+//  - checklist items are nested at most one level deeper than the item
+//    above them, the first not at all,
+//  - tags and attachments lose whitespace, ';' and comment openers, empty ones
+//    are dropped, and a tag starting with 'file:' becomes an attachment,
+//  - notes lose empty paragraphs and runs of whitespace, and words that would
+//    be read as comments are put in backticks,
+//  - the location is made plain as plain_place says, and dropped if that
+//    leaves nothing,
+//  - priorities are clamped to 1 to 5,
+//  - the id loses the characters an '#id:' token can't have, and is dropped
+//    if that leaves nothing, and
+//  - the source is None, since it's the name of the file read.
+pub fn normalize(task: &Task) -> Task {
+    let to_minute = |time: NaiveTime| time.with_second(0).and_then(|time| time.with_nanosecond(0));
+    let span = match (task.start_time, task.span) {
+        (Some(_), Some(span)) if span.last > task.date && !task.all_day => {
+            to_minute(span.end).map(|end| Span { last: span.last, end })
+        },
+        _ => None,
+    };
+    let (start_time, end_time) = match (task.start_time, task.end_time) {
+        (Some(start), _) if span.is_some() => (to_minute(start), Some(time::end_of_day())),
+        (Some(start), Some(end)) if start <= end && !task.all_day => (to_minute(start), to_minute(end)),
+        _ => (None, None),
+    };
+    let details = plain_text(&task.details, true);
+    let details = match details.split_once('\n') {
+        Some((first, rest)) => format!("{}\n{}", first.replace(';', ","), rest),
+        None => details.replace(';', ","),
+    };
+    let mut attachments = Vec::new();
+    let mut tags = Vec::new();
+    for tag in task.tags.iter().map(|tag| plain_name(tag)).filter(|tag| !tag.is_empty()) {
+        match tag.strip_prefix("file:") {
+            Some(path) => attachments.push(path.to_string()),
+            None => tags.push(tag),
+        }
+    }
+    attachments.extend(task.attachments.iter().map(|path| plain_name(path)));
+    attachments.retain(|path| !path.is_empty());
+    return Task {
+        date: task.date,
+        start_time,
+        end_time,
+        all_day: task.all_day,
+        span,
+        details,
+        tags,
+        done: task.done,
+        subtasks: nested(&task.subtasks),
+        notes: task.notes.iter()
+            .map(|paragraph| plain_line(&paragraph.replace('\n', " "), false))
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect(),
+        attachments,
+        attendees: task.attendees,
+        priority: task.priority.map(|level| level.clamp(1, 5)),
+        location: task.location.as_deref().map(plain_place).filter(|place| !place.is_empty()),
+        id: task.id.as_ref()
+            .map(|id| id.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_').collect::<String>())
+            .filter(|id| !id.is_empty()),
+        source: None,
+    };
+}
+
+// normalize for a whole schedule: what parsing format_tasks' file gives
+// back, with the tasks in order of date.
+pub fn normalize_tasks(tasks: &[Task]) -> Vec<Task> {
+    let mut tasks: Vec<Task> = tasks.iter().map(normalize).collect();
+    tasks.sort_by_key(|task| task.date);
+    return tasks;
+}
+
+// A task in canonical form, for adding tasks that came from elsewhere to a
+// file, written as normalize(task).
+pub fn format_task(task: &Task) -> String {
+    let task = normalize(task);
+    let mut lines = task.details.lines();
+    let mut tokens = vec![lines.next().unwrap_or("").trim().to_string()];
+    if let (Some(start), Some(span)) = (task.start_time, task.span) {
+        tokens.push(format!("@{}--+{}d{}", format_time(start), (span.last - task.date).num_days(), format_time(span.end)));
+    } else if let [Some(start), Some(end)] = [task.start_time, task.end_time] {
+        tokens.push(format!("@{}--{}", format_time(start), format_time(end)));
+    } else if task.all_day {
+        tokens.push("@allday".to_string());
+    }
+    if let Some(place) = &task.location {
+        tokens.push(format_location(place));
+    }
+    if let Some(count) = task.attendees {
+        tokens.push(format!("&{}", count));
+    }
+    if let Some(level) = task.priority {
+        tokens.push(format!("!{}", level));
+    }
+    if let Some(id) = &task.id {
+        tokens.push(format!("#id:{}", id));
+    }
+    tokens.extend(task.tags.iter().map(|tag| format!("+{}", tag)));
+    tokens.extend(task.attachments.iter().map(|file| format!("+file:{}", file)));
+    tokens.retain(|token| !token.is_empty());
+    let first = format!("{} {}", marker(task.done), tokens.join(" "));
+    let mut out = with_hard_breaks(first, lines, 2);
+    for (i, paragraph) in task.notes.iter().enumerate() {
+        if i > 0 {
+            out.push_str("  >\n");
+        }
+        out.push_str(&format!("  > {}\n", paragraph));
+    }
+    for subtask in &task.subtasks {
+        let mut lines = subtask.text.lines();
+        let first = format!("{}{} {}", item_indent(subtask.level), marker(subtask.done), lines.next().unwrap_or(""));
+        out.push_str(&with_hard_breaks(first, lines, 4 + 2 * subtask.level));
+    }
+    return out;
+}
+
+// A whole task file of `tasks`, one '# ' block per week starting on
+// `first_day` (and per year), such that `parse_tasks(&format_tasks(tasks, day))` is
+// `Ok(normalize_tasks(tasks))`. That holds for dates from 1969 to 2068, the
+// years a header's two digits can name.
+pub fn format_tasks(tasks: &[Task], first_day: Weekday) -> String {
+    let mut out = String::new();
+    let mut week = None;
+    let mut day = None;
+    for task in normalize_tasks(tasks) {
+        // Weeks that span New Year are split there, as the parser asks.
+        let new_year = task.date.with_ordinal(1).expect("every year has a first day");
+        let start = time::week_start(task.date, first_day).max(new_year);
+        if week != Some(start) {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("# {}\n", time::format_header_date(start)));
+            week = Some(start);
+        }
+        if day != Some(task.date) {
+            out.push_str(&format!("## {}\n", weekday_name(task.date.weekday())));
+            day = Some(task.date);
+        }
+        out.push_str(&format_task(&task));
+    }
+    return out;
+}
+
+// The canonical form of a task file. Refuses (rather than risk changing the
+// schedule) if the result wouldn't parse to exactly the same tasks.
+pub fn format_source(s: &str, options: &ParseOptions) -> Result<String, String> {
+    let describe = |errors: Vec<crate::ParseError>| {
+        return errors.iter().map(|e| e.to_string()).collect::<Vec<String>>().join("\n");
+    };
+    let before = parse_tasks_with(s, options).map_err(describe)?;
+    let mut out = String::new();
+    let mut items = Vec::new();
+    for (i, l) in normalize_source(s).split('\n').enumerate() {
+        let line = format_line(l, options, &mut items).map_err(|why| format!("{}: {}", i + 1, why))?;
+        out.push_str(&line);
+        out.push('\n');
+    }
+    let out = format!("{}\n", out.trim_end_matches('\n'));
+
+    let after = parse_tasks_with(&out, options).map_err(|errors| format!("formatting broke the file: {}", describe(errors)))?;
+    if before != after {
+        return Err("formatting would change the tasks; leaving the file alone".to_string());
+    }
+    return Ok(out);
+}
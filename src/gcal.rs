@@ -0,0 +1,278 @@
+// `wtd push-gcal`: one-way push of the coming days' tasks to a Google
+// Calendar through the Calendar API, for people whose phone only speaks to
+// Google.
+//
+// Authorization uses OAuth's device flow: the first run prints a code to
+// enter at google.com/device, and the refresh token it earns is kept in the
+// OS keyring (see credentials.rs) for later runs. Pushing is idempotent
+// without any state file: each event's ID is derived from its task, so a
+// re-run updates the events it made before, and events marked as wtd's whose
+// task is gone (or was edited, and so has a new ID) are deleted. Only the
+// pushed days are touched; past events stay as they were.
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use crate::{credentials, ics, percent_encode as encode, time, Task};
+
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const CALENDAR_URL: &str = "https://www.googleapis.com/calendar/v3/calendars";
+const SCOPE: &str = "https://www.googleapis.com/auth/calendar.events";
+// The keyring entry holding the refresh token.
+const TOKEN_SECRET: &str = "gcal-refresh-token";
+// The private extended property marking the events wtd manages.
+const MARKER: &str = "wtd";
+// The most days pushed at once, ten years' worth.
+pub const MAX_DAYS: i64 = 3660;
+
+pub struct GcalOptions<'a> {
+    /// An OAuth client of type "TVs and Limited Input devices".
+    pub client_id: &'a str,
+    pub client_secret: &'a str,
+    /// The calendar's ID, e.g. "primary".
+    pub calendar: &'a str,
+    /// How many days, starting today, to push.
+    pub days: i64,
+    /// The timezone the task file's times are in, which Google needs to
+    /// place them.
+    pub timezone: chrono_tz::Tz,
+}
+
+#[derive(Default)]
+pub struct Summary {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+impl Summary {
+    pub fn describe(&self) -> String {
+        return format!("{} created, {} updated, {} deleted", self.created, self.updated, self.deleted);
+    }
+}
+
+struct Client {
+    agent: ureq::Agent,
+    access_token: String,
+}
+
+fn read_json(response: Result<ureq::http::Response<ureq::Body>, ureq::Error>, what: &str) -> Result<(u16, Value), String> {
+    let mut response = response.map_err(|why| format!("{}: {}", what, why))?;
+    let status = response.status().as_u16();
+    let body = response.body_mut().read_to_string().map_err(|why| format!("{}: {}", what, why))?;
+    if body.trim().is_empty() {
+        return Ok((status, Value::Null));
+    }
+    let value = serde_json::from_str(&body).map_err(|why| format!("{}: unexpected response: {}", what, why))?;
+    return Ok((status, value));
+}
+
+fn error_code(value: &Value) -> &str {
+    return value["error"].as_str().unwrap_or("");
+}
+
+fn new_agent() -> ureq::Agent {
+    return ureq::Agent::config_builder().http_status_as_error(false).build().new_agent();
+}
+
+// Trades the stored refresh token for an access token, or None if Google no
+// longer accepts it (e.g. access was revoked) and the user has to authorize
+// again.
+fn refresh(agent: &ureq::Agent, options: &GcalOptions, refresh_token: &str) -> Result<Option<String>, String> {
+    let (status, value) = read_json(agent.post(TOKEN_URL).send_form([
+        ("client_id", options.client_id),
+        ("client_secret", options.client_secret),
+        ("refresh_token", refresh_token),
+        ("grant_type", "refresh_token"),
+    ]), "refreshing the Google token")?;
+    if status == 400 && error_code(&value) == "invalid_grant" {
+        return Ok(None);
+    }
+    return match value["access_token"].as_str() {
+        Some(token) if status == 200 => Ok(Some(token.to_string())),
+        _ => Err(format!("refreshing the Google token: {} {}", status, value)),
+    };
+}
+
+// The device flow: shows the user a code to enter in a browser, then polls
+// until they have (or the code expires).
+fn authorize(agent: &ureq::Agent, options: &GcalOptions) -> Result<String, String> {
+    let (status, device) = read_json(agent.post(DEVICE_CODE_URL).send_form([
+        ("client_id", options.client_id),
+        ("scope", SCOPE),
+    ]), "asking Google for a device code")?;
+    let (device_code, user_code, verification_url) =
+        match (device["device_code"].as_str(), device["user_code"].as_str(), device["verification_url"].as_str()) {
+            (Some(device_code), Some(user_code), Some(url)) => (device_code, user_code, url),
+            _ => return Err(format!("asking Google for a device code: {} {}", status, device)),
+        };
+    eprintln!("To let wtd use your Google Calendar, visit {} and enter the code {}", verification_url, user_code);
+    let mut interval = Duration::from_secs(device["interval"].as_u64().unwrap_or(5));
+    let deadline = Instant::now() + Duration::from_secs(device["expires_in"].as_u64().unwrap_or(1800));
+    while Instant::now() < deadline {
+        std::thread::sleep(interval);
+        let (status, value) = read_json(agent.post(TOKEN_URL).send_form([
+            ("client_id", options.client_id),
+            ("client_secret", options.client_secret),
+            ("device_code", device_code),
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+        ]), "waiting for Google authorization")?;
+        match error_code(&value) {
+            "authorization_pending" => continue,
+            "slow_down" => {
+                interval += Duration::from_secs(5);
+                continue;
+            },
+            "access_denied" => return Err("Google authorization was declined".to_string()),
+            _ => {},
+        }
+        let access_token = value["access_token"].as_str()
+            .ok_or_else(|| format!("waiting for Google authorization: {} {}", status, value))?;
+        if let Some(refresh_token) = value["refresh_token"].as_str() {
+            if let Err(why) = credentials::set(TOKEN_SECRET, refresh_token) {
+                eprintln!("Couldn't keep the Google token in the keyring, so the next run will ask again: {}", why);
+            }
+        }
+        return Ok(access_token.to_string());
+    }
+    return Err("the Google authorization code expired; run again to get a new one".to_string());
+}
+
+impl Client {
+    fn connect(options: &GcalOptions) -> Result<Client, String> {
+        let agent = new_agent();
+        let stored = credentials::get(TOKEN_SECRET).ok().filter(|token| !token.is_empty());
+        let refreshed = match stored {
+            Some(refresh_token) => refresh(&agent, options, &refresh_token)?,
+            None => None,
+        };
+        let access_token = match refreshed {
+            Some(token) => token,
+            None => authorize(&agent, options)?,
+        };
+        return Ok(Client { agent, access_token });
+    }
+
+    fn call(&self, method: &str, url: &str, body: Option<&Value>) -> Result<(u16, Value), String> {
+        let what = format!("{} {}", method, url);
+        let request = ureq::http::Request::builder().method(method).uri(url)
+            .header("Authorization", format!("Bearer {}", self.access_token));
+        let sent = match body {
+            Some(body) => request.header("Content-Type", "application/json").body(body.to_string())
+                .map_err(ureq::Error::from).and_then(|request| self.agent.run(request)),
+            None => request.body(()).map_err(ureq::Error::from).and_then(|request| self.agent.run(request)),
+        };
+        let (status, value) = read_json(sent, &what)?;
+        if status == 401 || status == 403 {
+            return Err(format!("{}: Google refused access ({}): {}", what, status, value["error"]["message"]));
+        }
+        return Ok((status, value));
+    }
+}
+
+// Event IDs may only use the characters of base32hex (0-9 and a-v), which
+// hex digits are; wtd's events are told from others by MARKER rather than
+// their IDs. Identical tasks on the same day are told apart by how many came
+// before.
+pub fn event_ids(tasks: &[&Task]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    return tasks.iter().map(|task| {
+        let mut key = format!("{}{:?}{:?}{}{:?}", task.date, task.start_time, task.end_time, task.details, task.tags);
+        if let Some(span) = task.span {
+            key.push_str(&format!("{:?}", span));
+        }
+        let count = seen.entry(key.clone()).or_insert(0);
+        *count += 1;
+        return sha1_smol::Sha1::from(format!("{}{}", key, count)).digest().to_string();
+    }).collect();
+}
+
+fn event(task: &Task, id: &str, tz: chrono_tz::Tz) -> Value {
+    let (start, end) = match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => {
+            let (last, end) = task.span.map_or((task.date, end), |span| (span.last, span.end));
+            (json!({ "dateTime": time::format_rfc3339(tz, task.date, start) }), json!({ "dateTime": time::format_rfc3339(tz, last, end) }))
+        },
+        _ => (json!({ "date": time::format_iso_date(task.date) }),
+              json!({ "date": time::format_iso_date(time::next_day(task.date)) })),
+    };
+    return json!({
+        "id": id,
+        "status": "confirmed",
+        "summary": task.details.lines().next().unwrap_or(""),
+        "description": ics::description(task),
+        "location": task.location,
+        "start": start,
+        "end": end,
+        "extendedProperties": { "private": { MARKER: "true" } },
+    });
+}
+
+// The IDs of wtd's events between `from` and `to` (both RFC 3339).
+fn list_ours(client: &Client, calendar_url: &str, from: &str, to: &str) -> Result<Vec<String>, String> {
+    let mut ids = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut url = format!("{}/events?privateExtendedProperty={}&timeMin={}&timeMax={}&maxResults=2500",
+                              calendar_url, encode(&format!("{}=true", MARKER)), encode(from), encode(to));
+        if let Some(token) = &page_token {
+            url.push_str(&format!("&pageToken={}", encode(token)));
+        }
+        let (status, value) = client.call("GET", &url, None)?;
+        if status != 200 {
+            return Err(format!("GET {}: unexpected status {}", url, status));
+        }
+        for item in value["items"].as_array().into_iter().flatten() {
+            if let Some(id) = item["id"].as_str() {
+                ids.push(id.to_string());
+            }
+        }
+        match value["nextPageToken"].as_str() {
+            Some(token) => page_token = Some(token.to_string()),
+            None => return Ok(ids),
+        }
+    }
+}
+
+pub fn push(tasks: &[Task], options: &GcalOptions) -> Result<Summary, String> {
+    let from = time::now_in(options.timezone).date();
+    let to = from + chrono::Duration::days(options.days);
+    let pushed: Vec<&Task> = tasks.iter().filter(|task| from <= task.date && task.date < to).collect();
+    let ids = event_ids(&pushed);
+
+    let client = Client::connect(options)?;
+    let calendar_url = format!("{}/{}", CALENDAR_URL, encode(options.calendar));
+    let mut summary = Summary::default();
+    for (task, id) in pushed.iter().zip(&ids) {
+        // Updating brings back an event deleted in Google Calendar, which
+        // keeps its ID; only if there never was one is it created.
+        let body = event(task, id, options.timezone);
+        let (status, value) = client.call("PUT", &format!("{}/events/{}", calendar_url, id), Some(&body))?;
+        match status {
+            200 => summary.updated += 1,
+            404 => {
+                let (status, value) = client.call("POST", &format!("{}/events", calendar_url), Some(&body))?;
+                if status != 200 {
+                    return Err(format!("creating an event for {}: {} {}", task.date, status, value));
+                }
+                summary.created += 1;
+            },
+            _ => return Err(format!("updating the event for {}: {} {}", task.date, status, value)),
+        }
+    }
+
+    let current: HashSet<&String> = ids.iter().collect();
+    let window = (time::format_rfc3339(options.timezone, from, time::midnight()),
+                  time::format_rfc3339(options.timezone, to, time::midnight()));
+    for id in list_ours(&client, &calendar_url, &window.0, &window.1)? {
+        if current.contains(&id) {
+            continue;
+        }
+        let (status, value) = client.call("DELETE", &format!("{}/events/{}", calendar_url, id), None)?;
+        match status {
+            200..=299 | 404 | 410 => summary.deleted += 1,
+            _ => return Err(format!("deleting event {}: {} {}", id, status, value)),
+        }
+    }
+    return Ok(summary);
+}
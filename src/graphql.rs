@@ -0,0 +1,337 @@
+// A read-only GraphQL endpoint over the task model, for dashboards that want
+// to pick exactly the fields they need. Only the subset of GraphQL needed for
+// plain queries is supported: nested selection sets and literal arguments (no
+// variables, fragments, or directives).
+//
+//   { tasks(from: "2024-03-04", to: "2024-03-10", tag: "busy") { date start end endDate allDay details location tags done }
+//     days(from: "2024-03-04", to: "2024-03-10") { date weekday tasks { start end } }
+//     tags { name count }
+//     freebusy(from: "2024-03-04", to: "2024-03-10") { date start end } }
+//
+// Unless private details are enabled, task details and locations are only
+// returned for `+public` tasks and only publicly described tags are listed.
+use std::collections::{BTreeMap, HashMap};
+use chrono::{Duration, NaiveDate};
+use serde_json::{json, Map, Value};
+use crate::{last_date, parse_cli_date, split_days, time, Task};
+
+// How deeply selection sets may nest; the deepest useful query,
+// `{ days { tasks { start } } }`, nests three deep.
+const MAX_DEPTH: usize = 8;
+
+// The most days `days` and `freebusy` cover at once.
+const MAX_DAYS: i64 = 366;
+
+struct Field {
+    name: String,
+    args: Vec<(String, String)>,
+    selection: Vec<Field>,
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_ignored(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || c == ',' {
+                self.chars.next();
+            } else if c == '#' {
+                for c in self.chars.by_ref() {
+                    if c == '\n' { break; }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_ignored();
+        return self.chars.peek().copied();
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => { self.chars.next(); Ok(()) },
+            Some(c) => Err(format!("expected '{}' but found '{}'", expected, c)),
+            None => Err(format!("expected '{}' but the query ended", expected)),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        self.skip_ignored();
+        let mut name = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_alphanumeric() || c == '_' {
+                name.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        if name.is_empty() {
+            return Err("expected a name".to_string());
+        }
+        return Ok(name);
+    }
+
+    fn value(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            // Numbers, booleans, and enum values are passed through as written.
+            return self.name();
+        }
+        self.chars.next();
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.chars.next() {
+                    Some(c) => value.push(c),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some(c) => value.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    // The fields between '{' and '}', themselves `depth` selection sets deep.
+    fn selection_set(&mut self, depth: usize) -> Result<Vec<Field>, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("selections can only nest {} deep", MAX_DEPTH));
+        }
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        while self.peek() != Some('}') {
+            if self.peek().is_none() {
+                return Err("expected '}' but the query ended".to_string());
+            }
+            let name = self.name()?;
+            let mut args = Vec::new();
+            if self.peek() == Some('(') {
+                self.chars.next();
+                while self.peek() != Some(')') {
+                    let arg = self.name()?;
+                    self.expect(':')?;
+                    args.push((arg, self.value()?));
+                }
+                self.chars.next();
+            }
+            let selection = if self.peek() == Some('{') { self.selection_set(depth + 1)? } else { Vec::new() };
+            fields.push(Field { name, args, selection });
+        }
+        self.chars.next();
+        return Ok(fields);
+    }
+}
+
+fn parse_query(query: &str) -> Result<Vec<Field>, String> {
+    let mut parser = Parser { chars: query.chars().peekable() };
+    if parser.peek() != Some('{') {
+        let keyword = parser.name()?;
+        if keyword != "query" {
+            return Err(format!("only queries are supported, not '{}'", keyword));
+        }
+        if parser.peek() != Some('{') {
+            parser.name()?;
+        }
+    }
+    let fields = parser.selection_set(1)?;
+    if parser.peek().is_some() {
+        return Err("unexpected text after the query".to_string());
+    }
+    return Ok(fields);
+}
+
+struct Context<'a> {
+    tasks: &'a [Task],
+    expose_private: bool,
+    public_tags: &'a HashMap<String, String>,
+}
+
+fn arg<'a>(field: &'a Field, name: &str) -> Option<&'a str> {
+    return field.args.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+}
+
+fn date_arg(field: &Field, name: &str) -> Result<Option<NaiveDate>, String> {
+    return match arg(field, name) {
+        Some(s) => parse_cli_date(s).map(Some),
+        None => Ok(None),
+    };
+}
+
+fn date_range(field: &Field) -> Result<(NaiveDate, NaiveDate), String> {
+    let from = date_arg(field, "from")?.unwrap_or_else(time::today);
+    let to = match date_arg(field, "to")? {
+        Some(to) => to,
+        None => from.checked_add_signed(Duration::days(13))
+            .ok_or_else(|| format!("'{}' needs a 'to' date this close to the end of the calendar", field.name))?,
+    };
+    if to - from >= Duration::days(MAX_DAYS) {
+        return Err(format!("'{}' can cover at most {} days at once", field.name, MAX_DAYS));
+    }
+    return Ok((from, to));
+}
+
+fn select(field: &Field, type_name: &str, resolve: &dyn Fn(&Field) -> Result<Value, String>) -> Result<Value, String> {
+    if field.selection.is_empty() {
+        return Err(format!("field '{}' of type {} needs a selection of subfields", field.name, type_name));
+    }
+    let mut object = Map::new();
+    for sub in &field.selection {
+        object.insert(sub.name.clone(), resolve(sub)?);
+    }
+    return Ok(Value::Object(object));
+}
+
+fn time_value(t: Option<chrono::NaiveTime>) -> Value {
+    return t.map_or(Value::Null, |t| json!(time::format_hhmm(t)));
+}
+
+fn task_value(ctx: &Context, field: &Field, task: &Task) -> Result<Value, String> {
+    let public_tags = ctx.public_tags;
+    let is_public = task.tags.iter().any(|t| t == "public");
+    return select(field, "Task", &|sub| Ok(match sub.name.as_str() {
+        "date" => json!(time::format_iso_date(task.date)),
+        "start" => time_value(task.start_time),
+        "end" => time_value(task.span.map(|span| span.end).or(task.end_time)),
+        "endDate" => json!(time::format_iso_date(last_date(task))),
+        "allDay" => json!(task.all_day),
+        "done" => json!(task.done),
+        "details" if ctx.expose_private || is_public => json!(task.details),
+        "details" => Value::Null,
+        "location" if ctx.expose_private || is_public => json!(task.location),
+        "location" => Value::Null,
+        "tags" => json!(task.tags.iter()
+                        .filter(|t| ctx.expose_private || public_tags.contains_key(t.as_str()))
+                        .collect::<Vec<_>>()),
+        other => return Err(format!("unknown field '{}' on Task", other)),
+    }));
+}
+
+fn resolve_tasks(ctx: &Context, field: &Field) -> Result<Value, String> {
+    let from = date_arg(field, "from")?;
+    let to = date_arg(field, "to")?;
+    let tag = arg(field, "tag");
+    // Filtering on a private tag would tell which tasks have it.
+    if let Some(tag) = tag {
+        if !ctx.expose_private && !ctx.public_tags.contains_key(tag) {
+            return Err(format!("'{}' is not a public tag", tag));
+        }
+    }
+    let mut values = Vec::new();
+    for task in ctx.tasks {
+        if from.is_some_and(|from| task.date < from) || to.is_some_and(|to| task.date > to) {
+            continue;
+        }
+        if let Some(tag) = tag {
+            if !task.tags.iter().any(|t| t == tag) {
+                continue;
+            }
+        }
+        values.push(task_value(ctx, field, task)?);
+    }
+    return Ok(Value::Array(values));
+}
+
+fn resolve_days(ctx: &Context, field: &Field) -> Result<Value, String> {
+    let (from, to) = date_range(field)?;
+    let mut values = Vec::new();
+    for date in from.iter_days().take_while(|date| *date <= to) {
+        values.push(select(field, "Day", &|sub| Ok(match sub.name.as_str() {
+            "date" => json!(time::format_iso_date(date)),
+            "weekday" => json!(time::format_weekday(date)),
+            "tasks" => {
+                let mut tasks = Vec::new();
+                for task in ctx.tasks.iter().filter(|t| t.date == date) {
+                    tasks.push(task_value(ctx, sub, task)?);
+                }
+                Value::Array(tasks)
+            },
+            other => return Err(format!("unknown field '{}' on Day", other)),
+        }))?);
+    }
+    return Ok(Value::Array(values));
+}
+
+fn resolve_tags(ctx: &Context, field: &Field) -> Result<Value, String> {
+    let public_tags = ctx.public_tags;
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for task in ctx.tasks {
+        for tag in &task.tags {
+            if ctx.expose_private || public_tags.contains_key(tag.as_str()) {
+                *counts.entry(tag.as_str()).or_default() += 1;
+            }
+        }
+    }
+    let mut values = Vec::new();
+    for (name, count) in counts {
+        values.push(select(field, "Tag", &|sub| Ok(match sub.name.as_str() {
+            "name" => json!(name),
+            "count" => json!(count),
+            "description" => public_tags.get(name).map_or(Value::Null, |d| json!(d)),
+            other => return Err(format!("unknown field '{}' on Tag", other)),
+        }))?);
+    }
+    return Ok(Value::Array(values));
+}
+
+// Busy spans per day, with overlapping and back-to-back tasks merged so that
+// individual events can't be told apart. All-day tasks take the whole day.
+fn resolve_freebusy(ctx: &Context, field: &Field) -> Result<Value, String> {
+    let (from, to) = date_range(field)?;
+    let mut spans: Vec<(NaiveDate, chrono::NaiveTime, chrono::NaiveTime)> = split_days(ctx.tasks).iter()
+        .filter(|t| t.date >= from && t.date <= to)
+        .filter_map(|t| match [t.start_time, t.end_time] {
+            [Some(start), Some(end)] => Some((t.date, start, end)),
+            _ if t.all_day => Some((t.date, time::midnight(), time::end_of_day())),
+            _ => None,
+        })
+        .collect();
+    spans.sort();
+    let mut merged: Vec<(NaiveDate, chrono::NaiveTime, chrono::NaiveTime)> = Vec::new();
+    for (date, start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if last.0 == date && start <= last.2 => last.2 = last.2.max(end),
+            _ => merged.push((date, start, end)),
+        }
+    }
+    let mut values = Vec::new();
+    for (date, start, end) in merged {
+        values.push(select(field, "Busy", &|sub| Ok(match sub.name.as_str() {
+            "date" => json!(time::format_iso_date(date)),
+            "start" => time_value(Some(start)),
+            "end" => time_value(Some(end)),
+            other => return Err(format!("unknown field '{}' on Busy", other)),
+        }))?);
+    }
+    return Ok(Value::Array(values));
+}
+
+fn resolve(ctx: &Context, fields: &[Field]) -> Result<Value, String> {
+    let mut data = Map::new();
+    for field in fields {
+        let value = match field.name.as_str() {
+            "tasks" => resolve_tasks(ctx, field)?,
+            "days" => resolve_days(ctx, field)?,
+            "tags" => resolve_tags(ctx, field)?,
+            "freebusy" => resolve_freebusy(ctx, field)?,
+            other => return Err(format!("unknown field '{}' on Query", other)),
+        };
+        data.insert(field.name.clone(), value);
+    }
+    return Ok(Value::Object(data));
+}
+
+// Runs a query and returns the JSON response body, with any error reported in
+// the standard `errors` list.
+pub fn execute(query: &str, tasks: &[Task], expose_private: bool, public_tags: &HashMap<String, String>) -> String {
+    let ctx = Context { tasks, expose_private, public_tags };
+    let response = match parse_query(query).and_then(|fields| resolve(&ctx, &fields)) {
+        Ok(data) => json!({ "data": data }),
+        Err(message) => json!({ "errors": [{ "message": message }] }),
+    };
+    return response.to_string();
+}
@@ -0,0 +1,101 @@
+// Commands of your own, set under [hooks] in the config, that wtd runs at
+// three points, each given a JSON object on standard input:
+//  - pre_parse, before a task file is read: {"path": ..., "source": ...}.
+//    Printing an object with a "source" has that read instead, e.g. to expand
+//    shorthand of your own.
+//  - post_render, after a calendar page is rendered: {"calendar": "public"
+//    or "private", "tasks": [...], "html": ...}, the tasks as `wtd json` gives
+//    them for that calendar. Printing an object with an "html" replaces the
+//    page.
+//  - pre_publish, before the rendered files are written: {"files": [...],
+//    "tasks": [...]}, the tasks the calendars were rendered from, with
+//    their private details.
+// Printing nothing leaves things as they were. Several commands at one point
+// run in turn, each given the object the one before it printed. A command
+// that exits nonzero stops wtd with what it wrote to standard error; before
+// publishing, that's a veto, and none of the files are written.
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use serde_json::{json, Value};
+use crate::{json, CalendarPrivacy, Task};
+
+// Runs `command` through the shell with `input` on standard input, returning
+// the object it printed, if any.
+fn run(point: &str, command: &str, input: &Value) -> Result<Option<Value>, String> {
+    let mut shell = if cfg!(windows) { Command::new("cmd") } else { Command::new("sh") };
+    shell.arg(if cfg!(windows) { "/C" } else { "-c" }).arg(command);
+    let mut child = shell
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|why| format!("Couldn't run the {} hook `{}`: {}", point, command, why))?;
+    // Written from another thread, so that a hook printing as it reads can't
+    // fill its output pipe while wtd is still writing.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().map_err(|why| format!("The {} hook `{}` failed: {}", point, command, why))?;
+    // A hook may exit without reading everything, which isn't an error.
+    let _ = writer.join();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("The {} hook `{}` stopped wtd: {}", point, command, stderr.trim()));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        return Ok(None);
+    }
+    return match serde_json::from_str(&stdout) {
+        Ok(Value::Object(object)) => Ok(Some(Value::Object(object))),
+        _ => Err(format!("The {} hook `{}` printed something other than a JSON object", point, command)),
+    };
+}
+
+// Runs each command in turn on `input`, returning what the last left of it.
+fn run_all(point: &str, commands: &[String], mut input: Value) -> Result<Value, String> {
+    for command in commands {
+        if let Some(output) = run(point, command, &input)? {
+            input = output;
+        }
+    }
+    return Ok(input);
+}
+
+// The string a hook left under `key`.
+fn string(point: &str, output: &Value, key: &str) -> Result<String, String> {
+    return output[key].as_str().map(str::to_string)
+        .ok_or_else(|| format!("A {} hook printed an object without a \"{}\" string", point, key));
+}
+
+pub fn pre_parse(commands: &[String], path: &Path, source: String) -> Result<String, String> {
+    if commands.is_empty() {
+        return Ok(source);
+    }
+    let output = run_all("pre_parse", commands, json!({ "path": path.to_string_lossy(), "source": source }))?;
+    return string("pre_parse", &output, "source");
+}
+
+// `tasks` are those shown on the calendar, so already redacted for the
+// public one.
+pub fn post_render(commands: &[String], privacy: CalendarPrivacy, tasks: &[Task], public_tags: &HashMap<String, String>,
+                   html: String) -> Result<String, String> {
+    if commands.is_empty() {
+        return Ok(html);
+    }
+    let private = matches!(privacy, CalendarPrivacy::Private);
+    let output = run_all("post_render", commands, json!({
+        "calendar": if private { "private" } else { "public" },
+        "tasks": json::tasks_to_json(tasks, private, public_tags),
+        "html": html,
+    }))?;
+    return string("post_render", &output, "html");
+}
+
+pub fn pre_publish(commands: &[String], files: &[&Path], tasks: &[Task], public_tags: &HashMap<String, String>) -> Result<(), String> {
+    let files: Vec<String> = files.iter().map(|file| file.to_string_lossy().to_string()).collect();
+    run_all("pre_publish", commands, json!({ "files": files, "tasks": json::tasks_to_json(tasks, true, public_tags) }))?;
+    return Ok(());
+}
@@ -0,0 +1,500 @@
+// iCalendar (RFC 5545) export of the public calendar. Events carry the same
+// labels as the public HTML; public tasks also carry their details and
+// checklist in DESCRIPTION so attendees can see the plan.
+//
+// Also the import side, for overlaying calendars kept elsewhere (e.g. work
+// meetings exported from Outlook) onto the rendered grid. Only what the grid
+// can show is read: each event's summary and when it happens. Recurring
+// events are only shown on their first occurrence.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration as StdDuration;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use crate::{checklist_summary, cmp_tasks, public_label, time, OutOfOffice, Span, Task};
+
+// How out-of-office days show to people checking your free/busy time.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShowAs {
+    // OPAQUE: nobody should book you.
+    Busy,
+    // TRANSPARENT: marked, but other calendars' free/busy lookups ignore it.
+    Free,
+}
+
+pub(crate) fn escape_text(s: &str) -> String {
+    return s.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n");
+}
+
+// Content lines are limited to 75 octets; longer ones continue on lines
+// starting with a space.
+pub(crate) fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn checklist_text(task: &Task) -> String {
+    let mut text = format!("Checklist ({}):", checklist_summary(task));
+    for subtask in &task.subtasks {
+        text.push('\n');
+        text.push_str(&"  ".repeat(subtask.level));
+        text.push_str(if subtask.done { "[x] " } else { "[ ] " });
+        text.push_str(&subtask.text);
+    }
+    return text;
+}
+
+// A task's details followed by its notes and checklist, if it has them.
+pub fn description(task: &Task) -> String {
+    let mut description = task.details.clone();
+    for paragraph in &task.notes {
+        description.push_str("\n\n");
+        description.push_str(paragraph);
+    }
+    if !task.subtasks.is_empty() {
+        description.push_str("\n\n");
+        description.push_str(&checklist_text(task));
+    }
+    return description;
+}
+
+fn push_times(out: &mut String, task: &Task) {
+    match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => {
+            // DTSTAMP is required; using the start keeps the file
+            // unchanged between runs.
+            push_line(out, &format!("DTSTAMP:{}", time::format_ics_datetime(task.date, start)));
+            push_line(out, &format!("DTSTART:{}", time::format_ics_datetime(task.date, start)));
+            let (last, end) = task.span.map_or((task.date, end), |span| (span.last, span.end));
+            push_line(out, &format!("DTEND:{}", time::format_ics_datetime(last, end)));
+        },
+        _ => {
+            push_line(out, &format!("DTSTAMP:{}T000000", time::format_ics_date(task.date)));
+            push_line(out, &format!("DTSTART;VALUE=DATE:{}", time::format_ics_date(task.date)));
+            // Only all-day tasks get an end, which tells them apart from
+            // untimed ones when read back.
+            if task.all_day {
+                push_line(out, &format!("DTEND;VALUE=DATE:{}", time::format_ics_date(time::next_day(task.date))));
+            }
+        },
+    }
+}
+
+// Out-of-office stretches as multi-day, all-day events.
+fn push_out_of_office(out: &mut String, range: &OutOfOffice, show_as: ShowAs) {
+    let uid = sha1_smol::Sha1::from(format!("ooo{}{}", range.first, range.last)).digest().to_string();
+    push_line(out, "BEGIN:VEVENT");
+    push_line(out, &format!("UID:{}@wtd", &uid[..16]));
+    push_line(out, &format!("DTSTAMP:{}T000000", time::format_ics_date(range.first)));
+    push_line(out, &format!("DTSTART;VALUE=DATE:{}", time::format_ics_date(range.first)));
+    push_line(out, &format!("DTEND;VALUE=DATE:{}", time::format_ics_date(time::next_day(range.last))));
+    push_line(out, "SUMMARY:Out of office");
+    push_line(out, match show_as {
+        ShowAs::Busy => "TRANSP:OPAQUE",
+        ShowAs::Free => "TRANSP:TRANSPARENT",
+    });
+    push_line(out, "END:VEVENT");
+}
+
+pub fn tasks_to_ics(tasks: &[Task], out_of_office: &[OutOfOffice], show_as: ShowAs, public_tags: &HashMap<String, String>) -> String {
+    let mut sorted: Vec<&Task> = tasks.iter()
+        .filter(|t| t.start_time.is_some() || t.all_day || t.tags.iter().any(|tag| tag == "public"))
+        .collect();
+    sorted.sort_by(|a, b| cmp_tasks(a, b));
+
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//wtd//wtd//EN");
+    for task in sorted {
+        let is_public = task.tags.iter().any(|tag| tag == "public");
+        let label = public_label(task, public_tags);
+        // Derived from the task itself so the UID is stable across exports
+        // and doesn't reveal private details.
+        let mut key = format!("{}{:?}{:?}{}", task.date, task.start_time, task.end_time, task.details);
+        if let Some(span) = task.span {
+            key.push_str(&format!("{:?}", span));
+        }
+        let uid = sha1_smol::Sha1::from(key).digest().to_string();
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(&mut out, &format!("UID:{}@wtd", &uid[..16]));
+        push_times(&mut out, task);
+        let summary = if label.is_empty() { "busy".to_string() } else { label };
+        push_line(&mut out, &format!("SUMMARY:{}", escape_text(&summary)));
+        if is_public {
+            push_line(&mut out, &format!("DESCRIPTION:{}", escape_text(&description(task))));
+            if let Some(place) = &task.location {
+                push_line(&mut out, &format!("LOCATION:{}", escape_text(place)));
+            }
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+    for range in out_of_office {
+        push_out_of_office(&mut out, range, show_as);
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    return out;
+}
+
+// A single task as a calendar of its own, with every detail, for storing on
+// a CalDAV server (see sync.rs). Tags are kept as CATEGORIES so they survive
+// a round trip.
+pub fn task_to_event(task: &Task, uid: &str) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//wtd//wtd//EN");
+    push_line(&mut out, "BEGIN:VEVENT");
+    push_line(&mut out, &format!("UID:{}", uid));
+    push_times(&mut out, task);
+    push_line(&mut out, &format!("SUMMARY:{}", escape_text(&task.details)));
+    if !task.subtasks.is_empty() {
+        push_line(&mut out, &format!("DESCRIPTION:{}", escape_text(&checklist_text(task))));
+    }
+    if let Some(place) = &task.location {
+        push_line(&mut out, &format!("LOCATION:{}", escape_text(place)));
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| escape_text(tag)).collect();
+        push_line(&mut out, &format!("CATEGORIES:{}", tags.join(",")));
+    }
+    push_line(&mut out, "END:VEVENT");
+    push_line(&mut out, "END:VCALENDAR");
+    return out;
+}
+
+pub(crate) fn unescape_text(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(escaped) => out.push(escaped),
+            None => out.push('\\'),
+        }
+    }
+    return out;
+}
+
+// A dur-value such as "PT1H30M" or "P1D". Negative durations make no sense
+// for an event's length and aren't accepted.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let rest = s.strip_prefix('+').unwrap_or(s).strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {},
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match c {
+                    'W' => Duration::weeks(n),
+                    'D' => Duration::days(n),
+                    'H' => Duration::hours(n),
+                    'M' => Duration::minutes(n),
+                    _ => Duration::seconds(n),
+                };
+            },
+            _ => return None,
+        }
+    }
+    return Some(total);
+}
+
+enum When {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+}
+
+fn parse_when(params: &str, value: &str, tz: Option<Tz>) -> Option<When> {
+    if params.to_uppercase().contains("VALUE=DATE") && !params.to_uppercase().contains("VALUE=DATE-TIME") {
+        return time::parse_ics_date(value).map(When::Date);
+    }
+    let tzid = split_params(params).into_iter()
+        .find_map(|param| param.split_once('=').filter(|(name, _)| name.eq_ignore_ascii_case("TZID")).map(|(_, tzid)| tzid.trim_matches('"')));
+    return time::parse_ics_datetime(tz, value, tzid).map(When::DateTime);
+}
+
+#[derive(Default)]
+struct Event {
+    uid: String,
+    summary: String,
+    categories: Vec<String>,
+    location: Option<String>,
+    start: Option<When>,
+    end: Option<When>,
+    duration: Option<Duration>,
+    cancelled: bool,
+}
+
+fn event_task(date: NaiveDate, times: Option<(chrono::NaiveTime, chrono::NaiveTime)>, all_day: bool, event: &Event) -> Task {
+    return Task {
+        date,
+        start_time: times.map(|(start, _)| start),
+        end_time: times.map(|(_, end)| end),
+        all_day,
+        span: None,
+        details: event.summary.clone(),
+        tags: event.categories.clone(),
+        done: false,
+        subtasks: Vec::new(),
+        notes: Vec::new(),
+        attachments: Vec::new(),
+        attendees: None,
+        priority: None,
+        location: event.location.clone(),
+        id: None,
+        source: None,
+    };
+}
+
+// The tasks covering an event: one, spanning days if the event runs past
+// midnight, or one per day for a multi-day all-day event. All-day events become all-day tasks, except for those with
+// only a start date, which is how untimed tasks are written (see push_times).
+// A DURATION is elapsed time on clocks in `tz` (see time::clocks).
+fn event_tasks(event: &Event, tz: Option<Tz>) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    match event.start {
+        Some(When::Date(start)) => {
+            let all_day = event.end.is_some() || event.duration.is_some();
+            let end = match (&event.end, event.duration) {
+                (Some(When::Date(end)), _) => *end,
+                (_, Some(duration)) => start + Duration::days(duration.num_days().max(1)),
+                _ => time::next_day(start),
+            };
+            let mut day = start;
+            loop {
+                tasks.push(event_task(day, None, all_day, event));
+                day = time::next_day(day);
+                if day >= end {
+                    break;
+                }
+            }
+        },
+        Some(When::DateTime(start)) => {
+            let end = match (&event.end, event.duration) {
+                (Some(When::DateTime(end)), _) => *end,
+                (Some(When::Date(end)), _) => end.and_time(time::midnight()),
+                (None, Some(duration)) => time::add_elapsed(tz, start, duration),
+                (None, None) => start,
+            }.max(start);
+            let mut day = start.date();
+            while day <= end.date() {
+                let from = if day == start.date() { start.time() } else { time::midnight() };
+                let to = if day == end.date() { end.time() } else { time::end_of_day() };
+                if day != start.date() && to == time::midnight() {
+                    break;
+                }
+                tasks.push(event_task(day, Some((from, to)), false, event));
+                day = time::next_day(day);
+            }
+            // An event running across days is one task spanning them.
+            if tasks.len() > 1 {
+                let last = tasks.pop().expect("more than one task");
+                tasks.truncate(1);
+                tasks[0].span = Some(Span { last: last.date, end: last.end_time.expect("a timed task") });
+            }
+        },
+        None => {},
+    }
+    return tasks;
+}
+
+// The parameters of a property, split at the ';'s outside of quotes.
+pub(crate) fn split_params(params: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut from = 0;
+    for (i, c) in params.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                parts.push(&params[from..i]);
+                from = i + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(&params[from..]);
+    return parts.into_iter().filter(|part| !part.is_empty()).collect();
+}
+
+// A content line, e.g. "DTSTART;TZID=Europe/Paris:20240304T090000".
+pub(crate) struct Property<'a> {
+    // 1-based, counting the unfolded lines.
+    pub line: usize,
+    // Uppercased.
+    pub name: String,
+    // The parameters after the name's ';', if any, as written.
+    pub params: &'a str,
+    pub value: &'a str,
+}
+
+// Long lines folded onto continuation lines are joined back up.
+pub(crate) fn unfold(s: &str) -> Result<String, String> {
+    let unfolded = s.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    if !unfolded.trim_start().starts_with("BEGIN:VCALENDAR") {
+        return Err("not an iCalendar file".to_string());
+    }
+    return Ok(unfolded);
+}
+
+// The content lines of an unfolded iCalendar file, skipping any without a
+// value.
+pub(crate) fn properties(unfolded: &str) -> Vec<Property<'_>> {
+    let mut properties = Vec::new();
+    for (i, line) in unfolded.lines().enumerate() {
+        // The value starts at the first ':' outside of a quoted parameter.
+        let mut quoted = false;
+        let colon = line.char_indices().find(|&(_, c)| {
+            if c == '"' { quoted = !quoted; }
+            return c == ':' && !quoted;
+        }).map(|(i, _)| i);
+        let (name_params, value) = match colon {
+            Some(colon) => (&line[..colon], &line[colon + 1..]),
+            None => continue,
+        };
+        let (name, params) = name_params.split_once(';').unwrap_or((name_params, ""));
+        properties.push(Property { line: i + 1, name: name.to_uppercase(), params, value });
+    }
+    return properties;
+}
+
+// The events of an iCalendar file by UID, each as the tasks covering it and
+// tagged with its CATEGORIES. Events whose times can't be read are skipped
+// with a warning rather than failing, since the file usually comes from
+// another program. Times are read onto clocks in `tz`, that of the task
+// file they're joining.
+pub fn read_calendar(s: &str, tz: Option<Tz>) -> Result<Vec<(String, Vec<Task>)>, String> {
+    let unfolded = unfold(s)?;
+    let mut events = Vec::new();
+    let mut event: Option<Event> = None;
+    // Components nested in the event, such as VALARM, whose properties
+    // aren't the event's.
+    let mut nested = 0;
+    let mut recurring = 0;
+    for Property { line, name, params, value } in properties(&unfolded) {
+        match (name.as_str(), value) {
+            ("BEGIN", "VEVENT") => event = Some(Event::default()),
+            ("END", "VEVENT") => if let Some(event) = event.take() {
+                if event.start.is_none() {
+                    eprintln!("Skipping event {:?} ending on line {}: no readable DTSTART", event.summary, line);
+                } else if !event.cancelled {
+                    events.push((event.uid.clone(), event_tasks(&event, tz)));
+                }
+            },
+            ("BEGIN", _) if event.is_some() => nested += 1,
+            ("END", _) if event.is_some() => nested -= 1,
+            (property, value) => if let (Some(event), 0) = (event.as_mut(), nested) {
+                match property {
+                    "UID" => event.uid = value.to_string(),
+                    "SUMMARY" => event.summary = unescape_text(value),
+                    "LOCATION" => event.location = Some(unescape_text(value)).filter(|place| !place.trim().is_empty()),
+                    "CATEGORIES" => event.categories.extend(value.split(',').map(unescape_text)
+                        .map(|tag| tag.split_whitespace().collect::<Vec<&str>>().join("-"))
+                        .filter(|tag| !tag.is_empty())),
+                    "DTSTART" => event.start = parse_when(params, value, tz),
+                    "DTEND" => event.end = parse_when(params, value, tz),
+                    "DURATION" => event.duration = parse_duration(value),
+                    "STATUS" => event.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+                    "RRULE" | "RDATE" => recurring += 1,
+                    _ => {},
+                }
+            },
+        }
+    }
+    if recurring > 0 {
+        eprintln!("{} recurring event(s) are only shown on their first occurrence", recurring);
+    }
+    return Ok(events);
+}
+
+// The events of an iCalendar file as tasks tagged only `tag`, for overlaying
+// on the calendar.
+pub fn read_events(s: &str, tag: &str, tz: Option<Tz>) -> Result<Vec<Task>, String> {
+    let mut tasks = Vec::new();
+    for (_, event_tasks) in read_calendar(s, tz)? {
+        tasks.extend(event_tasks.into_iter().map(|task| Task { tags: vec![tag.to_string()], ..task }));
+    }
+    return Ok(tasks);
+}
+
+// Downloads a calendar published at an http(s) URL.
+fn fetch(url: &str) -> Result<String, String> {
+    let mut response = ureq::get(url).call().map_err(|why| why.to_string())?;
+    return response.body_mut().read_to_string().map_err(|why| why.to_string());
+}
+
+// Reads an iCalendar file (or the calendar at a URL given in its place) to
+// overlay on the calendar, naming the file in any error.
+pub fn load(path: &Path, tag: &str, tz: Option<Tz>) -> Result<Vec<Task>, String> {
+    let s = match path.to_str() {
+        Some(url) if url.starts_with("http://") || url.starts_with("https://") => fetch(url),
+        _ => std::fs::read_to_string(path).map_err(|why| why.to_string()),
+    }.map_err(|why| format!("{}: {}", path.display(), why))?;
+    return read_events(&s, tag, tz).map_err(|why| format!("{}: {}", path.display(), why));
+}
+
+// The --merge-ics calendars as last fetched, for `serve` and `render --watch`,
+// which fetch them in the background so that a slow or unreachable calendar
+// server never holds up a page.
+pub struct Prefetch<'a> {
+    calendars: &'a [PathBuf],
+    tz: Option<Tz>,
+    fetched: Mutex<HashMap<PathBuf, Vec<Task>>>,
+}
+
+impl<'a> Prefetch<'a> {
+    pub fn new(calendars: &'a [PathBuf], tz: Option<Tz>) -> Prefetch<'a> {
+        return Prefetch { calendars, tz, fetched: Mutex::default() };
+    }
+
+    // The events of every calendar fetched so far, in the order given.
+    pub fn tasks(&self) -> Vec<Task> {
+        let fetched = self.fetched.lock().expect("fetched calendars");
+        return self.calendars.iter().filter_map(|calendar| fetched.get(calendar)).flatten().cloned().collect();
+    }
+
+    // Fetches the calendars now and then every `every`, calling `changed`
+    // after each round that brought new events. A calendar that can't be
+    // fetched keeps the events it had.
+    pub fn keep_fetching(&self, every: StdDuration, changed: impl Fn()) {
+        if self.calendars.is_empty() {
+            return;
+        }
+        loop {
+            let mut any = false;
+            for calendar in self.calendars {
+                match load(calendar, "external", self.tz) {
+                    Ok(tasks) => {
+                        let previous = self.fetched.lock().expect("fetched calendars").insert(calendar.clone(), tasks.clone());
+                        any |= previous != Some(tasks);
+                    },
+                    Err(why) => eprintln!("Keeping the last copy of a merged calendar: {}", why),
+                }
+            }
+            if any {
+                changed();
+            }
+            thread::sleep(every);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// `wtd init`: a first-run walkthrough that writes a starter wtd.md for the
+// current week, a wtd.toml holding the answers, and the stylesheet the
+// calendars link to. Existing files are left alone.
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+use chrono::{Duration, NaiveDate, Weekday};
+use crate::{parse_hour_range, public_tags, time, weekday_name};
+
+pub const STYLESHEET: &str = include_str!("../calendar_style.css");
+
+// Asks until `parse` accepts the answer; an empty answer (or the end of
+// input) takes the default.
+fn ask<T>(input: &mut impl BufRead, question: &str, default: &str,
+          parse: impl Fn(&str) -> Result<T, String>) -> (String, T) {
+    loop {
+        print!("{} [{}]: ", question, default);
+        io::stdout().flush().ok();
+        let mut answer = String::new();
+        let answer = match input.read_line(&mut answer) {
+            Ok(n) if n > 0 && !answer.trim().is_empty() => answer.trim().to_string(),
+            _ => default.to_string(),
+        };
+        match parse(&answer) {
+            Ok(value) => return (answer, value),
+            Err(why) => println!("  {}", why),
+        }
+    }
+}
+
+// The system timezone's name where it can be found and is one wtd knows,
+// for reading wtd.md against.
+fn system_timezone() -> Option<String> {
+    let name = std::env::var("TZ").ok().filter(|tz| !tz.is_empty())
+        .or_else(|| std::fs::read_link("/etc/localtime").ok()
+            .and_then(|target| target.to_str().and_then(|t| t.split("zoneinfo/").nth(1)).map(|t| t.to_string())));
+    return name.filter(|name| time::parse_timezone(name).is_ok());
+}
+
+// This week's block, with an empty section for each day.
+pub fn skeleton(today: NaiveDate, first_day: Weekday) -> String {
+    let start = time::week_start(today, first_day);
+    let mut out = format!("# {}\n", time::format_short_date(start));
+    for offset in 0..7 {
+        out.push_str(&format!("## {}\n\n", time::format_weekday(start + Duration::days(offset))));
+    }
+    return out;
+}
+
+fn write_new(path: &Path, contents: &str) {
+    if path.exists() {
+        println!("{} already exists; leaving it alone", path.display());
+        return;
+    }
+    if let Err(why) = std::fs::write(path, contents) {
+        panic!("Couldn't write {}: {}", path.display(), why);
+    }
+    println!("Wrote {}", path.display());
+}
+
+pub fn run(input: &mut impl BufRead) {
+    let timezone = system_timezone();
+    match &timezone {
+        Some(timezone) => println!("Times in wtd.md are read as wall-clock times in {}.", timezone),
+        None => println!("Times in wtd.md are read on clocks that never change; set timezone under [parse] to follow a timezone's."),
+    }
+
+    let default_day = weekday_name(time::locale_week_start()).to_lowercase();
+    let (_, first_day) = ask(input, "First day of the week", &default_day, |answer| {
+        return Weekday::from_str(answer).map_err(|_| format!("'{}' is not a day of the week", answer));
+    });
+    let (work_hours, _) = ask(input, "Working hours, for `wtd stats idle`", "9-17", parse_hour_range);
+
+    println!("Events are private by default. Tag one +public to show its description, and");
+    println!("these tags are shown (and styled) on the public calendar:");
+    let public_tags = public_tags();
+    let mut tags: Vec<&String> = public_tags.keys().collect();
+    tags.sort();
+    for tag in tags {
+        println!("  +{:<10} {}", tag, public_tags[tag]);
+    }
+
+    write_new(Path::new("wtd.md"), &skeleton(time::today(), first_day));
+    let parse = timezone.map(|timezone| format!("[parse]\ntimezone = \"{}\"\n\n", timezone)).unwrap_or_default();
+    write_new(Path::new("wtd.toml"), &format!(
+        "# Written by `wtd init`; `wtd config init` writes a template of every setting.\n\n\
+         {}[render]\nweek_start = \"{}\"\n\n[stats]\nwork_hours = \"{}\"\n",
+         parse, weekday_name(first_day).to_lowercase(), work_hours));
+    write_new(Path::new("calendar_style.css"), STYLESHEET);
+}
@@ -0,0 +1,163 @@
+// `wtd accept`: adds the event of an emailed invite (an iCalendar file sent
+// with METHOD:REQUEST, usually attached as invite.ics) to the task file under
+// its day, and can write the REPLY telling the organizer whether you're
+// coming, to attach to an email back. The event is read as for --merge-ics,
+// except that the organizer's CATEGORIES aren't kept: their tags needn't be
+// yours.
+use std::path::Path;
+use chrono::Weekday;
+use crate::edit::insert_task;
+use crate::ics::{self, escape_text, push_line, Property};
+use crate::{check_editable, format, lock, parse_tasks_with, read_file, time, ParseOptions, Task};
+
+pub struct AcceptOptions {
+    // Tag the event +tentative, and reply TENTATIVE rather than ACCEPTED.
+    pub tentative: bool,
+    pub parse: ParseOptions,
+    // For the week block of an event on a day the file has no block for.
+    pub week_start: Weekday,
+}
+
+#[derive(Default)]
+pub struct Invite {
+    pub uid: String,
+    pub summary: String,
+    // The tasks covering the event, as ics::read_calendar reads it.
+    pub tasks: Vec<Task>,
+    // The lines a reply repeats as they were written: ORGANIZER, DTSTART,
+    // and so on.
+    copied: Vec<String>,
+    // Each ATTENDEE's parameters and address, e.g.
+    // ("CN=Ann;RSVP=TRUE", "mailto:ann@example.com").
+    attendees: Vec<(String, String)>,
+}
+
+// The first event of an invite. An invite with more (the exceptions to a
+// recurring event) is accepted for the first only. Its times are read onto
+// clocks in `tz`, that of the task file it's added to.
+pub fn read_invite(s: &str, tz: Option<chrono_tz::Tz>) -> Result<Invite, String> {
+    let unfolded = ics::unfold(s)?;
+    let mut invite = Invite::default();
+    let mut events = 0;
+    let mut in_event = false;
+    // Components nested in the event, such as VALARM.
+    let mut nested = 0;
+    for Property { name, params, value, .. } in ics::properties(&unfolded) {
+        match (name.as_str(), value) {
+            ("METHOD", method) if method.eq_ignore_ascii_case("CANCEL") => {
+                return Err("this is a cancellation, not an invite; delete the event instead".to_string());
+            },
+            ("BEGIN", "VEVENT") => {
+                events += 1;
+                in_event = events == 1;
+            },
+            ("END", "VEVENT") => in_event = false,
+            ("BEGIN", _) if in_event => nested += 1,
+            ("END", _) if in_event => nested -= 1,
+            (property, value) if in_event && nested == 0 => match property {
+                "UID" => invite.uid = value.to_string(),
+                "SUMMARY" => invite.summary = ics::unescape_text(value),
+                "ATTENDEE" => invite.attendees.push((params.to_string(), value.to_string())),
+                "ORGANIZER" | "DTSTART" | "DTEND" | "DURATION" | "SEQUENCE" | "RECURRENCE-ID" => {
+                    let params = if params.is_empty() { String::new() } else { format!(";{}", params) };
+                    invite.copied.push(format!("{}{}:{}", property, params, value));
+                },
+                _ => {},
+            },
+            _ => {},
+        }
+    }
+    if events > 1 {
+        eprintln!("The invite has {} events; accepting only the first", events);
+    }
+    invite.tasks = ics::read_calendar(s, tz)?.into_iter()
+        .find(|(uid, _)| *uid == invite.uid)
+        .map(|(_, tasks)| tasks)
+        .unwrap_or_default();
+    if invite.tasks.is_empty() {
+        return Err("the invite has no event with a time wtd can read".to_string());
+    }
+    return Ok(invite);
+}
+
+fn same_event(a: &Task, b: &Task) -> bool {
+    return a.date == b.date && a.start_time == b.start_time && a.end_time == b.end_time && a.span == b.span
+        && a.details == b.details;
+}
+
+// Adds the invite's event to the task file at `path`, unless a task at the
+// same time with the same description is already there, so accepting twice
+// adds it once. Returns the tasks added.
+pub fn accept(path: &Path, invite: &Invite, options: &AcceptOptions) -> Result<Vec<Task>, String> {
+    check_editable(path)?;
+    let _lock = lock::lock(path)?;
+    let describe = |errors: Vec<crate::ParseError>| {
+        return errors.iter().map(|e| format!("{}:{}", path.display(), e)).collect::<Vec<String>>().join("\n");
+    };
+    let original = read_file(path)?;
+    let existing = parse_tasks_with(&original, &options.parse).map_err(describe)?;
+    let mut source = original.clone();
+    let mut added = Vec::new();
+    for task in &invite.tasks {
+        let tags = if options.tentative { vec!["tentative".to_string()] } else { Vec::new() };
+        let task = format::normalize(&Task { tags, ..task.clone() });
+        if existing.iter().any(|other| same_event(other, &task)) {
+            continue;
+        }
+        source = insert_task(&source, &task, &options.parse, options.week_start);
+        added.push(task);
+    }
+    parse_tasks_with(&source, &options.parse).map_err(describe)?;
+    lock::write_checked(path, &original, &source)?;
+    return Ok(added);
+}
+
+// "mailto:Ann@Example.com" as "ann@example.com".
+fn address(s: &str) -> String {
+    let s = s.trim();
+    let s = match s.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &s[7..],
+        _ => s,
+    };
+    return s.to_lowercase();
+}
+
+// The REPLY to the invite from `me` (an address, with or without mailto:), or
+// from its only attendee if not given.
+pub fn reply(invite: &Invite, me: Option<&str>, tentative: bool) -> Result<String, String> {
+    let attendees: Vec<String> = invite.attendees.iter().map(|(_, value)| address(value)).collect();
+    let attendee = match me {
+        Some(me) => invite.attendees.iter().find(|(_, value)| address(value) == address(me))
+            .ok_or_else(|| format!("{} isn't among the invite's attendees ({})", me, attendees.join(", ")))?,
+        None => match &invite.attendees[..] {
+            [only] => only,
+            [] => return Err("the invite names no attendees to reply as".to_string()),
+            _ => return Err(format!("pass --me with your address to reply; the invite is to {}", attendees.join(", "))),
+        },
+    };
+    if !invite.copied.iter().any(|line| line.starts_with("ORGANIZER")) {
+        return Err("the invite has no ORGANIZER to reply to".to_string());
+    }
+    // The attendee as invited, e.g. with their name, but with their answer
+    // in place of the request for one.
+    let mut params: Vec<&str> = ics::split_params(&attendee.0).into_iter()
+        .filter(|param| !param.to_uppercase().starts_with("PARTSTAT=") && !param.to_uppercase().starts_with("RSVP="))
+        .collect();
+    params.insert(0, if tentative { "PARTSTAT=TENTATIVE" } else { "PARTSTAT=ACCEPTED" });
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//wtd//wtd//EN");
+    push_line(&mut out, "METHOD:REPLY");
+    push_line(&mut out, "BEGIN:VEVENT");
+    push_line(&mut out, &format!("UID:{}", invite.uid));
+    push_line(&mut out, &format!("DTSTAMP:{}", time::ics_utc_now()));
+    for line in &invite.copied {
+        push_line(&mut out, line);
+    }
+    push_line(&mut out, &format!("ATTENDEE;{}:{}", params.join(";"), attendee.1));
+    push_line(&mut out, &format!("SUMMARY:{}", escape_text(&invite.summary)));
+    push_line(&mut out, "END:VEVENT");
+    push_line(&mut out, "END:VCALENDAR");
+    return Ok(out);
+}
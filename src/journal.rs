@@ -0,0 +1,155 @@
+// The journal: a directory of dated markdown files beside the task file, one
+// a day (journal/2026-10-16.md), kept by the tasks tagged +journal. `wtd open
+// TASK` opens the entry of a +journal task's day in $EDITOR, first writing it
+// from a template if there's none yet. The private calendar links each
+// +journal task to its day's entry, and a task with an '#id:' to the entries
+// that mention it as [[NAME]], so what was written about a meeting can be
+// found from the calendar.
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use chrono::NaiveDate;
+use regex::Regex;
+use crate::{find_tasks, json, time, Task};
+
+// The entry written when there's no template: the day, and a section for
+// each of its +journal tasks.
+pub const TEMPLATE: &str = "# {{ day }}\n{% for task in tasks %}\n## {{ task.details }}\n{% endfor %}";
+
+#[derive(Clone, Default)]
+pub struct Journal {
+    // The directory, relative to the task file's, as the calendars link it.
+    pub dir: PathBuf,
+    // The days with an entry.
+    pub dates: BTreeSet<NaiveDate>,
+    // The days whose entries mention each task id.
+    pub mentions: HashMap<String, Vec<NaiveDate>>,
+}
+
+pub struct OpenOptions<'a> {
+    // The journal directory, relative to the task file's.
+    pub dir: &'a Path,
+    // A template of your own for new entries, in place of TEMPLATE.
+    pub template: Option<&'a (PathBuf, String)>,
+    // Open the entry of the task on this day, rather than the nearest.
+    pub on: Option<NaiveDate>,
+    pub public_tags: &'a HashMap<String, String>,
+}
+
+pub fn is_journaled(task: &Task) -> bool {
+    return task.tags.iter().any(|tag| tag == "journal");
+}
+
+// The day's entry, under the journal directory `dir`.
+pub fn entry_path(dir: &Path, date: NaiveDate) -> PathBuf {
+    return dir.join(format!("{}.md", time::format_iso_date(date)));
+}
+
+// The entries under `input_dir`/`dir`. Files not named for a day are left
+// alone, so the journal can keep other notes too.
+pub fn load(input_dir: &Path, dir: &Path) -> Journal {
+    let mention = Regex::new(r"\[\[([A-Za-z0-9_-]+)\]\]").expect("a valid regex");
+    let mut journal = Journal { dir: dir.to_path_buf(), ..Journal::default() };
+    let entries = match std::fs::read_dir(input_dir.join(dir)) {
+        Ok(entries) => entries,
+        Err(_) => return journal,
+    };
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        let date = match path.file_stem().filter(|_| path.extension().is_some_and(|ext| ext == "md")) {
+            Some(stem) => time::parse_iso_date(&stem.to_string_lossy()),
+            None => None,
+        };
+        let date = match date {
+            Some(date) => date,
+            None => continue,
+        };
+        journal.dates.insert(date);
+        let text = std::fs::read_to_string(&path).unwrap_or_default();
+        for id in mention.captures_iter(&text).map(|captures| captures[1].to_string()) {
+            let dates = journal.mentions.entry(id).or_default();
+            if !dates.contains(&date) {
+                dates.push(date);
+            }
+        }
+    }
+    for dates in journal.mentions.values_mut() {
+        dates.sort();
+    }
+    return journal;
+}
+
+// The day of the +journal task `query` names: by its '#id:', or else by words
+// of its description, ignoring case. Of several days, `on` if given, else
+// today, else the latest before today, else the soonest after.
+fn find_day(tasks: &[Task], query: &str, on: Option<NaiveDate>, today: NaiveDate) -> Result<NaiveDate, String> {
+    let matching: Vec<&Task> = find_tasks(tasks, query).into_iter().map(|i| &tasks[i]).collect();
+    if matching.is_empty() {
+        return Err(format!("No task's id or description matches {:?}", query));
+    }
+    let journaled: Vec<&&Task> = matching.iter().filter(|task| is_journaled(task)).collect();
+    if journaled.is_empty() {
+        return Err(format!("{:?} on {} isn't tagged +journal; tag it to keep a journal of it",
+                           matching[0].details, time::format_date(matching[0].date)));
+    }
+    let dates: BTreeSet<NaiveDate> = journaled.iter().map(|task| task.date).collect();
+    if let Some(on) = on {
+        return match dates.contains(&on) {
+            true => Ok(on),
+            false => Err(format!("{:?} isn't on {}; it's on {}", query, time::format_date(on),
+                                 dates.iter().map(|date| time::format_date(*date)).collect::<Vec<String>>().join(", "))),
+        };
+    }
+    return Ok(time::nearest_day(&dates, today).expect("a matching task has a date"));
+}
+
+// Writes the day's entry from the template, filled in by minijinja with day
+// (e.g. "Fri 10/16/26"), date ("2026-10-16"), and tasks, the day's +journal
+// tasks as `wtd json --private` gives them.
+fn write_entry(path: &Path, date: NaiveDate, tasks: &[Task], options: &OpenOptions) -> Result<(), String> {
+    let (name, template) = match options.template {
+        Some((path, template)) => (path.display().to_string(), template.as_str()),
+        None => ("the built-in template".to_string(), TEMPLATE),
+    };
+    let journaled: Vec<&Task> = tasks.iter().filter(|task| task.date == date && is_journaled(task)).collect();
+    let context = minijinja::context! {
+        day => time::format_date(date),
+        date => time::format_iso_date(date),
+        tasks => journaled.iter().map(|task| json::task_to_json(task, true, options.public_tags)).collect::<Vec<_>>(),
+    };
+    let mut env = minijinja::Environment::new();
+    env.set_keep_trailing_newline(true);
+    let entry = env.render_str(template, context).map_err(|why| format!("{}: {}", name, why))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|why| format!("Couldn't create {}: {}", parent.display(), why))?;
+    }
+    return std::fs::write(path, entry).map_err(|why| format!("Couldn't write {}: {}", path.display(), why));
+}
+
+// The entry of the +journal task `query` names, in the journal under
+// `input_dir`, written from the template first if it doesn't exist.
+pub fn entry(tasks: &[Task], query: &str, input_dir: &Path, options: &OpenOptions) -> Result<PathBuf, String> {
+    let date = find_day(tasks, query, options.on, time::today())?;
+    let path = entry_path(&input_dir.join(options.dir), date);
+    if !path.exists() {
+        write_entry(&path, date, tasks, options)?;
+    }
+    return Ok(path);
+}
+
+// Opens `path` in $VISUAL or $EDITOR, returning whether there was one to
+// open it in.
+pub fn edit(path: &Path) -> Result<bool, String> {
+    let editor = match std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")) {
+        Ok(editor) if !editor.trim().is_empty() => editor,
+        _ => return Ok(false),
+    };
+    // The editor may come with arguments of its own, e.g. "code --wait".
+    let mut words = editor.split_whitespace();
+    let program = words.next().expect("the editor isn't blank");
+    let status = Command::new(program).args(words).arg(path).status()
+        .map_err(|why| format!("Couldn't run {}: {}", editor, why))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", editor, status));
+    }
+    return Ok(true);
+}
@@ -0,0 +1,33 @@
+// JSON representation of tasks shared by the machine-readable outputs.
+use std::collections::HashMap;
+use serde_json::{json, Value};
+use crate::{time, Task};
+
+// Unless `include_private` is set, this follows the public calendar's rules:
+// details only for `+public` tasks and only publicly described tags.
+pub fn task_to_json(task: &Task, include_private: bool, public_tags: &HashMap<String, String>) -> Value {
+    let is_public = task.tags.iter().any(|t| t == "public");
+    let details = if include_private || is_public { json!(task.details) } else { Value::Null };
+    let tags: Vec<&String> = task.tags.iter()
+        .filter(|t| include_private || public_tags.contains_key(t.as_str()))
+        .collect();
+    return json!({
+        "date": time::format_iso_date(task.date),
+        "start": task.start_time.map(time::format_hhmm),
+        "end": task.span.map(|span| span.end).or(task.end_time).map(time::format_end_hhmm),
+        "end_date": task.span.map(|span| time::format_iso_date(span.last)),
+        "all_day": task.all_day,
+        "details": details,
+        "notes": if include_private || is_public { json!(task.notes) } else { Value::Null },
+        "location": if include_private || is_public { json!(task.location) } else { Value::Null },
+        "tags": tags,
+        "done": task.done,
+        "priority": if include_private || is_public { json!(task.priority) } else { Value::Null },
+        "id": if include_private || is_public { json!(task.id) } else { Value::Null },
+        "source": if include_private { json!(task.source) } else { Value::Null },
+    });
+}
+
+pub fn tasks_to_json(tasks: &[Task], include_private: bool, public_tags: &HashMap<String, String>) -> Value {
+    return Value::Array(tasks.iter().map(|t| task_to_json(t, include_private, public_tags)).collect());
+}
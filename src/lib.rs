@@ -0,0 +1,2309 @@
+//! Parses wtd.md-style task files and renders them as HTML calendars. The
+//! `wtd` binary is a thin command-line wrapper around this crate.
+#![allow(clippy::needless_return)]
+
+use std::ffi::OsStr;
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Mutex, OnceLock};
+use clap::ValueEnum;
+use serde::Deserialize;
+use chrono::{Datelike, NaiveDate, NaiveTime, Weekday, Duration, Timelike};
+
+pub mod agenda;
+pub mod attachments;
+pub mod avail;
+pub mod bench;
+mod caldav;
+pub mod check;
+pub mod config;
+pub mod credentials;
+pub mod dirs;
+pub mod done;
+mod edit;
+pub mod format;
+pub mod gcal;
+mod graphql;
+pub mod hooks;
+pub mod redact;
+pub mod ics;
+pub mod init;
+pub mod invite;
+pub mod journal;
+pub mod lint;
+pub mod plugin;
+pub mod policy;
+pub mod progress;
+pub mod protect;
+pub mod json;
+pub mod lock;
+pub mod serve;
+pub mod staged;
+pub mod stats;
+mod structured;
+pub mod sync;
+mod text;
+pub mod time;
+pub mod tui;
+pub mod watch;
+pub mod widget;
+mod ws;
+pub mod year;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+    pub date: NaiveDate,
+    pub start_time: Option<NaiveTime>,
+    pub end_time: Option<NaiveTime>,
+    // From an '@allday' token: the task takes up the day rather than any
+    // hours of it, like a holiday or a day of travel. Such tasks have no times.
+    pub all_day: bool,
+    // From an '@Start--+NdEnd' token: the task runs from its start time on
+    // `date` to span.end N days later, like a conference or a trip. Its
+    // end_time is then the end of its first day; see `split_days`.
+    pub span: Option<Span>,
+    pub details: String,
+    pub tags: Vec<String>,
+    pub done: bool,
+    // Indented '- [ ]' lines under the task, e.g. the plan for a meeting.
+    pub subtasks: Vec<Subtask>,
+    // Paragraphs of prose from indented '> ...' lines under the task, such as
+    // directions or an agenda, which unlike other indented lines aren't read
+    // for times and tags.
+    pub notes: Vec<String>,
+    // Paths from '+file:PATH' tokens, relative to the wtd.md directory.
+    pub attachments: Vec<String>,
+    // From an '&N' token: how many people a meeting brings together, for
+    // estimating its cost.
+    pub attendees: Option<u32>,
+    // From a '!N' token: 1 for the most important, down to 5.
+    pub priority: Option<u8>,
+    // From an '@loc:PLACE' token, or '@loc:"A PLACE"' for one with spaces:
+    // where the task happens, linked to a map on the calendars.
+    pub location: Option<String>,
+    // From an '#id:NAME' token: what the task's entry on the calendars is
+    // anchored by, so links to it survive edits to its day and description.
+    pub id: Option<String>,
+    // The name of the '#include'd file the task came from, e.g. "work" for
+    // work.md; None for the task file's own tasks.
+    pub source: Option<String>,
+}
+
+// Where a task running across days ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span {
+    pub last: NaiveDate,
+    pub end: NaiveTime,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Subtask {
+    pub text: String,
+    pub done: bool,
+    // How deeply it's nested in the checklist: 0 for an item of the task's
+    // own, 1 for one under such an item, and so on.
+    pub level: usize,
+}
+
+// A part of a task file that couldn't be understood.
+#[derive(Debug)]
+pub struct ParseError {
+    // The file, when parsed from one (see `load`).
+    pub path: Option<PathBuf>,
+    // 1-based, with the column counted in characters.
+    pub line: usize,
+    pub column: usize,
+    // The offending text, e.g. the whole '@...' token for a bad time.
+    pub token: String,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        return write!(f, "{}:{}: {}", self.line, self.column, self.message);
+    }
+}
+
+// An error in one token of a line, before it's placed in the file.
+struct TokenError<'a> {
+    token: &'a str,
+    message: String,
+}
+
+// The 1-based character column at which `token`, a slice of `line`, starts.
+fn column(line: &str, token: &str) -> usize {
+    let offset = (token.as_ptr() as usize).checked_sub(line.as_ptr() as usize)
+        .filter(|offset| *offset <= line.len())
+        .or_else(|| line.find(token))
+        .unwrap_or(0);
+    return line.get(..offset).map_or(0, |before| before.chars().count()) + 1;
+}
+
+impl std::error::Error for ParseError {}
+
+// The formats a date in a file can be written in, after any the config adds
+// (see ParseOptions): 3/14/24, 3/14/2024, or 2024-03-14. The two-digit year
+// goes first, as %Y would read 3/14/24 as the year 24.
+pub const DATE_FORMATS: [&str; 3] = ["%m/%d/%y", "%m/%d/%Y", "%Y-%m-%d"];
+
+fn parse_date_with(s: &str, options: &ParseOptions) -> Option<NaiveDate> {
+    return options.date_formats.iter().map(String::as_str).chain(DATE_FORMATS)
+        .find_map(|format| time::parse_date_as(s, format));
+}
+
+// The date in a '# ' week header: the whole header, for formats with spaces
+// like "%B %d, %Y", or else its first word that's a date.
+fn parse_date_line(l: &str, options: &ParseOptions) -> Option<NaiveDate> {
+    let header = l.strip_prefix("# ").unwrap_or(l).trim();
+    return parse_date_with(header, options)
+        .or_else(|| header.split(' ').find_map(|word| parse_date_with(word, options)));
+}
+
+// A date in an OOO line: either with its year, like a week header's, or as
+// just M/D in whichever year puts it nearest `near`.
+fn parse_ooo_date(s: &str, near: Option<NaiveDate>, options: &ParseOptions) -> Result<NaiveDate, String> {
+    if let Some(date) = parse_date_with(s, options) {
+        return Ok(date);
+    }
+    let (month, day) = match s.split('/').map(|part| part.parse::<u32>()).collect::<Vec<_>>()[..] {
+        [Ok(month), Ok(day)] => (month, day),
+        _ => return Err(format!("'{}' is not a date like 3/20 or 3/20/24", s)),
+    };
+    let near = near.ok_or_else(|| format!("'{}' needs a year when there's no '# ' date above it", s))?;
+    return (near.year() - 1..=near.year() + 1)
+        .filter_map(|year| time::ymd(year, month, day))
+        .min_by_key(|date| (*date - near).num_days().abs())
+        .ok_or_else(|| format!("'{}' is not a valid date", s));
+}
+
+// '## OOO 3/20--3/24', or '## OOO 3/20' for a single day. `week` is the date
+// of the '# ' header above it, if any.
+fn parse_ooo_line(l: &str, week: Option<NaiveDate>, options: &ParseOptions) -> Result<OutOfOffice, String> {
+    let range = l.strip_prefix("## OOO").unwrap_or(l).trim();
+    if range.is_empty() {
+        return Err("Expected dates after 'OOO', like '## OOO 3/20--3/24'".to_string());
+    }
+    let (first, last) = range.split_once("--").unwrap_or((range, range));
+    let first = parse_ooo_date(first.trim(), week, options)?;
+    let last = parse_ooo_date(last.trim(), Some(first), options)?;
+    if last < first {
+        return Err(format!("'{}' ends before it starts", range));
+    }
+    return Ok(OutOfOffice { first, last });
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + if ca == *cb { 0 } else { 1 };
+            cur.push(substitute.min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    return prev[b.len()];
+}
+
+const WEEKDAYS: [(Weekday, &str); 7] = [
+    (Weekday::Mon, "Monday"), (Weekday::Tue, "Tuesday"), (Weekday::Wed, "Wednesday"),
+    (Weekday::Thu, "Thursday"), (Weekday::Fri, "Friday"), (Weekday::Sat, "Saturday"),
+    (Weekday::Sun, "Sunday"),
+];
+
+pub fn weekday_name(day: Weekday) -> &'static str {
+    return WEEKDAYS.iter().find(|(d, _)| *d == day).map_or("", |(_, name)| name);
+}
+
+// The weekday a misspelled name was most likely meant to be: the one within
+// two edits, if exactly one is closest.
+fn closest_weekday(daystr: &str) -> Option<(Weekday, &'static str)> {
+    let daystr = daystr.to_lowercase();
+    let mut best: Option<(usize, Weekday, &'static str)> = None;
+    let mut tied = false;
+    for (day, name) in WEEKDAYS.iter() {
+        let distance = edit_distance(&daystr, &name.to_lowercase());
+        match best {
+            Some((d, _, _)) if distance > d => {},
+            Some((d, _, _)) if distance == d => tied = true,
+            _ => { best = Some((distance, *day, *name)); tied = false; },
+        }
+    }
+    return match best {
+        Some((distance, day, name)) if distance <= 2 && !tied => Some((day, name)),
+        _ => None,
+    };
+}
+
+fn parse_day_line(l: &str, options: &ParseOptions) -> Result<Weekday, String> {
+    let daystr = l.get(3..).unwrap_or("").trim();
+    if let Ok(day) = Weekday::from_str(daystr) {
+        return Ok(day);
+    }
+    if options.lenient {
+        if let Some((day, name)) = closest_weekday(daystr) {
+            if !options.quiet {
+                eprintln!("Interpreting '{}' as {}", daystr, name);
+            }
+            return Ok(day);
+        }
+    }
+    return Err(format!("'{}' is not a day of the week", daystr));
+}
+
+// The first date on or after `start` that falls on `weekday`. Plain date
+// arithmetic, so week blocks that cross December 31 or February 29 land in
+// the right year.
+pub fn resolve_day(start: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let offset = (7 + weekday.num_days_from_monday() - start.weekday().num_days_from_monday()) % 7;
+    return start + Duration::days(offset as i64);
+}
+
+fn parse_time(s_: &str) -> Result<NaiveTime, String> {
+    let formats = vec!["%l:%M%p", "%H:%M"];
+    let s = if s_.contains(':') {
+        s_.to_string()
+    } else if let Some(hour) = s_.strip_suffix("AM").or_else(|| s_.strip_suffix("PM")) {
+        format!("{}:00{}", hour, &s_[hour.len()..])
+    } else {
+        format!("{}:00", s_)
+    };
+    for format in formats {
+        match time::parse_time_as(&s, format) {
+            None => continue,
+            Some(parsed) => {
+                if !format.contains("%p") && parsed.hour() < 6 {
+                    return Ok(parsed + Duration::hours(12));
+                }
+                return Ok(parsed);
+            }
+        }
+    }
+    return Err(format!("Couldn't parse time {}", s_));
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let number = |n: &str| n.parse::<u32>().map(i64::from).map_err(|_| format!("Couldn't parse duration {}", s));
+    // We try to find Mm, HhMm, Hh
+    if s.contains("h") && s.contains("m") {
+        // TODO: Decompose this case into the two below.
+        let hstr = s.split('h').next().expect("");
+        let mstr = s.split('h').nth(1).expect("").split('m').next().expect("");
+        return Ok(Duration::minutes(number(hstr)? * 60 + number(mstr)?));
+    } else if s.contains("h") {
+        let hstr = s.split('h').next().expect("");
+        return Ok(Duration::hours(number(hstr)?));
+    } else if s.contains("m") {
+        let mstr = s.split('m').next().expect("");
+        return Ok(Duration::minutes(number(mstr)?));
+    }
+    return Err(format!("Couldn't parse duration {}", s));
+}
+
+// '&6', six people. Anything else starting with '&' is just a word.
+fn attendee_count(tok: &str) -> Option<u32> {
+    let digits = tok.strip_prefix('&')?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    return digits.parse().ok();
+}
+
+// '!1' (most important) to '!5'. None if `tok` isn't '!' followed by digits,
+// which is just a word, like '!!'.
+fn priority_level(tok: &str) -> Option<Result<u8, String>> {
+    let digits = tok.strip_prefix('!')?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    return Some(match digits.parse() {
+        Ok(level @ 1..=5) => Ok(level),
+        _ => Err(format!("'{}' isn't a priority; use !1 (most important) to !5", tok)),
+    });
+}
+
+// The name of an '#id:NAME' token, which may only have the letters, digits,
+// '-' and '_' that can go in a link's '#' as they are.
+fn task_id(tok: &str) -> Option<Result<String, String>> {
+    let name = tok.strip_prefix("#id:")?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Some(Err(format!("'{}' isn't an id; use letters, digits, '-' and '_', e.g. #id:standup", tok)));
+    }
+    return Some(Ok(name.to_string()));
+}
+
+// The tasks `query` names, by index: those with it as their '#id:' (written
+// with or without the '#id:'), or else those whose description has it in,
+// ignoring case.
+pub fn find_tasks(tasks: &[Task], query: &str) -> Vec<usize> {
+    let id = query.strip_prefix("#id:").unwrap_or(query);
+    let by_id: Vec<usize> = (0..tasks.len()).filter(|&i| tasks[i].id.as_deref() == Some(id)).collect();
+    if !by_id.is_empty() {
+        return by_id;
+    }
+    let lowered = query.to_lowercase();
+    return (0..tasks.len()).filter(|&i| tasks[i].details.to_lowercase().contains(&lowered)).collect();
+}
+
+// Adds a '> ...' line to the task's notes: its words continue the last
+// paragraph, and a bare '>' ends it. Until parsing is done, an empty last
+// paragraph stands for that break.
+fn add_note_line(task: &mut Task, line: &str) {
+    let words = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if words.is_empty() {
+        task.notes.push(String::new());
+        return;
+    }
+    match task.notes.last_mut() {
+        Some(paragraph) if !paragraph.is_empty() => {
+            paragraph.push(' ');
+            paragraph.push_str(&words);
+        },
+        Some(paragraph) => paragraph.push_str(&words),
+        None => task.notes.push(words),
+    }
+}
+
+// The words of a line of details, except that a quoted location such as
+// '@loc:"Coffee Bar, 5th Ave"' is one, spaces and all. An unclosed quote runs
+// to the end of the line.
+pub(crate) fn detail_tokens(l: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut quote_start: Option<usize> = None;
+    for word in l.split_whitespace() {
+        let offset = word.as_ptr() as usize - l.as_ptr() as usize;
+        match quote_start {
+            Some(start) if word.ends_with('"') => {
+                tokens.push(&l[start..offset + word.len()]);
+                quote_start = None;
+            },
+            Some(_) => {},
+            None if word.starts_with("@loc:\"") && (word.len() == 6 || !word.ends_with('"')) => quote_start = Some(offset),
+            None => tokens.push(word),
+        }
+    }
+    if let Some(start) = quote_start {
+        tokens.push(l[start..].trim_end());
+    }
+    return tokens;
+}
+
+// The place of an '@loc:' token, with runs of whitespace in quotes made single
+// spaces. None if `tok` isn't one.
+fn location(tok: &str) -> Option<Result<String, String>> {
+    let place = tok.strip_prefix("@loc:")?;
+    let place = match place.strip_prefix('"') {
+        Some(quoted) => match quoted.strip_suffix('"') {
+            Some(inner) => inner.split_whitespace().collect::<Vec<&str>>().join(" "),
+            None => return Some(Err(format!("'{}' has no closing quote", tok))),
+        },
+        None => place.to_string(),
+    };
+    if place.is_empty() {
+        return Some(Err("'@loc:' needs a place, e.g. @loc:Library or @loc:\"Coffee Bar, 5th Ave\"".to_string()));
+    }
+    return Some(Ok(place));
+}
+
+// Tags wtd gives a meaning of its own, which strict parsing always accepts:
+// those it reads (+journal, +habit:NAME, +done:HH:MM, and +meetings for the
+// stats) and those it adds (+tentative, +external).
+const BUILTIN_TAGS: [&str; 6] = ["journal", "habit", "done", "meetings", "tentative", "external"];
+
+// Whether strict parsing accepts the tag: a tag with a ':VALUE', like
+// +habit:run, is known by the part before the ':'.
+fn is_known_tag(tag: &str, options: &ParseOptions) -> bool {
+    let name = tag.split(':').next().unwrap_or(tag);
+    return BUILTIN_TAGS.contains(&name) || options.tags.iter().any(|known| known == name || known == tag);
+}
+
+// `separator` goes between the existing details and the first new word: a
+// space, or a newline after a hard break.
+fn handle_task_details<'a>(l: &'a str, t: &mut Task, mut separator: char, options: &ParseOptions) -> Result<(), TokenError<'a>> {
+    for tok in detail_tokens(l) {
+        let error = |message: String| TokenError { token: tok, message };
+        if let Some(place) = location(tok) {
+            t.location = Some(place.map_err(error)?);
+        } else if let Some(path) = tok.strip_prefix("+file:") {
+            t.attachments.push(path.to_string());
+        } else if tok.starts_with("+") {
+            let tag = tok.get(1..).expect("Unexpected");
+            if options.strict && tag.is_empty() {
+                return Err(error("'+' needs a tag after it, e.g. +busy".to_string()));
+            } else if options.strict && !is_known_tag(tag, options) {
+                return Err(error(format!("+{} isn't a tag the config knows; list it under tags in [parse] to use it with --strict", tag)));
+            }
+            t.tags.push(tag.to_string());
+        } else if let Some(count) = attendee_count(tok) {
+            t.attendees = Some(count);
+        } else if let Some(level) = priority_level(tok) {
+            t.priority = Some(level.map_err(error)?);
+        } else if let Some(id) = task_id(tok) {
+            t.id = Some(id.map_err(error)?);
+        } else if tok.eq_ignore_ascii_case("@allday") {
+            if t.start_time.is_some() {
+                return Err(error("An all-day task can't also have a time".to_string()));
+            }
+            t.all_day = true;
+        } else if tok.starts_with("@") {
+            if t.all_day {
+                return Err(error("An all-day task can't also have a time".to_string()));
+            }
+            let timestr = tok.get(1..).expect("Unexpected");
+            if let Some((startstr, rest)) = timestr.split_once("--+") { // @Start--+NdEnd
+                let (days, endstr) = rest.split_once(['d', 'D'])
+                    .ok_or_else(|| error(format!("'{}' is not of the form Start--+NdEnd, e.g. 6PM--+2d10AM", timestr)))?;
+                let days: i64 = days.parse().ok().filter(|days| *days >= 1)
+                    .ok_or_else(|| error(format!("'{}' in {} isn't a number of days from 1 up; use Start--End within a day", days, timestr)))?;
+                t.start_time = Some(parse_time(startstr).map_err(error)?);
+                t.end_time = Some(time::end_of_day());
+                let last = Duration::try_days(days).and_then(|days| t.date.checked_add_signed(days))
+                    .ok_or_else(|| error(format!("{} ends past the end of the calendar", timestr)))?;
+                t.span = Some(Span { last, end: parse_time(endstr).map_err(error)? });
+            } else if timestr.contains("+") { // @Start+Duration
+                let parts: Vec<&str> = timestr.split("+").collect();
+                match parts[..] {
+                    [startstr, durstr] => {
+                        let start = parse_time(startstr).map_err(error)?;
+                        t.start_time = Some(start);
+                        t.end_time = time::add_wall_clock(options.timezone, t.date, start, parse_duration(durstr).map_err(error)?);
+                        if t.end_time.is_none() {
+                            return Err(error(format!("{} runs past midnight; split it across days", timestr)));
+                        }
+                    },
+                    _ => return Err(error(format!("Not 2 parts to {}", timestr))),
+                }
+            } else if timestr.contains("--") { // @Start--End
+                let parts: Vec<&str> = timestr.split("--").collect();
+                match parts[..] {
+                    [startstr, endstr] => {
+                        t.start_time = Some(parse_time(startstr).map_err(error)?);
+                        t.end_time = Some(parse_time(endstr).map_err(error)?);
+                        if t.start_time > t.end_time {
+                            return Err(error(format!("Start time {} interpreted as after end time {}",
+                                                     startstr, endstr)));
+                        }
+                    },
+                    _ => return Err(error(format!("Not 2 parts to {}", timestr))),
+                }
+            } else {
+                return Err(error(format!("'{}' is not of the form Start+Duration or Start--End", timestr)));
+            }
+        } else {
+            if !t.details.is_empty() {
+                t.details.push(separator);
+            }
+            separator = ' ';
+            t.details.push_str(tok.trim());
+        }
+    }
+    return Ok(());
+}
+
+// A total order on tasks, so every output lists simultaneous tasks the same
+// way regardless of where they appear in the file: by date, then all-day
+// tasks first, then start time (untimed tasks last, most important first),
+// then duration (shorter first), then details and tags.
+pub fn cmp_tasks(a: &Task, b: &Task) -> Ordering {
+    let time_order = match [a.start_time, b.start_time] {
+        [None, None] => a.priority.unwrap_or(u8::MAX).cmp(&b.priority.unwrap_or(u8::MAX)),
+        [None, Some(_)] => Ordering::Greater,
+        [Some(_), None] => Ordering::Less,
+        [Some(atime), Some(btime)] => atime.cmp(&btime),
+    };
+    return a.date.cmp(&b.date)
+        .then(b.all_day.cmp(&a.all_day))
+        .then(time_order)
+        .then_with(|| a.end_time.cmp(&b.end_time))
+        .then_with(|| a.span.cmp(&b.span))
+        .then_with(|| a.details.cmp(&b.details))
+        .then_with(|| a.tags.cmp(&b.tags))
+        .then_with(|| a.done.cmp(&b.done))
+        .then_with(|| a.subtasks.cmp(&b.subtasks))
+        .then_with(|| a.notes.cmp(&b.notes))
+        .then_with(|| a.attachments.cmp(&b.attachments))
+        .then_with(|| a.attendees.cmp(&b.attendees))
+        .then_with(|| a.priority.cmp(&b.priority))
+        .then_with(|| a.location.cmp(&b.location))
+        .then_with(|| a.id.cmp(&b.id))
+        .then_with(|| a.source.cmp(&b.source));
+}
+
+fn does_overlap(timespan_start: &NaiveTime, timespan_end: &NaiveTime, start: &NaiveTime, end: &NaiveTime) -> bool {
+    return (start <= timespan_start && timespan_start < end)
+        || (start < timespan_end && timespan_end < end);
+}
+
+// The day a task ends on: its own, unless it spans several.
+pub fn last_date(task: &Task) -> NaiveDate {
+    return task.span.map_or(task.date, |span| span.last);
+}
+
+// The times a timed task takes up on each of its days, as (date, start, end):
+// for a task spanning days, from its start to the end of the first day, whole
+// days in between, and up to its end on the last day (unless that's midnight).
+fn day_times(task: &Task) -> Vec<(NaiveDate, NaiveTime, NaiveTime)> {
+    let (start, end) = match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => (start, end),
+        _ => return Vec::new(),
+    };
+    let span = match task.span {
+        Some(span) => span,
+        None => return vec![(task.date, start, end)],
+    };
+    let mut times = vec![(task.date, start, time::end_of_day())];
+    let mut date = time::next_day(task.date);
+    while date < span.last {
+        times.push((date, time::midnight(), time::end_of_day()));
+        date = time::next_day(date);
+    }
+    if span.end > time::midnight() {
+        times.push((span.last, time::midnight(), span.end));
+    }
+    return times;
+}
+
+// The days a task takes up any of: its own, or each of a span's.
+fn task_dates(task: &Task) -> Vec<NaiveDate> {
+    if task.span.is_none() {
+        return vec![task.date];
+    }
+    return day_times(task).into_iter().map(|(date, _, _)| date).collect();
+}
+
+// The tasks with each one spanning days cut into a task per day, for
+// anything that looks at one day at a time, like free time or overlaps.
+// Other tasks are kept as they are.
+pub fn split_days(tasks: &[Task]) -> Vec<Task> {
+    let mut out = Vec::new();
+    for task in tasks {
+        if task.span.is_none() {
+            out.push(task.clone());
+            continue;
+        }
+        for (date, start, end) in day_times(task) {
+            out.push(Task { date, start_time: Some(start), end_time: Some(end), span: None, ..task.clone() });
+        }
+    }
+    return out;
+}
+
+#[derive(Clone, Copy)]
+pub enum CalendarPrivacy {
+    Public,
+    Private,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+pub enum TimeFormat {
+    /// "2:30 PM"
+    #[value(name = "12h")]
+    #[serde(rename = "12h")]
+    TwelveHour,
+    /// "14:30"
+    #[value(name = "24h")]
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CellOverflow {
+    /// Let long text wrap onto further lines within the cell.
+    Wrap,
+    /// Keep each cell to a single line, cutting long text off with an ellipsis.
+    Ellipsis,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum View {
+    /// Consecutive days starting today.
+    Grid,
+    /// The same weekday from several weeks side by side, to compare weeks.
+    Compare,
+    /// Whole weeks from the current one, a row each, with each day's tasks
+    /// listed in its box: a summary of the weeks ahead.
+    Month,
+}
+
+// Where a task's location links to in the list below the calendar.
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum MapLinks {
+    #[serde(rename = "openstreetmap")]
+    OpenStreetMap,
+    #[serde(rename = "google")]
+    Google,
+}
+
+impl MapLinks {
+    pub fn url(&self, place: &str) -> String {
+        return match self {
+            MapLinks::OpenStreetMap => format!("https://www.openstreetmap.org/search?query={}", percent_encode(place)),
+            MapLinks::Google => format!("https://www.google.com/maps/search/?api=1&query={}", percent_encode(place)),
+        };
+    }
+}
+
+// One of the calendars stacked on a page: a view with its own window of days
+// (see RenderOptions::sections). Unset counts fall back to the page's.
+#[derive(Clone, Copy)]
+pub struct Section {
+    pub view: View,
+    // Days shown by the grid view.
+    pub days: Option<i64>,
+    // Weeks shown by the compare and month views.
+    pub weeks: Option<i64>,
+}
+
+#[derive(Clone, Default)]
+pub struct ParseOptions {
+    // Guess at misspelled day headers instead of stopping.
+    pub lenient: bool,
+    // chrono formats for the dates in week headers and OOO lines, tried
+    // before DATE_FORMATS, e.g. "%d.%m.%Y".
+    pub date_formats: Vec<String>,
+    // Commands each task file is passed through before it's read (see
+    // hooks.rs).
+    pub pre_parse: Vec<String>,
+    // Stop on what's otherwise only warned about (lines that aren't read,
+    // task markers that aren't quite right, comments left open), and on
+    // tags outside `tags` and BUILTIN_TAGS.
+    pub strict: bool,
+    // The tags strict parsing accepts besides BUILTIN_TAGS.
+    pub tags: Vec<String>,
+    // Don't print warnings.
+    pub quiet: bool,
+    // The timezone the file's times are on clocks in, for durations across
+    // DST changes; UTC's, which never change, if None (see time::clocks).
+    pub timezone: Option<chrono_tz::Tz>,
+}
+
+// Free text outside of any task: the preamble before the first week header,
+// and each week's notes between its '# ' header and its first day, e.g.
+// "Traveling this week, responses slow." Kept as paragraphs. Also the days
+// declared out of office, and the journal kept beside the file.
+#[derive(Clone, Default)]
+pub struct Notes {
+    pub preamble: Vec<String>,
+    // By the date in the week's header.
+    pub weeks: Vec<(NaiveDate, Vec<String>)>,
+    pub out_of_office: Vec<OutOfOffice>,
+    // Only ever shown on the private calendar; see journal.rs.
+    pub journal: journal::Journal,
+}
+
+// Whole days away, from a '## OOO 3/20--3/24' line.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct OutOfOffice {
+    pub first: NaiveDate,
+    // Inclusive.
+    pub last: NaiveDate,
+}
+
+impl OutOfOffice {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        return self.first <= date && date <= self.last;
+    }
+}
+
+pub fn is_out_of_office(ranges: &[OutOfOffice], date: NaiveDate) -> bool {
+    return ranges.iter().any(|range| range.contains(date));
+}
+
+pub struct RenderOptions {
+    pub cell_max_chars: Option<usize>,
+    pub cell_overflow: CellOverflow,
+    // The clock the calendars' times are shown on; task files are always
+    // written on the 12-hour clock.
+    pub time_format: TimeFormat,
+    pub view: View,
+    // Number of weeks shown by the compare view.
+    pub weeks: i64,
+    // Number of days shown by the grid view.
+    pub days: i64,
+    // The first day of the grid view, and the current week of the compare
+    // view; today if unset.
+    pub start: Option<NaiveDate>,
+    // The first day of the week, for the compare and month views and full weeks.
+    pub week_start: Weekday,
+    // Show whole weeks in the grid view, from the start of the current week
+    // to the end of the week of its last day.
+    pub full_weeks: bool,
+    // Leave done tasks out of the public calendar.
+    pub hide_done: bool,
+    // Minutes per grid row; divides a day evenly.
+    pub increment: i64,
+    // The hours of the day the grid shows, from the start of the first to
+    // the start of the second, e.g. (8, 20) for a small screen; the whole day
+    // if unset. Tasks outside them are still in the list.
+    pub hours: Option<(u32, u32)>,
+    // Tags explained on the public calendar, with their descriptions.
+    pub public_tags: HashMap<String, String>,
+    // The stylesheet linked from the page.
+    pub stylesheet: String,
+    // The text of a stylesheet to write into the page instead of linking
+    // one, so that it stands alone.
+    pub embedded_css: Option<String>,
+    // When set, the private calendar shows what each meeting with an '&N'
+    // count costs at this hourly rate per person.
+    pub hourly_rate: Option<f64>,
+    // Put each note, grid row, and list entry on a line of its own, so that
+    // renders kept for comparison diff line by line.
+    pub line_breaks: bool,
+    // The calendars shown one after another, e.g. a week's grid followed by
+    // a month; when empty, the one given by view, days, and weeks. Either
+    // way, the notes and the list come once, covering every calendar's days.
+    pub sections: Vec<Section>,
+    // The map a task's location links to.
+    pub map_links: MapLinks,
+    // Tags in the order they're listed in a cell, most important first; the
+    // first of a cell's tags sets its colors. Others follow in the order
+    // written.
+    pub tag_priority: Vec<String>,
+    // Background colors for tasks by tag, written into the page as CSS that
+    // overrides the stylesheet's.
+    pub tag_colors: Vec<(String, String)>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        return RenderOptions {
+            cell_max_chars: None,
+            cell_overflow: CellOverflow::Wrap,
+            time_format: TimeFormat::TwelveHour,
+            view: View::Grid,
+            weeks: 4,
+            days: 14,
+            start: None,
+            week_start: Weekday::Mon,
+            full_weeks: false,
+            hide_done: false,
+            increment: 15,
+            hours: None,
+            public_tags: public_tags(),
+            stylesheet: "calendar_style.css".to_string(),
+            embedded_css: None,
+            hourly_rate: None,
+            line_breaks: false,
+            sections: Vec::new(),
+            map_links: MapLinks::OpenStreetMap,
+            tag_priority: Vec::new(),
+            tag_colors: Vec::new(),
+        };
+    }
+}
+
+// A section of the page, with the days it shows in the order of its columns
+// (or of its boxes, for the month view).
+struct Window {
+    view: View,
+    weeks: i64,
+    dates: Vec<NaiveDate>,
+}
+
+fn windows(today: NaiveDate, options: &RenderOptions) -> Vec<Window> {
+    let default = [Section { view: options.view, days: None, weeks: None }];
+    let sections = if options.sections.is_empty() { &default[..] } else { &options.sections[..] };
+    return sections.iter().map(|section| {
+        let (days, weeks) = (section.days.unwrap_or(options.days), section.weeks.unwrap_or(options.weeks));
+        return Window { view: section.view, weeks, dates: window_dates(today, section.view, days, weeks, options.week_start, options.full_weeks) };
+    }).collect();
+}
+
+fn window_dates(today: NaiveDate, view: View, days: i64, weeks: i64, week_start: Weekday, full_weeks: bool) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    match view {
+        View::Grid if full_weeks => {
+            // Back to the start of the week, and on to the end of the week of
+            // the last day asked for.
+            let first = time::week_start(today, week_start);
+            let days = ((today - first).num_days() + days + 6) / 7 * 7;
+            for offset in 0..days {
+                dates.push(first + Duration::days(offset));
+            }
+        },
+        View::Grid => {
+            for offset in 0..days {
+                dates.push(today + Duration::days(offset));
+            }
+        },
+        View::Compare => {
+            // The requested number of weeks, ending with the current one. Columns are
+            // grouped by weekday so the same day of each week sits side by side.
+            let this_week = time::week_start(today, week_start);
+            let first_week = this_week - Duration::weeks(weeks - 1);
+            for day in 0..7 {
+                for week in 0..weeks {
+                    dates.push(first_week + Duration::weeks(week) + Duration::days(day));
+                }
+            }
+        },
+        View::Month => {
+            let this_week = time::week_start(today, week_start);
+            for offset in 0..7 * weeks {
+                dates.push(this_week + Duration::days(offset));
+            }
+        },
+    }
+    return dates;
+}
+
+// Shortens text shown inside a grid cell according to the render options. The
+// full text is still available in the detail list below the grid.
+fn fit_cell_text<'a>(text: &'a str, options: &RenderOptions) -> Cow<'a, str> {
+    return match options.cell_max_chars {
+        Some(max_chars) => Cow::Owned(text::truncate_to_width(text, max_chars)),
+        None => Cow::Borrowed(text),
+    };
+}
+
+// Percent-encodes everything but RFC 3986's unreserved characters.
+pub fn percent_encode(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    return out;
+}
+
+pub fn escape_html(s: &str) -> String {
+    return s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;");
+}
+
+// The tags explained on the public calendar, with their descriptions, unless
+// the config gives its own under [tags].
+pub fn public_tags() -> HashMap<String, String> {
+    return [
+        ("busy", "I will be genuinely busy, e.g., a meeting with others."),
+        ("rough", "The nature of the event (e.g., a hike) makes it difficult to preduct the exact start/end times."),
+        ("tentative", "This event timing is only tentative."),
+        ("join-me", "This is an open event; if you're interested in joining please reach out!"),
+        ("self", "This is scheduled time for me to complete a specific work or personal task; I can usually reschedule such blocks when requested."),
+    ].iter().map(|(tag, description)| (tag.to_string(), description.to_string())).collect();
+}
+
+// A task's publicly described tags, those named in `priority` first and in
+// its order, then the rest in the order written.
+fn ranked_tags<'a>(task: &'a Task, public_tags: &HashMap<String, String>, priority: &[String]) -> Vec<&'a str> {
+    let mut tags: Vec<&str> = task.tags.iter().map(|tag| tag.as_str()).filter(|tag| public_tags.contains_key(*tag)).collect();
+    tags.sort_by_key(|tag| priority.iter().position(|ranked| ranked == tag).unwrap_or(priority.len()));
+    return tags;
+}
+
+// What the public calendar says about a task: its publicly described tags,
+// followed by the quoted details if the task is tagged `public`. Empty if
+// there is nothing public to say.
+pub fn public_label(task: &Task, public_tags: &HashMap<String, String>) -> String {
+    return label_with_tags(task, &ranked_tags(task, public_tags, &[]));
+}
+
+// public_label, listing `tags`.
+fn label_with_tags(task: &Task, tags: &[&str]) -> String {
+    let mut label = tags.join(", ");
+    if task.tags.iter().any(|tag| tag == "public") {
+        label.push_str(if label.is_empty() { "\"" } else { ": \"" });
+        label.push_str(task.details.as_str());
+        label.push('"');
+    }
+    return label;
+}
+
+// E.g., "2/3 done".
+pub fn checklist_summary(task: &Task) -> String {
+    let done = task.subtasks.iter().filter(|s| s.done).count();
+    return format!("{}/{} done", done, task.subtasks.len());
+}
+
+fn checklist_html(task: &Task) -> String {
+    if task.subtasks.is_empty() {
+        return String::new();
+    }
+    let mut html = format!("<li><b>Checklist ({}):</b><ul>", checklist_summary(task));
+    // Each item's <li> is left open for any items nested in it.
+    let mut level = 0;
+    for (i, subtask) in task.subtasks.iter().enumerate() {
+        if i > 0 && subtask.level > level {
+            html.push_str("<ul>");
+        } else if i > 0 {
+            html.push_str(&"</li></ul>".repeat(level - subtask.level));
+            html.push_str("</li>");
+        }
+        level = subtask.level;
+        html.push_str(if subtask.done { "<li>&#9745; " } else { "<li>&#9744; " });
+        html.push_str(&escape_html(&subtask.text).replace('\n', "<br>"));
+    }
+    html.push_str(&"</li></ul>".repeat(level));
+    html.push_str("</li></ul></li>");
+    return html;
+}
+
+fn notes_html(task: &Task) -> String {
+    let mut html = String::new();
+    for paragraph in &task.notes {
+        html.push_str("<li><b>Note:</b> ");
+        html.push_str(&escape_html(paragraph));
+        html.push_str("</li>");
+    }
+    return html;
+}
+
+fn location_html(task: &Task, map_links: MapLinks) -> String {
+    return match &task.location {
+        Some(place) => format!("<li><b>Location:</b> <a href=\"{}\">{}</a></li>",
+                               escape_html(&map_links.url(place)), escape_html(place)),
+        None => String::new(),
+    };
+}
+
+fn attachments_html(task: &Task) -> String {
+    if task.attachments.is_empty() {
+        return String::new();
+    }
+    let mut html = "<li><b>Files:</b> ".to_string();
+    for (i, attachment) in task.attachments.iter().enumerate() {
+        if i > 0 { html.push_str(", "); }
+        let name = Path::new(attachment).file_name().map_or(attachment.clone(), |n| n.to_string_lossy().to_string());
+        html.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(attachment.trim_start_matches("./")), escape_html(&name)));
+    }
+    html.push_str("</li>");
+    return html;
+}
+
+// Links from a +journal task to its day's entry in the journal, once it's
+// written, and from a task with an id to the entries that mention it.
+fn journal_html(task: &Task, journal: &journal::Journal) -> String {
+    let link = |date: &NaiveDate| {
+        let path = journal::entry_path(&journal.dir, *date).to_string_lossy().replace('\\', "/");
+        return format!("<a href=\"{}\">{}</a>", escape_html(&path), time::format_short_date(*date));
+    };
+    let mut html = String::new();
+    if journal::is_journaled(task) && journal.dates.contains(&task.date) {
+        html.push_str(&format!("<li><b>Journal:</b> {}</li>", link(&task.date)));
+    }
+    if let Some(dates) = task.id.as_ref().and_then(|id| journal.mentions.get(id)) {
+        html.push_str(&format!("<li><b>Mentioned in:</b> {}</li>", dates.iter().map(link).collect::<Vec<String>>().join(", ")));
+    }
+    return html;
+}
+
+// Thumbnails of a public task's image attachments and of image URLs in its
+// details. Their size is capped by the stylesheet; attached images are copied
+// alongside the calendar, while URLs are linked as they are.
+fn thumbnails_html(task: &Task) -> String {
+    if !task.tags.iter().any(|t| t == "public") {
+        return String::new();
+    }
+    let mut sources: Vec<&str> = task.attachments.iter()
+        .filter(|a| attachments::is_image(a))
+        .map(|a| a.trim_start_matches("./"))
+        .collect();
+    sources.extend(task.details.split_whitespace()
+        .filter(|w| (w.starts_with("https://") || w.starts_with("http://")) && attachments::is_image(w)));
+    if sources.is_empty() {
+        return String::new();
+    }
+    let mut html = "<li class=\"thumbnails\">".to_string();
+    for source in sources {
+        let source = escape_html(source);
+        html.push_str(&format!("<a href=\"{}\"><img src=\"{}\" alt=\"\" loading=\"lazy\"></a>", source, source));
+    }
+    html.push_str("</li>");
+    return html;
+}
+
+// The tasks a calendar shows, by index into its tasks in cmp_tasks order, and
+// the anchor of each one's entry in the list.
+struct Shown {
+    ids: Vec<usize>,
+    anchors: HashMap<usize, String>,
+}
+
+// The anchor of each task's entry in the list, which links into the calendar
+// keep pointing at from one render to the next: "task-" and the task's '#id:'
+// if it has one, else a hash of its date and description. Tasks that would
+// share one are told apart in `ids`' order, as "task-...-2" and so on. The
+// public calendar only goes by what it shows of a task, so a private task's
+// anchor gives away neither its id nor its description.
+fn task_anchors(tasks: &[Task], ids: &[usize], privacy: CalendarPrivacy, options: &RenderOptions) -> HashMap<usize, String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    return ids.iter().map(|&idx| {
+        let task = &tasks[idx];
+        let described = matches!(privacy, CalendarPrivacy::Private) || task.tags.iter().any(|tag| tag == "public");
+        let anchor = match (&task.id, described) {
+            (Some(id), true) => format!("task-{}", id),
+            _ => {
+                let details = match described {
+                    true => task.details.clone(),
+                    false => label_with_tags(task, &ranked_tags(task, &options.public_tags, &options.tag_priority)),
+                };
+                let digest = sha1_smol::Sha1::from(format!("{}{}", task.date, details)).digest().to_string();
+                format!("task-{}", &digest[..10])
+            },
+        };
+        let count = seen.entry(anchor.clone()).or_insert(0);
+        *count += 1;
+        return (idx, if *count == 1 { anchor } else { format!("{}-{}", anchor, count) });
+    }).collect();
+}
+
+// The grid cell of task `idx`, spanning `rowspan` rows, linking to its entry
+// in the list.
+fn push_task_cell(html: &mut String, tasks: &[Task], idx: usize, shown: &Shown, rowspan: usize, privacy: CalendarPrivacy,
+                  options: &RenderOptions) {
+    html.push_str("<td class=\"");
+    push_task_classes(html, &tasks[idx], options);
+    let _ = write!(html, "\" rowspan=\"{}\">", rowspan);
+    push_task_link(html, &tasks[idx], &shown.anchors[&idx], "", privacy, options);
+    html.push_str("</td>");
+}
+
+// The classes of a task's cell: has-task, done, then tag-NAME for each
+// publicly described tag in the order of options.tag_priority, and lead-NAME
+// for the first of several, whose colors the cell takes.
+fn push_task_classes(html: &mut String, task: &Task, options: &RenderOptions) {
+    html.push_str("has-task");
+    if task.done {
+        html.push_str(" done");
+    }
+    let tags = ranked_tags(task, &options.public_tags, &options.tag_priority);
+    for tag in &tags {
+        html.push_str(" tag-");
+        html.push_str(&escape_html(tag));
+    }
+    if tags.len() > 1 {
+        html.push_str(" lead-");
+        html.push_str(&escape_html(tags[0]));
+    }
+}
+
+// A task's label, after `prefix` (such as its time), linking to its entry in
+// the list, at `anchor`.
+fn push_task_link(html: &mut String, task: &Task, anchor: &str, prefix: &str, privacy: CalendarPrivacy, options: &RenderOptions) {
+    let label: Cow<str> = match privacy {
+        CalendarPrivacy::Public => Cow::Owned(label_with_tags(task, &ranked_tags(task, &options.public_tags, &options.tag_priority))),
+        CalendarPrivacy::Private => Cow::Borrowed(&task.details),
+    };
+    let label = if label.is_empty() { Cow::Borrowed("has-task") } else { label };
+    let label = if prefix.is_empty() { label } else { Cow::Owned(format!("{} {}", prefix, label)) };
+    let shown = fit_cell_text(&label, options);
+    let _ = write!(html, "<a href=\"#{}", anchor);
+    if shown != label {
+        html.push_str("\" title=\"");
+        html.push_str(&escape_html(&label));
+    }
+    html.push_str("\">");
+    html.push_str(&escape_html(&shown));
+    html.push_str("</a>");
+}
+
+// The page render_html writes, as a template for render_into_template.
+pub const PAGE_TEMPLATE: &str = "<html><head><meta charset=\"UTF-8\"><title>Calendar</title>{% if css %}<style>{{ css }}</style>{% else %}<link rel=\"stylesheet\" href=\"{{ stylesheet }}\"></link>{% endif %}</head><body>{{ newline }}{{ calendar }}<a href=\"https://github.com/matthewsot/wtd\">src</a></body></html>{{ newline }}";
+
+// The file a page's stylesheet link `href` names, taken relative to the task
+// file at `path`; None for a stylesheet linked by URL.
+pub fn stylesheet_file(href: &str, path: &Path) -> Option<PathBuf> {
+    if href.contains("://") || href.starts_with("//") {
+        return None;
+    }
+    return Some(path.parent().unwrap_or(Path::new("")).join(href.trim_start_matches('/')));
+}
+
+// The stylesheet `file`, or the built-in one if there isn't one to read.
+pub fn read_stylesheet(file: Option<&Path>) -> String {
+    return file.and_then(|file| std::fs::read_to_string(file).ok()).unwrap_or_else(|| init::STYLESHEET.to_string());
+}
+
+// The calendar as a page of its own, linking the stylesheet.
+pub fn render_html(tasks: &[Task], notes: &Notes, privacy: CalendarPrivacy, options: &RenderOptions) -> String {
+    return render_into_template(PAGE_TEMPLATE, tasks, notes, privacy, options).expect("PAGE_TEMPLATE is a valid template");
+}
+
+// The comment in a page of your own that render_into_template replaces with
+// the calendar, for pages written before templates were.
+pub const CALENDAR_MARKER: &str = "<!-- wtd:calendar -->";
+
+// `template`, a page of your own, filled in by minijinja (Jinja2 syntax) with:
+//  - calendar: everything render_fragment writes, or in pieces: style (the
+//    tag colors), notes, calendars (a table per section), legend, and list,
+//  - tasks: the tasks in the list, in its order, each as `wtd json` gives it
+//    for the calendar's privacy, with the id of its entry in the list,
+//  - stylesheet, css (options.embedded_css, if set), private (whether this is
+//    the private calendar), and newline ('\n' if options.line_breaks is set,
+//    else empty).
+// Values are escaped as HTML unless they are pieces of the calendar or marked
+// `|safe`. CALENDAR_MARKER stands for `{{ calendar }}`.
+pub fn render_into_template(template: &str, tasks: &[Task], notes: &Notes, privacy: CalendarPrivacy, options: &RenderOptions) -> Result<String, String> {
+    let template = template.replacen(CALENDAR_MARKER, "{{ calendar }}", 1);
+    let mut env = minijinja::Environment::new();
+    env.set_keep_trailing_newline(true);
+    env.set_formatter(|out, _, value| {
+        if value.is_safe() {
+            write!(out, "{}", value)?;
+        } else {
+            write!(out, "{}", escape_html(&value.to_string()))?;
+        }
+        return Ok(());
+    });
+    let private = matches!(privacy, CalendarPrivacy::Private);
+    let parts = fragment_parts(tasks, notes, privacy, options);
+    let listed: Vec<serde_json::Value> = parts.listed.iter().map(|(i, anchor)| {
+        let mut task = json::task_to_json(&tasks[*i], private, &options.public_tags);
+        task["id"] = serde_json::json!(anchor);
+        return task;
+    }).collect();
+    let safe = minijinja::Value::from_safe_string;
+    let context = minijinja::context! {
+        calendar => safe(parts.joined()),
+        style => safe(parts.style),
+        notes => safe(parts.notes),
+        calendars => parts.calendars.into_iter().map(safe).collect::<Vec<_>>(),
+        legend => safe(parts.legend),
+        list => safe(parts.list),
+        tasks => minijinja::Value::from_serialize(&listed),
+        stylesheet => &options.stylesheet,
+        // Kept from closing the <style> it goes in.
+        css => options.embedded_css.as_ref().map(|css| safe(css.replace("</", "<\\/"))),
+        private,
+        newline => safe(if options.line_breaks { "\n" } else { "" }.to_string()),
+    };
+    return env.render_str(&template, context).map_err(|why| why.to_string());
+}
+
+// The grid (or compare) view of the window's days: a column per day and a row per
+// `options.increment` minutes, with all-day tasks in rows of their own above.
+fn push_grid(html: &mut String, tasks: &[Task], shown: &Shown, window: &Window,
+             notes: &Notes, privacy: CalendarPrivacy, options: &RenderOptions) {
+    let ids = &shown.ids;
+    let newline = if options.line_breaks { "\n" } else { "" };
+    let dates = &window.dates;
+    let n_days = dates.len() as i64;
+    let columns: HashMap<NaiveDate, usize> = dates.iter().enumerate().map(|(i, date)| (*date, i)).collect();
+
+    let min_incr = options.increment;
+    let timespans_per_day = (24 * 60 ) / min_incr;
+    let mut table: Vec<Vec<Option<usize>>> = vec![vec![None; n_days as usize]; timespans_per_day as usize];
+    // The rows shown, all of them unless the grid is limited to some hours.
+    let (first_row, end_row) = match options.hours {
+        Some((start, end)) => (start as i64 * 60 / min_incr, end as i64 * 60 / min_incr),
+        None => (0, timespans_per_day),
+    };
+
+    html.push_str(match (options.cell_overflow, options.hours.is_some()) {
+        (CellOverflow::Wrap, false) => "<table class=\"cells-wrap\">",
+        (CellOverflow::Ellipsis, false) => "<table class=\"cells-ellipsis\">",
+        // Labeled by the hour only, and styled to fit small screens.
+        (CellOverflow::Wrap, true) => "<table class=\"cells-wrap partial-day\">",
+        (CellOverflow::Ellipsis, true) => "<table class=\"cells-ellipsis partial-day\">",
+    });
+    html.push_str(newline);
+    // Days out of office are shaded whole, under and around their tasks.
+    let away: Vec<bool> = dates.iter().map(|date| is_out_of_office(&notes.out_of_office, *date)).collect();
+    html.push_str("<tr><th>Time</th>");
+    for (i, date) in dates.iter().enumerate() {
+        let mut classes = Vec::new();
+        if window.view == View::Compare && i as i64 % window.weeks == 0 {
+            classes.push("group-start");
+        }
+        if away[i] {
+            classes.push("ooo");
+        }
+        if classes.is_empty() {
+            html.push_str("<th>");
+        } else {
+            html.push_str(&format!("<th class=\"{}\">", classes.join(" ")));
+        }
+        html.push_str(&time::format_date(*date));
+        if away[i] {
+            html.push_str("<br>Out of office");
+        }
+        html.push_str("</th>");
+    }
+    html.push_str("</tr>");
+    html.push_str(newline);
+
+    // The timed task ids of each column, with their times that day, so that
+    // each cell only looks at its own day's tasks.
+    let mut by_column: Vec<Vec<(usize, NaiveTime, NaiveTime)>> = vec![Vec::new(); n_days as usize];
+    for &idx in ids.iter() {
+        for (date, start, end) in day_times(&tasks[idx]) {
+            if let Some(&col) = columns.get(&date) {
+                by_column[col].push((idx, start, end));
+            }
+        }
+    }
+
+    // All-day tasks go in banner rows under the day headers instead of
+    // filling their columns: the first of each day's in the first row, and
+    // so on.
+    let mut all_day: Vec<Vec<usize>> = vec![Vec::new(); n_days as usize];
+    // The ids cover every section's days, so some fall outside this one.
+    for &idx in ids.iter().filter(|&&idx| tasks[idx].all_day) {
+        if let Some(&col) = columns.get(&tasks[idx].date) {
+            all_day[col].push(idx);
+        }
+    }
+    for row in 0..all_day.iter().map(Vec::len).max().unwrap_or(0) {
+        html.push_str("<tr class=\"all-day\"><td><b>");
+        html.push_str(if row == 0 { "All day" } else { "" });
+        html.push_str("</b></td>");
+        for (col_idx, on_this_date) in all_day.iter().enumerate() {
+            match on_this_date.get(row) {
+                Some(&idx) => push_task_cell(html, tasks, idx, shown, 1, privacy, options),
+                None if away[col_idx] => html.push_str("<td class=\"ooo\"></td>"),
+                None => html.push_str("<td></td>"),
+            }
+        }
+        html.push_str("</tr>");
+        html.push_str(newline);
+    }
+
+    for i in 0..timespans_per_day {
+        let timespan_start = time::time_of_day(i * min_incr);
+        let timespan_end = time::time_of_day((i + 1) * min_incr);
+        for (offset, on_this_date) in by_column.iter().enumerate() {
+            // Of the tasks that intersect this timespan on this day, the one
+            // ending first goes in the table.
+            table[i as usize][offset] = on_this_date.iter()
+                .filter(|(_, start, end)| does_overlap(&timespan_start, &timespan_end, start, end))
+                .min_by_key(|(_, _, end)| *end)
+                .map(|(idx, _, _)| *idx);
+        }
+    }
+    for row_idx in first_row..end_row {
+        let timespan_start = time::time_of_day(row_idx * min_incr);
+        if options.hours.is_none() {
+            html.push_str("<tr><td><b>");
+            html.push_str(&time::format_row_time(timespan_start, options.time_format));
+            html.push_str("</b></td>");
+        } else if timespan_start.minute() == 0 {
+            html.push_str("<tr><td><b>");
+            html.push_str(&time::format_hour(timespan_start, options.time_format));
+            html.push_str("</b></td>");
+        } else {
+            html.push_str("<tr><td></td>");
+        }
+        for col_idx in 0..n_days {
+            let task_idx = table[row_idx as usize][col_idx as usize];
+            match task_idx {
+                Some(idx) => {
+                    // A task already under way when the shown hours start
+                    // begins in the first row.
+                    if row_idx == first_row || table[(row_idx - 1) as usize][col_idx as usize] != task_idx {
+                        let mut rowspan = 0;
+                        for i in row_idx..end_row {
+                            if table[i as usize][col_idx as usize] == task_idx {
+                                rowspan += 1;
+                            } else {
+                                break;
+                            }
+                        }
+                        push_task_cell(html, tasks, idx, shown, rowspan, privacy, options);
+                    }
+                },
+                None if away[col_idx as usize] => {
+                    html.push_str("<td class=\"ooo\"></td>");
+                },
+                None => {
+                    html.push_str("<td></td>");
+                },
+            }
+        }
+        html.push_str("</tr>");
+        html.push_str(newline);
+    }
+    html.push_str("</table>");
+    html.push_str(newline);
+}
+
+// The month view of the window's days, whole weeks: a row per week and a box per day,
+// listing the day's tasks (and each day of any spanning several) with their
+// start times, each linking to its entry in the list like a grid cell does.
+fn push_month(html: &mut String, tasks: &[Task], shown: &Shown, window: &Window,
+              notes: &Notes, privacy: CalendarPrivacy, options: &RenderOptions) {
+    let ids = &shown.ids;
+    let newline = if options.line_breaks { "\n" } else { "" };
+    let dates = &window.dates;
+    let mut by_date: HashMap<NaiveDate, Vec<usize>> = HashMap::new();
+    for &idx in ids {
+        let task = &tasks[idx];
+        // As in the list, the public calendar leaves out private untimed tasks.
+        let is_public = task.tags.iter().any(|tag| tag == "public");
+        if matches!(privacy, CalendarPrivacy::Public) && task.start_time.is_none() && !task.all_day && !is_public {
+            continue;
+        }
+        for date in task_dates(task) {
+            by_date.entry(date).or_default().push(idx);
+        }
+    }
+    html.push_str("<table class=\"month\">");
+    html.push_str(newline);
+    html.push_str("<tr>");
+    for date in dates.iter().take(7) {
+        let _ = write!(html, "<th>{}</th>", time::format_weekday(*date));
+    }
+    html.push_str("</tr>");
+    html.push_str(newline);
+    for week in dates.chunks(7) {
+        html.push_str("<tr>");
+        for date in week {
+            html.push_str(if is_out_of_office(&notes.out_of_office, *date) { "<td class=\"ooo\">" } else { "<td>" });
+            let _ = write!(html, "<b>{}</b>", time::format_month_day(*date));
+            for &idx in by_date.get(date).map_or(&[][..], Vec::as_slice) {
+                let task = &tasks[idx];
+                // Only the day a task starts on gives its time.
+                let start = match task.start_time {
+                    Some(start) if task.date == *date => time::format_time_as(start, options.time_format).trim().to_string(),
+                    _ => String::new(),
+                };
+                html.push_str("<div class=\"");
+                push_task_classes(html, task, options);
+                html.push_str("\">");
+                push_task_link(html, task, &shown.anchors[&idx], &start, privacy, options);
+                html.push_str("</div>");
+            }
+            html.push_str("</td>");
+        }
+        html.push_str("</tr>");
+        html.push_str(newline);
+    }
+    html.push_str("</table>");
+    html.push_str(newline);
+}
+
+// A <style> giving tasks with each tag its color, cells and month entries
+// alike; as in the stylesheet, a cell with several tags takes the colors of
+// its lead tag.
+fn tag_colors_style(colors: &[(String, String)]) -> String {
+    let mut css = String::from("<style>");
+    for (tag, color) in colors {
+        let _ = write!(css, "td.tag-{0}, div.tag-{0} {{ background-color: {1}; }}", tag, color);
+    }
+    for (tag, color) in colors {
+        let _ = write!(css, "td.lead-{0}, div.lead-{0} {{ background-color: {1}; background-image: none; }}", tag, color);
+    }
+    css.push_str("</style>");
+    return css;
+}
+
+// A swatch and description of each publicly described tag on the tasks
+// shown, ranked as options.tag_priority says and then by name. Tags nothing
+// shown carries are left out, and with them the whole legend if there are none.
+fn push_legend(html: &mut String, tasks: &[Task], ids: &[usize], privacy: CalendarPrivacy, options: &RenderOptions) {
+    // The public calendar doesn't show private untimed tasks at all.
+    let shown = |task: &&Task| matches!(privacy, CalendarPrivacy::Private) || task.start_time.is_some() || task.all_day
+        || task.tags.iter().any(|tag| tag == "public");
+    let mut used: Vec<&str> = ids.iter().map(|&idx| &tasks[idx]).filter(shown).flat_map(|task| task.tags.iter())
+        .map(|tag| tag.as_str())
+        .filter(|tag| options.public_tags.contains_key(*tag))
+        .collect();
+    used.sort_by_key(|tag| (options.tag_priority.iter().position(|ranked| ranked == tag).unwrap_or(options.tag_priority.len()), *tag));
+    used.dedup();
+    if used.is_empty() {
+        return;
+    }
+    html.push_str("<ul class=\"legend\">");
+    for tag in used {
+        let _ = write!(html, "<li><div class=\"has-task tag-{}\">{}</div> {}</li>",
+                       escape_html(tag), escape_html(tag), escape_html(&options.public_tags[tag]));
+    }
+    html.push_str("</ul>");
+    if options.line_breaks {
+        html.push('\n');
+    }
+}
+
+// The calendar without the page around it: a <style> with any tag colors,
+// the notes, a table for each section (see RenderOptions::sections), the
+// legend, and the list of tasks, for embedding in a page of your own that
+// links the stylesheet.
+// The output only depends on the arguments (and on today's date, unless
+// `options.start` is set), and is always written the same way:
+//  - no whitespace between elements, unless `options.line_breaks` ends each
+//    note, table row, and list entry (and the table, legend, and list tags)
+//    with '\n',
+//  - attributes in the order id, class, rowspan, href, title,
+//  - a cell's (or month entry's) classes in the order has-task, done,
+//    tag-NAME for each publicly described tag (as push_task_classes orders
+//    them), then lead-NAME, and a list entry's in the order done, priority-N,
+//  - tasks in the list in the order of cmp_tasks.
+pub fn render_fragment(tasks: &[Task], notes: &Notes, privacy: CalendarPrivacy, options: &RenderOptions) -> String {
+    return fragment_parts(tasks, notes, privacy, options).joined();
+}
+
+// The pieces of render_fragment's calendar, in order, for templates to lay out
+// as they like.
+struct Fragment {
+    style: String,
+    notes: String,
+    calendars: Vec<String>,
+    legend: String,
+    list: String,
+    // The tasks in the list, in its order, with the anchors of their entries.
+    listed: Vec<(usize, String)>,
+}
+
+impl Fragment {
+    fn joined(&self) -> String {
+        return [self.style.as_str(), &self.notes, &self.calendars.concat(), &self.legend, &self.list].concat();
+    }
+}
+
+fn fragment_parts(tasks: &[Task], notes: &Notes, privacy: CalendarPrivacy, options: &RenderOptions) -> Fragment {
+    let public_tags = &options.public_tags;
+    let newline = if options.line_breaks { "\n" } else { "" };
+
+    let mut html = String::new();
+    if !options.tag_colors.is_empty() {
+        html.push_str(&tag_colors_style(&options.tag_colors));
+        html.push_str(newline);
+    }
+    let style = std::mem::take(&mut html);
+    let today = options.start.unwrap_or_else(time::today);
+    let windows = windows(today, options);
+    // Every day any section shows.
+    let dates: HashSet<NaiveDate> = windows.iter().flat_map(|window| window.dates.iter().copied()).collect();
+    for paragraph in &notes.preamble {
+        html.push_str(&format!("<p class=\"note\">{}</p>{}", escape_html(paragraph), newline));
+    }
+    // The notes of every week the window shows any of.
+    for (start, paragraphs) in &notes.weeks {
+        if !(0..7).any(|day| dates.contains(&(*start + Duration::days(day)))) {
+            continue;
+        }
+        for paragraph in paragraphs {
+            html.push_str(&format!("<p class=\"note\"><b>Week of {}:</b> {}</p>{}",
+                                   time::format_short_date(*start), escape_html(paragraph), newline));
+        }
+    }
+    let mut week_task_ids: Vec<usize> = Vec::new();
+    let hide_done = options.hide_done && matches!(privacy, CalendarPrivacy::Public);
+    for (i, task) in tasks.iter().enumerate() {
+        // A task spanning days shows if any of its days do.
+        let shown = dates.contains(&task.date) || (task.span.is_some() && task_dates(task).iter().any(|date| dates.contains(date)));
+        if shown && !(hide_done && task.done) {
+            week_task_ids.push(i);
+        }
+    }
+    week_task_ids.sort_by(|a, b| cmp_tasks(&tasks[*a], &tasks[*b]));
+    let anchors = task_anchors(tasks, &week_task_ids, privacy, options);
+    let shown = Shown { ids: week_task_ids, anchors };
+    let note_paragraphs = std::mem::take(&mut html);
+
+    let calendars = windows.iter().map(|window| {
+        let mut html = String::new();
+        match window.view {
+            View::Grid | View::Compare => push_grid(&mut html, tasks, &shown, window, notes, privacy, options),
+            View::Month => push_month(&mut html, tasks, &shown, window, notes, privacy, options),
+        }
+        return html;
+    }).collect();
+    push_legend(&mut html, tasks, &shown.ids, privacy, options);
+    let legend = std::mem::take(&mut html);
+    let mut listed = Vec::new();
+    html.push_str("<ul>");
+    html.push_str(newline);
+    for i in shown.ids.iter() {
+        let task = &tasks[*i];
+        let is_public = task.tags.iter().any(|tag| tag == "public");
+        let in_grid = task.start_time.is_some() || task.all_day;
+        if let (CalendarPrivacy::Public, false, false) = (&privacy, in_grid, is_public) {
+            continue;
+        }
+        listed.push((*i, shown.anchors[i].clone()));
+        let mut classes = Vec::new();
+        if task.done {
+            classes.push("done".to_string());
+        }
+        // Like the details, a task's priority is only public if it is.
+        if let (Some(level), true) = (task.priority, is_public || matches!(privacy, CalendarPrivacy::Private)) {
+            classes.push(format!("priority-{}", level));
+        }
+        let _ = write!(html, "<li id=\"{}\"", shown.anchors[i]);
+        if !classes.is_empty() {
+            let _ = write!(html, " class=\"{}\"", classes.join(" "));
+        }
+        html.push('>');
+        html.push_str(&time::format_date(task.date));
+        html.push(' ');
+        if let (Some(start), Some(span)) = (task.start_time, task.span) {
+            html.push_str(&time::format_time_as(start, options.time_format));
+            html.push_str(" -- ");
+            html.push_str(&time::format_date(span.last));
+            html.push(' ');
+            html.push_str(&time::format_time_as(span.end, options.time_format));
+        } else if let [Some(start), Some(end)] = [task.start_time, task.end_time] {
+            html.push_str(&time::format_time_as(start, options.time_format));
+            html.push_str(" -- ");
+            html.push_str(&time::format_time_as(end, options.time_format));
+        } else if task.all_day {
+            html.push_str("All day");
+        }
+        html.push_str("<ul>");
+        match privacy {
+            CalendarPrivacy::Public => {
+                if is_public {
+                    html.push_str("<li><b>Description:</b> ");
+                    html.push_str(&escape_html(&task.details).replace('\n', "<br>"));
+                    html.push_str("</li>");
+                    html.push_str(&location_html(task, options.map_links));
+                    html.push_str(&notes_html(task));
+                    html.push_str(&checklist_html(task));
+                    html.push_str(&attachments_html(task));
+                    html.push_str(&thumbnails_html(task));
+                }
+                for tag in &task.tags {
+                    if public_tags.contains_key(tag.as_str()) {
+                        html.push_str("<li>Tagged <b>");
+                        html.push_str(&escape_html(tag));
+                        html.push_str(":</b> ");
+                        html.push_str(&escape_html(public_tags.get(tag.as_str()).expect("")));
+                        html.push_str("</li>");
+                    }
+                }
+            },
+            CalendarPrivacy::Private => {
+                html.push_str("<li><b>Description:</b> ");
+                html.push_str(&escape_html(&task.details).replace('\n', "<br>"));
+                html.push_str("</li>");
+                html.push_str(&location_html(task, options.map_links));
+                html.push_str(&notes_html(task));
+                if let Some(source) = &task.source {
+                    html.push_str(&format!("<li><b>From:</b> {}</li>", escape_html(source)));
+                }
+                if let Some(cost) = options.hourly_rate.and_then(|rate| stats::meeting_cost(task, rate)) {
+                    html.push_str(&format!("<li><b>Estimated cost:</b> {:.0} ({} people for {:.1}h)</li>",
+                                           cost, task.attendees.unwrap_or(0), stats::task_hours(task)));
+                }
+                html.push_str(&checklist_html(task));
+                html.push_str(&attachments_html(task));
+                html.push_str(&thumbnails_html(task));
+                html.push_str(&journal_html(task, &notes.journal));
+                html.push_str("<li>Tagged: ");
+                for (i, tag) in task.tags.iter().enumerate() {
+                    if i > 0 { html.push_str(", "); }
+                    html.push_str("<b>");
+                    html.push_str(&escape_html(tag));
+                    html.push_str("</b>");
+                }
+                html.push_str("</li>");
+            }
+        }
+        html.push_str("</ul>");
+        html.push_str("</li>");
+        html.push_str(newline);
+    }
+    html.push_str("</ul>");
+    html.push_str(newline);
+    return Fragment { style, notes: note_paragraphs, calendars, legend, list: html, listed };
+}
+
+// A date written out in full: 2024-03-14, 3/14/24, or 3/14/2024, as in
+// files, where a date can't be relative to when it's read.
+pub fn parse_full_date(s: &str) -> Result<NaiveDate, String> {
+    for format in DATE_FORMATS {
+        if let Some(date) = time::parse_date_as(s, format) {
+            return Ok(date);
+        }
+    }
+    return Err(format!("couldn't parse date '{}'; expected e.g. 2024-03-14 or 3/14/24", s));
+}
+
+// "3 days", "2w", ... as a number of days.
+fn parse_day_count(count: &str, unit: &str) -> Option<i64> {
+    let count: i64 = count.parse().ok()?;
+    return match unit {
+        "d" | "day" | "days" => Some(count),
+        "w" | "week" | "weeks" => count.checked_mul(7),
+        _ => None,
+    };
+}
+
+// A date relative to `today`: today, tomorrow, yesterday, a weekday (the next
+// one from today on, or strictly after/before today with "next"/"last"), or an
+// offset like "in 3 days", "2 weeks ago", "+3d", or "-1w".
+fn parse_relative_date(s: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let words: Vec<&str> = s.split_whitespace().collect();
+    let days = match words[..] {
+        ["today"] => 0,
+        ["tomorrow"] => 1,
+        ["yesterday"] => -1,
+        ["in", count, unit] => parse_day_count(count, unit)?,
+        [count, unit, "ago"] => parse_day_count(count, unit)?.checked_neg()?,
+        [token] if token.starts_with(['+', '-']) => {
+            let digits = token[1..].find(|c: char| !c.is_ascii_digit())?;
+            let (count, unit) = token[1..].split_at(digits);
+            let days = parse_day_count(count, unit)?;
+            if token.starts_with('-') { -days } else { days }
+        },
+        [modifier, day] if ["this", "next", "last"].contains(&modifier) => {
+            let weekday = Weekday::from_str(day).ok()?;
+            let ahead = (weekday.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64).rem_euclid(7);
+            match (modifier, ahead) {
+                ("last", 0) => -7,
+                ("last", ahead) => ahead - 7,
+                ("next", 0) => 7,
+                (_, ahead) => ahead,
+            }
+        },
+        [day] => {
+            let weekday = Weekday::from_str(day).ok()?;
+            (weekday.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64).rem_euclid(7)
+        },
+        _ => return None,
+    };
+    // Offsets past the end of the calendar aren't dates at all.
+    return today.checked_add_signed(Duration::try_days(days)?);
+}
+
+// "Mar 14 2024", "14 March 2024", ...
+const MONTH_DAY_YEAR: [&str; 4] = ["%b %d %Y", "%d %b %Y", "%B %d %Y", "%d %B %Y"];
+
+// A date given on the command line (or in a GraphQL query): in full, as M/D
+// or a month and day ("Mar 14", "14 March") in whichever year is nearest
+// today, or relative to today (see parse_relative_date).
+pub fn parse_cli_date(s: &str) -> Result<NaiveDate, String> {
+    let today = time::today();
+    let normalized = s.trim().to_lowercase().replace(',', " ");
+    if let Ok(date) = parse_full_date(&normalized) {
+        return Ok(date);
+    }
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    for format in MONTH_DAY_YEAR {
+        if let Some(date) = time::parse_date_as(&words.join(" "), format) {
+            return Ok(date);
+        }
+    }
+    if let Some(date) = parse_relative_date(&normalized, today) {
+        return Ok(date);
+    }
+    // Without a year: as M/D, or by trying each of the years around today.
+    if let Ok(date) = parse_ooo_date(&normalized, Some(today), &ParseOptions::default()) {
+        return Ok(date);
+    }
+    for format in MONTH_DAY_YEAR {
+        let nearest = (today.year() - 1..=today.year() + 1)
+            .filter_map(|year| time::parse_date_as(&format!("{} {}", words.join(" "), year), format))
+            .min_by_key(|date| (*date - today).num_days().abs());
+        if let Some(date) = nearest {
+            return Ok(date);
+        }
+    }
+    return Err(format!("couldn't read '{}' as a date; expected e.g. 2024-03-14, 3/14/24, 3/14, 'Mar 14', \
+                        'today', 'tomorrow', 'fri', 'next tue', 'last mon', 'in 3 days', or '2 weeks ago'", s));
+}
+
+pub fn parse_hour_range(s: &str) -> Result<(u32, u32), String> {
+    let parts: Vec<&str> = s.split('-').collect();
+    if let [start, end] = parts[..] {
+        if let (Ok(start), Ok(end)) = (start.trim().parse::<u32>(), end.trim().parse::<u32>()) {
+            if start < end && end <= 24 {
+                return Ok((start, end));
+            }
+        }
+    }
+    return Err(format!("expected hours like 9-17, got '{}'", s));
+}
+
+// Standard input, read once on first use, for a task file given as '-'.
+static STDIN: OnceLock<Result<String, String>> = OnceLock::new();
+
+// The contents of the file at `path`, or of standard input for '-'.
+pub fn read_file(path: &Path) -> Result<String, String> {
+    if path == Path::new("-") {
+        return STDIN.get_or_init(|| {
+            let mut s = String::new();
+            std::io::stdin().read_to_string(&mut s).map_err(|why| format!("Couldn't read standard input: {}", why))?;
+            return Ok(s);
+        }).clone();
+    }
+    return std::fs::read_to_string(path).map_err(|why| format!("Couldn't read {}: {}", path.display(), why));
+}
+
+// Irons out differences between editors: drops a leading byte-order mark,
+// turns '\r\n' and lone '\r' line endings into '\n', and turns every other kind
+// of whitespace (tabs, non-breaking and other Unicode spaces, zero-width
+// spaces) into a plain space.
+fn normalize_source(s: &str) -> String {
+    let s = s.strip_prefix('\u{feff}').unwrap_or(s);
+    let s = s.replace("\r\n", "\n").replace('\r', "\n");
+    return s.chars()
+        .map(|c| if c != '\n' && (c.is_whitespace() || c == '\u{200b}') { ' ' } else { c })
+        .collect();
+}
+
+// Comments, `<!-- ... -->` anywhere on a line or `//` at the start of a word
+// through the end of the line, annotate the source without being read. The
+// line is returned with them blanked out, so columns stay the same, along with
+// the comments themselves for the formatter to keep.
+pub fn blank_comments(l: &str) -> (String, Vec<&str>) {
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < l.len() {
+        let rest = &l[i..];
+        if rest.starts_with("<!--") {
+            // An unclosed comment runs to the end of the line.
+            let end = rest.find("-->").map_or(l.len(), |end| i + end + 3);
+            spans.push((i, end));
+            i = end;
+        } else if rest.starts_with("//") && (i == 0 || l[..i].ends_with(' ')) {
+            spans.push((i, l.len()));
+            break;
+        } else {
+            i += rest.chars().next().map_or(1, |c| c.len_utf8());
+        }
+    }
+    let mut blanked = l.to_string();
+    for &(start, end) in &spans {
+        blanked.replace_range(start..end, &" ".repeat(end - start));
+    }
+    return (blanked, spans.into_iter().map(|(start, end)| &l[start..end]).collect());
+}
+
+// A line ending in '\' (a Markdown hard break) keeps its line break in the
+// details instead of being joined to the next continuation line with a space.
+pub fn strip_hard_break(l: &str) -> (&str, bool) {
+    return match l.trim_end().strip_suffix('\\') {
+        Some(rest) => (rest, true),
+        None => (l, false),
+    };
+}
+
+// Whether the line looks meant as a task or checklist item but its marker
+// isn't one, e.g. '- []', '-[ ]', '* [ ]', or '- [v]'.
+fn is_malformed_marker(l: &str) -> bool {
+    let l = l.trim_start();
+    let rest = match l.strip_prefix(['-', '*']) {
+        Some(rest) => rest.trim_start(),
+        None => return false,
+    };
+    let checkbox = rest.starts_with("[]") || (rest.starts_with('[') && rest.chars().nth(2) == Some(']'));
+    return checkbox && !is_task_line(l);
+}
+
+// Reports something parsing gets past but that's likely a mistake: as an
+// error when strict, else as a warning unless quiet.
+fn warn(errors: &mut Vec<ParseError>, error: ParseError, options: &ParseOptions) {
+    if options.strict {
+        errors.push(error);
+    } else if !options.quiet {
+        eprintln!("Warning: line {}: {}", error.line, error.message);
+    }
+}
+
+// Whether the line starts with a task marker: '- [ ]', or '- [X]' (or
+// '- [x]') for one that's done. Checklist items are the same once their
+// indentation is trimmed.
+pub fn is_task_line(l: &str) -> bool {
+    return l.starts_with("- [ ]") || is_done(l);
+}
+
+pub fn is_done(l: &str) -> bool {
+    return l.starts_with("- [X]") || l.starts_with("- [x]");
+}
+
+pub fn indentation(l: &str) -> usize {
+    return l.len() - l.trim_start().len();
+}
+
+// Opens a checklist item indented `indent` among `open`, the indentation of
+// the items above it still open, outermost first, and returns its level: it
+// nests under each of them indented less than it, and closes the rest.
+fn nest_item(open: &mut Vec<usize>, indent: usize) -> usize {
+    while open.last().is_some_and(|last| *last >= indent) {
+        open.pop();
+    }
+    open.push(indent);
+    return open.len() - 1;
+}
+
+// Parses a task file with the default options.
+pub fn parse_tasks(s: &str) -> Result<Vec<Task>, Vec<ParseError>> {
+    return parse_tasks_with(s, &ParseOptions::default());
+}
+
+// Parses the whole file even after an error, so that every problem can be
+// reported at once.
+pub fn parse_tasks_with(s: &str, options: &ParseOptions) -> Result<Vec<Task>, Vec<ParseError>> {
+    let mut tasks = Vec::new();
+    let mut errors = Vec::new();
+    let mut start_date = None;
+    let mut the_date = None;
+    // Set when a day header couldn't be read, so its tasks aren't each
+    // reported as being outside of any day.
+    let mut in_bad_day = false;
+    // Before the first day of a week, where free text is a note (see
+    // parse_notes) rather than a stray line.
+    let mut in_notes = true;
+    // The indentation of the open checklist items, outermost first (see
+    // nest_item). Lines indented deeper than the last still extend it rather
+    // than the task.
+    let mut items: Vec<usize> = Vec::new();
+    // Whether the last task or checklist line ended in a hard break.
+    let mut hard_break = false;
+    let source = normalize_source(s);
+    for (i, raw) in source.split('\n').enumerate() {
+        let (l, comments) = blank_comments(raw);
+        let l = l.as_str();
+        let error = |token: &str, message: &str| ParseError {
+            path: None,
+            line: i + 1,
+            column: column(l, token),
+            token: token.to_string(),
+            message: message.to_string(),
+        };
+        if let Some(open) = comments.iter().find(|comment| comment.starts_with("<!--") && !comment.ends_with("-->")) {
+            // Where it is in the blanked line, for its column.
+            let at = open.as_ptr() as usize - raw.as_ptr() as usize;
+            warn(&mut errors, error(&l[at..], "'<!--' isn't closed on its line; a comment ends with its line, so the lines after it are read"),
+                 options);
+        }
+        let malformed = is_malformed_marker(l);
+        if malformed {
+            warn(&mut errors, error(l.trim_start(), &format!("'{}' looks like a task but isn't one; tasks start with '- [ ]' or '- [X]'",
+                                                             l.trim())), options);
+        }
+        if l.starts_with("# ") || l.starts_with("## ") || is_task_line(l) {
+            items.clear();
+            hard_break = false;
+        }
+        if l.starts_with("# ") {
+            // '# 12/27/21', starts a new week block
+            in_notes = true;
+            start_date = parse_date_line(l, options);
+            if start_date.is_none() {
+                // E.g., '# 2/29/25', which only exists in leap years.
+                warn(&mut errors, error(l, &format!("No valid date in week header: {}", l)), options);
+            }
+        } else if include_path(l).is_some() {
+            // '#include work.md', read by load.
+        } else if let Some(range) = l.strip_prefix("## OOO") {
+            // '## OOO 3/20--3/24', days away (see parse_notes). Ends the day
+            // block above it.
+            in_notes = false;
+            the_date = None;
+            in_bad_day = false;
+            if let Err(why) = parse_ooo_line(l, start_date, options) {
+                errors.push(error(range.trim(), &why));
+            }
+        } else if let Some(day) = l.strip_prefix("## ") {
+            // '## Monday/Tuesday/...', starts a new day block
+            in_notes = false;
+            the_date = None;
+            in_bad_day = true;
+            let dayofweek = match parse_day_line(l, options) {
+                Ok(dayofweek) => dayofweek,
+                Err(why) => {
+                    errors.push(error(day.trim(), &why));
+                    continue;
+                },
+            };
+            let start = match start_date {
+                Some(start) => start,
+                None => {
+                    errors.push(error(l, "Day header without a valid '# ' date above it"));
+                    continue;
+                },
+            };
+            in_bad_day = false;
+            let date = resolve_day(start, dayofweek);
+            let earlier = date - Duration::days(7);
+            // A day that falls before the header's weekday is taken from the
+            // following week, which may be in the next year. If the previous
+            // week would have put it in the header's year instead, the block
+            // probably meant that one, so say which date was picked.
+            if date.year() != start.year() && earlier.year() == start.year()
+                && dayofweek.num_days_from_monday() < start.weekday().num_days_from_monday() {
+                warn(&mut errors, error(l, &format!("'{}' under '# {}' resolves to {}, not {}; start a new '# ' block at the year boundary to be explicit",
+                                                    l, time::format_short_date(start), time::format_date(date), time::format_date(earlier))),
+                     options);
+            }
+            the_date = Some(date);
+        } else if is_task_line(l) {
+            // '- [ ] ...', starts a new task block. Several short tasks can
+            // share a line, separated by ';', e.g. '- [ ] bank @10AM+15m; post office @11AM+15m'.
+            let date = match the_date {
+                Some(date) => date,
+                None => {
+                    if !in_bad_day {
+                        errors.push(error(l, "Task before any '## ' day header"));
+                    }
+                    continue;
+                },
+            };
+            let (details, breaks) = strip_hard_break(l.get(5..).unwrap_or(""));
+            hard_break = breaks;
+            let details = details.trim();
+            let mut parts: Vec<&str> = details.split(';').filter(|part| !part.trim().is_empty()).collect();
+            if parts.is_empty() {
+                // A bare '- [ ]' whose details are all on continuation lines.
+                parts.push("");
+            }
+            for part in parts {
+                tasks.push(Task {
+                    date,
+                    start_time: None,
+                    end_time: None,
+                    all_day: false,
+                    span: None,
+                    details: "".to_string(),
+                    tags: Vec::new(),
+                    done: is_done(l),
+                    subtasks: Vec::new(),
+                    notes: Vec::new(),
+                    attachments: Vec::new(),
+                    attendees: None,
+                    priority: None,
+                    location: None,
+                    id: None,
+                    source: None,
+                });
+                if let Err(why) = handle_task_details(part, tasks.last_mut().expect("Unexpected error..."), ' ', options) {
+                    errors.push(error(why.token, &why.message));
+                }
+            }
+        } else if is_task_line(l.trim_start()) {
+            // '  - [ ] ...', an item in the last task's checklist, or in
+            // the item above it if indented deeper.
+            let item = l.trim_start();
+            let (text, breaks) = strip_hard_break(item.get(5..).unwrap_or(""));
+            let level = nest_item(&mut items, indentation(l));
+            hard_break = breaks;
+            match tasks.last_mut() {
+                Some(task) => task.subtasks.push(Subtask {
+                    text: text.trim().to_string(),
+                    done: is_done(item),
+                    level,
+                }),
+                None if in_bad_day => {},
+                None => errors.push(error(item, "Checklist item before any task")),
+            }
+        } else if l.starts_with(' ') {
+            // Extends the last checklist item if indented deeper than it, and
+            // otherwise the last task.
+            let (text, breaks) = strip_hard_break(l);
+            let separator = if hard_break { '\n' } else { ' ' };
+            if text.trim().is_empty() {
+                continue;
+            }
+            hard_break = breaks;
+            if items.last().is_some_and(|indent| indentation(l) <= *indent) {
+                items.clear();
+            }
+            // '  > ...', a line of the last task's notes. Lines run together
+            // into paragraphs, which a bare '>' separates.
+            if let (true, Some(note)) = (items.is_empty(), l.trim_start().strip_prefix('>')) {
+                hard_break = false;
+                match tasks.last_mut() {
+                    Some(task) => add_note_line(task, note),
+                    None if in_bad_day => {},
+                    None => errors.push(error(l.trim_start(), "Note before any task")),
+                }
+                continue;
+            }
+            match tasks.last_mut() {
+                Some(task) if !items.is_empty() => if let Some(item) = task.subtasks.last_mut() {
+                    if !item.text.is_empty() {
+                        item.text.push(separator);
+                    }
+                    item.text.push_str(&text.split_whitespace().collect::<Vec<&str>>().join(" "));
+                },
+                Some(task) => if let Err(why) = handle_task_details(text, task, separator, options) {
+                    errors.push(error(why.token, &why.message));
+                },
+                None if in_bad_day => {},
+                None => errors.push(error(l.trim_start(), "Continuation line before any task")),
+            }
+        } else if !l.trim().is_empty() && !in_notes && !malformed {
+            warn(&mut errors, error(l, &format!("'{}' isn't a header, a task, or indented under one, so it isn't read", l.trim())),
+                 options);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    for task in tasks.iter_mut() {
+        task.notes.retain(|paragraph| !paragraph.is_empty());
+    }
+    return Ok(tasks);
+}
+
+// The preamble and week notes of a task file: unindented lines of text
+// before the first '# ' header, or between a week's header and its first
+// '## ' day. Lines run together into paragraphs, which blank lines separate.
+// OOO lines that parse_tasks would reject are left out.
+pub fn parse_notes(s: &str) -> Notes {
+    return parse_notes_with(s, &ParseOptions::default());
+}
+
+pub fn parse_notes_with(s: &str, options: &ParseOptions) -> Notes {
+    // Each paragraph with the week it's under: None for the preamble, and
+    // Some(None) for a week whose date couldn't be read.
+    let mut paragraphs: Vec<(Option<Option<NaiveDate>>, String)> = Vec::new();
+    let mut week = None;
+    let mut in_notes = true;
+    let mut continues = false;
+    let mut out_of_office = Vec::new();
+    for l in normalize_source(s).split('\n') {
+        let (l, _) = blank_comments(l);
+        let is_text = in_notes && !l.trim().is_empty() && !l.starts_with(' ')
+            && !l.starts_with("# ") && !l.starts_with("## ") && !is_task_line(&l) && include_path(&l).is_none();
+        if is_text && continues {
+            let (_, paragraph) = paragraphs.last_mut().expect("continues a paragraph");
+            paragraph.push(' ');
+            paragraph.push_str(l.trim());
+        } else if is_text {
+            paragraphs.push((week, l.trim().to_string()));
+        } else if l.starts_with("# ") {
+            in_notes = true;
+            week = Some(parse_date_line(&l, options));
+        } else if l.starts_with("## OOO") {
+            in_notes = false;
+            if let Ok(range) = parse_ooo_line(&l, week.flatten(), options) {
+                out_of_office.push(range);
+            }
+        } else if l.starts_with("## ") {
+            in_notes = false;
+        }
+        continues = is_text;
+    }
+
+    let mut notes = Notes { out_of_office, ..Notes::default() };
+    for (week, paragraph) in paragraphs {
+        match (week, notes.weeks.last_mut()) {
+            (None, _) => notes.preamble.push(paragraph),
+            (Some(Some(start)), Some((last, week_paragraphs))) if *last == start => week_paragraphs.push(paragraph),
+            (Some(Some(start)), _) => notes.weeks.push((start, vec![paragraph])),
+            (Some(None), _) => {},
+        }
+    }
+    return notes;
+}
+
+// Leaves the file (and so its modification time) alone when the contents are
+// unchanged, so static hosts keep serving the same ETag.
+pub fn write_if_changed(path: &Path, contents: &str) -> std::io::Result<()> {
+    if std::fs::read_to_string(path).ok().as_deref() == Some(contents) {
+        return Ok(());
+    }
+    return std::fs::write(path, contents);
+}
+
+// Reads and parses a task file, with every error (one per line) naming the
+// file.
+pub fn load(path: &Path, options: &ParseOptions) -> Result<Vec<Task>, String> {
+    return load_including(path, options, &mut path.canonicalize().into_iter().collect());
+}
+
+// The file named by an '#include other.md' line, relative to the including
+// file; None if `l` isn't one.
+fn include_path(l: &str) -> Option<&str> {
+    return l.strip_prefix("#include").filter(|rest| rest.is_empty() || rest.starts_with(' ')).map(str::trim);
+}
+
+// The '#include' lines of a markdown task file, with their line numbers.
+fn includes(source: &str) -> Vec<(usize, String)> {
+    return normalize_source(source).split('\n').enumerate()
+        .filter_map(|(i, l)| include_path(&blank_comments(l).0).map(|include| (i + 1, include.to_string())))
+        .collect();
+}
+
+// The file an '#include' names, checked to exist and not to be one of the
+// files that (directly or not) include it, which are in `including`.
+fn resolve_include(path: &Path, line: usize, include: &str, including: &[PathBuf]) -> Result<PathBuf, String> {
+    let error = |message: String| ParseError {
+        path: Some(path.to_path_buf()),
+        line,
+        column: 1,
+        token: include.to_string(),
+        message,
+    }.to_string();
+    if include.is_empty() {
+        return Err(error("Expected a file to include, e.g. '#include work.md'".to_string()));
+    }
+    let included = path.parent().unwrap_or(Path::new("")).join(include);
+    let canonical = included.canonicalize().map_err(|why| error(format!("Couldn't include {}: {}", included.display(), why)))?;
+    if including.contains(&canonical) {
+        return Err(error(format!("{} includes itself", included.display())));
+    }
+    return Ok(included);
+}
+
+// What a task is labelled with when it comes from the file at `path`.
+fn source_label(path: &Path) -> String {
+    return path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+}
+
+// What the pre_parse hooks last made of each file: the commands, the file as
+// it was read, and what they left of it.
+type PreParsed = (Vec<String>, String, String);
+static PRE_PARSED: Mutex<BTreeMap<PathBuf, PreParsed>> = Mutex::new(BTreeMap::new());
+
+// The task file at `path` as the pre_parse hooks leave it. A file is read by
+// load, load_notes and the archive alike, so the hooks are only run again
+// once it (or the hooks) change.
+fn read_source(path: &Path, options: &ParseOptions) -> Result<String, String> {
+    let source = read_file(path)?;
+    if options.pre_parse.is_empty() {
+        return Ok(source);
+    }
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if let Some((commands, read, transformed)) = PRE_PARSED.lock().expect("pre-parsed sources").get(&key) {
+        if *commands == options.pre_parse && *read == source {
+            return Ok(transformed.clone());
+        }
+    }
+    let transformed = hooks::pre_parse(&options.pre_parse, path, source.clone())?;
+    PRE_PARSED.lock().expect("pre-parsed sources").insert(key, (options.pre_parse.clone(), source, transformed.clone()));
+    return Ok(transformed);
+}
+
+fn load_including(path: &Path, options: &ParseOptions, including: &mut Vec<PathBuf>) -> Result<Vec<Task>, String> {
+    let source = read_source(path, options)?;
+    let format = structured::detect(path, &source);
+    if format != structured::Format::Markdown {
+        return structured::parse(format, &source, options.timezone).map(|(tasks, _)| tasks)
+            .map_err(|why| format!("{}: {}", path.display(), why));
+    }
+    let mut tasks = parse_tasks_with(&source, options).map_err(|errors| {
+        return errors.into_iter()
+            .map(|error| ParseError { path: Some(path.to_path_buf()), ..error }.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+    })?;
+    for (line, include) in includes(&source) {
+        let included = resolve_include(path, line, &include, including)?;
+        including.push(included.canonicalize().unwrap_or_else(|_| included.clone()));
+        let result = load_including(&included, options, including);
+        including.pop();
+        // Attachments are relative to the file that names them, so are made
+        // relative to this one instead.
+        let dir = Path::new(&include).parent().unwrap_or(Path::new(""));
+        for mut task in result? {
+            task.source.get_or_insert_with(|| source_label(&included));
+            task.attachments = task.attachments.iter().map(|a| dir.join(a).to_string_lossy().to_string()).collect();
+            tasks.push(task);
+        }
+    }
+    return Ok(tasks);
+}
+
+// The task file at `path` and those it includes, directly or not: the files
+// `load` reads. An include that can't be read is still listed, in case it
+// comes to be.
+pub fn sources(path: &Path, options: &ParseOptions) -> Vec<PathBuf> {
+    let mut sources = vec![path.to_path_buf()];
+    sources_including(path, options, &mut path.canonicalize().into_iter().collect(), &mut sources);
+    return sources;
+}
+
+fn sources_including(path: &Path, options: &ParseOptions, including: &mut Vec<PathBuf>, sources: &mut Vec<PathBuf>) {
+    let source = match read_source(path, options) {
+        Ok(source) if structured::detect(path, &source) == structured::Format::Markdown => source,
+        _ => return,
+    };
+    for (line, include) in includes(&source) {
+        if include.is_empty() {
+            continue;
+        }
+        sources.push(path.parent().unwrap_or(Path::new("")).join(&include));
+        if let Ok(included) = resolve_include(path, line, &include, including) {
+            including.push(included.canonicalize().unwrap_or_else(|_| included.clone()));
+            sources_including(&included, options, including, sources);
+            including.pop();
+        }
+    }
+}
+
+// The notes of the task file at `path`, along with those of the files it
+// includes. A file in another format that can't be read has none; `load`
+// reports why.
+pub fn load_notes(path: &Path, options: &ParseOptions) -> Notes {
+    return load_notes_including(path, options, &mut path.canonicalize().into_iter().collect());
+}
+
+fn load_notes_including(path: &Path, options: &ParseOptions, including: &mut Vec<PathBuf>) -> Notes {
+    let source = match read_source(path, options) {
+        Ok(source) => source,
+        Err(_) => return Notes::default(),
+    };
+    let format = structured::detect(path, &source);
+    if format != structured::Format::Markdown {
+        return structured::parse(format, &source, options.timezone).map(|(_, notes)| notes).unwrap_or_default();
+    }
+    let mut notes = parse_notes_with(&source, options);
+    for (line, include) in includes(&source) {
+        let included = match resolve_include(path, line, &include, including) {
+            Ok(included) => included,
+            Err(_) => continue,
+        };
+        including.push(included.canonicalize().unwrap_or_else(|_| included.clone()));
+        let more = load_notes_including(&included, options, including);
+        including.pop();
+        notes.preamble.extend(more.preamble);
+        notes.weeks.extend(more.weeks);
+        notes.out_of_office.extend(more.out_of_office);
+    }
+    notes.weeks.sort_by_key(|(start, _)| *start);
+    return notes;
+}
+
+// Whether wtd can edit the task file at `path` in place: only markdown files
+// are ever written, and standard input can't be.
+pub fn check_editable(path: &Path) -> Result<(), String> {
+    if path == Path::new("-") {
+        return Err("standard input can only be read; name the task file to edit instead".to_string());
+    }
+    if structured::detect(path, &read_file(path)?) != structured::Format::Markdown {
+        return Err(format!("{} isn't a markdown task file, which is all wtd edits; edit the markdown one instead", path.display()));
+    }
+    return Ok(());
+}
+
+// The .md files of older weeks kept in the archive directory, in name order.
+fn archive_files(archive_dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(archive_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|p| p.extension() == Some(OsStr::new("md")))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    return paths;
+}
+
+// Reads wtd.md along with any older weeks kept as .md files in the archive
+// directory. Used by outputs that look back over the whole history, which
+// report their progress through it unless `quiet`.
+pub fn load_archive(path: &Path, archive_dir: &Path, options: &ParseOptions, quiet: bool) -> Result<Vec<Task>, String> {
+    let mut tasks = load(path, options)?;
+    let files = archive_files(archive_dir);
+    let mut progress = progress::Progress::new("Reading the archive", files.len(), quiet);
+    for p in files {
+        tasks.extend(load(&p, options)?);
+        progress.advance();
+    }
+    return Ok(tasks);
+}
+
+// The out-of-office days declared in wtd.md and the archive.
+pub fn load_archive_out_of_office(path: &Path, archive_dir: &Path, options: &ParseOptions) -> Vec<OutOfOffice> {
+    let mut ranges = load_notes(path, options).out_of_office;
+    for p in archive_files(archive_dir) {
+        ranges.extend(parse_notes_with(&read_source(&p, options).unwrap_or_default(), options).out_of_office);
+    }
+    return ranges;
+}
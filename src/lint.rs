@@ -0,0 +1,127 @@
+// `wtd lint`: mistakes in a task file that still parse, reported by line so
+// it can run before a commit. On top of what the parser rejects (such as a
+// task that ends before it starts), it looks for:
+//  - timed tasks on the same day that overlap, counting each day of a task
+//    that spans several,
+//  - day headers that fall outside the calendar week of the '# ' header
+//    above them, or that repeat one already under it,
+//  - tasks with a public tag but no time (nor '@allday'), which the public
+//    calendar's grid can't show,
+//  - '#id:' tokens that repeat one on an earlier task, whose entries on the
+//    calendars can't both have the anchor, and
+//  - tasks outside the hours of a "flag" policy on their tag (see policy.rs),
+// along with whatever the plugins that check task files find (see plugin.rs).
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+use chrono::{NaiveDate, Weekday};
+use crate::edit::task_lines;
+use crate::plugin::Plugin;
+use crate::policy::{self, Policy};
+use crate::{blank_comments, normalize_source, parse_date_line, parse_tasks_with, read_file, resolve_day, split_days, structured, time};
+use crate::{ParseOptions, Task};
+
+pub struct LintOptions<'a> {
+    pub parse: ParseOptions,
+    pub week_start: Weekday,
+    pub public_tags: &'a HashMap<String, String>,
+    pub policies: &'a [Policy],
+    // Plugins whose checks are run along with these.
+    pub plugins: &'a [Plugin],
+}
+
+fn format_span(task: &Task) -> String {
+    if let (Some(start), Some(span)) = (task.start_time, task.span) {
+        return format!("{}--+{}d{}", time::format_time(start).trim(), (span.last - task.date).num_days(), time::format_time(span.end).trim());
+    }
+    return match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => format!("{}--{}", time::format_time(start).trim(), time::format_time(end).trim()),
+        _ => String::new(),
+    };
+}
+
+fn overlaps(a: &Task, b: &Task) -> bool {
+    return match [a.start_time, a.end_time, b.start_time, b.end_time] {
+        [Some(a_start), Some(a_end), Some(b_start), Some(b_end)] => a.date == b.date && a_start < b_end && b_start < a_end,
+        _ => false,
+    };
+}
+
+fn day_headers(source: &str, options: &LintOptions) -> Vec<(usize, String)> {
+    let mut problems = Vec::new();
+    let mut week: Option<(NaiveDate, String)> = None;
+    let mut seen: Vec<Weekday> = Vec::new();
+    for (i, l) in normalize_source(source).split('\n').enumerate() {
+        let (l, _) = blank_comments(l);
+        if l.starts_with("# ") {
+            week = parse_date_line(&l, &options.parse).map(|date| (date, l.trim().to_string()));
+            seen.clear();
+            continue;
+        }
+        let name = match l.strip_prefix("## ") {
+            Some(name) if !name.starts_with("OOO") => name.trim(),
+            _ => continue,
+        };
+        let (start, header, day) = match (&week, Weekday::from_str(name)) {
+            (Some((start, header)), Ok(day)) => (*start, header, day),
+            _ => continue,
+        };
+        let date = resolve_day(start, day);
+        if seen.contains(&day) {
+            problems.push((i + 1, format!("'## {}' is already under '{}'", name, header)));
+        } else if time::week_start(date, options.week_start) != time::week_start(start, options.week_start) {
+            problems.push((i + 1, format!("'## {}' under '{}' falls on {}, in the following week; start a new '# ' block for it",
+                                          name, header, time::format_date(date))));
+        }
+        seen.push(day);
+    }
+    return problems;
+}
+
+// Every problem with the markdown task file at `path`, as "PATH:LINE: ...",
+// in line order.
+pub fn lint(path: &Path, options: &LintOptions) -> Result<Vec<String>, String> {
+    let source = read_file(path)?;
+    if structured::detect(path, &source) != structured::Format::Markdown {
+        return Err(format!("{} isn't a markdown task file, which is all lint checks", path.display()));
+    }
+    let tasks = match parse_tasks_with(&source, &options.parse) {
+        Ok(tasks) => tasks,
+        Err(errors) => return Ok(errors.iter().map(|error| format!("{}:{}", path.display(), error)).collect()),
+    };
+    let lines = task_lines(&source, &options.parse);
+    let mut problems = day_headers(&source, options);
+    // Tasks spanning days are compared a day at a time.
+    let days: Vec<Vec<Task>> = tasks.iter().map(|task| split_days(std::slice::from_ref(task))).collect();
+    for (j, task) in tasks.iter().enumerate() {
+        let overlapping = |i: &usize| days[*i].iter().any(|a| days[j].iter().any(|b| overlaps(a, b)));
+        for (i, other) in (0..j).filter(overlapping).map(|i| (i, &tasks[i])) {
+            problems.push((lines[j], format!("{} overlaps {} on line {}", format_span(task), format_span(other), lines[i])));
+        }
+        if let Some(id) = &task.id {
+            if let Some(i) = (0..j).find(|&i| tasks[i].id.as_ref() == Some(id)) {
+                problems.push((lines[j], format!("#id:{} is already the id of the task on line {}", id, lines[i])));
+            }
+        }
+        let public: Vec<&String> = task.tags.iter().filter(|tag| options.public_tags.contains_key(tag.as_str())).collect();
+        if !public.is_empty() && task.start_time.is_none() && !task.all_day {
+            problems.push((lines[j], format!("tagged +{} but has no time, so the public calendar can't show it",
+                                             public[0])));
+        }
+        for policy in policy::broken(task, options.policies) {
+            problems.push((lines[j], policy::describe(policy)));
+        }
+    }
+    // A plugin's problem without a line is about the whole file, so comes
+    // first, as line 0.
+    for plugin in options.plugins {
+        for problem in plugin.check(path, &source, &tasks, options.public_tags)? {
+            problems.push((problem.line.unwrap_or(0), problem.message));
+        }
+    }
+    problems.sort_by_key(|(line, _)| *line);
+    return Ok(problems.into_iter().map(|(line, problem)| match line {
+        0 => format!("{}: {}", path.display(), problem),
+        _ => format!("{}:{}: {}", path.display(), line, problem),
+    }).collect());
+}
@@ -0,0 +1,54 @@
+// Edits to the task file by more than one wtd at once (a sync from cron while
+// `wtd protect` runs, say) would otherwise each read the file, change it, and
+// write it back, so the last to finish silently drops the others' edits.
+// Each edit therefore holds an advisory lock on a `.wtd.md.lock` file beside
+// the task file from reading it until writing it, and writes only if the file
+// still says what was read, which catches edits made meanwhile by programs
+// that don't take the lock, such as an editor. The lock is released when the
+// process exits, however it exits, so a crash can't leave it stuck.
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use crate::read_file;
+
+pub struct FileLock {
+    _file: File,
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    return path.with_file_name(format!(".{}.{}", name, suffix));
+}
+
+// Waits until no other wtd is editing the file at `path`.
+pub fn lock(path: &Path) -> Result<FileLock, String> {
+    let lock_path = sibling(path, "lock");
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)
+        .map_err(|why| format!("Couldn't open {}: {}", lock_path.display(), why))?;
+    match file.try_lock() {
+        Ok(()) => {},
+        Err(TryLockError::WouldBlock) => {
+            eprintln!("Waiting for another wtd to finish editing {}...", path.display());
+            file.lock().map_err(|why| format!("Couldn't lock {}: {}", lock_path.display(), why))?;
+        },
+        Err(TryLockError::Error(why)) => return Err(format!("Couldn't lock {}: {}", lock_path.display(), why)),
+    }
+    return Ok(FileLock { _file: file });
+}
+
+// Replaces the file's contents, read earlier as `original`, with `contents`,
+// unless it was changed in the meantime. The new contents are written beside
+// it and renamed into place, so the file is never seen half-written. A
+// symlink is followed, so that it stays a symlink.
+pub fn write_checked(path: &Path, original: &str, contents: &str) -> Result<(), String> {
+    let target = path.canonicalize().map_err(|why| format!("{}: {}", path.display(), why))?;
+    if read_file(&target)? != original {
+        return Err(format!("{} was changed by another program while wtd was editing it, so wtd's edits \
+                            weren't saved; run it again", path.display()));
+    }
+    if contents == original {
+        return Ok(());
+    }
+    let temporary = sibling(&target, "tmp");
+    std::fs::write(&temporary, contents).map_err(|why| format!("Couldn't write {}: {}", temporary.display(), why))?;
+    return std::fs::rename(&temporary, &target).map_err(|why| format!("Couldn't replace {}: {}", path.display(), why));
+}
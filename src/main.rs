@@ -1,396 +1,1164 @@
-use std::fs::File;
+#![allow(clippy::needless_return)]
+
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::cmp::Ordering;
-use std::collections::HashMap;
-use chrono::{Datelike, NaiveDate, NaiveTime, Weekday, Duration, Timelike, Local};
-// use chrono::format::ParseError;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use chrono_tz::Tz;
+
+use wtd::config::{self, Audience, Config};
+use wtd::{agenda, attachments, avail, bench, check, credentials, dirs, gcal, hooks, ics, init, journal, json, lint, lock, plugin, policy, protect, redact, serve, stats, sync, time, tui, watch, widget, year};
+use wtd::{check_editable, cmp_tasks, load, load_archive, load_archive_out_of_office, load_notes, parse_cli_date, parse_hour_range, public_tags, read_file, read_stylesheet, render_fragment, render_html, render_into_template, sources, split_days, stylesheet_file, write_if_changed};
+use wtd::redact::Privacy;
+use wtd::staged::Staged;
+use wtd::{CalendarPrivacy, CellOverflow, MapLinks, Notes, ParseOptions, RenderOptions, Section, Task, TimeFormat, View};
 
-struct Task {
-    date: NaiveDate,
-    start_time: Option<NaiveTime>,
-    end_time: Option<NaiveTime>,
-    details: String,
-    tags: Vec<String>,
+#[derive(Parser)]
+#[command(name = "wtd", version, about = "Render wtd.md into public and private HTML calendars.")]
+struct Args {
+    /// Maximum width of text shown inside a grid cell, in characters (wide
+    /// characters such as CJK and most emoji count as two).
+    #[arg(long, value_name = "N")]
+    cell_max_chars: Option<usize>,
+    /// How text that doesn't fit in a grid cell is handled [default: wrap].
+    #[arg(long, value_enum)]
+    cell_overflow: Option<CellOverflow>,
+    /// Clock the calendars' times are shown on [default: 12h].
+    #[arg(long, value_enum)]
+    time_format: Option<TimeFormat>,
+    /// Layout of the calendar grid.
+    #[arg(long, value_enum, default_value_t = View::Grid)]
+    view: View,
+    /// Number of weeks shown by the compare view.
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(i64).range(1..))]
+    weeks: i64,
+    /// Leave tasks that are checked off out of the public calendar.
+    #[arg(long)]
+    hide_done: bool,
+    /// First day of the week, e.g. mon or sun [default: week_start from the
+    /// config, or the locale's].
+    #[arg(long, value_name = "DAY", value_parser = parse_weekday)]
+    week_start: Option<Weekday>,
+    /// Show whole weeks in the grid, from the start of the current week
+    /// through the end of the week of its last day.
+    #[arg(long)]
+    full_weeks: bool,
+    /// How much of the schedule the public calendar and other public output
+    /// show [default: none, or privacy under [redact] in the config].
+    #[arg(long, value_enum)]
+    privacy: Option<Privacy>,
+    /// Write the stylesheet into each page instead of linking it, so the page
+    /// stands alone; the built-in one unless the stylesheet file exists.
+    #[arg(long)]
+    embed_css: bool,
+    /// Settings file; defaults to ./wtd.toml or else the user config.toml.
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Apply the named [profiles.NAME] section of the config.
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+    /// Directory of older .md files consulted by history-wide commands.
+    #[arg(long, value_name = "DIR", default_value = "archive")]
+    archive: PathBuf,
+    /// Read misspelled day headers such as "## Tuessday" as the closest day, with a warning.
+    #[arg(long, conflicts_with = "strict")]
+    lenient: bool,
+    /// Stop on lines that aren't read, tags the config doesn't know, and
+    /// other likely mistakes that are otherwise warnings, and on misspelled
+    /// day headers even if the config enables lenient parsing.
+    #[arg(long)]
+    strict: bool,
+    /// Draw the events of an iCalendar file on the rendered calendars, tagged
+    /// +external; may be repeated [default: merge_ics from the config].
+    #[arg(long, value_name = "PATH")]
+    merge_ics: Vec<PathBuf>,
+    /// Task file to read, or - for standard input: markdown, TOML, JSON, or
+    /// iCalendar [default: wtd.md, or input from the config].
+    #[arg(value_name = "PATH")]
+    input: Option<PathBuf>,
+    /// Don't report progress through long operations, such as reading the
+    /// archive or syncing, nor warnings about the task file.
+    #[arg(long, short)]
+    quiet: bool,
+    /// Print what this version of wtd supports, as JSON, and exit.
+    #[arg(long)]
+    capabilities: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn parse_date_line(l: &str) -> Option<NaiveDate> {
-    for maybe_date_str in l.split(' ') {
-        match NaiveDate::parse_from_str(maybe_date_str, "%m/%d/%y") {
-            Err(_) => continue,
-            Ok(date) => { return Some(date); }
-        }
-    };
-    return None;
+#[derive(Subcommand)]
+enum Command {
+    /// Render a single calendar from any task file, over any window of days.
+    Render {
+        /// Task file to read [default: wtd.md, or input from the config].
+        #[arg(long, short, value_name = "PATH")]
+        input: Option<PathBuf>,
+        /// Where to write the HTML; printed to standard output if omitted.
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Number of days shown by the grid view [default: 14, or days under [render] in the config].
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(i64).range(1..))]
+        days: Option<i64>,
+        /// Hours of the day the grid shows, as START-END in 24-hour time [default: hours under [render] in the config, or all].
+        #[arg(long, value_name = "START-END", value_parser = parse_hour_range)]
+        hours: Option<(u32, u32)>,
+        /// First day shown, e.g. 2024-03-04 or 3/4/24 [default: today].
+        #[arg(long, value_parser = parse_cli_date, allow_hyphen_values = true)]
+        start: Option<NaiveDate>,
+        /// Render the private calendar, with every detail, instead of the public one.
+        #[arg(long)]
+        private: bool,
+        /// Keep running, and render again whenever the task file, one it includes,
+        /// the config or the template is saved.
+        #[arg(long, requires = "output")]
+        watch: bool,
+        /// Put each grid row and list entry on a line of its own, so that
+        /// renders kept for comparison diff line by line.
+        #[arg(long)]
+        line_breaks: bool,
+        /// Write just the calendar, without the page around it, for embedding.
+        #[arg(long, conflicts_with = "template")]
+        fragment: bool,
+        /// A template of a page of your own to put the calendar in, at {{ calendar }}
+        /// or a <!-- wtd:calendar --> comment.
+        #[arg(long, value_name = "PATH")]
+        template: Option<PathBuf>,
+    },
+    /// Write a year-at-a-glance SVG poster with one column per day.
+    Year {
+        /// Year to draw; defaults to the current year.
+        #[arg(long)]
+        year: Option<i32>,
+        /// Where to write the SVG; defaults to year-YYYY.svg.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print the tasks, here and in the archive, that match all of the
+    /// filters given; exits nonzero if none do.
+    List {
+        /// Only tasks with this tag; may be repeated for tasks with all of them.
+        #[arg(long, value_name = "TAG")]
+        tag: Vec<String>,
+        /// Only tasks on or after this day, e.g. 2024-03-01 or 3/1/24.
+        #[arg(long, value_parser = parse_cli_date, allow_hyphen_values = true)]
+        from: Option<NaiveDate>,
+        /// Only tasks on or before this day.
+        #[arg(long, value_parser = parse_cli_date, allow_hyphen_values = true)]
+        to: Option<NaiveDate>,
+        /// Only tasks whose description, notes, checklist, or location match
+        /// this regular expression, ignoring case.
+        #[arg(long, value_name = "PATTERN")]
+        grep: Option<String>,
+    },
+    /// Print what was scheduled on a past day, e.g. a year ago today.
+    Recall {
+        /// Day to look up, e.g. 2023-03-14 or 3/14/23.
+        #[arg(long, value_parser = parse_cli_date, allow_hyphen_values = true, conflicts_with = "years_ago")]
+        on: Option<NaiveDate>,
+        /// Look up today's date this many years back.
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        years_ago: i32,
+    },
+    /// Summarize the task history.
+    Stats {
+        #[command(subcommand)]
+        report: StatsReport,
+    },
+    /// Set up a new calendar: a starter wtd.md for this week, wtd.toml, and the stylesheet.
+    Init,
+    /// Time parsing and rendering over a generated schedule, to catch slowdowns.
+    Bench {
+        /// Number of tasks in the generated schedule.
+        #[arg(long, value_name = "N", default_value_t = 10000, value_parser = clap::value_parser!(u64).range(1..))]
+        synthetic: u64,
+        /// Times to run each phase; the fastest is reported.
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        runs: usize,
+    },
+    /// Browse the week in the terminal, with each task's details in a side pane.
+    Tui,
+    /// Print the parsed tasks as JSON, for jq and other scripts.
+    Json {
+        /// Include every task's details and tags, not just what the public calendar shows.
+        #[arg(long)]
+        private: bool,
+    },
+    /// Rewrite the task file in a canonical form, without changing any task.
+    Fmt {
+        /// Don't write anything; exit with an error if the file isn't formatted.
+        #[arg(long)]
+        check: bool,
+    },
+    /// Locate or create the config file.
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Manage secrets kept in the OS keyring, referenced from config as keyring:NAME.
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+    /// Write a standalone data file for use outside of wtd.
+    Export {
+        #[arg(long, value_enum, required_unless_present = "plugin")]
+        format: Option<ExportFormat>,
+        /// Write the public calendar in the output format of the plugin of
+        /// this name, in place of --format.
+        #[arg(long, value_name = "NAME", conflicts_with = "format")]
+        plugin: Option<String>,
+        /// Where to write the file; defaults to a name based on the format,
+        /// or standard output for a plugin's.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Number of upcoming events included in widget-json.
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        count: usize,
+    },
+    /// Check the task file against the tag policies in the config, and
+    /// generated output before publishing it.
+    Check {
+        /// A public calendar to check for private details, non-public tags,
+        /// and markup that didn't come from wtd.
+        #[arg(long, value_name = "PATH")]
+        published: Option<PathBuf>,
+    },
+    /// Report overlapping tasks, day headers outside their week, and public
+    /// tasks with no time, by line; exits nonzero if there are any.
+    Lint,
+    /// List every tag in the task file and archive, with how many tasks carry
+    /// it and the first and last days it was used.
+    Tags,
+    /// Two-way sync of the task file with the CalDAV calendar set under [sync] in the config.
+    Sync,
+    /// Book focus time into the free working hours of next week, as +self tasks.
+    Protect {
+        /// Hours of focus time to book over the week, counting blocks already booked.
+        #[arg(long, value_parser = parse_focus_hours)]
+        hours: f64,
+        /// What the blocks are called.
+        #[arg(long, default_value = "Focus time")]
+        label: String,
+        /// Working hours as START-END in 24-hour time [default: 9-17].
+        #[arg(long, value_name = "START-END", value_parser = parse_hour_range)]
+        work_hours: Option<(u32, u32)>,
+        /// Shortest block to book, in minutes.
+        #[arg(long, value_name = "MINUTES", default_value_t = 60, value_parser = clap::value_parser!(i64).range(1..=24 * 60))]
+        min_block: i64,
+        /// Longest block to book, in minutes.
+        #[arg(long, value_name = "MINUTES", default_value_t = 120, value_parser = clap::value_parser!(i64).range(1..=24 * 60))]
+        max_block: i64,
+    },
+    /// Add the event of an emailed invite (.ics) to the task file, and optionally write a reply.
+    Accept {
+        /// The invite, as attached to the email.
+        invite: PathBuf,
+        /// Tag the event +tentative, and reply that you might come.
+        #[arg(long)]
+        tentative: bool,
+        /// Write a reply with your answer here, to attach to an email to the organizer.
+        #[arg(long, value_name = "FILE")]
+        reply: Option<PathBuf>,
+        /// Your address among the invite's attendees [default: its only attendee].
+        #[arg(long, value_name = "ADDRESS", requires = "reply")]
+        me: Option<String>,
+    },
+    /// Open the journal entry of a +journal task's day in $EDITOR, writing
+    /// it from the template first if there's none yet.
+    Open {
+        /// The task's #id:, or words of its description.
+        task: String,
+        /// The day of the task to open, if it's on several [default: today,
+        /// else the latest before it, else the soonest after].
+        #[arg(long, value_parser = parse_cli_date, allow_hyphen_values = true)]
+        on: Option<NaiveDate>,
+        /// Just print the entry's path, without opening it.
+        #[arg(long)]
+        print: bool,
+    },
+    /// Check a task off in the task file.
+    Done {
+        /// The task's #id:, or words of its description.
+        task: String,
+        /// The day of the task to check off, if it's on several [default:
+        /// today, else the latest before it, else the soonest after].
+        #[arg(long, value_parser = parse_cli_date, allow_hyphen_values = true)]
+        on: Option<NaiveDate>,
+        /// Also record the time it was done, as +done:HH:MM.
+        #[arg(long)]
+        time: bool,
+    },
+    /// Print the free stretches of the working hours over the next working
+    /// days, to answer "when are you free?".
+    Avail {
+        /// Working days to list, from today.
+        #[arg(long, value_name = "N", default_value_t = 5)]
+        days: usize,
+        /// Working hours as START-END in 24-hour time [default: work_hours under [stats], or 9-17].
+        #[arg(long, value_name = "START-END", value_parser = parse_hour_range)]
+        work_hours: Option<(u32, u32)>,
+        /// Leave out free stretches shorter than this, in minutes.
+        #[arg(long, value_name = "MINUTES", default_value_t = 30)]
+        min_free: i64,
+        #[arg(long, value_enum, default_value_t = AvailFormat::Text)]
+        format: AvailFormat,
+        /// Give the free stretches on the clock of someone in this timezone, e.g. Asia/Tokyo.
+        #[arg(long, value_name = "TZ", value_parser = time::parse_timezone)]
+        their_tz: Option<chrono_tz::Tz>,
+        /// With --their-tz, give the stretches on both clocks.
+        #[arg(long, requires = "their_tz")]
+        both: bool,
+    },
+    /// Push the coming days' tasks to the Google Calendar set under [gcal] in the config.
+    PushGcal {
+        /// How many days, starting today, to push [default: 14].
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(i64).range(1..=gcal::MAX_DAYS))]
+        days: Option<i64>,
+    },
+    /// Serve the public calendar over HTTP, re-reading wtd.md on every request.
+    Serve {
+        /// Address to listen on [default: 127.0.0.1:8080].
+        #[arg(long)]
+        bind: Option<String>,
+        /// Tag counted by the meetings metric on /metrics [default: meetings].
+        #[arg(long)]
+        meeting_tag: Option<String>,
+        /// Let /graphql and /ws return private task details and tags.
+        #[arg(long)]
+        expose_private: bool,
+        /// Don't log requests, and send no-referrer and no-store headers.
+        #[arg(long)]
+        privacy_mode: bool,
+        /// Minutes between fetches of the --merge-ics calendars [default: 15].
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+        refresh_minutes: Option<u64>,
+        /// Number of days the grid shows, e.g. 3 for a dashboard of the next few
+        /// [default: days under [serve], else under [render], in the config].
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(i64).range(1..))]
+        days: Option<i64>,
+        /// Hours of the day the grid shows, as START-END in 24-hour time, e.g.
+        /// 8-20 for a small screen [default: hours under [serve], else under
+        /// [render], in the config, or all].
+        #[arg(long, value_name = "START-END", value_parser = parse_hour_range)]
+        hours: Option<(u32, u32)>,
+    },
 }
 
-fn parse_day_line(l: &str) -> Weekday {
-    let daystr = l.get(3..).expect("Day-of-week line not long enough...");
-    return Weekday::from_str(daystr).expect("Misparse day-of-week str...");
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the config file in use and the data directory.
+    Path,
+    /// Write a starter config.toml to the user config directory.
+    Init,
 }
 
-fn parse_time(s_: &str) -> NaiveTime {
-    let formats = vec!["%l:%M%p", "%H:%M"];
-    let mut s = s_.to_string();
-    if !s.contains(":") {
-        if s.ends_with("M") {
-            s.insert_str(s.len() - 2, ":00");
-        } else {
-            s.push_str(":00");
-        }
-    }
-    for format in formats {
-        match NaiveTime::parse_from_str(&s, format) {
-            Err(_) => continue,
-            Ok(parsed) => {
-                if !format.contains("%p") && parsed.hour() < 6 {
-                    return parsed + Duration::hours(12);
-                }
-                return parsed;
-            }
-        }
-    }
-    panic!("Couldn't parse time {}", s);
+#[derive(Subcommand)]
+enum SecretAction {
+    /// Store a secret read from standard input.
+    Set { name: String },
+    /// Print a stored secret.
+    Get { name: String },
+    /// Remove a stored secret.
+    Delete { name: String },
 }
 
-fn parse_duration(s: &str) -> chrono::Duration {
-    // We try to find Mm, HhMm, Hh
-    if s.contains("h") && s.contains("m") {
-        // TODO: Decompose this case into the two below.
-        let hstr = s.split("h").collect::<Vec<&str>>().get(0).expect("").to_string();
-        let mstr = s.split("h").collect::<Vec<&str>>().get(1).expect("").split("m").collect::<Vec<&str>>().get(0).expect("Expected XhYm").to_string();
-        let secs = ((hstr.parse::<u64>().unwrap() * 60)
-                    + (mstr.parse::<u64>().unwrap())) * 60;
-        return chrono::Duration::from_std(std::time::Duration::new(secs, 0)).unwrap();
-    } else if s.contains("h") {
-        let hstr = s.split("h").collect::<Vec<&str>>().get(0).expect("").to_string();
-        let secs = hstr.parse::<u64>().unwrap() * 60 * 60;
-        return chrono::Duration::from_std(std::time::Duration::new(secs, 0)).unwrap();
-    } else if s.contains("m") {
-        let hstr = s.split("m").collect::<Vec<&str>>().get(0).expect("").to_string();
-        let secs = hstr.parse::<u64>().unwrap() * 60;
-        return chrono::Duration::from_std(std::time::Duration::new(secs, 0)).unwrap();
-    }
-    panic!("Couldn't parse duration {}", s);
+#[derive(Clone, Copy, ValueEnum)]
+enum AvailFormat {
+    /// A line per day, e.g. "Tue 3/19: free 10–12, 2–4", to paste into an email.
+    Text,
+    /// Each day's date and free stretches, in 24-hour time.
+    Json,
 }
 
-fn handle_task_details(l: &str, t: &mut Task) {
-    for tok in l.split(' ') {
-        if tok.starts_with("+") {
-            let tag = tok.get(1..).expect("Unexpected");
-            t.tags.push(tag.to_string());
-        } else if tok.starts_with("@") {
-            let timestr = tok.get(1..).expect("Unexpected");
-            if timestr.contains("+") { // @Start+Duration
-                let parts: Vec<&str> = timestr.split("+").collect();
-                match parts[..] {
-                    [startstr, durstr] => {
-                        t.start_time = Some(parse_time(startstr));
-                        t.end_time = Some(t.start_time.unwrap() + parse_duration(durstr));
-                    },
-                    _ => panic!("Not 2 parts to {}\n", timestr)
-                }
-            } else if timestr.contains("--") { // @Start--End
-                let parts: Vec<&str> = timestr.split("--").collect();
-                match parts[..] {
-                    [startstr, endstr] => {
-                        t.start_time = Some(parse_time(startstr));
-                        t.end_time = Some(parse_time(endstr));
-                        if t.start_time > t.end_time {
-                            panic!("Start time {} interpreted as after end time {}",
-                                   startstr, endstr);
-                        }
-                    },
-                    _ => panic!("Not 2 parts to {}\n", timestr)
-                }
-            } else {
-                panic!("'{}' is not of the form Start+Duration or Start--End\n", timestr);
-            }
-        } else {
-            if t.details.len() > 0 {
-                t.details.push(' ');
-            }
-            t.details.push_str(tok.trim());
-        }
-    }
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// The upcoming public events read by widget.js.
+    WidgetJson,
+    /// An iCalendar file of the public calendar, for subscribing from calendar apps.
+    Ics,
 }
 
-fn cmp_tasks(a: &Task, b: &Task) -> Ordering {
-    if a.date < b.date {
-        return Ordering::Less;
-    } else if b.date < a.date {
-        return Ordering::Greater;
-    }
-    match [a.start_time, b.start_time] {
-        [None, None] => return Ordering::Equal,
-        [None, Some(_)] => return Ordering::Greater,
-        [Some(_), None] => return Ordering::Less,
-        [Some(atime), Some(btime)] => return if atime < btime { Ordering::Less } else { Ordering::Greater },
+// The most weeks a stats report looks back over, ten years' worth.
+const MAX_WEEKS: i64 = 520;
+
+#[derive(Subcommand)]
+enum StatsReport {
+    /// Completion streaks and weekly adherence for +habit:NAME tasks.
+    Habits {
+        /// Number of recent weeks to show adherence for.
+        #[arg(long, value_name = "N", default_value_t = 8, value_parser = clap::value_parser!(i64).range(1..=MAX_WEEKS))]
+        weeks: i64,
+    },
+    /// Checklist items checked off per week, nested ones included.
+    Checklists {
+        /// Number of weeks to look at, ending with the current one.
+        #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(i64).range(1..=MAX_WEEKS))]
+        weeks: i64,
+    },
+    /// Hours scheduled under each tag, and untagged, per week or day.
+    Tags {
+        /// Number of weeks to look at, ending with the current one.
+        #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(i64).range(1..=MAX_WEEKS))]
+        weeks: i64,
+        /// One row per day instead of per week.
+        #[arg(long)]
+        daily: bool,
+        /// Also write the table, with bars, as a section of HTML.
+        #[arg(long, value_name = "PATH")]
+        html: Option<PathBuf>,
+    },
+    /// Hours of the working day that were left unscheduled, by weekday.
+    Idle {
+        /// Number of past weeks to look at.
+        #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(i64).range(1..=MAX_WEEKS))]
+        weeks: i64,
+        /// Working hours as START-END in 24-hour time [default: 9-17].
+        #[arg(long, value_name = "START-END", value_parser = parse_hour_range)]
+        work_hours: Option<(u32, u32)>,
+    },
+    /// When timed tasks were checked off (+done:HH:MM) against when they were planned to end.
+    Done {
+        /// Number of weeks to look at, ending with the current one.
+        #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(i64).range(1..=MAX_WEEKS))]
+        weeks: i64,
+    },
+    /// Weekly hours spent in meetings, as a sparkline or an SVG chart.
+    Meetings {
+        /// Number of weeks to plot, ending with the current one.
+        #[arg(long, value_name = "N", default_value_t = 12, value_parser = clap::value_parser!(i64).range(1..=MAX_WEEKS))]
+        weeks: i64,
+        /// Tag marking meetings.
+        #[arg(long, default_value = "meetings")]
+        tag: String,
+        /// Also write the trend as an SVG bar chart.
+        #[arg(long, value_name = "PATH")]
+        svg: Option<PathBuf>,
+        /// Hourly cost of one attendee, for estimating what meetings with an &N count cost.
+        #[arg(long, value_name = "RATE")]
+        rate: Option<f64>,
+    },
+}
+
+// The same month and day some years before `date`, landing on Feb 28 when
+// the original date is a leap day.
+fn years_before(date: NaiveDate, years: i32) -> NaiveDate {
+    let year = date.year() - years;
+    return date.with_year(year).or_else(|| time::ymd(year, 2, 28)).unwrap_or(date);
+}
+
+// Adds the events of the --merge-ics calendars to the tasks being rendered.
+fn merge_ics(tasks: &mut Vec<Task>, paths: &[PathBuf], tz: Option<Tz>) {
+    for path in paths {
+        tasks.extend(or_exit(ics::load(path, "external", tz)));
     }
 }
 
-fn does_overlap(timespan_start: &NaiveTime, timespan_end: &NaiveTime, task: &Task) -> bool {
-    return match [task.start_time, task.end_time] {
-        [Some(start), Some(end)] => (&start <= timespan_start && timespan_start < &end)
-                                    || (&start < timespan_end && timespan_end < &end),
-        _ => false,
+// The names of a command's subcommands, with theirs after them as e.g.
+// "stats idle".
+fn command_names(command: &clap::Command, prefix: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for subcommand in command.get_subcommands().filter(|subcommand| subcommand.get_name() != "help") {
+        let name = format!("{}{}", prefix, subcommand.get_name());
+        let nested = command_names(subcommand, &format!("{} ", name));
+        names.push(name);
+        names.extend(nested);
     }
+    return names;
 }
 
-enum CalendarPrivacy {
-    Public,
-    Private,
+fn value_names<T: ValueEnum>() -> Vec<String> {
+    return T::value_variants().iter()
+        .filter_map(|value| value.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect();
 }
 
-fn tasks_to_html(tasks: &Vec<Task>, privacy: CalendarPrivacy) -> String {
-    let public_tags = HashMap::from([
-        ("busy", "I will be genuinely busy, e.g., a meeting with others."),
-        ("rough", "The nature of the event (e.g., a hike) makes it difficult to preduct the exact start/end times."),
-        ("tentative", "This event timing is only tentative."),
-        ("join-me", "This is an open event; if you're interested in joining please reach out!"),
-        ("self", "This is scheduled time for me to complete a specific work or personal task; I can usually reschedule such blocks when requested."),
-    ]);
+// What `--capabilities` prints, for wrapper scripts and editor plugins to
+// adapt to the installed version.
+fn capabilities() -> serde_json::Value {
+    return serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "commands": command_names(&Args::command(), ""),
+        "input_formats": ["markdown", "toml", "json", "ics"],
+        "export_formats": value_names::<ExportFormat>(),
+        "views": value_names::<View>(),
+        "config": {
+            "schema_version": config::SCHEMA_VERSION,
+            "keys": config::KEYS,
+        },
+    });
+}
 
-    let mut html = "<html><head><meta charset=\"UTF-8\"><title>Calendar</title><link rel=\"stylesheet\" href=\"calendar_style.css\"></link></head><body>".to_string();
+fn parse_weekday(s: &str) -> Result<Weekday, String> {
+    return Weekday::from_str(s).map_err(|_| format!("{:?} is not a day of the week", s));
+}
 
-    let n_days = 14;
-    let start_period = Local::now().date().naive_local();
-    let end_period = start_period + Duration::days(n_days);
-    let mut week_task_ids: Vec<usize> = Vec::new();
-    for (i, task) in tasks.iter().enumerate() {
-        if task.date >= start_period && task.date < end_period {
-            week_task_ids.push(i);
-        }
-    }
+// Hours of focus time, at most the whole week.
+fn parse_focus_hours(s: &str) -> Result<f64, String> {
+    return match s.parse::<f64>() {
+        Ok(hours) if (0.0..=protect::MAX_HOURS).contains(&hours) => Ok(hours),
+        _ => Err(format!("expected a number of hours from 0 to {}", protect::MAX_HOURS)),
+    };
+}
 
-    let min_incr: i64 = 15;
-    let timespans_per_day = (24 * 60 ) / min_incr;
-    let mut table: Vec<Vec<Option<usize>>> = Vec::new();
-    for i in 0..timespans_per_day {
-        table.push(Vec::new());
-        for _ in 0..n_days {
-            table[i as usize].push(None);
-        }
-    }
+fn read_template(path: &Path) -> Result<(PathBuf, String), String> {
+    return std::fs::read_to_string(path)
+        .map(|template| (path.to_path_buf(), template))
+        .map_err(|why| format!("Couldn't read template {}: {}", path.display(), why));
+}
 
-    html.push_str("<table>");
-    html.push_str("<tr><th>Time</th>");
-    for offset in 0..n_days {
-        html.push_str("<th>");
-        html.push_str(&(start_period + Duration::days(offset)).format("%a %-m/%-d/%y").to_string());
-        html.push_str("</th>");
-    }
-    html.push_str("</tr>");
+// The file written for a calendar: a page of its own, or `template` filled in.
+fn page(tasks: &[Task], notes: &Notes, privacy: CalendarPrivacy, options: &RenderOptions,
+        template: Option<&(PathBuf, String)>) -> Result<String, String> {
+    return match template {
+        Some((path, template)) => render_into_template(template, tasks, notes, privacy, options)
+            .map_err(|why| format!("{}: {}", path.display(), why)),
+        None => Ok(format!("{}\n", render_html(tasks, notes, privacy, options))),
+    };
+}
 
-    week_task_ids.sort_by(|a, b| cmp_tasks(&tasks[*a], &tasks[*b]));
+// Reports an error loading the task file and exits.
+fn or_exit<T>(result: Result<T, String>) -> T {
+    return result.unwrap_or_else(|why| {
+        eprintln!("{}", why);
+        std::process::exit(1);
+    });
+}
 
-    for i in 0..timespans_per_day {
-        let timespan_start = NaiveTime::from_hms(0, 0, 0) + Duration::minutes(i * min_incr);
-        let timespan_end = NaiveTime::from_hms(0, 0, 0) + Duration::minutes((i + 1) * min_incr);
-        for offset in 0..n_days {
-            // (1) Find all task ids that intersect this timespan on this day.
-            let this_date = start_period + Duration::days(offset);
-            let on_this_date: Vec<usize>
-                = week_task_ids.iter().map(|&idx| idx)
-                  .filter(|&idx| tasks[idx].date == this_date).collect();
-            let intersecting: Vec<usize>
-                = on_this_date.iter().map(|&idx| idx)
-                  .filter(|&idx| does_overlap(&timespan_start, &timespan_end, &tasks[idx])).collect();
-            // (2) Find the event ending first and place it in the table.
-            table[i as usize][offset as usize] = intersecting.iter()
-                .map(|&idx| idx)
-                .min_by_key(|&idx| tasks[idx].end_time.expect("Should have an end time at this point..."));
-        }
+// Writes an output file (see write_if_changed), exiting if that fails.
+fn write_or_exit(path: &Path, contents: &str) {
+    or_exit(write_if_changed(path, contents).map_err(|why| format!("Couldn't write {}: {}", path.display(), why)));
+}
+
+// Reports a problem with the config and exits.
+fn config_error<T>(why: impl std::fmt::Display) -> T {
+    return or_exit(Err(format!("Couldn't load config: {}", why)));
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.capabilities {
+        println!("{:#}", capabilities());
+        return;
     }
-    for row_idx in 0..timespans_per_day {
-        let timespan_start = NaiveTime::from_hms(0, 0, 0) + Duration::minutes(row_idx * min_incr);
-        html.push_str("<tr><td><b>");
-        html.push_str(&timespan_start.format("%l:%M %p").to_string());
-        html.push_str("</b></td>");
-        for col_idx in 0..n_days {
-            let task_idx = table[row_idx as usize][col_idx as usize];
-            match task_idx {
-                Some(idx) => {
-                    if row_idx == 0 || table[(row_idx - 1) as usize][col_idx as usize] != task_idx {
-                        let mut rowspan = 0;
-                        for i in row_idx..timespans_per_day {
-                            if table[i as usize][col_idx as usize] == task_idx {
-                                rowspan += 1;
-                            } else {
-                                break;
-                            }
-                        }
-                        html.push_str("<td class=\"has-task");
-                        for tag in &tasks[idx].tags {
-                            if public_tags.contains_key(tag.as_str()) {
-                                html.push_str(" tag-");
-                                html.push_str(tag.as_str());
-                            }
-                        }
-                        html.push_str("\" rowspan=\"");
-                        html.push_str(rowspan.to_string().as_str());
-                        html.push_str("\">");
-                        html.push_str("<a href=\"#");
-                        html.push_str("task-");
-                        html.push_str(idx.to_string().as_str());
-                        html.push_str("\">");
-                        match privacy {
-                            CalendarPrivacy::Public => {
-                                let mut any_yet = false;
-                                for tag in &tasks[idx].tags {
-                                    if public_tags.contains_key(tag.as_str()) {
-                                        if any_yet { html.push_str(", "); }
-                                        html.push_str(tag.as_str());
-                                        any_yet = true;
-                                    }
-                                }
-                                if tasks[idx].tags.contains(&&"public".to_string()) {
-                                    if any_yet { html.push_str(": \""); }
-                                    html.push_str(&tasks[idx].details.as_str());
-                                    html.push_str("\"");
-                                    any_yet = true;
-                                }
-                                if !any_yet {
-                                    html.push_str("has-task");
-                                }
-                            },
-                            CalendarPrivacy::Private => {
-                                html.push_str(&tasks[idx].details.as_str());
-                            },
-                        }
-                        html.push_str("</a></td>");
-                    }
-                },
-                _ => {
-                    html.push_str("<td></td>");
-                },
-            }
+    let profile = args.profile.as_deref();
+    let config_path = config::find(args.config.as_deref());
+    let config = match &config_path {
+        Some(config_path) => config::load(config_path, profile),
+        None if profile.is_some() => Err("--profile needs a config file".to_string()),
+        None => Ok(Config::default()),
+    }.unwrap_or_else(config_error);
+    let path = args.input.as_deref().or(config.input.as_deref()).unwrap_or(Path::new("wtd.md"));
+    let merged_calendars = if args.merge_ics.is_empty() { &config.merge_ics } else { &args.merge_ics };
+    let parse_options = ParseOptions {
+        lenient: args.lenient || (config.parse.lenient && !args.strict),
+        date_formats: config.parse.date_formats.clone(),
+        pre_parse: config.hooks.pre_parse.clone(),
+        strict: args.strict,
+        // Every tag the config mentions, besides those listed for this.
+        tags: config.parse.tags.iter()
+            .chain(config.tags.clone().unwrap_or_else(public_tags).keys())
+            .chain(config.render.colors.keys())
+            .chain(&config.render.tag_priority)
+            .chain(config.policies.iter().map(|policy| &policy.tag))
+            .chain(&config.serve.meeting_tag)
+            .cloned()
+            .collect(),
+        quiet: args.quiet,
+        timezone: config.parse.timezone.as_deref().map(time::parse_timezone).transpose()
+            .unwrap_or_else(|why| config_error(format!("timezone under [parse]: {}", why))),
+    };
+    for format in &parse_options.date_formats {
+        if !time::is_valid_format(format) {
+            config_error(format!("{:?} under [parse] date_formats isn't a date format", format))
         }
-        html.push_str("</tr>");
     }
-    html.push_str("</table><ul>");
-    for i in week_task_ids.iter() {
-        let task = &tasks[*i];
-        let is_public = task.tags.contains(&"public".to_string());
-        match (&privacy, &task.start_time, &is_public) {
-            (CalendarPrivacy::Public, None, false) => continue,
-            _ => (),
-        }
-        html.push_str("<li id=\"task-");
-        html.push_str(i.to_string().as_str());
-        html.push_str("\">");
-        html.push_str(task.date.format("%a %-m/%-d/%y ").to_string().as_str());
-        match [task.start_time, task.end_time] {
-            [Some(start), Some(end)] => {
-                html.push_str(start.format("%l:%M%p").to_string().as_str());
-                html.push_str(" -- ");
-                html.push_str(end.format("%l:%M%p").to_string().as_str());
-            }
-            _ => (),
-        }
-        html.push_str("<ul>");
-        match privacy {
-            CalendarPrivacy::Public => {
-                if task.tags.contains(&"public".to_string()) {
-                    html.push_str("<li><b>Description:</b> ");
-                    html.push_str(task.details.as_str());
-                    html.push_str("</li>");
-                }
-                for tag in &task.tags {
-                    if public_tags.contains_key(&tag.as_str()) {
-                        html.push_str("<li>Tagged <b>");
-                        html.push_str(tag.as_str());
-                        html.push_str(":</b> ");
-                        html.push_str(public_tags.get(&tag.as_str()).expect(""));
-                        html.push_str("</li>");
-                    }
+    let redactor = redact::Redactor::new(&config.redact, &config.policies)
+        .unwrap_or_else(config_error)
+        .with_privacy(args.privacy);
+    let stylesheet = config.render.stylesheet.clone().unwrap_or_else(|| "calendar_style.css".to_string());
+    let options = RenderOptions {
+        cell_max_chars: args.cell_max_chars.or(config.render.cell_max_chars),
+        cell_overflow: args.cell_overflow.or(config.render.cell_overflow).unwrap_or(CellOverflow::Wrap),
+        time_format: args.time_format.or(config.render.time_format).unwrap_or(TimeFormat::TwelveHour),
+        view: args.view,
+        weeks: args.weeks,
+        days: match config.render.days {
+            Some(days) if days < 1 => config_error("days must be at least 1"),
+            days => days.unwrap_or(14),
+        },
+        start: None,
+        week_start: match (args.week_start, config.render.week_start.as_deref()) {
+            (Some(day), _) => day,
+            (None, Some(day)) => Weekday::from_str(day)
+                .unwrap_or_else(|_| config_error(format!("week_start {:?} is not a day of the week", day))),
+            (None, None) => time::locale_week_start(),
+        },
+        full_weeks: args.full_weeks || config.render.full_weeks,
+        hide_done: args.hide_done || config.render.hide_done,
+        increment: match config.render.increment {
+            Some(increment) if !(1..=60).contains(&increment) || 60 % increment != 0 =>
+                config_error(format!("increment must be a number of minutes that divides an hour, not {}", increment)),
+            increment => increment.unwrap_or(15),
+        },
+        hours: config.render.hours.as_deref().map(|hours| {
+            return parse_hour_range(hours).unwrap_or_else(|why| config_error(format!("hours under [render]: {}", why)));
+        }),
+        public_tags: config.tags.clone().unwrap_or_else(public_tags),
+        stylesheet: stylesheet.clone(),
+        // The built-in stylesheet stands in for one that was never written out.
+        embedded_css: (args.embed_css || config.render.embed_css)
+            .then(|| read_stylesheet(stylesheet_file(&stylesheet, path).as_deref())),
+        hourly_rate: match (config.render.meeting_costs, config.stats.hourly_rate) {
+            (false, _) => None,
+            (true, Some(rate)) => Some(rate),
+            (true, None) => config_error("meeting_costs needs an hourly_rate under [stats]"),
+        },
+        line_breaks: false,
+        sections: config.render.sections.iter().map(|section| {
+            if section.days.is_some_and(|days| days < 1) || section.weeks.is_some_and(|weeks| weeks < 1) {
+                config_error("a section's days and weeks must be at least 1")
+            }
+            return Section { view: section.view, days: section.days, weeks: section.weeks };
+        }).collect(),
+        map_links: config.render.map_links.unwrap_or(MapLinks::OpenStreetMap),
+        tag_priority: config.render.tag_priority.clone(),
+        tag_colors: config.render.colors.iter().map(|(tag, color)| {
+            if tag.is_empty() || !tag.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+                config_error(format!("{:?} under [render.colors] isn't a tag", tag))
+            }
+            if color.is_empty() || !color.chars().all(|c| c.is_alphanumeric() || " #(),.%-".contains(c)) {
+                config_error(format!("{:?} for {} under [render.colors] isn't a CSS color", color, tag))
+            }
+            return (tag.clone(), color.clone());
+        }).collect(),
+    };
+    let journal_dir = config.journal.dir.clone().unwrap_or_else(|| PathBuf::from("journal"));
+    match args.command {
+        Some(Command::Render { input, output, days, hours, start, private, watch, line_breaks, fragment, template }) => {
+            let path = input.as_deref().unwrap_or(path);
+            let options = RenderOptions { days: days.unwrap_or(options.days), hours: hours.or(options.hours), start, line_breaks, ..options };
+            // The merged calendars are fetched when rendering once, and in the
+            // background while watching, as `serve` does.
+            let render = |prefetched: Option<&ics::Prefetch>| -> Result<(), String> {
+                let mut tasks = load(path, &parse_options)?;
+                let input_dir = path.parent().unwrap_or(Path::new(""));
+                attachments::check(&mut tasks, input_dir);
+                match prefetched {
+                    Some(prefetched) => tasks.extend(prefetched.tasks()),
+                    None => for calendar in merged_calendars {
+                        tasks.extend(ics::load(calendar, "external", parse_options.timezone)?);
+                    },
                 }
-            },
-            CalendarPrivacy::Private => {
-                html.push_str("<li><b>Description:</b> ");
-                html.push_str(task.details.as_str());
-                html.push_str("</li><li>Tagged: ");
-                for (i, tag) in task.tags.iter().enumerate() {
-                    if i > 0 { html.push_str(", "); }
-                    html.push_str("<b>");
-                    html.push_str(tag.as_str());
-                    html.push_str("</b>");
+                let notes = Notes { journal: journal::load(input_dir, &journal_dir), ..load_notes(path, &parse_options) };
+                let (privacy, tasks, notes) = if private {
+                    (CalendarPrivacy::Private, tasks, notes)
+                } else {
+                    (CalendarPrivacy::Public, redactor.redact(&tasks), redactor.redact_notes(&notes))
+                };
+                let html = match (&template, fragment) {
+                    (Some(template), _) => page(&tasks, &notes, privacy, &options, Some(&read_template(template)?))?,
+                    (None, true) => format!("{}\n", render_fragment(&tasks, &notes, privacy, &options)),
+                    (None, false) => page(&tasks, &notes, privacy, &options, None)?,
+                };
+                let html = hooks::post_render(&config.hooks.post_render, privacy, &tasks, &options.public_tags, html)?;
+                match &output {
+                    Some(output) => {
+                        let mut staged = Staged::default();
+                        staged.write(output, html);
+                        let output_dir = output.parent().unwrap_or(Path::new(""));
+                        attachments::publish(&tasks, input_dir, output_dir, &privacy, &mut staged)?;
+                        hooks::pre_publish(&config.hooks.pre_publish, &staged.paths(), &tasks, &options.public_tags)?;
+                        staged.commit()?;
+                    },
+                    None => print!("{}", html),
                 }
-                html.push_str("</li>");
+                return Ok(());
+            };
+            if !watch {
+                or_exit(render(None));
+                return;
             }
-        }
-        html.push_str("</ul>");
-        html.push_str("</li>");
-    }
-    html.push_str("</ul><a href=\"https://github.com/matthewsot/wtd\">src</a></body></html>");
-    return html;
-}
-
-// https://doc.rust-lang.org/std/fs/struct.File.html
-fn main() {
-    let path = Path::new("wtd.md");
-    let display = path.display();
-
-    // Open the path in read-only mode, returns `io::Result<File>`
-    let mut file = match File::open(&path) {
-        Err(why) => panic!("Error opening {}: {}", display, why),
-        Ok(file) => file,
-    };
-
-    // Read the file contents into a string, returns `io::Result<usize>`
-    let mut s = String::new();
-    match file.read_to_string(&mut s) {
-        Err(why) => panic!("Couldn't read {}: {}", display, why),
-        Ok(_) => {
-
-            let mut tasks = Vec::new();
-            let mut start_date = None;
-            let mut the_date = None;
-            for l in s.split('\n') {
-                if l.starts_with("# ") {
-                    // '# 12/27/21', starts a new week block
-                    start_date = parse_date_line(l);
-                } else if l.starts_with("## ") {
-                    // '## Monday/Tuesday/...', starts a new day block
-                    // Need to compute the actual date, basically looking for the first one after
-                    // start_date.
-                    let dayofweek = parse_day_line(l);
-                    let mut current = start_date.expect("Invalid or missing '# ' date");
-                    the_date = loop {
-                        if current.weekday() == dayofweek {
-                            break Some(current);
-                        }
-                        current = current.succ();
+            if path == Path::new("-") {
+                eprintln!("--watch needs a task file to watch rather than standard input");
+                std::process::exit(1);
+            }
+            let prefetched = ics::Prefetch::new(merged_calendars, parse_options.timezone);
+            let refresh = std::time::Duration::from_secs(60 * config.serve.refresh_minutes.unwrap_or(15).max(1));
+            let (changed, changes) = std::sync::mpsc::channel();
+            if let Err(why) = render(Some(&prefetched)) {
+                eprintln!("{}", why);
+            }
+            eprintln!("Watching {} for changes; press Ctrl-C to stop.", path.display());
+            std::thread::scope(|scope| {
+                scope.spawn(|| prefetched.keep_fetching(refresh, || { let _ = changed.send(()); }));
+                scope.spawn(|| {
+                    let files = || {
+                        let mut files = sources(path, &parse_options);
+                        files.extend(config_path.clone());
+                        files.extend(template.clone());
+                        return files;
                     };
-                } else if l.starts_with("- [ ]") || l.starts_with("- [X]") {
-                    // '- [ ] ...', starts a new task block
-                    let date = the_date.expect("No current date parsed yet...");
-                    tasks.push(Task {
-                        date: date,
-                        start_time: None,
-                        end_time: None,
-                        details: "".to_string(),
-                        tags: Vec::new(),
+                    watch::watch(files, |files| {
+                        if let Some(config_path) = config_path.as_ref().filter(|config_path| files.contains(config_path)) {
+                            watch::restart(&format!("{} changed", config_path.display()));
+                        }
+                        let _ = changed.send(());
                     });
-                    let details = l.get(5..).expect("").trim();
-                    handle_task_details(details, tasks.last_mut().expect("Unexpected error..."));
-                } else if l.starts_with(" ") {
-                    // Extends the last task.
-                    handle_task_details(l, tasks.last_mut().expect("Unexpected error..."));
-                } else {
-                    if l.trim().len() > 0 {
-                        eprint!("Ignoring line: {}\n", l);
+                });
+                for () in changes.iter() {
+                    // Changes that came in together are rendered once.
+                    while changes.try_recv().is_ok() {}
+                    match render(Some(&prefetched)) {
+                        Ok(()) => eprintln!("Rendered {} at {}", path.display(), time::format_hhmmss(time::now().time())),
+                        Err(why) => eprintln!("{}", why),
                     }
                 }
+            });
+        },
+        Some(Command::Year { year, output }) => {
+            let tasks = or_exit(load_archive(path, &args.archive, &parse_options, args.quiet));
+            let year = year.unwrap_or_else(|| time::today().year());
+            let svg = or_exit(year::year_to_svg(&split_days(&tasks), year, &options.public_tags));
+            let output = output.unwrap_or_else(|| PathBuf::from(format!("year-{}.svg", year)));
+            write_or_exit(&output, &format!("{}\n", svg));
+        },
+        Some(Command::List { tag, from, to, grep }) => {
+            let grep = or_exit(grep.map(|pattern| {
+                return regex::RegexBuilder::new(&pattern).case_insensitive(true).build()
+                    .map_err(|why| format!("--grep {:?}: {}", pattern, why));
+            }).transpose());
+            let filter = agenda::Filter { tags: tag, from, to, grep };
+            let tasks = or_exit(load_archive(path, &args.archive, &parse_options, args.quiet));
+            let matching: Vec<&Task> = tasks.iter().filter(|task| filter.matches(task)).collect();
+            if matching.is_empty() {
+                std::process::exit(1);
             }
-            let public_html = tasks_to_html(&tasks, CalendarPrivacy::Public);
-            let private_html = tasks_to_html(&tasks, CalendarPrivacy::Private);
-            // https://riptutorial.com/rust/example/4276/write-in-a-file
-            let mut public = File::create(Path::new("public.html")).unwrap();
-            writeln!(&mut public, "{}", public_html).unwrap();
-            let mut private = File::create(Path::new("private.html")).unwrap();
-            writeln!(&mut private, "{}", private_html).unwrap();
-        }
+            print!("{}", agenda::format_agenda(&matching));
+        },
+        Some(Command::Recall { on, years_ago }) => {
+            let tasks = or_exit(load_archive(path, &args.archive, &parse_options, args.quiet));
+            let day = on.unwrap_or_else(|| years_before(time::today(), years_ago));
+            let on_day: Vec<&Task> = tasks.iter().filter(|t| t.date == day).collect();
+            if on_day.is_empty() {
+                println!("Nothing recorded for {}.", time::format_date(day));
+            } else {
+                print!("{}", agenda::format_agenda(&on_day));
+            }
+        },
+        Some(Command::Tags) => {
+            let tasks = or_exit(load_archive(path, &args.archive, &parse_options, args.quiet));
+            print!("{}", stats::tag_usage_report(&tasks, &options.public_tags));
+        },
+        Some(Command::Stats { report: StatsReport::Habits { weeks } }) => {
+            let tasks = or_exit(load_archive(path, &args.archive, &parse_options, args.quiet));
+            print!("{}", stats::habits_report(&tasks, time::today(), weeks, options.week_start));
+        },
+        Some(Command::Stats { report: StatsReport::Checklists { weeks } }) => {
+            let tasks = or_exit(load_archive(path, &args.archive, &parse_options, args.quiet));
+            print!("{}", stats::checklists_report(&tasks, time::today(), weeks, options.week_start));
+        },
+        Some(Command::Stats { report: StatsReport::Done { weeks } }) => {
+            let tasks = or_exit(load_archive(path, &args.archive, &parse_options, args.quiet));
+            print!("{}", stats::done_report(&tasks, time::today(), weeks, options.week_start));
+        },
+        Some(Command::Stats { report: StatsReport::Tags { weeks, daily, html } }) => {
+            let tasks = split_days(&or_exit(load_archive(path, &args.archive, &parse_options, args.quiet)));
+            let table = stats::tag_hours(&tasks, time::today(), weeks, daily, options.week_start);
+            print!("{}", stats::tag_hours_report(&table, daily));
+            if let Some(html) = html {
+                write_or_exit(&html, &format!("{}\n", stats::tag_hours_html(&table, daily)));
+            }
+        },
+        Some(Command::Stats { report: StatsReport::Idle { weeks, work_hours } }) => {
+            let tasks = split_days(&or_exit(load_archive(path, &args.archive, &parse_options, args.quiet)));
+            let work_hours = work_hours.unwrap_or_else(|| {
+                return parse_hour_range(config.stats.work_hours.as_deref().unwrap_or("9-17"))
+                    .unwrap_or_else(|why| config_error(format!("work_hours: {}", why)));
+            });
+            let out_of_office = load_archive_out_of_office(path, &args.archive, &parse_options);
+            print!("{}", stats::idle_report(&tasks, &out_of_office, time::today(), weeks, work_hours, options.week_start));
+        },
+        Some(Command::Stats { report: StatsReport::Meetings { weeks, tag, svg, rate } }) => {
+            // Hours are counted on the day they fall on, even for tasks spanning days.
+            let tasks = split_days(&or_exit(load_archive(path, &args.archive, &parse_options, args.quiet)));
+            let hours = stats::weekly_tag_hours(&tasks, time::today(), weeks, &tag, options.week_start);
+            print!("{}", stats::sparkline_report(&hours, &tag));
+            if let Some(svg) = svg {
+                write_or_exit(&svg, &format!("{}\n", stats::bar_chart_svg(&hours, &tag)));
+            }
+            if let Some(rate) = rate.or(config.stats.hourly_rate) {
+                print!("{}", stats::cost_report(&tasks, time::today(), weeks, &tag, rate, options.week_start));
+            }
+        },
+        Some(Command::Config { action: ConfigAction::Path }) => {
+            match &config_path {
+                Some(config_path) => println!("config: {}", config_path.display()),
+                None => println!("config: none (would use {})", config::user_config_path().display()),
+            }
+            println!("data: {}", dirs::data_dir().display());
+        },
+        Some(Command::Fmt { check }) => {
+            or_exit(check_editable(path));
+            let _lock = or_exit(lock::lock(path));
+            let source = or_exit(read_file(path));
+            let formatted = or_exit(wtd::format::format_source(&source, &parse_options)
+                                    .map_err(|why| {
+                                        // One error per line, each starting with its line number.
+                                        return why.lines()
+                                            .map(|line| format!("{}:{}", path.display(), line))
+                                            .collect::<Vec<String>>()
+                                            .join("\n");
+                                    }));
+            if check {
+                if formatted != source {
+                    eprintln!("{} isn't formatted; run `wtd fmt`", path.display());
+                    std::process::exit(1);
+                }
+            } else {
+                or_exit(lock::write_checked(path, &source, &formatted));
+            }
+        },
+        Some(Command::Bench { synthetic, runs }) => {
+            print!("{}", or_exit(bench::run(&bench::BenchOptions { tasks: synthetic as usize, runs })));
+        },
+        Some(Command::Tui) => or_exit(tui::run(path, &tui::TuiOptions {
+            parse: parse_options,
+            week_start: options.week_start,
+        })),
+        Some(Command::Json { private }) => {
+            let mut tasks = or_exit(load(path, &parse_options));
+            if !private {
+                tasks = redactor.redact(&tasks);
+            }
+            tasks.sort_by(cmp_tasks);
+            println!("{:#}", json::tasks_to_json(&tasks, private, &options.public_tags));
+        },
+        Some(Command::Init) => init::run(&mut std::io::stdin().lock()),
+        Some(Command::Config { action: ConfigAction::Init }) => {
+            let target = config::user_config_path();
+            if target.exists() {
+                eprintln!("{} already exists", target.display());
+                std::process::exit(1);
+            }
+            if let Err(why) = std::fs::create_dir_all(dirs::config_dir())
+                    .and_then(|_| std::fs::write(&target, config::STARTER)) {
+                eprintln!("Couldn't write {}: {}", target.display(), why);
+                std::process::exit(1);
+            }
+            println!("Wrote {}", target.display());
+        },
+        Some(Command::Secret { action }) => {
+            let result = match action {
+                SecretAction::Set { name } => {
+                    let mut secret = String::new();
+                    if let Err(why) = std::io::stdin().read_line(&mut secret) {
+                        eprintln!("Couldn't read the secret from stdin: {}", why);
+                        std::process::exit(1);
+                    }
+                    credentials::set(&name, secret.trim_end_matches(['\r', '\n']))
+                },
+                SecretAction::Get { name } => credentials::get(&name).map(|secret| println!("{}", secret)),
+                SecretAction::Delete { name } => credentials::delete(&name),
+            };
+            if let Err(why) = result {
+                eprintln!("{}", why);
+                std::process::exit(1);
+            }
+        },
+        Some(Command::Export { plugin: Some(name), output, .. }) => {
+            let plugins = or_exit(plugin::load_all(&config.plugins));
+            let plugin = or_exit(plugin::find(&plugins, &name));
+            let tasks = or_exit(load(path, &parse_options));
+            let file = or_exit(plugin.render(&redactor.redact(&tasks), &options.public_tags));
+            match output {
+                Some(output) => or_exit(std::fs::write(&output, &file)
+                                        .map_err(|why| format!("Couldn't write {}: {}", output.display(), why))),
+                None => or_exit(std::io::stdout().write_all(&file).map_err(|why| why.to_string())),
+            }
+        },
+        Some(Command::Export { format: Some(ExportFormat::WidgetJson), output, count, .. }) => {
+            let tasks = or_exit(load(path, &parse_options));
+            let json = widget::upcoming(&redactor.redact(&tasks), time::now(), count, &options.public_tags);
+            let output = output.unwrap_or_else(|| PathBuf::from("widget.json"));
+            write_or_exit(&output, &format!("{:#}\n", json));
+        },
+        Some(Command::Export { format: Some(ExportFormat::Ics), output, .. }) => {
+            let tasks = or_exit(load(path, &parse_options));
+            let output = output.unwrap_or_else(|| PathBuf::from("calendar.ics"));
+            let out_of_office = load_notes(path, &parse_options).out_of_office;
+            let show_as = config.export.out_of_office.unwrap_or(ics::ShowAs::Busy);
+            write_or_exit(&output, &ics::tasks_to_ics(&redactor.redact(&tasks), &out_of_office, show_as, &options.public_tags));
+        },
+        Some(Command::Export { .. }) => unreachable!("export needs --format or --plugin"),
+        Some(Command::Check { published }) => {
+            let tasks = or_exit(load(path, &parse_options));
+            let policies = policy::from_config(&config.policies)
+                .unwrap_or_else(config_error);
+            let mut problems: Vec<(&Path, String)> = policy::violations(&tasks, &policies).into_iter()
+                .map(|problem| (path, problem))
+                .collect();
+            if let Some(published) = &published {
+                problems.extend(check::check_published(&or_exit(read_file(published)), &tasks, &options.public_tags).into_iter()
+                                .map(|problem| (published.as_path(), problem)));
+            }
+            for (file, problem) in &problems {
+                eprintln!("{}: {}", file.display(), problem);
+            }
+            if !problems.is_empty() {
+                std::process::exit(1);
+            }
+            println!("{}: ok", published.as_deref().unwrap_or(path).display());
+        },
+        Some(Command::Lint) => {
+            let plugins = or_exit(plugin::load_all(&config.plugins));
+            let policies = policy::from_config(&config.policies)
+                .unwrap_or_else(config_error);
+            let problems = or_exit(lint::lint(path, &lint::LintOptions {
+                parse: parse_options,
+                week_start: options.week_start,
+                public_tags: &options.public_tags,
+                policies: &policies,
+                plugins: &plugins,
+            }));
+            for problem in &problems {
+                eprintln!("{}", problem);
+            }
+            if !problems.is_empty() {
+                std::process::exit(1);
+            }
+            println!("{}: ok", path.display());
+        },
+        Some(Command::Sync) => {
+            let url = config.sync.url.as_deref()
+                .unwrap_or_else(|| config_error("sync needs a url under [sync]"));
+            let summary = or_exit(sync::sync(path, &sync::SyncOptions {
+                url,
+                username: config.sync.username.as_deref(),
+                password: config.sync.password.as_deref(),
+                state: config.sync.state.clone(),
+                parse: parse_options,
+                week_start: options.week_start,
+                quiet: args.quiet,
+            }));
+            println!("{}", summary.describe());
+        },
+        Some(Command::Protect { hours, label, work_hours, min_block, max_block }) => {
+            let work_hours = work_hours.unwrap_or_else(|| {
+                return parse_hour_range(config.stats.work_hours.as_deref().unwrap_or("9-17"))
+                    .unwrap_or_else(|why| config_error(format!("work_hours: {}", why)));
+            });
+            let protected = or_exit(protect::protect(path, &protect::ProtectOptions {
+                label: &label,
+                hours,
+                work_hours,
+                min_block: Duration::minutes(min_block),
+                max_block: Duration::minutes(max_block.max(min_block)),
+                parse: parse_options,
+                week_start: options.week_start,
+            }));
+            let added: Vec<&Task> = protected.added.iter().collect();
+            if added.is_empty() {
+                println!("Nothing to add.");
+            } else {
+                print!("{}", agenda::format_agenda(&added));
+            }
+            if protected.missing > 0.0 {
+                eprintln!("Couldn't find room for {:.1} of the {} hours", protected.missing, hours);
+            }
+        },
+        Some(Command::Accept { invite, tentative, reply, me }) => {
+            let source = or_exit(std::fs::read_to_string(&invite).map_err(|why| format!("{}: {}", invite.display(), why)));
+            let invite = or_exit(wtd::invite::read_invite(&source, parse_options.timezone).map_err(|why| format!("{}: {}", invite.display(), why)));
+            // Worked out first, so that a reply that can't be written leaves
+            // the task file alone.
+            let answer = reply.as_ref().map(|_| or_exit(wtd::invite::reply(&invite, me.as_deref(), tentative)));
+            let added = or_exit(wtd::invite::accept(path, &invite, &wtd::invite::AcceptOptions {
+                tentative,
+                parse: parse_options,
+                week_start: options.week_start,
+            }));
+            let added: Vec<&Task> = added.iter().collect();
+            if added.is_empty() {
+                println!("Already in {}.", path.display());
+            } else {
+                print!("{}", agenda::format_agenda(&added));
+            }
+            if let (Some(reply), Some(answer)) = (reply, answer) {
+                or_exit(std::fs::write(&reply, answer).map_err(|why| format!("Couldn't write {}: {}", reply.display(), why)));
+            }
+        },
+        Some(Command::Done { task, on, time: record }) => {
+            let done = or_exit(wtd::done::done(path, &task, &wtd::done::DoneOptions {
+                on,
+                at: if record { Some(time::now().time()) } else { None },
+                parse: parse_options,
+            }));
+            print!("{}", agenda::format_agenda(&[&done]));
+        },
+        Some(Command::Open { task, on, print }) => {
+            let tasks = or_exit(load(path, &parse_options));
+            let template = or_exit(config.journal.template.as_deref().map(read_template).transpose());
+            let input_dir = path.parent().unwrap_or(Path::new(""));
+            let entry = or_exit(journal::entry(&tasks, &task, input_dir, &journal::OpenOptions {
+                dir: &journal_dir,
+                template: template.as_ref(),
+                on,
+                public_tags: &options.public_tags,
+            }));
+            // Without an editor, the path is printed for something else to open.
+            if print || !or_exit(journal::edit(&entry)) {
+                println!("{}", entry.display());
+            }
+        },
+        Some(Command::Avail { days, work_hours, min_free, format, their_tz, both }) => {
+            let work_hours = work_hours.unwrap_or_else(|| {
+                return parse_hour_range(config.stats.work_hours.as_deref().unwrap_or("9-17"))
+                    .unwrap_or_else(|why| config_error(format!("work_hours: {}", why)));
+            });
+            let mine = parse_options.timezone;
+            if their_tz.is_some() && mine.is_none() {
+                eprintln!("--their-tz needs the timezone your own times are in; set timezone under [parse]");
+                std::process::exit(1);
+            }
+            let tasks = or_exit(load(path, &parse_options));
+            let now = mine.map_or_else(time::now, time::now_in);
+            let available = avail::availability(&tasks, &load_notes(path, &parse_options).out_of_office, now, &avail::AvailOptions {
+                days,
+                work_hours,
+                min_free: Duration::minutes(min_free),
+            });
+            let zones = match (mine, their_tz, both) {
+                (Some(mine), Some(theirs), false) => avail::Zones::Theirs { mine, theirs },
+                (Some(mine), Some(theirs), true) => avail::Zones::Both { mine, theirs },
+                _ => avail::Zones::Mine,
+            };
+            match format {
+                AvailFormat::Text => print!("{}", avail::format_text(&available, work_hours, zones)),
+                AvailFormat::Json => println!("{}", avail::to_json(&available, zones)),
+            }
+        },
+        Some(Command::PushGcal { days }) => {
+            let (client_id, client_secret) = match (&config.gcal.client_id, &config.gcal.client_secret) {
+                (Some(id), Some(secret)) => (id.as_str(), secret.as_str()),
+                _ => config_error("push-gcal needs a client_id and client_secret under [gcal]"),
+            };
+            let days = match days.or(config.gcal.days) {
+                Some(days) if !(1..=gcal::MAX_DAYS).contains(&days) => config_error(format!("days under [gcal] must be from 1 to {}", gcal::MAX_DAYS)),
+                days => days.unwrap_or(14),
+            };
+            let timezone = parse_options.timezone
+                .unwrap_or_else(|| config_error("push-gcal needs the timezone your times are in, as timezone under [parse]"));
+            let tasks = or_exit(load(path, &parse_options));
+            let summary = or_exit(gcal::push(&tasks, &gcal::GcalOptions {
+                client_id,
+                client_secret,
+                calendar: config.gcal.calendar.as_deref().unwrap_or("primary"),
+                days,
+                timezone,
+            }));
+            println!("{}", summary.describe());
+        },
+        Some(Command::Serve { bind, meeting_tag, expose_private, privacy_mode, refresh_minutes, days, hours }) => {
+            let bind = bind.or(config.serve.bind).unwrap_or_else(|| "127.0.0.1:8080".to_string());
+            let meeting_tag = meeting_tag.or(config.serve.meeting_tag).unwrap_or_else(|| "meetings".to_string());
+            let options = RenderOptions {
+                days: match days.or(config.serve.days) {
+                    Some(days) if days < 1 => config_error("days under [serve] must be at least 1"),
+                    days => days.unwrap_or(options.days),
+                },
+                hours: match (hours, config.serve.hours.as_deref()) {
+                    (Some(hours), _) => Some(hours),
+                    (None, Some(hours)) => Some(parse_hour_range(hours)
+                        .unwrap_or_else(|why| config_error(format!("hours under [serve]: {}", why)))),
+                    (None, None) => options.hours,
+                },
+                ..options
+            };
+            or_exit(serve::serve(&bind, path, &serve::ServeOptions {
+                render: &options,
+                parse: parse_options,
+                redactor: &redactor,
+                meeting_tag: &meeting_tag,
+                expose_private: expose_private || config.serve.expose_private,
+                privacy_mode: privacy_mode || config.serve.privacy_mode,
+                merge_ics: merged_calendars,
+                refresh: std::time::Duration::from_secs(60 * refresh_minutes.or(config.serve.refresh_minutes).unwrap_or(15).max(1)),
+                stylesheet: stylesheet_file(&stylesheet, path),
+                config: config_path.as_deref(),
+            }));
+        },
+        None => {
+            let mut tasks = or_exit(load(path, &parse_options));
+            let input_dir = path.parent().unwrap_or(Path::new(""));
+            attachments::check(&mut tasks, input_dir);
+            merge_ics(&mut tasks, merged_calendars, parse_options.timezone);
+            let notes = Notes { journal: journal::load(input_dir, &journal_dir), ..load_notes(path, &parse_options) };
+            let public_template = or_exit(config.output.public_template.as_deref().map(read_template).transpose());
+            let private_template = or_exit(config.output.private_template.as_deref().map(read_template).transpose());
+            // The outputs only read the tasks, so they're rendered at the same
+            // time.
+            let audiences = &config.output.audiences;
+            let post_render = &config.hooks.post_render;
+            // The public calendar's attachments are those of the redacted tasks
+            // too, so none are copied for a task shown as busy.
+            let public_tasks = redactor.redact(&tasks);
+            let outputs = &config.output;
+            let (public_html, private_html, ics_file, json_file, year_file) = std::thread::scope(|scope| {
+                let public = scope.spawn(|| audiences.contains(&Audience::Public).then(|| {
+                    let tasks = &public_tasks;
+                    return page(tasks, &redactor.redact_notes(&notes), CalendarPrivacy::Public, &options, public_template.as_ref())
+                        .and_then(|html| hooks::post_render(post_render, CalendarPrivacy::Public, tasks, &options.public_tags, html));
+                }));
+                let private = scope.spawn(|| audiences.contains(&Audience::Private).then(|| {
+                    return page(&tasks, &notes, CalendarPrivacy::Private, &options, private_template.as_ref())
+                        .and_then(|html| hooks::post_render(post_render, CalendarPrivacy::Private, &tasks, &options.public_tags, html));
+                }));
+                // The other formats are of the public calendar, as `export`,
+                // `json` and `year` write them.
+                let ics_file = scope.spawn(|| outputs.ics.is_some().then(|| {
+                    let show_as = config.export.out_of_office.unwrap_or(ics::ShowAs::Busy);
+                    return ics::tasks_to_ics(&public_tasks, &notes.out_of_office, show_as, &options.public_tags);
+                }));
+                let json_file = scope.spawn(|| outputs.json.is_some().then(|| {
+                    let mut tasks = public_tasks.clone();
+                    tasks.sort_by(cmp_tasks);
+                    return format!("{:#}\n", json::tasks_to_json(&tasks, false, &options.public_tags));
+                }));
+                let year_file = scope.spawn(|| outputs.year.is_some().then(|| {
+                    return year::year_to_svg(&split_days(&public_tasks), time::today().year(), &options.public_tags)
+                        .map(|svg| format!("{}\n", svg));
+                }));
+                return (public.join().expect("rendering the public calendar"), private.join().expect("rendering the private calendar"),
+                        ics_file.join().expect("rendering the iCalendar file"), json_file.join().expect("rendering the JSON file"),
+                        year_file.join().expect("rendering the year poster"));
+            });
+            // All the files and attachments are written together, so a
+            // failure partway can't leave a site that's half old and half new.
+            let mut staged = Staged::default();
+            if let Some(public_html) = public_html {
+                staged.write(&config.output.public, or_exit(public_html));
+                let output_dir = config.output.public.parent().unwrap_or(Path::new(""));
+                or_exit(attachments::publish(&public_tasks, input_dir, output_dir, &CalendarPrivacy::Public, &mut staged));
+            }
+            if let Some(private_html) = private_html {
+                staged.write(&config.output.private, or_exit(private_html));
+                let output_dir = config.output.private.parent().unwrap_or(Path::new(""));
+                or_exit(attachments::publish(&tasks, input_dir, output_dir, &CalendarPrivacy::Private, &mut staged));
+            }
+            if let (Some(path), Some(file)) = (&outputs.ics, ics_file) {
+                staged.write(path, file);
+            }
+            if let (Some(path), Some(file)) = (&outputs.json, json_file) {
+                staged.write(path, file);
+            }
+            if let (Some(path), Some(file)) = (&outputs.year, year_file) {
+                staged.write(path, or_exit(file));
+            }
+            or_exit(hooks::pre_publish(&config.hooks.pre_publish, &staged.paths(), &tasks, &options.public_tags));
+            or_exit(staged.commit());
+        },
     }
 }
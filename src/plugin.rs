@@ -0,0 +1,135 @@
+// Plugins: WebAssembly modules, listed as `plugins = ["csv.wasm"]` in the
+// config, that add output formats and lint rules without a fork of wtd. A
+// plugin is known by its file's name without the extension ("csv"), and
+// exports its memory as `memory` along with
+//
+//   wtd_alloc(len: i32) -> i32
+//
+// returning where wtd may write `len` bytes of input, and either or both of
+//
+//   wtd_render(ptr: i32, len: i32) -> i64
+//   wtd_check(ptr: i32, len: i32) -> i64
+//
+// each given a JSON object at `ptr` and returning where its output is, as the
+// address shifted left 32 bits and or'd with the length. wtd_render is given
+// {"tasks": [...]}, the public calendar's tasks as `wtd json` gives them, and
+// returns the file `wtd export --plugin NAME` writes. wtd_check is given
+// {"path": ..., "source": ..., "tasks": [...]}, the tasks with their private
+// details, and returns a JSON array of the problems `wtd lint` reports, each
+// {"line": N, "message": ...}, the line optional.
+//
+// Plugins are given no imports, so they can't read or write files or the
+// network, and a call that runs too long, grows its memory too far, or
+// returns too much is stopped.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use wasmi::{Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+use crate::{json, Task};
+
+// Instructions a call may run before it's stopped, for a plugin stuck in a
+// loop: about a second's worth.
+const FUEL: u64 = 1_000_000_000;
+
+// The most memory a plugin may have, and the most it may return.
+const MEMORY: usize = 256 * 1024 * 1024;
+const OUTPUT: usize = 64 * 1024 * 1024;
+
+pub struct Plugin {
+    pub name: String,
+    path: PathBuf,
+    engine: Engine,
+    module: Module,
+}
+
+#[derive(Deserialize)]
+pub struct Problem {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+pub fn load(path: &Path) -> Result<Plugin, String> {
+    let wasm = std::fs::read(path).map_err(|why| format!("Couldn't read plugin {}: {}", path.display(), why))?;
+    let mut config = wasmi::Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, &wasm).map_err(|why| format!("{} isn't a WebAssembly module: {}", path.display(), why))?;
+    let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    return Ok(Plugin { name, path: path.to_path_buf(), engine, module });
+}
+
+pub fn load_all(paths: &[PathBuf]) -> Result<Vec<Plugin>, String> {
+    return paths.iter().map(|path| load(path)).collect();
+}
+
+// The plugin named `name` among `plugins`.
+pub fn find<'a>(plugins: &'a [Plugin], name: &str) -> Result<&'a Plugin, String> {
+    return plugins.iter().find(|plugin| plugin.name == name).ok_or_else(|| {
+        let names: Vec<&str> = plugins.iter().map(|plugin| plugin.name.as_str()).collect();
+        return match names.is_empty() {
+            true => format!("There's no plugin {}; list plugins as plugins = [...] in the config", name),
+            false => format!("There's no plugin {}; the plugins are {}", name, names.join(", ")),
+        };
+    });
+}
+
+impl Plugin {
+    pub fn exports(&self, function: &str) -> bool {
+        return self.module.exports().any(|export| export.name() == function);
+    }
+
+    // Calls `function` on `input`, in an instance of its own so that no call
+    // sees what another left behind.
+    fn call(&self, function: &str, input: &Value) -> Result<Vec<u8>, String> {
+        let describe = |why: String| format!("Plugin {} ({}) failed in {}: {}", self.name, self.path.display(), function, why);
+        let limits = StoreLimitsBuilder::new().memory_size(MEMORY).trap_on_grow_failure(true).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.set_fuel(FUEL).map_err(|why| describe(why.to_string()))?;
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|why| describe(why.to_string()))?;
+        let memory = instance.get_memory(&store, "memory").ok_or_else(|| describe("it exports no memory".to_string()))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "wtd_alloc").map_err(|why| describe(why.to_string()))?;
+        let run = instance.get_typed_func::<(i32, i32), i64>(&store, function).map_err(|why| describe(why.to_string()))?;
+
+        let input = input.to_string().into_bytes();
+        let len = i32::try_from(input.len()).map_err(|_| describe("the input is too large".to_string()))?;
+        let at = alloc.call(&mut store, len).map_err(|why| describe(why.to_string()))?;
+        memory.write(&mut store, at as u32 as usize, &input).map_err(|why| describe(why.to_string()))?;
+        let packed = run.call(&mut store, (at, len)).map_err(|why| describe(why.to_string()))? as u64;
+        let len = (packed & 0xffff_ffff) as usize;
+        if len > OUTPUT {
+            return Err(describe(format!("it returned {} bytes, more than the {} allowed", len, OUTPUT)));
+        }
+        let mut output = vec![0; len];
+        memory.read(&store, (packed >> 32) as usize, &mut output).map_err(|why| describe(why.to_string()))?;
+        return Ok(output);
+    }
+
+    // The file the plugin makes of the public calendar's `tasks`.
+    pub fn render(&self, tasks: &[Task], public_tags: &HashMap<String, String>) -> Result<Vec<u8>, String> {
+        if !self.exports("wtd_render") {
+            return Err(format!("Plugin {} has no output format (it exports no wtd_render)", self.name));
+        }
+        return self.call("wtd_render", &json!({ "tasks": json::tasks_to_json(tasks, false, public_tags) }));
+    }
+
+    // The problems the plugin finds with the task file at `path`, or none if
+    // it doesn't check them.
+    pub fn check(&self, path: &Path, source: &str, tasks: &[Task], public_tags: &HashMap<String, String>) -> Result<Vec<Problem>, String> {
+        if !self.exports("wtd_check") {
+            return Ok(Vec::new());
+        }
+        let output = self.call("wtd_check", &json!({
+            "path": path.to_string_lossy(),
+            "source": source,
+            "tasks": json::tasks_to_json(tasks, true, public_tags),
+        }))?;
+        return serde_json::from_slice(&output)
+            .map_err(|why| format!("Plugin {} returned problems that aren't a JSON array of {{\"line\", \"message\"}}: {}", self.name, why));
+    }
+}
@@ -0,0 +1,106 @@
+// Per-tag working-hours policies from the config, e.g.
+//
+//   [[policies]]
+//   tag = "personal"
+//   hours = "18-24"
+//   enforce = "busy"
+//
+// A "busy" policy is enforced when rendering anything public (see
+// redact.rs): the tag's tasks show only as busy blocks, and only within the
+// hours; outside them they're left off. A "flag" policy is enforced by
+// `wtd check` and `wtd lint`, which report the tag's tasks that fall outside
+// the hours.
+use serde::Deserialize;
+use crate::config::PolicyConfig;
+use crate::{parse_hour_range, time, Task};
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Enforce {
+    Busy,
+    Flag,
+}
+
+pub struct Policy {
+    pub tag: String,
+    pub hours: (u32, u32),
+    pub enforce: Enforce,
+}
+
+pub fn from_config(configs: &[PolicyConfig]) -> Result<Vec<Policy>, String> {
+    return configs.iter().map(|config| {
+        let hours = parse_hour_range(&config.hours).map_err(|why| format!("policy for +{}: {}", config.tag, why))?;
+        return Ok(Policy { tag: config.tag.trim_start_matches('+').to_string(), hours, enforce: config.enforce });
+    }).collect();
+}
+
+fn applies(policy: &Policy, task: &Task, enforce: Enforce) -> bool {
+    return policy.enforce == enforce && task.tags.contains(&policy.tag);
+}
+
+// Whether the task is timed and lies wholly within the hours.
+fn within(task: &Task, (first_hour, last_hour): (u32, u32)) -> bool {
+    let from = time::time_of_day(first_hour as i64 * 60);
+    // The hour range may end at 24, which NaiveTime can't represent.
+    let until = time::hms(last_hour, 0, 0).unwrap_or_else(time::end_of_day);
+    return match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => from <= start && (end <= until || until == time::end_of_day()),
+        _ => false,
+    };
+}
+
+// The tasks as the public may see them under the "busy" policies.
+pub fn apply_public(tasks: &[Task], policies: &[Policy]) -> Vec<Task> {
+    return tasks.iter().filter_map(|task| {
+        let policy = match policies.iter().find(|policy| applies(policy, task, Enforce::Busy)) {
+            Some(policy) => policy,
+            None => return Some(task.clone()),
+        };
+        if !within(task, policy.hours) {
+            return None;
+        }
+        return Some(Task {
+            details: String::new(),
+            tags: vec!["busy".to_string()],
+            subtasks: Vec::new(),
+            notes: Vec::new(),
+            attachments: Vec::new(),
+            attendees: None,
+            priority: None,
+            location: None,
+            ..task.clone()
+        });
+    }).collect();
+}
+
+fn format_span(task: &Task) -> String {
+    return match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => format!(" {}--{}", time::format_time(start).trim(), time::format_time(end).trim()),
+        _ => String::new(),
+    };
+}
+
+// The "flag" policies the task breaks.
+pub fn broken<'a>(task: &Task, policies: &'a [Policy]) -> Vec<&'a Policy> {
+    if task.start_time.is_none() {
+        return Vec::new();
+    }
+    return policies.iter().filter(|policy| applies(policy, task, Enforce::Flag) && !within(task, policy.hours)).collect();
+}
+
+// What breaking the policy is, as check and lint report it.
+pub fn describe(policy: &Policy) -> String {
+    return format!("+{} task outside {}:00--{}:00", policy.tag, policy.hours.0, policy.hours.1);
+}
+
+// The tasks breaking a "flag" policy, described.
+pub fn violations(tasks: &[Task], policies: &[Policy]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for task in tasks {
+        for policy in broken(task, policies) {
+            problems.push(format!("{}{}: {}: {}", time::format_date(task.date), format_span(task), describe(policy),
+                                  task.details.lines().next().unwrap_or("")));
+        }
+    }
+    return problems;
+}
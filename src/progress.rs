@@ -0,0 +1,69 @@
+// Progress through operations that take a while on long histories, such as
+// reading years of archived weeks or syncing many events. On a terminal it's
+// a bar redrawn in place; otherwise, in a log say, a line each tenth of the
+// way. Written to standard error, and not at all with --quiet.
+use std::io::{stderr, IsTerminal, Write};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute};
+
+pub struct Progress {
+    label: String,
+    total: usize,
+    done: usize,
+    terminal: bool,
+    quiet: bool,
+}
+
+const BAR_WIDTH: usize = 30;
+// Fewer steps than this are over too soon for lines in a log to say anything.
+const LOGGED_MINIMUM: usize = 20;
+
+impl Progress {
+    pub fn new(label: &str, total: usize, quiet: bool) -> Progress {
+        let terminal = stderr().is_terminal();
+        let progress = Progress {
+            label: label.to_string(),
+            total,
+            done: 0,
+            terminal,
+            quiet: quiet || total == 0 || (!terminal && total < LOGGED_MINIMUM),
+        };
+        if progress.terminal {
+            progress.draw();
+        }
+        return progress;
+    }
+
+    pub fn advance(&mut self) {
+        if self.quiet || self.done == self.total {
+            return;
+        }
+        self.done += 1;
+        if self.terminal || self.done * 10 / self.total != (self.done - 1) * 10 / self.total {
+            self.draw();
+        }
+    }
+
+    fn draw(&self) {
+        if self.quiet {
+            return;
+        }
+        if !self.terminal {
+            return eprintln!("{}: {}/{}", self.label, self.done, self.total);
+        }
+        let filled = BAR_WIDTH * self.done / self.total;
+        eprint!("\r{} [{}{}] {}/{}", self.label, "#".repeat(filled), " ".repeat(BAR_WIDTH - filled), self.done, self.total);
+        let _ = stderr().flush();
+    }
+}
+
+// Clears the bar, so that whatever's printed next starts on a clean line.
+// crossterm does so through the console API on older Windows consoles, which
+// would print an escape code as it is.
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.terminal && !self.quiet {
+            let _ = execute!(stderr(), cursor::MoveToColumn(0), terminal::Clear(ClearType::UntilNewLine));
+        }
+    }
+}
@@ -0,0 +1,159 @@
+// `wtd protect`: books focus time for next week into the free stretches of
+// its working hours, before meetings fill them. The blocks are written into
+// wtd.md as ordinary tasks tagged +self, so they can be moved or dropped like
+// any other; blocks with the same label already there count towards the
+// hours asked for, so running it twice doesn't book twice.
+//
+// Blocks are handed out a day at a time, round after round, so that focus
+// time is spread over the week rather than piled onto its emptiest day.
+use std::path::Path;
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Weekday};
+use crate::edit::insert_task;
+use crate::stats::task_hours;
+use crate::{check_editable, is_out_of_office, lock, parse_notes_with, parse_tasks_with, read_file, split_days, time, ParseOptions, Task};
+
+// The most focus time that can be asked for: the whole week.
+pub const MAX_HOURS: f64 = 7.0 * 24.0;
+
+pub struct ProtectOptions<'a> {
+    pub label: &'a str,
+    /// Total focus time wanted over the week.
+    pub hours: f64,
+    /// Working hours as (start, end) in 24-hour time.
+    pub work_hours: (u32, u32),
+    /// No block is shorter than this (unless less time is left to book) or
+    /// longer than the maximum.
+    pub min_block: Duration,
+    pub max_block: Duration,
+    pub parse: ParseOptions,
+    pub week_start: Weekday,
+}
+
+pub struct Protected {
+    pub added: Vec<Task>,
+    // Hours that couldn't be found room for.
+    pub missing: f64,
+}
+
+fn is_block(task: &Task, label: &str) -> bool {
+    return task.details == label && task.tags.iter().any(|tag| tag == "self");
+}
+
+// The stretches of working hours on `date` that no timed task overlaps. A
+// day with an all-day task, such as a holiday, has none.
+pub(crate) fn free_stretches(tasks: &[Task], date: NaiveDate, work_hours: (u32, u32)) -> Vec<(NaiveTime, NaiveTime)> {
+    if tasks.iter().any(|task| task.date == date && task.all_day) {
+        return Vec::new();
+    }
+    let day_start = time::time_of_day(work_hours.0 as i64 * 60);
+    let day_end = time::hms(work_hours.1, 0, 0).unwrap_or_else(time::end_of_day);
+    let mut busy: Vec<(NaiveTime, NaiveTime)> = tasks.iter()
+        .filter(|task| task.date == date)
+        .filter_map(|task| task.start_time.zip(task.end_time))
+        .collect();
+    busy.sort();
+    let mut free = Vec::new();
+    let mut from = day_start;
+    for (start, end) in busy {
+        if start > from && from < day_end {
+            free.push((from, start.min(day_end)));
+        }
+        from = from.max(end);
+    }
+    if from < day_end {
+        free.push((from, day_end));
+    }
+    return free;
+}
+
+// The blocks to add to the week starting `week`.
+pub fn plan(tasks: &[Task], source: &str, week: NaiveDate, options: &ProtectOptions) -> Protected {
+    let out_of_office = parse_notes_with(source, &options.parse).out_of_office;
+    let weekdays = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+    let days: Vec<NaiveDate> = (0..7).map(|offset| week + Duration::days(offset))
+        .filter(|date| weekdays.contains(&date.weekday()))
+        .filter(|date| !is_out_of_office(&out_of_office, *date))
+        .collect();
+    let booked: f64 = tasks.iter()
+        .filter(|task| days.contains(&task.date) && is_block(task, options.label))
+        .map(task_hours)
+        .sum();
+    // NaN hours come to none.
+    let mut left = Duration::minutes(((options.hours - booked).clamp(0.0, MAX_HOURS) * 60.0).round() as i64);
+    let by_day = split_days(tasks);
+    let mut free: Vec<(NaiveDate, Vec<(NaiveTime, NaiveTime)>)> = days.iter()
+        .map(|date| (*date, free_stretches(&by_day, *date, options.work_hours)))
+        .collect();
+
+    let mut added = Vec::new();
+    let mut placed = true;
+    while placed && left > Duration::zero() {
+        placed = false;
+        for (date, stretches) in free.iter_mut() {
+            if left <= Duration::zero() {
+                break;
+            }
+            let shortest = options.min_block.min(left);
+            let stretch = match stretches.iter_mut().find(|(start, end)| end > start && *end - *start >= shortest) {
+                Some(stretch) => stretch,
+                None => continue,
+            };
+            let length = (stretch.1 - stretch.0).min(options.max_block).min(left);
+            // A block of nothing books nothing, and would never end the rounds.
+            if length <= Duration::zero() {
+                continue;
+            }
+            let end = stretch.0 + length;
+            added.push(Task {
+                date: *date,
+                start_time: Some(stretch.0),
+                end_time: Some(end),
+                all_day: false,
+                span: None,
+                details: options.label.to_string(),
+                tags: vec!["self".to_string()],
+                done: false,
+                subtasks: Vec::new(),
+                notes: Vec::new(),
+                attachments: Vec::new(),
+                attendees: None,
+                priority: None,
+                location: None,
+                id: None,
+                source: None,
+            });
+            stretch.0 = end;
+            left -= length;
+            placed = true;
+        }
+    }
+    added.sort_by_key(|task| (task.date, task.start_time));
+    return Protected { added, missing: left.num_minutes() as f64 / 60.0 };
+}
+
+// Books next week's focus time into the file at `path`.
+pub fn protect(path: &Path, options: &ProtectOptions) -> Result<Protected, String> {
+    check_editable(path)?;
+    let _lock = lock::lock(path)?;
+    let label_words: Vec<&str> = options.label.split_whitespace().collect();
+    if label_words.is_empty() || options.label.contains(';') || options.label.contains("<!--")
+        || label_words.iter().any(|word| word.starts_with(['+', '@', '/']) || word.ends_with('\\')) {
+        return Err(format!("'{}' can't be used as a label; it has to read back as plain text", options.label));
+    }
+    let label = label_words.join(" ");
+    let options = &ProtectOptions { label: &label, parse: options.parse.clone(), ..*options };
+    let describe = |errors: Vec<crate::ParseError>| {
+        return errors.iter().map(|e| format!("{}:{}", path.display(), e)).collect::<Vec<String>>().join("\n");
+    };
+    let original = read_file(path)?;
+    let mut source = original.clone();
+    let tasks = parse_tasks_with(&source, &options.parse).map_err(describe)?;
+    let week = time::week_start(time::today(), options.week_start) + Duration::weeks(1);
+    let protected = plan(&tasks, &source, week, options);
+    for task in &protected.added {
+        source = insert_task(&source, task, &options.parse, options.week_start);
+    }
+    parse_tasks_with(&source, &options.parse).map_err(describe)?;
+    lock::write_checked(path, &original, &source)?;
+    return Ok(protected);
+}
@@ -0,0 +1,189 @@
+// Redaction rules applied to everything the public sees, so that names, phone
+// numbers and the like are scrubbed even from `+public` descriptions. The
+// "busy" tag policies (see policy.rs), and the rounding and merging of
+// private tasks into busy blocks, are applied here too.
+use std::collections::BTreeMap;
+use chrono::{NaiveDate, NaiveTime, Timelike};
+use clap::ValueEnum;
+use regex::Regex;
+use serde::Deserialize;
+use crate::config::{PolicyConfig, RedactConfig};
+use crate::policy::{self, Policy};
+use crate::{split_days, time, Notes, Task};
+
+// How much of the schedule public output shows.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Privacy {
+    /// Each task on its own, with the details of +public ones.
+    None,
+    /// Private timed tasks merged into busy blocks; +public ones as they are.
+    BusyOnly,
+    /// Every task as a merged busy block, and no notes: only when you're busy.
+    Full,
+}
+
+pub struct Redactor {
+    rules: Vec<Regex>,
+    replacement: String,
+    policies: Vec<Policy>,
+    privacy: Privacy,
+    round_minutes: Option<i64>,
+}
+
+fn is_private(task: &Task) -> bool {
+    return task.start_time.is_some() && !task.all_day && !task.tags.iter().any(|tag| tag == "public");
+}
+
+// The task with its start moved back and its end moved on to multiples of
+// `minutes` after midnight. An end rounded up to midnight is the end of the day.
+fn round_out(task: &Task, minutes: i64) -> Task {
+    let down = |t: NaiveTime| time::time_of_day(t.num_seconds_from_midnight() as i64 / 60 / minutes * minutes);
+    let up = |t: NaiveTime| {
+        let rounded = (t.num_seconds_from_midnight() as i64 + 60 * minutes - 1) / (60 * minutes) * minutes;
+        return if rounded >= 24 * 60 { time::end_of_day() } else { time::time_of_day(rounded) };
+    };
+    let mut rounded = task.clone();
+    rounded.start_time = task.start_time.map(down);
+    match &mut rounded.span {
+        // The end is the end of the first day; the last day's end is the span's.
+        Some(span) if span.end != time::midnight() => span.end = up(span.end),
+        Some(_) => {},
+        None => rounded.end_time = task.end_time.map(up),
+    }
+    return rounded;
+}
+
+// The tasks with the timed ones not tagged `public` replaced by busy blocks,
+// one for each run of them that overlap or follow on without a gap, a day at
+// a time. A block is done if all of its tasks are. Other tasks are kept as
+// they are, ahead of the blocks.
+fn merge_private(tasks: &[Task]) -> Vec<Task> {
+    let mut out: Vec<Task> = tasks.iter().filter(|task| !is_private(task)).cloned().collect();
+    let mut by_day: BTreeMap<NaiveDate, Vec<Task>> = BTreeMap::new();
+    for task in split_days(tasks).into_iter().filter(is_private) {
+        by_day.entry(task.date).or_default().push(task);
+    }
+    for (_, mut day) in by_day {
+        day.sort_by_key(|task| (task.start_time, task.end_time));
+        let mut blocks: Vec<Task> = Vec::new();
+        for task in day {
+            match blocks.last_mut() {
+                Some(block) if task.start_time <= block.end_time => {
+                    block.end_time = block.end_time.max(task.end_time);
+                    block.done = block.done && task.done;
+                },
+                _ => blocks.push(as_busy(task)),
+            }
+        }
+        out.extend(blocks);
+    }
+    return out;
+}
+
+// Just the task's time, tagged busy.
+fn as_busy(task: Task) -> Task {
+    return Task {
+        details: String::new(),
+        tags: vec!["busy".to_string()],
+        subtasks: Vec::new(),
+        notes: Vec::new(),
+        attachments: Vec::new(),
+        attendees: None,
+        priority: None,
+        location: None,
+        id: None,
+        source: None,
+        ..task
+    };
+}
+
+impl Redactor {
+    pub fn new(config: &RedactConfig, policies: &[PolicyConfig]) -> Result<Redactor, String> {
+        let mut rules = Vec::new();
+        // Keywords match whole words, ignoring case.
+        for keyword in &config.keywords {
+            rules.push(Regex::new(&format!(r"(?i)\b{}\b", regex::escape(keyword)))
+                       .map_err(|why| format!("redaction keyword {:?}: {}", keyword, why))?);
+        }
+        for pattern in &config.patterns {
+            rules.push(Regex::new(pattern).map_err(|why| format!("redaction pattern {:?}: {}", pattern, why))?);
+        }
+        let replacement = config.replacement.clone().unwrap_or_else(|| "[redacted]".to_string());
+        if let Some(minutes) = config.round_minutes.filter(|minutes| !(1..=60).contains(minutes) || 60 % minutes != 0) {
+            return Err(format!("round_minutes must be a number of minutes that divides an hour, not {}", minutes));
+        }
+        return Ok(Redactor {
+            rules,
+            replacement,
+            policies: policy::from_config(policies)?,
+            privacy: match (config.privacy, config.merge_private) {
+                (Some(privacy), _) => privacy,
+                (None, merge_private) => if merge_private { Privacy::BusyOnly } else { Privacy::None },
+            },
+            round_minutes: config.round_minutes,
+        });
+    }
+
+    // The redactor with the config's privacy level overridden, e.g. from the
+    // command line.
+    pub fn with_privacy(self, privacy: Option<Privacy>) -> Redactor {
+        return Redactor { privacy: privacy.unwrap_or(self.privacy), ..self };
+    }
+
+    pub fn redact_text(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for rule in &self.rules {
+            text = rule.replace_all(&text, regex::NoExpand(&self.replacement)).into_owned();
+        }
+        return text;
+    }
+
+    // A copy of the tasks with their descriptions, notes, checklists, and
+    // locations scrubbed. An attachment whose name a rule matches is dropped,
+    // since renaming it would break its link and keeping it would publish
+    // the name beside the page.
+    pub fn redact(&self, tasks: &[Task]) -> Vec<Task> {
+        let mut redacted = policy::apply_public(tasks, &self.policies);
+        // Only the busy blocks are left, so only tasks with times matter.
+        if self.privacy == Privacy::Full {
+            redacted = redacted.into_iter().filter(|task| task.start_time.is_some() || task.all_day).map(as_busy).collect();
+        }
+        if let Some(minutes) = self.round_minutes {
+            redacted = redacted.iter().map(|task| if is_private(task) { round_out(task, minutes) } else { task.clone() }).collect();
+        }
+        // After rounding, so that tasks rounded into touching are merged too.
+        if self.privacy != Privacy::None {
+            redacted = merge_private(&redacted);
+        }
+        if self.rules.is_empty() {
+            return redacted;
+        }
+        for task in redacted.iter_mut() {
+            task.details = self.redact_text(&task.details);
+            for subtask in task.subtasks.iter_mut() {
+                subtask.text = self.redact_text(&subtask.text);
+            }
+            for paragraph in task.notes.iter_mut() {
+                *paragraph = self.redact_text(paragraph);
+            }
+            task.location = task.location.as_ref().map(|place| self.redact_text(place));
+            task.attachments.retain(|attachment| !self.rules.iter().any(|rule| rule.is_match(attachment)));
+        }
+        return redacted;
+    }
+
+    pub fn redact_notes(&self, notes: &Notes) -> Notes {
+        if self.privacy == Privacy::Full {
+            return Notes { out_of_office: notes.out_of_office.clone(), ..Notes::default() };
+        }
+        let redact_all = |paragraphs: &Vec<String>| paragraphs.iter().map(|p| self.redact_text(p)).collect();
+        return Notes {
+            preamble: redact_all(&notes.preamble),
+            weeks: notes.weeks.iter().map(|(start, paragraphs)| (*start, redact_all(paragraphs))).collect(),
+            out_of_office: notes.out_of_office.clone(),
+            // The journal is private.
+            journal: Default::default(),
+        };
+    }
+}
@@ -0,0 +1,398 @@
+// A small HTTP server for the public calendar. wtd.md is re-read on every
+// request, so the served page is always current without a separate build step.
+// Clients connected to /ws are additionally pushed the task list whenever the
+// file, one it includes, the config or the stylesheet changes (see watch.rs),
+// and the page itself connects to reload when that happens.
+//
+// The --merge-ics calendars, which may live at URLs, are instead fetched in
+// the background every few minutes and drawn from memory (see ics::Prefetch)
+// on every route, so that a slow or unreachable calendar server never holds up
+// a response.
+use std::io::prelude::*;
+use std::io::{BufReader, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration as StdDuration;
+use chrono::{Duration, Weekday};
+use crate::ics::Prefetch;
+use crate::redact::Redactor;
+use crate::stats::task_hours;
+use crate::ws::Hub;
+use crate::{attachments, graphql, json, time, watch, widget, ws};
+use crate::{load, load_notes, read_stylesheet, sources, render_html, CalendarPrivacy, ParseOptions, RenderOptions, Task};
+
+fn respond(stream: &mut TcpStream, options: &ServeOptions, status: &str, content_type: &str, body: &str) {
+    respond_with_headers(stream, options, status, content_type, &[], body);
+}
+
+fn respond_with_headers(stream: &mut TcpStream, options: &ServeOptions, status: &str, content_type: &str,
+                        headers: &[(&str, &str)], body: &str) {
+    let mut response = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                               status, content_type, body.len());
+    if options.privacy_mode {
+        // Keep browsers from telling the sites linked from the calendar (or the
+        // sites embedding the widget) which page the visitor came from, and keep
+        // intermediaries from storing copies of the schedule.
+        response.push_str("Referrer-Policy: no-referrer\r\nCache-Control: no-store\r\n");
+    }
+    for (name, value) in headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    if let Err(why) = stream.write_all(response.as_bytes()) {
+        eprintln!("Couldn't write response: {}", why);
+    }
+}
+
+// For a task file that can't be parsed. The details go to the server's log
+// rather than the visitor, since they quote the file.
+fn respond_error(stream: &mut TcpStream, options: &ServeOptions, why: &str) {
+    eprintln!("{}", why);
+    respond(stream, options, "500 Internal Server Error", "text/plain", "The calendar couldn't be read\n");
+}
+
+// Schedule load in the Prometheus text exposition format, from the tasks as
+// the public sees them, since it's served beside the public page.
+fn metrics(tasks: &[Task], meeting_tag: &str, first_day: Weekday) -> String {
+    let today = time::today();
+    let week_start = time::week_start(today, first_day);
+    let scheduled_today = tasks.iter().filter(|t| t.date == today).map(task_hours).fold(0.0, |a, b| a + b);
+    let meetings = tasks.iter()
+        .filter(|t| t.date >= week_start && t.date < week_start + Duration::weeks(1))
+        .filter(|t| t.tags.iter().any(|tag| tag == meeting_tag))
+        .count();
+    let open = tasks.iter().filter(|t| !t.done).count();
+
+    let mut out = String::new();
+    for (name, help, value) in [
+        ("wtd_scheduled_hours_today", "Hours of timed tasks scheduled today.", scheduled_today),
+        ("wtd_meetings_this_week", "Tasks tagged as meetings in the current week.", meetings as f64),
+        ("wtd_tasks_open", "Tasks not yet checked off.", open as f64),
+        ("wtd_tasks_total", "All tasks on the public calendar.", tasks.len() as f64),
+    ] {
+        out.push_str(&format!("# HELP {} {}\n# TYPE {} gauge\n{} {}\n", name, help, name, name, value));
+    }
+    return out;
+}
+
+struct Request {
+    method: String,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        return self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str());
+    }
+
+    fn route(&self) -> &str {
+        return self.target.split('?').next().unwrap_or("/");
+    }
+
+    fn query_param(&self, name: &str) -> Option<String> {
+        let query = self.target.split_once('?')?.1;
+        for pair in query.split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if key == name {
+                return Some(percent_decode(value));
+            }
+        }
+        return None;
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = iter.by_ref().take(2).collect();
+                match std::str::from_utf8(&hex).ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(decoded) => bytes.push(decoded),
+                    None => { bytes.push(b'%'); bytes.extend(hex); },
+                }
+            },
+            _ => bytes.push(b),
+        }
+    }
+    return String::from_utf8_lossy(&bytes).to_string();
+}
+
+// The most a request's body may hold. Only GraphQL queries are sent in one,
+// and no sensible query comes near this.
+const MAX_BODY: usize = 64 * 1024;
+
+// The longest request or header line, and the most headers, a request may
+// have; browsers stay well within both.
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+// How long a client may take to send its request, or to read the response.
+const TIMEOUT: StdDuration = StdDuration::from_secs(10);
+
+const BAD_REQUEST: (&str, &str) = ("400 Bad Request", "Bad request\n");
+const TIMED_OUT: (&str, &str) = ("408 Request Timeout", "The request took too long to arrive\n");
+const TOO_LARGE: (&str, &str) = ("413 Payload Too Large", "The request body is too large\n");
+const HEADERS_TOO_LARGE: (&str, &str) = ("431 Request Header Fields Too Large", "The request headers are too large\n");
+
+fn read_error(why: std::io::Error) -> (&'static str, &'static str) {
+    return match why.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => TIMED_OUT,
+        _ => BAD_REQUEST,
+    };
+}
+
+// The next line of the request line and headers, up to MAX_LINE long.
+fn read_head_line(reader: &mut impl BufRead) -> Result<String, (&'static str, &'static str)> {
+    let mut line = String::new();
+    let read = reader.take(MAX_LINE as u64).read_line(&mut line).map_err(read_error)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(HEADERS_TOO_LARGE);
+    }
+    return Ok(line);
+}
+
+// The request on `stream`, or the status and message to answer with instead.
+fn read_request(stream: &TcpStream) -> Result<Request, (&'static str, &'static str)> {
+    let mut reader = BufReader::new(stream);
+    let request_line = read_head_line(&mut reader)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().ok_or(BAD_REQUEST)?.to_string();
+    let target = parts.next().ok_or(BAD_REQUEST)?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_head_line(&mut reader)?;
+        if line.trim().is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(HEADERS_TOO_LARGE);
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Request { method, target, headers, body: String::new() };
+    let length = match request.header("Content-Length") {
+        Some(length) => length.parse::<usize>().map_err(|_| BAD_REQUEST)?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(TOO_LARGE);
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(read_error)?;
+    request.body = String::from_utf8_lossy(&body).to_string();
+    return Ok(request);
+}
+
+// Pulls the query out of either `?query=` or a POST body, which may be the
+// usual `{"query": "..."}` JSON or a raw application/graphql document.
+fn graphql_query(request: &Request) -> Option<String> {
+    if request.method == "GET" {
+        return request.query_param("query");
+    }
+    if request.header("Content-Type").is_some_and(|t| t.starts_with("application/graphql")) {
+        return Some(request.body.clone());
+    }
+    let body: serde_json::Value = serde_json::from_str(&request.body).ok()?;
+    return body.get("query")?.as_str().map(|q| q.to_string());
+}
+
+// The tasks of the file at `path`, less the attachments a rendered page
+// would leave out (those missing, or outside the file's directory), along
+// with the merged calendars' events as last fetched.
+fn load_tasks(path: &Path, options: &ServeOptions, merged: &Prefetch) -> Result<Vec<Task>, String> {
+    let mut tasks = load(path, &options.parse)?;
+    attachments::check(&mut tasks, path.parent().unwrap_or(Path::new("")));
+    tasks.extend(merged.tasks());
+    return Ok(tasks);
+}
+
+// The tasks as the public sees them, with redaction rules applied.
+fn public_tasks(path: &Path, options: &ServeOptions, merged: &Prefetch) -> Result<Vec<Task>, String> {
+    let tasks = load_tasks(path, options, merged)?;
+    if options.expose_private {
+        return Ok(tasks);
+    }
+    return Ok(options.redactor.redact(&tasks));
+}
+
+fn tasks_message(path: &Path, options: &ServeOptions, merged: &Prefetch) -> String {
+    return match public_tasks(path, options, merged) {
+        Ok(tasks) => serde_json::json!({
+            "type": "tasks",
+            "tasks": json::tasks_to_json(&tasks, options.expose_private, &options.render.public_tags),
+        }),
+        Err(why) => {
+            eprintln!("{}", why);
+            serde_json::json!({ "type": "error", "message": "The calendar couldn't be read" })
+        },
+    }.to_string();
+}
+
+fn handle_ws(mut stream: TcpStream, request: &Request, path: &Path, options: &ServeOptions, hub: &Hub, merged: &Prefetch) {
+    let key = match request.header("Sec-WebSocket-Key") {
+        Some(key) => key,
+        None => return respond(&mut stream, options, "400 Bad Request", "text/plain", "Expected a WebSocket upgrade\n"),
+    };
+    if ws::handshake(&mut stream, key).is_err()
+            || ws::send_text(&mut stream, &tasks_message(path, options, merged)).is_err() {
+        return;
+    }
+    // Subscribers can stay connected without sending anything.
+    if let Err(why) = stream.set_read_timeout(None) {
+        return eprintln!("Couldn't register WebSocket client: {}", why);
+    }
+    match stream.try_clone() {
+        Ok(clone) => hub.add(clone),
+        Err(why) => return eprintln!("Couldn't register WebSocket client: {}", why),
+    }
+    ws::drain(&mut stream);
+    // The hub's copy of the stream would otherwise keep the connection open
+    // until the next broadcast.
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+}
+
+// Reloads the page whenever /ws says the file changed, that is, on any message
+// after the one sent on connecting. If the server goes away the page keeps
+// trying, and reloads once it's back in case the file changed meanwhile.
+const LIVE_RELOAD: &str = r#"<script>
+(function connect(reconnecting) {
+  var socket = new WebSocket((location.protocol === "https:" ? "wss://" : "ws://") + location.host + "/ws");
+  var connected = false;
+  socket.onmessage = function () {
+    if (connected || reconnecting) location.reload();
+    connected = true;
+  };
+  socket.onclose = function () { setTimeout(function () { connect(reconnecting || connected); }, 1000); };
+})(false);
+</script>"#;
+
+fn with_live_reload(html: String) -> String {
+    return match html.rfind("</body>") {
+        Some(i) => format!("{}{}{}", &html[..i], LIVE_RELOAD, &html[i..]),
+        None => html + LIVE_RELOAD,
+    };
+}
+
+fn handle(mut stream: TcpStream, path: &Path, options: &ServeOptions, hub: &Hub, merged: &Prefetch) {
+    if let Err(why) = stream.set_read_timeout(Some(TIMEOUT)).and_then(|_| stream.set_write_timeout(Some(TIMEOUT))) {
+        return eprintln!("Couldn't set up connection: {}", why);
+    }
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err((status, message)) => {
+            respond(&mut stream, options, status, "text/plain", message);
+            // Closing with the rest of the request unread would reset the
+            // connection, and the client might never see the response.
+            let _ = stream.shutdown(std::net::Shutdown::Write);
+            let _ = std::io::copy(&mut Read::by_ref(&mut stream).take(MAX_BODY as u64), &mut std::io::sink());
+            return;
+        },
+    };
+    if !options.privacy_mode {
+        eprintln!("{} {}", request.method, request.target);
+    }
+
+    match (request.method.as_str(), request.route()) {
+        ("GET", "/") | ("GET", "/public.html") => match load_tasks(path, options, merged) {
+            Ok(tasks) => {
+                let notes = options.redactor.redact_notes(&load_notes(path, &options.parse));
+                let html = render_html(&options.redactor.redact(&tasks), &notes, CalendarPrivacy::Public, options.render);
+                respond(&mut stream, options, "200 OK", "text/html; charset=utf-8", &with_live_reload(html));
+            },
+            Err(why) => respond_error(&mut stream, options, &why),
+        },
+        ("GET", route) if options.stylesheet.is_some() && route.trim_start_matches('/') == options.render.stylesheet.trim_start_matches('/') =>
+            respond(&mut stream, options, "200 OK", "text/css", &read_stylesheet(options.stylesheet.as_deref())),
+        ("GET", "/metrics") => match public_tasks(path, options, merged) {
+            Ok(tasks) => respond(&mut stream, options, "200 OK", "text/plain; version=0.0.4",
+                                 &metrics(&tasks, options.meeting_tag, options.render.week_start)),
+            Err(why) => respond_error(&mut stream, options, &why),
+        },
+        ("GET", "/widget.js") => respond_with_headers(&mut stream, options, "200 OK", "text/javascript",
+                                                      &[("Access-Control-Allow-Origin", "*")], widget::SCRIPT),
+        ("GET", "/widget.json") => match load_tasks(path, options, merged) {
+            Ok(tasks) => {
+                let count = request.query_param("count").and_then(|c| c.parse().ok()).unwrap_or(3);
+                respond_with_headers(&mut stream, options, "200 OK", "application/json", &[("Access-Control-Allow-Origin", "*")],
+                                     &widget::upcoming(&options.redactor.redact(&tasks), time::now(), count, &options.render.public_tags).to_string());
+            },
+            Err(why) => respond_error(&mut stream, options, &why),
+        },
+        ("GET", "/ws") => handle_ws(stream, &request, path, options, hub, merged),
+        ("GET", "/graphql") | ("POST", "/graphql") => match graphql_query(&request) {
+            Some(query) => match public_tasks(path, options, merged) {
+                Ok(tasks) => respond(&mut stream, options, "200 OK", "application/json",
+                                     &graphql::execute(&query, &tasks, options.expose_private, &options.render.public_tags)),
+                Err(why) => respond_error(&mut stream, options, &why),
+            },
+            None => respond(&mut stream, options, "400 Bad Request", "application/json",
+                            "{\"errors\":[{\"message\":\"missing query\"}]}"),
+        },
+        (_, "/" | "/public.html" | "/metrics") =>
+            respond(&mut stream, options, "405 Method Not Allowed", "text/plain", "Only GET is supported\n"),
+        _ => respond(&mut stream, options, "404 Not Found", "text/plain", "Not found\n"),
+    }
+}
+
+pub struct ServeOptions<'a> {
+    pub render: &'a RenderOptions,
+    pub parse: ParseOptions,
+    /// Applied to everything served unless private details are exposed.
+    pub redactor: &'a Redactor,
+    pub meeting_tag: &'a str,
+    pub expose_private: bool,
+    /// Don't log requests and ask clients not to leak referrers or cache pages.
+    pub privacy_mode: bool,
+    /// Calendars drawn on the page, as for --merge-ics.
+    pub merge_ics: &'a [PathBuf],
+    /// How often the merged calendars are fetched again.
+    pub refresh: StdDuration,
+    /// The stylesheet the page links to, served at that link; None if it's
+    /// linked by URL.
+    pub stylesheet: Option<PathBuf>,
+    /// The config, watched along with the task file; the server starts over
+    /// when it changes.
+    pub config: Option<&'a Path>,
+}
+
+pub fn serve(bind: &str, path: &Path, options: &ServeOptions) -> Result<(), String> {
+    let listener = TcpListener::bind(bind).map_err(|why| format!("Couldn't listen on {}: {}", bind, why))?;
+    eprintln!("Serving {} on http://{}/", path.display(), bind);
+    let hub = Hub::default();
+    let merged = Prefetch::new(options.merge_ics, options.parse.timezone);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            let files = || {
+                let mut files = sources(path, &options.parse);
+                files.extend(options.config.map(Path::to_path_buf));
+                files.extend(options.stylesheet.clone());
+                return files;
+            };
+            watch::watch(files, |files| {
+                if let Some(config) = options.config.filter(|config| files.iter().any(|file| file == config)) {
+                    watch::restart(&format!("{} changed", config.display()));
+                }
+                hub.broadcast(&tasks_message(path, options, &merged));
+            });
+        });
+        // Pages reload to show the new events.
+        scope.spawn(|| merged.keep_fetching(options.refresh, || hub.broadcast(&tasks_message(path, options, &merged))));
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => { scope.spawn(|| handle(stream, path, options, &hub, &merged)); },
+                Err(why) => eprintln!("Connection failed: {}", why),
+            }
+        }
+    });
+    return Ok(());
+}
@@ -0,0 +1,99 @@
+// Writes that land together or not at all. Rendering the site writes both
+// calendars and copies their attachments; written one at a time, a failure
+// partway (an attachment that can't be read, a full disk) would leave a site
+// with the new public page linking to files that were never copied. Instead,
+// every file is first written beside its target as `.NAME.tmp`, and only once
+// all of them are written are they renamed into place, keeping the old files
+// as `.NAME.bak` until the last rename succeeds so that a failed rename can
+// put back the ones already replaced.
+use std::path::{Path, PathBuf};
+
+#[derive(Default)]
+pub struct Staged {
+    files: Vec<(PathBuf, Vec<u8>)>,
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    return path.with_file_name(format!(".{}.{}", name, suffix));
+}
+
+// Puts back the files in `replaced` and removes whatever is left of the
+// staged copies.
+fn roll_back(replaced: &[(&Path, bool)], temporaries: &[PathBuf]) {
+    for (path, existed) in replaced {
+        if *existed {
+            let _ = std::fs::rename(sibling(path, "bak"), path);
+        } else {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    for temporary in temporaries {
+        let _ = std::fs::remove_file(temporary);
+    }
+}
+
+impl Staged {
+    // Nothing is written until commit.
+    pub fn write(&mut self, path: &Path, contents: impl Into<Vec<u8>>) {
+        let contents = contents.into();
+        match self.files.iter_mut().find(|(staged, _)| staged == path) {
+            Some((_, staged)) => *staged = contents,
+            None => self.files.push((path.to_path_buf(), contents)),
+        }
+    }
+
+    // The files that commit would write, in the order they were staged.
+    pub fn paths(&self) -> Vec<&Path> {
+        return self.files.iter().map(|(path, _)| path.as_path()).collect();
+    }
+
+    // Writes every staged file, leaving the ones that are already up to date
+    // alone so static hosts keep serving the same ETag. On error, the files
+    // are as they were before.
+    pub fn commit(self) -> Result<(), String> {
+        let changed: Vec<&(PathBuf, Vec<u8>)> = self.files.iter()
+            .filter(|(path, contents)| std::fs::read(path).ok().as_ref() != Some(contents))
+            .collect();
+
+        let mut written: Vec<PathBuf> = Vec::new();
+        for (path, contents) in &changed {
+            let temporary = sibling(path, "tmp");
+            let result = match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                Some(parent) => std::fs::create_dir_all(parent),
+                None => Ok(()),
+            }.and_then(|_| std::fs::write(&temporary, contents));
+            if let Err(why) = result {
+                roll_back(&[], &written);
+                return Err(format!("Couldn't write {}: {}; nothing was changed", path.display(), why));
+            }
+            written.push(temporary);
+        }
+
+        // The targets replaced so far, and whether each existed before.
+        let mut replaced: Vec<(&Path, bool)> = Vec::new();
+        for (path, _) in &changed {
+            let existed = path.exists();
+            if existed {
+                if let Err(why) = std::fs::rename(path, sibling(path, "bak")) {
+                    roll_back(&replaced, &written);
+                    return Err(format!("Couldn't replace {}: {}; nothing was changed", path.display(), why));
+                }
+            }
+            if let Err(why) = std::fs::rename(sibling(path, "tmp"), path) {
+                if existed {
+                    let _ = std::fs::rename(sibling(path, "bak"), path);
+                }
+                roll_back(&replaced, &written);
+                return Err(format!("Couldn't replace {}: {}; nothing was changed", path.display(), why));
+            }
+            replaced.push((path, existed));
+        }
+        for (path, existed) in replaced {
+            if existed {
+                let _ = std::fs::remove_file(sibling(path, "bak"));
+            }
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,415 @@
+// Reports computed over the whole task history.
+use std::collections::{BTreeMap, HashMap};
+use chrono::{Duration, NaiveDate, Weekday};
+use crate::done::done_at;
+use crate::{escape_html, is_out_of_office, resolve_day, time, OutOfOffice, Subtask, Task};
+
+// For every habit (tasks tagged `+habit:NAME`), reports how many scheduled
+// occurrences up to `today` were checked off, the current and longest runs of
+// consecutive completed occurrences, and per-week adherence for the last
+// `weeks` weeks.
+pub fn habits_report(tasks: &[Task], today: NaiveDate, weeks: i64, first_day: Weekday) -> String {
+    let mut habits: BTreeMap<&str, Vec<&Task>> = BTreeMap::new();
+    for task in tasks.iter().filter(|t| t.date <= today) {
+        for tag in &task.tags {
+            if let Some(name) = tag.strip_prefix("habit:") {
+                habits.entry(name).or_default().push(task);
+            }
+        }
+    }
+    if habits.is_empty() {
+        return "No +habit:NAME tasks found.\n".to_string();
+    }
+
+    let first_week = time::week_start(today, first_day) - Duration::weeks(weeks - 1);
+    let mut out = String::new();
+    for (name, mut occurrences) in habits {
+        occurrences.sort_by_key(|t| (t.date, t.start_time));
+        let n_done = occurrences.iter().filter(|t| t.done).count();
+        let mut longest = 0;
+        let mut run = 0;
+        for task in &occurrences {
+            run = if task.done { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+        // Today's occurrence may simply not be done *yet*, so it doesn't break the
+        // current streak.
+        let current = occurrences.iter().rev()
+            .skip_while(|t| t.date == today && !t.done)
+            .take_while(|t| t.done)
+            .count();
+
+        out.push_str(&format!("{}\n", name));
+        out.push_str(&format!("  done {} of {} ({:.0}%)\n", n_done, occurrences.len(),
+                              100.0 * n_done as f64 / occurrences.len() as f64));
+        out.push_str(&format!("  current streak: {}, longest: {}\n", current, longest));
+        let items: Vec<&Subtask> = occurrences.iter().flat_map(|t| &t.subtasks).collect();
+        if !items.is_empty() {
+            let items_done = items.iter().filter(|item| item.done).count();
+            out.push_str(&format!("  checklist items done {} of {} ({:.0}%)\n", items_done, items.len(),
+                                  100.0 * items_done as f64 / items.len() as f64));
+        }
+        out.push_str("  weekly:");
+        for week in 0..weeks {
+            let start = first_week + Duration::weeks(week);
+            let in_week: Vec<&&Task> = occurrences.iter()
+                .filter(|t| t.date >= start && t.date < start + Duration::weeks(1))
+                .collect();
+            let done = in_week.iter().filter(|t| t.done).count();
+            out.push_str(&format!(" {} {}/{}", time::format_month_day(start), done, in_week.len()));
+        }
+        out.push('\n');
+    }
+    return out;
+}
+
+// For each of the `weeks` weeks ending with the current one, how many of the
+// checklist items of that week's tasks were checked off, nested ones
+// included, and how many of the checklists were finished.
+pub fn checklists_report(tasks: &[Task], today: NaiveDate, weeks: i64, first_day: Weekday) -> String {
+    let first_week = time::week_start(today, first_day) - Duration::weeks(weeks - 1);
+    let mut out = String::new();
+    for week in 0..weeks {
+        let start = first_week + Duration::weeks(week);
+        let lists: Vec<&Task> = tasks.iter()
+            .filter(|t| t.date >= start && t.date < start + Duration::weeks(1) && !t.subtasks.is_empty())
+            .collect();
+        out.push_str(&format!("{:>5}  ", time::format_month_day(start)));
+        if lists.is_empty() {
+            out.push_str("no checklists\n");
+            continue;
+        }
+        let items = lists.iter().map(|t| t.subtasks.len()).sum::<usize>();
+        let done = lists.iter().map(|t| t.subtasks.iter().filter(|item| item.done).count()).sum::<usize>();
+        let finished = lists.iter().filter(|t| t.subtasks.iter().all(|item| item.done)).count();
+        out.push_str(&format!("{}/{} items done ({:.0}%), {}/{} checklists finished\n",
+                              done, items, 100.0 * done as f64 / items as f64, finished, lists.len()));
+    }
+    return out;
+}
+
+// For each of the `weeks` weeks ending with the current one, how the timed
+// tasks checked off with a '+done:HH:MM' finished against the end they were
+// planned for: how many by then, and how far past it they ran on average
+// (negative when early).
+pub fn done_report(tasks: &[Task], today: NaiveDate, weeks: i64, first_day: Weekday) -> String {
+    let first_week = time::week_start(today, first_day) - Duration::weeks(weeks - 1);
+    let mut out = String::new();
+    for week in 0..weeks {
+        let start = first_week + Duration::weeks(week);
+        // Against the end of the last day for tasks spanning days.
+        let overruns: Vec<i64> = tasks.iter()
+            .filter(|t| t.date >= start && t.date < start + Duration::weeks(1) && t.done)
+            .filter_map(|t| {
+                let end = t.span.map(|span| span.end).or(t.end_time)?;
+                return Some((done_at(t)? - end).num_minutes());
+            })
+            .collect();
+        out.push_str(&format!("{:>5}  ", time::format_month_day(start)));
+        if overruns.is_empty() {
+            out.push_str("no finishing times recorded\n");
+            continue;
+        }
+        let on_time = overruns.iter().filter(|minutes| **minutes <= 0).count();
+        let average = overruns.iter().sum::<i64>() as f64 / overruns.len() as f64;
+        out.push_str(&format!("{}/{} finished by their planned end, {:+.0}m past it on average\n",
+                              on_time, overruns.len(), average));
+    }
+    return out;
+}
+
+fn is_free(tasks: &[&Task], date: NaiveDate, hour: u32) -> bool {
+    let span_start = time::time_of_day(hour as i64 * 60);
+    // The last hour of the day ends at midnight, which NaiveTime can't represent.
+    let span_end = time::hms(hour + 1, 0, 0).unwrap_or_else(time::end_of_day);
+    return !tasks.iter().any(|t| {
+        t.date == date && match [t.start_time, t.end_time] {
+            [Some(start), Some(end)] => start < span_end && span_start < end,
+            _ => false,
+        }
+    });
+}
+
+// Counts, for each weekday and working hour, how many of the last `weeks` full
+// weeks left that hour completely unscheduled, then lists the stretches that
+// were free in every one of those weeks. Days out of office are never free.
+pub fn idle_report(tasks: &[Task], out_of_office: &[OutOfOffice], today: NaiveDate, weeks: i64,
+                   work_hours: (u32, u32), first_day: Weekday) -> String {
+    let (first_hour, last_hour) = work_hours;
+    let first_week = time::week_start(today, first_day) - Duration::weeks(weeks);
+    let end = time::week_start(today, first_day);
+    let in_range: Vec<&Task> = tasks.iter().filter(|t| t.date >= first_week && t.date < end).collect();
+    let weekdays = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+
+    let mut out = format!("Weeks free during working hours, {} weeks from {}:\n",
+                          weeks, time::format_short_date(first_week));
+    out.push_str("    ");
+    for hour in first_hour..last_hour {
+        out.push_str(&format!("{:>4}", hour));
+    }
+    out.push('\n');
+
+    let mut always_free = Vec::new();
+    for weekday in weekdays {
+        out.push_str(&format!("{:<4}", weekday));
+        let mut block_start = None;
+        for hour in first_hour..=last_hour {
+            let n_free = if hour == last_hour {
+                0
+            } else {
+                (0..weeks).filter(|&week| {
+                    let date = resolve_day(first_week + Duration::weeks(week), weekday);
+                    is_free(&in_range, date, hour) && !is_out_of_office(out_of_office, date)
+                }).count() as i64
+            };
+            if hour < last_hour {
+                out.push_str(&format!("{:>4}", n_free));
+            }
+            match (block_start, n_free == weeks) {
+                (None, true) => block_start = Some(hour),
+                (Some(start), false) => {
+                    always_free.push(format!("{} {}:00--{}:00", weekday, start, hour));
+                    block_start = None;
+                },
+                _ => (),
+            }
+        }
+        out.push('\n');
+    }
+
+    if always_free.is_empty() {
+        out.push_str("No working hours were free every week.\n");
+    } else {
+        out.push_str("Free every week:\n");
+        for block in always_free {
+            out.push_str(&format!("  {}\n", block));
+        }
+    }
+    return out;
+}
+
+// A meeting's estimated cost: its length times the people in it times the
+// hourly rate. None for tasks without a time or an '&N' count.
+pub fn meeting_cost(task: &Task, hourly_rate: f64) -> Option<f64> {
+    let attendees = task.attendees?;
+    task.start_time?;
+    return Some(task_hours(task) * attendees as f64 * hourly_rate);
+}
+
+pub fn task_hours(task: &Task) -> f64 {
+    return match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => {
+            let end = task.span.map_or(task.date.and_time(end), |span| span.last.and_time(span.end));
+            (end - task.date.and_time(start)).num_minutes() as f64 / 60.0
+        },
+        _ => 0.0,
+    };
+}
+
+// Hours of timed tasks carrying `tag` in each of the `weeks` weeks ending with
+// the current one, oldest first, keyed by the first day of the week.
+pub fn weekly_tag_hours(tasks: &[Task], today: NaiveDate, weeks: i64, tag: &str, first_day: Weekday) -> Vec<(NaiveDate, f64)> {
+    let first_week = time::week_start(today, first_day) - Duration::weeks(weeks - 1);
+    let mut hours = Vec::new();
+    for week in 0..weeks {
+        let start = first_week + Duration::weeks(week);
+        let total = tasks.iter()
+            .filter(|t| t.date >= start && t.date < start + Duration::weeks(1))
+            .filter(|t| t.tags.iter().any(|t| t == tag))
+            .map(task_hours)
+            .fold(0.0, |a, b| a + b);
+        hours.push((start, total));
+    }
+    return hours;
+}
+
+// The estimated cost of the `tag`ged meetings in each of the `weeks` weeks
+// ending with the current one, then of each of this week's meetings. Meetings
+// without an '&N' count can't be costed, and are only counted.
+pub fn cost_report(tasks: &[Task], today: NaiveDate, weeks: i64, tag: &str, hourly_rate: f64, first_day: Weekday) -> String {
+    let this_week = time::week_start(today, first_day);
+    let first_week = this_week - Duration::weeks(weeks - 1);
+    let meetings: Vec<&Task> = tasks.iter()
+        .filter(|t| t.date >= first_week && t.date < this_week + Duration::weeks(1))
+        .filter(|t| t.tags.iter().any(|t| t == tag) && t.start_time.is_some())
+        .collect();
+    let mut out = format!("Estimated cost of +{} at {} per person per hour:\n", tag, hourly_rate);
+    for week in 0..weeks {
+        let start = first_week + Duration::weeks(week);
+        let cost = meetings.iter()
+            .filter(|t| t.date >= start && t.date < start + Duration::weeks(1))
+            .filter_map(|t| meeting_cost(t, hourly_rate))
+            .fold(0.0, |a, b| a + b);
+        out.push_str(&format!("  {:<8} {:>8.0}\n", time::format_short_date(start), cost));
+    }
+    let mut current: Vec<&&Task> = meetings.iter().filter(|t| t.date >= this_week).collect();
+    current.sort_by(|a, b| crate::cmp_tasks(a, b));
+    out.push_str("This week:\n");
+    for task in &current {
+        if let Some(cost) = meeting_cost(task, hourly_rate) {
+            out.push_str(&format!("  {:>8.0}  {}  {} &{}\n", cost, time::format_date(task.date),
+                                  task.details.lines().next().unwrap_or(""), task.attendees.unwrap_or(0)));
+        }
+    }
+    let uncounted = current.iter().filter(|t| t.attendees.is_none()).count();
+    if uncounted > 0 {
+        out.push_str(&format!("  ({} without an &N attendee count left out)\n", uncounted));
+    }
+    return out;
+}
+
+pub fn sparkline_report(hours: &[(NaiveDate, f64)], tag: &str) -> String {
+    let bars = ['\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}', '\u{2588}'];
+    let max = hours.iter().map(|(_, h)| *h).fold(0.0, f64::max);
+    let mut out = format!("Hours tagged +{} per week: ", tag);
+    for (_, h) in hours {
+        let level = if max > 0.0 { ((h / max) * (bars.len() - 1) as f64).round() as usize } else { 0 };
+        out.push(bars[level]);
+    }
+    out.push('\n');
+    for (week, h) in hours {
+        out.push_str(&format!("  {:<8} {:>5.1}\n", time::format_short_date(*week), h));
+    }
+    return out;
+}
+
+pub fn bar_chart_svg(hours: &[(NaiveDate, f64)], tag: &str) -> String {
+    let (bar_width, chart_height, left, top, bottom) = (30.0, 150.0, 40.0, 25.0, 30.0);
+    let max = hours.iter().map(|(_, h)| *h).fold(1.0, f64::max);
+    let width = left + bar_width * hours.len() as f64 + 10.0;
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"10\">",
+                          width, top + chart_height + bottom);
+    svg.push_str(&format!("<text x=\"{}\" y=\"15\">Hours tagged +{} per week</text>", left, tag));
+    svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{:.0}</text>", left - 4.0, top + 4.0, max));
+    svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"end\">0</text>", left - 4.0, top + chart_height));
+    for (i, (week, h)) in hours.iter().enumerate() {
+        let bar_height = chart_height * h / max;
+        let x = left + bar_width * i as f64;
+        svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#d22\"><title>{:.1}h</title></rect>",
+                              x + 2.0, top + chart_height - bar_height, bar_width - 4.0, bar_height, h));
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>",
+                              x + bar_width / 2.0, top + chart_height + 14.0, time::format_month_day(*week)));
+    }
+    svg.push_str("</svg>");
+    return svg;
+}
+
+// Hours of timed tasks per tag in each period (day, or week if not `daily`)
+// of the `weeks` weeks ending with the current one, oldest first. The columns
+// are the tags, most hours first, then "untagged" and "total"; a task with
+// several tags counts towards each of them.
+pub struct TagHours {
+    pub columns: Vec<String>,
+    pub rows: Vec<(NaiveDate, Vec<f64>)>,
+}
+
+pub fn tag_hours(tasks: &[Task], today: NaiveDate, weeks: i64, daily: bool, first_day: Weekday) -> TagHours {
+    let first_week = time::week_start(today, first_day) - Duration::weeks(weeks - 1);
+    let (periods, length) = if daily { (weeks * 7, Duration::days(1)) } else { (weeks, Duration::weeks(1)) };
+    let end = first_week + Duration::weeks(weeks);
+    let in_range: Vec<&Task> = tasks.iter()
+        .filter(|t| t.date >= first_week && t.date < end && t.start_time.is_some())
+        .collect();
+
+    let mut totals: BTreeMap<&str, f64> = BTreeMap::new();
+    for task in &in_range {
+        for tag in &task.tags {
+            *totals.entry(tag.as_str()).or_default() += task_hours(task);
+        }
+    }
+    let mut tags: Vec<(&str, f64)> = totals.into_iter().collect();
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+    let mut columns: Vec<String> = tags.iter().map(|(tag, _)| tag.to_string()).collect();
+
+    let mut rows = Vec::new();
+    for period in 0..periods {
+        let start = first_week + length * period as i32;
+        let in_period: Vec<&&Task> = in_range.iter().filter(|t| t.date >= start && t.date < start + length).collect();
+        let sum = |keep: &dyn Fn(&Task) -> bool| {
+            return in_period.iter().filter(|t| keep(t)).map(|t| task_hours(t)).fold(0.0, |a, b| a + b);
+        };
+        let mut hours: Vec<f64> = tags.iter().map(|(tag, _)| sum(&|t: &Task| t.tags.iter().any(|t| t == tag))).collect();
+        hours.push(sum(&|t: &Task| t.tags.is_empty()));
+        hours.push(sum(&|_: &Task| true));
+        rows.push((start, hours));
+    }
+    columns.push("untagged".to_string());
+    columns.push("total".to_string());
+    return TagHours { columns, rows };
+}
+
+// Every tag in `tasks`, most used first, with the number of tasks carrying it
+// and the first and last days it was used on; tags described on the public
+// calendar are starred. Tags used once or twice are often typos.
+pub fn tag_usage_report(tasks: &[Task], public_tags: &HashMap<String, String>) -> String {
+    let mut usage: BTreeMap<&str, (usize, NaiveDate, NaiveDate)> = BTreeMap::new();
+    for task in tasks {
+        for tag in &task.tags {
+            let entry = usage.entry(tag.as_str()).or_insert((0, task.date, task.date));
+            entry.0 += 1;
+            entry.1 = entry.1.min(task.date);
+            entry.2 = entry.2.max(task.date);
+        }
+    }
+    if usage.is_empty() {
+        return "No tags found.\n".to_string();
+    }
+    let mut tags: Vec<(&str, (usize, NaiveDate, NaiveDate))> = usage.into_iter().collect();
+    tags.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.0.cmp(b.0)));
+    let names: Vec<String> = tags.iter()
+        .map(|(tag, _)| format!("{}{}", tag, if public_tags.contains_key(*tag) { "*" } else { "" }))
+        .collect();
+    let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0).max(3);
+    let mut out = format!("  {:<width$}  {:>5}  {:<10}  {}\n", "tag", "tasks", "first", "last", width = width);
+    for (name, (_, (count, first, last))) in names.iter().zip(&tags) {
+        out.push_str(&format!("  {:<width$}  {:>5}  {}  {}\n", name, count,
+                              time::format_iso_date(*first), time::format_iso_date(*last), width = width));
+    }
+    out.push_str("(* described on the public calendar)\n");
+    return out;
+}
+
+fn period_label(start: NaiveDate, daily: bool) -> String {
+    return if daily { time::format_short_day(start) } else { time::format_short_date(start) };
+}
+
+pub fn tag_hours_report(table: &TagHours, daily: bool) -> String {
+    let label_width = table.rows.iter().map(|(start, _)| period_label(*start, daily).len()).max().unwrap_or(0);
+    let widths: Vec<usize> = table.columns.iter().map(|column| column.len().max(5)).collect();
+    let mut out = format!("Hours per tag per {}:\n", if daily { "day" } else { "week" });
+    out.push_str(&" ".repeat(label_width + 2));
+    for (column, width) in table.columns.iter().zip(&widths) {
+        out.push_str(&format!("  {:>width$}", column, width = width));
+    }
+    out.push('\n');
+    for (start, hours) in &table.rows {
+        out.push_str(&format!("  {:<width$}", period_label(*start, daily), width = label_width));
+        for (h, width) in hours.iter().zip(&widths) {
+            out.push_str(&format!("  {:>width$.1}", h, width = width));
+        }
+        out.push('\n');
+    }
+    return out;
+}
+
+// The same table as a section of HTML, each tag's hours drawn as a bar scaled
+// to the busiest period.
+pub fn tag_hours_html(table: &TagHours, daily: bool) -> String {
+    let max = table.rows.iter().map(|(_, hours)| hours.last().copied().unwrap_or(0.0)).fold(1.0, f64::max);
+    let mut html = format!("<section class=\"tag-hours\"><h2>Hours per tag per {}</h2><table><tr><th></th>",
+                           if daily { "day" } else { "week" });
+    for column in &table.columns {
+        html.push_str(&format!("<th>{}</th>", escape_html(column)));
+    }
+    html.push_str("</tr>");
+    for (start, hours) in &table.rows {
+        html.push_str(&format!("<tr><th>{}</th>", period_label(*start, daily)));
+        for h in hours {
+            html.push_str(&format!("<td><div style=\"background:#d22;height:1em;width:{:.0}px\"></div>{:.1}</td>",
+                                   100.0 * h / max, h));
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table></section>");
+    return html;
+}
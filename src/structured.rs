@@ -0,0 +1,253 @@
+// Task files in other formats than markdown, for schedules generated by a
+// program rather than written by hand. A TOML file is read into the same
+// tasks (and notes) as wtd.md:
+//
+//   notes = ["Back on the 26th."]
+//
+//   [[out_of_office]]
+//   first = 2026-10-20
+//   last = 2026-10-23
+//
+//   [[tasks]]
+//   date = 2026-10-16
+//   start = 09:00:00
+//   end = 10:00:00
+//   details = "Standup"
+//   tags = ["busy"]
+//
+// Dates and times may be TOML's own or strings ("2026-10-16", "09:00"); an
+// end of "24:00" runs to midnight. Only `date` and `details` are required.
+//
+// JSON takes the same shape, or just the array of tasks that `wtd json`
+// prints, so its output can be filtered and read back. An iCalendar file's
+// events are read as private tasks. The format is told by the extension,
+// or, for standard input and unknown extensions, by the contents.
+//
+// Such files are read-only to wtd: fmt, sync, and protect edit markdown.
+use std::path::Path;
+use chrono::{NaiveDate, NaiveTime};
+use serde::Deserialize;
+use toml::Value;
+use crate::{ics, parse_full_date, task_id, time, Notes, OutOfOffice, Span, Subtask, Task};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default)]
+    notes: Vec<String>,
+    #[serde(default)]
+    out_of_office: Vec<OutOfOfficeEntry>,
+    #[serde(default)]
+    tasks: Vec<TaskEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutOfOfficeEntry {
+    first: Value,
+    last: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaskEntry {
+    date: Value,
+    start: Option<Value>,
+    end: Option<Value>,
+    end_date: Option<Value>,
+    #[serde(default)]
+    all_day: bool,
+    details: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    subtasks: Vec<SubtaskEntry>,
+    #[serde(default)]
+    notes: Vec<String>,
+    #[serde(default)]
+    attachments: Vec<String>,
+    attendees: Option<u32>,
+    priority: Option<u8>,
+    location: Option<String>,
+    id: Option<String>,
+    source: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SubtaskEntry {
+    text: String,
+    #[serde(default)]
+    done: bool,
+    // Nested under the item above it this many levels deep.
+    #[serde(default)]
+    level: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Toml,
+    Json,
+    Ics,
+}
+
+pub fn detect(path: &Path, source: &str) -> Format {
+    match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()).as_deref() {
+        Some("md") | Some("markdown") => return Format::Markdown,
+        Some("toml") => return Format::Toml,
+        Some("json") => return Format::Json,
+        Some("ics") | Some("ical") => return Format::Ics,
+        _ => {},
+    }
+    let trimmed = source.trim_start_matches('\u{feff}').trim_start();
+    if trimmed.starts_with("BEGIN:VCALENDAR") {
+        return Format::Ics;
+    }
+    if (trimmed.starts_with('[') || trimmed.starts_with('{')) && serde_json::from_str::<serde_json::Value>(source).is_ok() {
+        return Format::Json;
+    }
+    // Markdown headers are TOML comments, so a task file with only headers
+    // reads as an empty table.
+    if toml::from_str::<toml::Table>(source).is_ok_and(|table| !table.is_empty()) {
+        return Format::Toml;
+    }
+    return Format::Markdown;
+}
+
+fn to_date(value: &Value) -> Result<NaiveDate, String> {
+    return match value {
+        Value::Datetime(datetime) => match (datetime.date, datetime.time) {
+            (Some(date), None) => time::ymd(date.year as i32, date.month as u32, date.day as u32)
+                .ok_or_else(|| format!("{} is not a valid date", datetime)),
+            _ => Err(format!("expected a date, not {}", datetime)),
+        },
+        Value::String(s) => parse_full_date(s),
+        _ => Err(format!("expected a date, not {}", value)),
+    };
+}
+
+fn to_time(value: &Value) -> Result<NaiveTime, String> {
+    return match value {
+        Value::Datetime(datetime) => match (datetime.date, datetime.time) {
+            (None, Some(t)) => time::hms(t.hour as u32, t.minute as u32, t.second.unwrap_or(0) as u32)
+                .ok_or_else(|| format!("{} is not a valid time", datetime)),
+            _ => Err(format!("expected a time of day, not {}", datetime)),
+        },
+        Value::String(s) if s == "24:00" => Ok(time::end_of_day()),
+        Value::String(s) => time::parse_hhmm(s)
+            .ok_or_else(|| format!("couldn't parse time '{}'; expected e.g. \"09:30\"", s)),
+        _ => Err(format!("expected a time of day, not {}", value)),
+    };
+}
+
+fn to_task(entry: TaskEntry) -> Result<Task, String> {
+    let date = to_date(&entry.date)?;
+    // A task ending on a later day spans days, running to `end` on that day.
+    let last = match &entry.end_date {
+        Some(end_date) => to_date(end_date)?,
+        None => date,
+    };
+    if last < date {
+        return Err(format!("ends on {} before it starts on {}", time::format_iso_date(last), time::format_iso_date(date)));
+    }
+    let (start_time, end_time, span) = match (&entry.start, &entry.end) {
+        (Some(start), Some(end)) if last > date => {
+            (Some(to_time(start)?), Some(time::end_of_day()), Some(Span { last, end: to_time(end)? }))
+        },
+        (Some(start), Some(end)) => {
+            let (start, end) = (to_time(start)?, to_time(end)?);
+            if end <= start {
+                return Err(format!("ends at {} before it starts at {}", time::format_hhmm(end), time::format_hhmm(start)));
+            }
+            (Some(start), Some(end), None)
+        },
+        (None, None) if last > date => return Err("ends on a later day, so needs a start and an end".to_string()),
+        (None, None) => (None, None, None),
+        _ => return Err("needs both a start and an end, or neither".to_string()),
+    };
+    if entry.all_day && start_time.is_some() {
+        return Err("is all day, so can't also have a start and end".to_string());
+    }
+    if entry.details.trim().is_empty() {
+        return Err("has no details".to_string());
+    }
+    let tags = entry.tags.iter().map(|tag| tag.trim_start_matches('+').to_string()).collect::<Vec<String>>();
+    if let Some(tag) = tags.iter().find(|tag| tag.is_empty() || tag.contains(char::is_whitespace)) {
+        return Err(format!("'{}' is not a tag", tag));
+    }
+    let priority = match entry.priority {
+        Some(level) if !(1..=5).contains(&level) => return Err(format!("priority {} isn't from 1 (most important) to 5", level)),
+        priority => priority,
+    };
+    if let Some(Some(Err(why))) = entry.id.as_ref().map(|id| task_id(&format!("#id:{}", id))) {
+        return Err(why);
+    }
+    let mut above = None;
+    for subtask in &entry.subtasks {
+        if subtask.level > above.map_or(0, |level| level + 1) {
+            return Err(format!("checklist item '{}' is nested deeper than just under the item above it", subtask.text));
+        }
+        above = Some(subtask.level);
+    }
+    return Ok(Task {
+        date,
+        start_time,
+        end_time,
+        all_day: entry.all_day,
+        span,
+        details: entry.details.trim().to_string(),
+        tags,
+        done: entry.done,
+        subtasks: entry.subtasks.into_iter().map(|subtask| Subtask { text: subtask.text, done: subtask.done, level: subtask.level }).collect(),
+        notes: entry.notes,
+        attachments: entry.attachments,
+        attendees: entry.attendees,
+        priority,
+        location: entry.location.filter(|place| !place.trim().is_empty()),
+        id: entry.id,
+        source: entry.source,
+    });
+}
+
+fn to_tasks_and_notes(file: File) -> Result<(Vec<Task>, Notes), String> {
+    let mut tasks = Vec::new();
+    for (i, entry) in file.tasks.into_iter().enumerate() {
+        tasks.push(to_task(entry).map_err(|why| format!("task {}: {}", i + 1, why))?);
+    }
+    let mut out_of_office = Vec::new();
+    for (i, entry) in file.out_of_office.iter().enumerate() {
+        let range = (to_date(&entry.first), to_date(&entry.last));
+        match range {
+            (Ok(first), Ok(last)) if first <= last => out_of_office.push(OutOfOffice { first, last }),
+            (Ok(_), Ok(_)) => return Err(format!("out_of_office {}: ends before it starts", i + 1)),
+            (Err(why), _) | (_, Err(why)) => return Err(format!("out_of_office {}: {}", i + 1, why)),
+        }
+    }
+    return Ok((tasks, Notes { preamble: file.notes, out_of_office, ..Notes::default() }));
+}
+
+fn parse_json(s: &str) -> Result<File, String> {
+    let value: serde_json::Value = serde_json::from_str(s).map_err(|why| why.to_string())?;
+    if value.is_array() {
+        let tasks = serde_json::from_value(value).map_err(|why| why.to_string())?;
+        return Ok(File { notes: Vec::new(), out_of_office: Vec::new(), tasks });
+    }
+    return serde_json::from_value(value).map_err(|why| why.to_string());
+}
+
+// The tasks and notes of a task file in any format but markdown. An
+// iCalendar file's times are read onto clocks in `tz`.
+pub fn parse(format: Format, s: &str, tz: Option<chrono_tz::Tz>) -> Result<(Vec<Task>, Notes), String> {
+    return match format {
+        Format::Markdown => Err("markdown is read by parse_tasks".to_string()),
+        Format::Toml => to_tasks_and_notes(toml::from_str(s).map_err(|why| why.to_string())?),
+        Format::Json => to_tasks_and_notes(parse_json(s)?),
+        Format::Ics => {
+            let tasks = ics::read_calendar(s, tz)?.into_iter().flat_map(|(_, tasks)| tasks).collect();
+            Ok((tasks, Notes::default()))
+        },
+    };
+}
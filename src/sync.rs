@@ -0,0 +1,269 @@
+// `wtd sync`: two-way sync of the task file with a CalDAV calendar, so that
+// wtd.md can stay the single source of truth while phones and other calendar
+// apps see (and edit) the same events.
+//
+// Every task is stored as one event. A state file remembers, for each event,
+// where it lives on the server, the ETag it had when last seen, and a
+// fingerprint of the task it stands for; tasks carry no IDs of their own, so
+// the fingerprint is how a task is recognized between runs. A run first pulls:
+// events deleted on the server are deleted from the file, changed ones
+// replace their task (keeping its checklist, files and check mark, which
+// events don't carry), and new ones are added under their day. It then
+// pushes: tasks no longer in the file are deleted from the server, and tasks
+// the server doesn't have yet (including edited ones, whose fingerprint has
+// changed) are uploaded. When a task was edited on both sides, the local
+// edit wins.
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use chrono::Weekday;
+use serde::{Deserialize, Serialize};
+use crate::caldav::Client;
+use crate::edit::{insert_task, splice_task};
+use crate::progress::Progress;
+use crate::{check_editable, dirs, format, ics, lock, parse_tasks_with, read_file, ParseOptions, Task};
+
+pub struct SyncOptions<'a> {
+    /// The calendar collection, e.g. https://caldav.fastmail.com/dav/calendars/user/NAME/Default/
+    pub url: &'a str,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a str>,
+    /// Defaults to a file named after the URL in the data directory.
+    pub state: Option<PathBuf>,
+    pub parse: ParseOptions,
+    /// For the week blocks of events on days the file has no block for.
+    pub week_start: Weekday,
+    /// Don't report progress.
+    pub quiet: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Synced {
+    href: String,
+    etag: String,
+    fingerprint: String,
+}
+
+// Keyed by UID.
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    events: BTreeMap<String, Synced>,
+}
+
+#[derive(Default)]
+pub struct Summary {
+    pub pulled: usize,
+    pub pushed: usize,
+    pub deleted_here: usize,
+    pub deleted_there: usize,
+}
+
+fn fingerprint(task: &Task) -> String {
+    let mut key = format!("{}{:?}{:?}{}{:?}", task.date, task.start_time, task.end_time, task.details, task.tags);
+    // Only marked when set, so that the fingerprints of other tasks in older
+    // sync state still match.
+    if task.all_day {
+        key.push_str("allday");
+    }
+    if let Some(span) = task.span {
+        key.push_str(&format!("{:?}", span));
+    }
+    if let Some(place) = &task.location {
+        key.push_str(&format!("at {}", place));
+    }
+    return sha1_smol::Sha1::from(key).digest().to_string();
+}
+
+fn state_path(options: &SyncOptions) -> PathBuf {
+    return options.state.clone().unwrap_or_else(|| {
+        let name = sha1_smol::Sha1::from(options.url).digest().to_string();
+        return dirs::data_dir().join("sync").join(format!("{}.json", &name[..16]));
+    });
+}
+
+fn load_state(path: &Path) -> Result<State, String> {
+    return match std::fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json).map_err(|why| format!("{}: {}", path.display(), why)),
+        Err(_) => Ok(State::default()),
+    };
+}
+
+fn save_state(path: &Path, state: &State) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(state).map_err(|why| why.to_string())?;
+    return path.parent().map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| std::fs::write(path, format!("{}\n", json)))
+        .map_err(|why| format!("Couldn't write {}: {}", path.display(), why));
+}
+
+// A pulled task as it reads back once written (see format::normalize), so
+// that it can be found again by its fingerprint. CATEGORIES that look like
+// attachments are dropped rather than read as files to publish.
+fn writable(task: Task) -> Task {
+    return format::normalize(&Task {
+        tags: task.tags.into_iter().filter(|tag| !tag.starts_with("file:")).collect(),
+        ..task
+    });
+}
+
+fn parse(source: &str, path: &Path, options: &ParseOptions) -> Result<Vec<Task>, String> {
+    return parse_tasks_with(source, options).map_err(|errors| {
+        return errors.iter().map(|error| format!("{}:{}", path.display(), error)).collect::<Vec<String>>().join("\n");
+    });
+}
+
+fn find(tasks: &[Task], fingerprint_: &str) -> Option<usize> {
+    return tasks.iter().position(|task| fingerprint(task) == fingerprint_);
+}
+
+// The single task an event stands for, if it is one wtd can hold.
+fn event_task(client: &Client, href: &str, options: &ParseOptions) -> Result<Option<(String, Task)>, String> {
+    let events = ics::read_calendar(&client.get(href)?, options.timezone).map_err(|why| format!("{}: {}", href, why))?;
+    return Ok(match &events[..] {
+        [(uid, tasks)] if tasks.len() == 1 => Some((uid.clone(), writable(tasks[0].clone()))),
+        _ => {
+            eprintln!("{} isn't a single event on a single day; leaving it out", href);
+            None
+        },
+    });
+}
+
+fn pull(client: &Client, path: &Path, source: &mut String, state: &mut State, options: &SyncOptions,
+        summary: &mut Summary) -> Result<(), String> {
+    let remote: BTreeMap<String, String> = client.list()?.into_iter().map(|r| (r.href, r.etag)).collect();
+    let tracked: Vec<(String, Synced)> = state.events.iter().map(|(uid, s)| (uid.clone(), s.clone())).collect();
+    let known: HashSet<String> = tracked.iter().map(|(_, s)| s.href.clone()).collect();
+    let untracked: Vec<(&String, &String)> = remote.iter().filter(|(href, _)| !known.contains(*href)).collect();
+    let mut progress = Progress::new("Pulling", tracked.len() + untracked.len(), options.quiet);
+    for (uid, synced) in tracked {
+        progress.advance();
+        let tasks = parse(source, path, &options.parse)?;
+        match remote.get(&synced.href) {
+            None => {
+                if let Some(index) = find(&tasks, &synced.fingerprint) {
+                    *source = splice_task(source, index, "", &options.parse)?;
+                    summary.deleted_here += 1;
+                }
+                state.events.remove(&uid);
+            },
+            Some(etag) if *etag != synced.etag => {
+                let pulled = event_task(client, &synced.href, &options.parse)?;
+                let entry = state.events.get_mut(&uid).expect("tracked above");
+                entry.etag = etag.clone();
+                match (pulled, find(&tasks, &synced.fingerprint)) {
+                    (Some((_, pulled)), Some(index)) => {
+                        let local = &tasks[index];
+                        let merged = Task {
+                            done: local.done,
+                            subtasks: local.subtasks.clone(),
+                            notes: local.notes.clone(),
+                            attachments: local.attachments.clone(),
+                            attendees: local.attendees,
+                            priority: local.priority,
+                            ..pulled
+                        };
+                        entry.fingerprint = fingerprint(&merged);
+                        if merged.date == local.date {
+                            *source = splice_task(source, index, &format::format_task(&merged), &options.parse)?;
+                        } else {
+                            *source = splice_task(source, index, "", &options.parse)?;
+                            *source = insert_task(source, &merged, &options.parse, options.week_start);
+                        }
+                        summary.pulled += 1;
+                    },
+                    (Some(_), None) => eprintln!("{} was changed both here and on the server; keeping the change here",
+                                                 synced.href),
+                    (None, _) => {},
+                }
+            },
+            Some(_) => {},
+        }
+    }
+
+    for (href, etag) in untracked {
+        progress.advance();
+        let (uid, task) = match event_task(client, href, &options.parse)? {
+            Some(event) => event,
+            None => continue,
+        };
+        let print = fingerprint(&task);
+        // A task already in the file (say, pushed by a run whose state was
+        // lost) is only adopted, not added again.
+        let tasks = parse(source, path, &options.parse)?;
+        if find(&tasks, &print).is_none() {
+            *source = insert_task(source, &task, &options.parse, options.week_start);
+            summary.pulled += 1;
+        }
+        state.events.insert(uid, Synced { href: href.clone(), etag: etag.clone(), fingerprint: print });
+    }
+    return Ok(());
+}
+
+fn push(client: &Client, path: &Path, source: &str, state: &mut State, options: &SyncOptions,
+        summary: &mut Summary) -> Result<(), String> {
+    let tasks = parse(source, path, &options.parse)?;
+    let local: HashSet<String> = tasks.iter().map(fingerprint).collect();
+    let gone: Vec<String> = state.events.iter()
+        .filter(|(_, synced)| !local.contains(&synced.fingerprint))
+        .map(|(uid, _)| uid.clone())
+        .collect();
+    let mut progress = Progress::new("Pushing", gone.len() + tasks.len(), options.quiet);
+    for uid in gone {
+        progress.advance();
+        let synced = &state.events[&uid];
+        client.delete(&synced.href, &synced.etag)?;
+        state.events.remove(&uid);
+        summary.deleted_there += 1;
+    }
+
+    let mut tracked: HashSet<String> = state.events.values().map(|s| s.fingerprint.clone()).collect();
+    for task in &tasks {
+        progress.advance();
+        let print = fingerprint(task);
+        if !tracked.insert(print.clone()) {
+            continue;
+        }
+        let uid = format!("{}@wtd", &print[..16]);
+        let href = client.href_for(&format!("{}.ics", &print[..16]));
+        let etag = client.put(&href, &ics::task_to_event(task, &uid), None)?;
+        state.events.insert(uid, Synced { href, etag: etag.unwrap_or_default(), fingerprint: print });
+        summary.pushed += 1;
+    }
+
+    // Servers needn't return an ETag from PUT; look up any that are missing.
+    if state.events.values().any(|synced| synced.etag.is_empty()) {
+        let remote: BTreeMap<String, String> = client.list()?.into_iter().map(|r| (r.href, r.etag)).collect();
+        for synced in state.events.values_mut().filter(|synced| synced.etag.is_empty()) {
+            synced.etag = remote.get(&synced.href).cloned().unwrap_or_default();
+        }
+    }
+    return Ok(());
+}
+
+// The file and the state are saved even when a run stops partway, so that
+// what was already done on the server isn't done again. The file is written
+// between pulling and pushing, before anything on the server has changed: if
+// it was edited meanwhile, the run stops there without saving the state, and
+// the next run pulls the same changes again.
+pub fn sync(path: &Path, options: &SyncOptions) -> Result<Summary, String> {
+    check_editable(path)?;
+    let _lock = lock::lock(path)?;
+    let client = Client::new(options.url, options.username, options.password);
+    let state_path = state_path(options);
+    let mut state = load_state(&state_path)?;
+    let original = read_file(path)?;
+    let mut source = original.clone();
+    parse(&source, path, &options.parse)?;
+
+    let mut summary = Summary::default();
+    let pulled = pull(&client, path, &mut source, &mut state, options, &mut summary);
+    lock::write_checked(path, &original, &source)?;
+    let result = pulled.and_then(|_| push(&client, path, &source, &mut state, options, &mut summary));
+    save_state(&state_path, &state)?;
+    return result.map(|_| summary);
+}
+
+impl Summary {
+    pub fn describe(&self) -> String {
+        return format!("{} pulled, {} deleted here; {} pushed, {} deleted on the server",
+                       self.pulled, self.deleted_here, self.pushed, self.deleted_there);
+    }
+}
@@ -0,0 +1,37 @@
+// Measuring and cutting text by how wide it displays rather than by bytes or
+// chars, so that emoji, combining accents, and CJK text line up.
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+pub fn display_width(s: &str) -> usize {
+    return s.width();
+}
+
+// Cuts `s` down to at most `max_width` columns, ending in an ellipsis when
+// anything was removed. Never splits a grapheme cluster.
+pub fn truncate_to_width(s: &str, max_width: usize) -> String {
+    if display_width(s) <= max_width {
+        return s.to_string();
+    }
+    let mut shortened = String::new();
+    let mut width = 0;
+    for grapheme in s.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if width + grapheme_width + 1 > max_width {
+            break;
+        }
+        shortened.push_str(grapheme);
+        width += grapheme_width;
+    }
+    shortened.push('\u{2026}');
+    return shortened;
+}
+
+// Left-aligns `s` in a column `width` columns wide.
+pub fn pad_to_width(s: &str, width: usize) -> String {
+    let mut padded = s.to_string();
+    for _ in display_width(s)..width {
+        padded.push(' ');
+    }
+    return padded;
+}
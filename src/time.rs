@@ -0,0 +1,328 @@
+// All construction of dates and times, and every format they're shown in,
+// goes through this module. Keeping chrono behind these few functions means
+// the non-panicking constructors are used everywhere, and a future move to
+// another time library (or a 24-hour output option) touches only this file.
+use std::collections::BTreeSet;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Timelike, Utc, Weekday};
+use chrono::format::{Item, StrftimeItems};
+use chrono_tz::Tz;
+use crate::TimeFormat;
+
+pub fn today() -> NaiveDate {
+    return now().date();
+}
+
+pub fn now() -> NaiveDateTime {
+    return Local::now().naive_local();
+}
+
+// The time now on clocks in `tz`.
+pub fn now_in(tz: Tz) -> NaiveDateTime {
+    return Utc::now().with_timezone(&tz).naive_local();
+}
+
+pub fn ymd(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    return NaiveDate::from_ymd_opt(year, month, day);
+}
+
+pub fn hms(hour: u32, minute: u32, second: u32) -> Option<NaiveTime> {
+    return NaiveTime::from_hms_opt(hour, minute, second);
+}
+
+pub fn midnight() -> NaiveTime {
+    return NaiveTime::from_hms_opt(0, 0, 0).expect("midnight is a valid time");
+}
+
+// The last representable second of the day; NaiveTime has no 24:00.
+pub fn end_of_day() -> NaiveTime {
+    return NaiveTime::from_hms_opt(23, 59, 59).expect("23:59:59 is a valid time");
+}
+
+// The time `minutes` after midnight, wrapping around past the end of the day.
+pub fn time_of_day(minutes: i64) -> NaiveTime {
+    return midnight() + Duration::minutes(minutes);
+}
+
+// The clocks a task file's times are read on: those in `tz` if one is
+// configured, else UTC's, which never change, so that without one a file
+// reads the same on every machine.
+pub fn clocks(tz: Option<Tz>) -> Tz {
+    return tz.unwrap_or(Tz::UTC);
+}
+
+// The time `duration` after `start` on `date`, as clocks in `tz` (see
+// clocks) will read then, or None if that's past midnight. Durations are
+// elapsed time, so a 2h block starting at 1AM on the night clocks spring
+// forward ends at 4AM.
+pub fn add_wall_clock(tz: Option<Tz>, date: NaiveDate, start: NaiveTime, duration: Duration) -> Option<NaiveTime> {
+    return add_wall_clock_in(&clocks(tz), date, start, duration);
+}
+
+// As add_wall_clock, on clocks in `tz`. Ending exactly at midnight ends at
+// end_of_day().
+pub fn add_wall_clock_in<Z: TimeZone>(tz: &Z, date: NaiveDate, start: NaiveTime, duration: Duration) -> Option<NaiveTime> {
+    let end = add_elapsed_in(tz, date.and_time(start), duration);
+    if end.date() == date {
+        return Some(end.time());
+    }
+    if end.time() == midnight() && end.date().pred_opt() == Some(date) {
+        return Some(end_of_day());
+    }
+    return None;
+}
+
+// The wall-clock time `duration` of elapsed time after `start`, on clocks in
+// `tz` (see clocks).
+pub fn add_elapsed(tz: Option<Tz>, start: NaiveDateTime, duration: Duration) -> NaiveDateTime {
+    return add_elapsed_in(&clocks(tz), start, duration);
+}
+
+// As add_elapsed, on clocks in `tz`.
+pub fn add_elapsed_in<Z: TimeZone>(tz: &Z, start: NaiveDateTime, duration: Duration) -> NaiveDateTime {
+    return (instant_in(tz, start) + duration).naive_local();
+}
+
+// The moment clocks in `tz` read `naive`. A time skipped over by a DST change
+// is read with the offset from before it, as if the clocks hadn't changed
+// yet; one that happens twice is the first.
+fn instant_in<Z: TimeZone>(tz: &Z, naive: NaiveDateTime) -> DateTime<Z> {
+    return match tz.from_local_datetime(&naive).earliest() {
+        Some(instant) => instant,
+        None => tz.from_utc_datetime(&(naive - tz.offset_from_utc_datetime(&(naive - Duration::days(1))).fix())),
+    };
+}
+
+// The last day on or before `date` that falls on `first_day`, i.e. the start
+// of the week containing `date`.
+pub fn week_start(date: NaiveDate, first_day: Weekday) -> NaiveDate {
+    let offset = (7 + date.weekday().num_days_from_monday() - first_day.num_days_from_monday()) % 7;
+    return date - Duration::days(offset as i64);
+}
+
+// Countries whose weeks start on Sunday or Saturday, per the Unicode CLDR;
+// everywhere else starts on Monday.
+const SUNDAY_FIRST: [&str; 54] = [
+    "AG", "AS", "BD", "BR", "BS", "BT", "BW", "BZ", "CA", "CN", "CO", "DM", "DO", "ET", "GT", "GU",
+    "HK", "HN", "ID", "IL", "IN", "JM", "JP", "KE", "KH", "KR", "LA", "MH", "MM", "MO", "MT", "MX",
+    "MZ", "NI", "NP", "PA", "PE", "PH", "PK", "PR", "PT", "PY", "SA", "SG", "SV", "TH", "TT", "TW",
+    "UM", "US", "VE", "VI", "WS", "ZA",
+];
+const SATURDAY_FIRST: [&str; 15] = [
+    "AE", "AF", "BH", "DJ", "DZ", "EG", "IQ", "IR", "JO", "KW", "LY", "OM", "QA", "SD", "SY",
+];
+
+// The first day of the week for the user's locale, going by the country in
+// LC_ALL, LC_TIME or LANG (e.g. "en_US.UTF-8").
+pub fn locale_week_start() -> Weekday {
+    let locale = ["LC_ALL", "LC_TIME", "LANG"].iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let country = locale.split(['.', '@']).next().unwrap_or("")
+        .split(['_', '-']).nth(1).unwrap_or("").to_uppercase();
+    if SUNDAY_FIRST.contains(&country.as_str()) {
+        return Weekday::Sun;
+    } else if SATURDAY_FIRST.contains(&country.as_str()) {
+        return Weekday::Sat;
+    }
+    return Weekday::Mon;
+}
+
+pub fn next_day(date: NaiveDate) -> NaiveDate {
+    return date + Duration::days(1);
+}
+
+// "Mon 3/4/24"
+pub fn format_date(date: NaiveDate) -> String {
+    return date.format("%a %-m/%-d/%y").to_string();
+}
+
+// "Monday"
+pub fn format_weekday(date: NaiveDate) -> String {
+    return date.format("%A").to_string();
+}
+
+// "Mar"
+pub fn format_month(date: NaiveDate) -> String {
+    return date.format("%b").to_string();
+}
+
+// "3/4/24"
+pub fn format_short_date(date: NaiveDate) -> String {
+    return date.format("%-m/%-d/%y").to_string();
+}
+
+// "Mon 3/4"
+pub fn format_short_day(date: NaiveDate) -> String {
+    return date.format("%a %-m/%-d").to_string();
+}
+
+// "3/4"
+pub fn format_month_day(date: NaiveDate) -> String {
+    return date.format("%-m/%-d").to_string();
+}
+
+// "2024-03-04", for machine-readable output.
+pub fn format_iso_date(date: NaiveDate) -> String {
+    return date.format("%Y-%m-%d").to_string();
+}
+
+// "2024-03-04", as format_iso_date writes it.
+pub fn parse_iso_date(s: &str) -> Option<NaiveDate> {
+    return NaiveDate::parse_from_str(s, "%Y-%m-%d").ok();
+}
+
+// "03/04/24", as a week header is written.
+pub fn format_header_date(date: NaiveDate) -> String {
+    return date.format("%m/%d/%y").to_string();
+}
+
+// A date in a strftime-style `format`, such as those a file's dates can be
+// written in (see DATE_FORMATS).
+pub fn parse_date_as(s: &str, format: &str) -> Option<NaiveDate> {
+    return NaiveDate::parse_from_str(s, format).ok();
+}
+
+// A time of day in a strftime-style `format`.
+pub fn parse_time_as(s: &str, format: &str) -> Option<NaiveTime> {
+    return NaiveTime::parse_from_str(s, format).ok();
+}
+
+// Whether `format` is a strftime-style format chrono understands.
+pub fn is_valid_format(format: &str) -> bool {
+    return !StrftimeItems::new(format).any(|item| item == Item::Error);
+}
+
+// Of `dates`, `today` if it's among them, else the latest before it, else
+// the soonest after.
+pub fn nearest_day(dates: &BTreeSet<NaiveDate>, today: NaiveDate) -> Option<NaiveDate> {
+    return dates.range(..=today).next_back().or_else(|| dates.range(today..).next()).copied();
+}
+
+// The current time as an iCalendar UTC DATE-TIME, e.g. "20240304T143000Z".
+pub fn ics_utc_now() -> String {
+    return Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+}
+
+// "20240304", an iCalendar DATE.
+pub fn format_ics_date(date: NaiveDate) -> String {
+    return date.format("%Y%m%d").to_string();
+}
+
+// "20240304T093000", an iCalendar floating (local) DATE-TIME.
+pub fn format_ics_datetime(date: NaiveDate, time: NaiveTime) -> String {
+    return date.and_time(time).format("%Y%m%dT%H%M%S").to_string();
+}
+
+// An iCalendar DATE, e.g. "20240304".
+pub fn parse_ics_date(s: &str) -> Option<NaiveDate> {
+    return NaiveDate::parse_from_str(s, "%Y%m%d").ok();
+}
+
+// An iCalendar DATE-TIME as a wall-clock time on clocks in `tz` (see
+// clocks). UTC times ("...Z") and those with a TZID naming an IANA timezone
+// are converted to it; floating times, and those with a TZID defined only by
+// the file's own VTIMEZONE (such as Windows' "Eastern Standard Time"), are
+// taken as already on it.
+pub fn parse_ics_datetime(tz: Option<Tz>, s: &str, tzid: Option<&str>) -> Option<NaiveDateTime> {
+    return parse_ics_datetime_in(&clocks(tz), s, tzid);
+}
+
+// As parse_ics_datetime, converting to clocks in `local`.
+pub fn parse_ics_datetime_in<Z: TimeZone>(local: &Z, s: &str, tzid: Option<&str>) -> Option<NaiveDateTime> {
+    if let Some(utc) = s.strip_suffix('Z') {
+        let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Utc.from_utc_datetime(&utc).with_timezone(local).naive_local());
+    }
+    let naive = NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S").ok()?;
+    return match tzid.and_then(|tzid| tzid.parse::<Tz>().ok()) {
+        Some(tz) => Some(instant_in(&tz, naive).with_timezone(local).naive_local()),
+        None => Some(naive),
+    };
+}
+
+// "2024-03-04T09:30:00-05:00", a wall-clock time on clocks in `tz` with
+// their UTC offset on that day, as the Google Calendar API wants it.
+pub fn format_rfc3339(tz: Tz, date: NaiveDate, time: NaiveTime) -> String {
+    return instant_in(&tz, date.and_time(time)).to_rfc3339();
+}
+
+// An IANA timezone name, e.g. "Asia/Tokyo".
+pub fn parse_timezone(s: &str) -> Result<Tz, String> {
+    return s.parse::<Tz>().map_err(|_| format!("'{}' isn't a timezone name like Asia/Tokyo", s));
+}
+
+// "JST", the abbreviation for the zone in effect at `datetime`.
+pub fn format_zone(datetime: &DateTime<Tz>) -> String {
+    return datetime.format("%Z").to_string();
+}
+
+// The wall-clock time `date` `time` on clocks in `from` as it reads on clocks
+// in `to`.
+pub fn in_timezone(from: Tz, date: NaiveDate, time: NaiveTime, to: Tz) -> DateTime<Tz> {
+    return instant_in(&from, date.and_time(time)).with_timezone(&to);
+}
+
+// " 9:30AM"
+pub fn format_time(time: NaiveTime) -> String {
+    return time.format("%l:%M%p").to_string();
+}
+
+// " 9:30AM", or "09:30" on the 24-hour clock.
+pub fn format_time_as(time: NaiveTime, format: TimeFormat) -> String {
+    return match format {
+        TimeFormat::TwelveHour => format_time(time),
+        TimeFormat::TwentyFourHour => format_hhmm(time),
+    };
+}
+
+// " 9:30 AM", as used for the grid's row labels, or "09:30".
+pub fn format_row_time(time: NaiveTime, format: TimeFormat) -> String {
+    return match format {
+        TimeFormat::TwelveHour => time.format("%l:%M %p").to_string(),
+        TimeFormat::TwentyFourHour => format_hhmm(time),
+    };
+}
+
+// "9AM", or "09:00" on the 24-hour clock, as used for the row labels of a
+// grid showing part of the day.
+pub fn format_hour(time: NaiveTime, format: TimeFormat) -> String {
+    return match format {
+        TimeFormat::TwelveHour => time.format("%-I%p").to_string(),
+        TimeFormat::TwentyFourHour => format_hhmm(time),
+    };
+}
+
+// "9AM" or "9:30AM", for text written to a person.
+pub fn format_compact_time(time: NaiveTime) -> String {
+    return time.format(if time.minute() == 0 { "%-I%p" } else { "%-I:%M%p" }).to_string();
+}
+
+// "2024-03-04T09:30", for machine-readable output.
+pub fn format_iso_datetime(datetime: NaiveDateTime) -> String {
+    return datetime.format("%Y-%m-%dT%H:%M").to_string();
+}
+
+// "09:30", for machine-readable output.
+pub fn format_hhmm(time: NaiveTime) -> String {
+    return time.format("%H:%M").to_string();
+}
+
+// As format_hhmm, but "24:00" for a task running to midnight, so that it
+// reads back as one (see structured.rs).
+pub fn format_end_hhmm(time: NaiveTime) -> String {
+    if time == end_of_day() {
+        return "24:00".to_string();
+    }
+    return format_hhmm(time);
+}
+
+// "09:30:05", for log lines.
+pub fn format_hhmmss(time: NaiveTime) -> String {
+    return time.format("%H:%M:%S").to_string();
+}
+
+// "09:30", as format_hhmm writes it.
+pub fn parse_hhmm(s: &str) -> Option<NaiveTime> {
+    return NaiveTime::parse_from_str(s, "%H:%M").ok();
+}
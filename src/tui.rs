@@ -0,0 +1,287 @@
+// `wtd tui`: the week in the terminal, for when wtd.md lives on a machine
+// you're ssh'd into. Each column is a day of the week listing its tasks in
+// order, and the side pane shows everything about the selected task; this is
+// your own view, so nothing is redacted. The file is read again whenever it
+// changes, so it can stay open next to an editor.
+//
+// Keys: Left/Right (h/l) move between days, Up/Down (k/j) between the day's
+// tasks, PageUp/PageDown ([/]) between weeks, t goes back to today, and q or
+// Esc quits.
+use std::io::{stdout, Stdout, Write};
+use std::path::Path;
+use std::time::{Duration as StdDuration, SystemTime};
+use chrono::{Duration, NaiveDate, Weekday};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::{cursor, execute, queue, terminal};
+use crate::text::{display_width, pad_to_width, truncate_to_width};
+use crate::{checklist_summary, cmp_tasks, is_out_of_office, load, load_notes, time, OutOfOffice, ParseOptions, Task};
+
+pub struct TuiOptions {
+    pub parse: ParseOptions,
+    pub week_start: Weekday,
+}
+
+struct View {
+    // Sorted with cmp_tasks, so each day's tasks are in order.
+    tasks: Vec<Task>,
+    out_of_office: Vec<OutOfOffice>,
+    // Why the file couldn't be read last time; the tasks from before are
+    // still shown.
+    error: Option<String>,
+    selected: NaiveDate,
+    // The selected task, counted within the selected day.
+    index: usize,
+}
+
+impl View {
+    fn reload(&mut self, path: &Path, options: &TuiOptions) {
+        match load(path, &options.parse) {
+            Ok(mut tasks) => {
+                tasks.sort_by(cmp_tasks);
+                self.tasks = tasks;
+                self.out_of_office = load_notes(path, &options.parse).out_of_office;
+                self.error = None;
+            },
+            Err(why) => self.error = Some(why.lines().next().unwrap_or("").to_string()),
+        }
+        self.index = self.index.min(self.day(self.selected).len().saturating_sub(1));
+    }
+
+    fn day(&self, date: NaiveDate) -> Vec<&Task> {
+        return self.tasks.iter().filter(|task| task.date == date).collect();
+    }
+
+    fn select_date(&mut self, date: NaiveDate) {
+        self.selected = date;
+        self.index = 0;
+    }
+}
+
+// Puts the terminal back however the viewer exits, panics included.
+struct Screen(Stdout);
+
+impl Screen {
+    fn enter() -> Result<Screen, String> {
+        terminal::enable_raw_mode().map_err(|why| format!("Couldn't set up the terminal: {}", why))?;
+        let mut out = stdout();
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)
+            .map_err(|why| format!("Couldn't set up the terminal: {}", why))?;
+        return Ok(Screen(out));
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(self.0, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    return std::fs::metadata(path).and_then(|m| m.modified()).ok();
+}
+
+fn format_span(task: &Task) -> String {
+    if let (Some(start), Some(span)) = (task.start_time, task.span) {
+        return format!("{}--{} {}", time::format_time(start).trim(), time::format_short_date(span.last), time::format_time(span.end).trim());
+    }
+    return match [task.start_time, task.end_time] {
+        [Some(start), Some(end)] => format!("{}--{}", time::format_time(start).trim(), time::format_time(end).trim()),
+        _ if task.all_day => "All day".to_string(),
+        _ => "No set time".to_string(),
+    };
+}
+
+// Breaks `s` into lines at most `width` columns wide, at spaces where it can.
+fn wrap(s: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in s.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && display_width(&line) + 1 + display_width(word) > width {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    return lines.into_iter().map(|line| truncate_to_width(&line, width)).collect();
+}
+
+// What the side pane says about a task.
+fn describe(task: &Task, width: usize) -> Vec<String> {
+    let mut lines = wrap(&task.details, width);
+    lines.push(String::new());
+    lines.push(format!("{}, {}", time::format_weekday(task.date), time::format_short_date(task.date)));
+    lines.push(format_span(task));
+    if !task.tags.is_empty() {
+        lines.extend(wrap(&task.tags.iter().map(|tag| format!("+{}", tag)).collect::<Vec<String>>().join(" "), width));
+    }
+    if let Some(level) = task.priority {
+        lines.push(format!("Priority {}", level));
+    }
+    if let Some(place) = &task.location {
+        lines.push(format!("At {}", place));
+    }
+    if let Some(source) = &task.source {
+        lines.push(format!("From {}", source));
+    }
+    if let Some(attendees) = task.attendees {
+        lines.push(format!("{} attendees", attendees));
+    }
+    if task.done {
+        lines.push("Done".to_string());
+    }
+    for paragraph in &task.notes {
+        lines.push(String::new());
+        lines.extend(wrap(paragraph, width));
+    }
+    if !task.subtasks.is_empty() {
+        lines.push(String::new());
+        lines.push(format!("Checklist ({})", checklist_summary(task)));
+        for subtask in &task.subtasks {
+            let indent = "  ".repeat(subtask.level);
+            lines.extend(wrap(&format!("{}[{}] {}", indent, if subtask.done { "x" } else { " " }, subtask.text), width));
+        }
+    }
+    for attachment in &task.attachments {
+        lines.extend(wrap(&format!("File: {}", attachment), width));
+    }
+    return lines;
+}
+
+fn draw(out: &mut Stdout, view: &View, options: &TuiOptions) -> std::io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let (width, height) = (width as usize, height as usize);
+    queue!(out, terminal::Clear(terminal::ClearType::All))?;
+    if width < 40 || height < 8 {
+        queue!(out, cursor::MoveTo(0, 0), Print("Terminal too small"))?;
+        return out.flush();
+    }
+    let side_width = (width / 4).max(24);
+    let column_width = (width - side_width - 1 - 6) / 7;
+    let grid_width = column_width * 7 + 6;
+    let week = time::week_start(view.selected, options.week_start);
+    let today = time::today();
+
+    let title = match &view.error {
+        Some(why) => format!("Week of {}  (showing the last good read: {})", time::format_date(week), why),
+        None => format!("Week of {}", time::format_date(week)),
+    };
+    queue!(out, cursor::MoveTo(0, 0), Print(truncate_to_width(&title, width)))?;
+
+    for offset in 0..7 {
+        let date = week + Duration::days(offset);
+        let x = (offset as usize * (column_width + 1)) as u16;
+        let mut header = time::format_short_day(date);
+        if date == today {
+            header.push('*');
+        }
+        let header = pad_to_width(&truncate_to_width(&header, column_width), column_width);
+        if date == view.selected {
+            queue!(out, cursor::MoveTo(x, 1), SetAttribute(Attribute::Reverse), Print(header), SetAttribute(Attribute::Reset))?;
+        } else {
+            queue!(out, cursor::MoveTo(x, 1), SetAttribute(Attribute::Bold), Print(header), SetAttribute(Attribute::Reset))?;
+        }
+
+        let mut top = 3;
+        if is_out_of_office(&view.out_of_office, date) {
+            queue!(out, cursor::MoveTo(x, top as u16), SetAttribute(Attribute::Dim),
+                   Print(truncate_to_width("Out of office", column_width)), SetAttribute(Attribute::Reset))?;
+            top += 1;
+        }
+        let rows = height - 1 - top;
+        let tasks = view.day(date);
+        // Scroll the selected day so that its selected task is in view.
+        let first = if date == view.selected { (view.index + 1).saturating_sub(rows) } else { 0 };
+        for (row, (i, task)) in tasks.iter().enumerate().skip(first).take(rows).enumerate() {
+            let start = match task.start_time {
+                Some(start) => time::format_hhmm(start),
+                None if task.all_day => "all ".to_string(),
+                None => "     ".to_string(),
+            };
+            let line = pad_to_width(&truncate_to_width(&format!("{} {}", start, task.details.lines().next().unwrap_or("")), column_width), column_width);
+            queue!(out, cursor::MoveTo(x, (row + top) as u16))?;
+            if date == view.selected && i == view.index {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            if task.done {
+                queue!(out, SetAttribute(Attribute::CrossedOut))?;
+            }
+            queue!(out, Print(line), SetAttribute(Attribute::Reset))?;
+        }
+    }
+    queue!(out, cursor::MoveTo(0, 2), Print("─".repeat(grid_width)))?;
+    for y in 0..height - 1 {
+        queue!(out, cursor::MoveTo(grid_width as u16, y as u16), Print("│"))?;
+    }
+
+    let side_x = (grid_width + 2) as u16;
+    let pane_width = width.saturating_sub(grid_width + 2);
+    let details = match view.day(view.selected).get(view.index) {
+        Some(task) => describe(task, pane_width),
+        None => vec!["Nothing scheduled".to_string()],
+    };
+    for (y, line) in details.iter().take(height - 1).enumerate() {
+        queue!(out, cursor::MoveTo(side_x, y as u16), Print(line))?;
+    }
+
+    let help = "←/→ day  ↑/↓ task  PgUp/PgDn week  t today  q quit";
+    queue!(out, cursor::MoveTo(0, (height - 1) as u16), SetAttribute(Attribute::Dim),
+           Print(truncate_to_width(help, width)), SetAttribute(Attribute::Reset))?;
+    return out.flush();
+}
+
+pub fn run(path: &Path, options: &TuiOptions) -> Result<(), String> {
+    if path == Path::new("-") {
+        return Err("the viewer reads keys from the terminal, so it needs a task file rather than standard input".to_string());
+    }
+    let mut view = View {
+        tasks: Vec::new(),
+        out_of_office: Vec::new(),
+        error: None,
+        selected: time::today(),
+        index: 0,
+    };
+    view.reload(path, options);
+    if let Some(why) = view.error.take() {
+        return Err(why);
+    }
+    let mut last = modified(path);
+
+    let mut screen = Screen::enter()?;
+    let failed = |why: std::io::Error| format!("Terminal error: {}", why);
+    loop {
+        draw(&mut screen.0, &view, options).map_err(failed)?;
+        // Wake up now and then to notice the file changing.
+        if !event::poll(StdDuration::from_millis(500)).map_err(failed)? {
+            let current = modified(path);
+            if current != last {
+                last = current;
+                view.reload(path, options);
+            }
+            continue;
+        }
+        let key = match event::read().map_err(failed)? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        let count = view.day(view.selected).len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Left | KeyCode::Char('h') => view.select_date(view.selected - Duration::days(1)),
+            KeyCode::Right | KeyCode::Char('l') => view.select_date(view.selected + Duration::days(1)),
+            KeyCode::PageUp | KeyCode::Char('[') => view.select_date(view.selected - Duration::weeks(1)),
+            KeyCode::PageDown | KeyCode::Char(']') => view.select_date(view.selected + Duration::weeks(1)),
+            KeyCode::Char('t') => view.select_date(time::today()),
+            KeyCode::Up | KeyCode::Char('k') => view.index = view.index.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => view.index = (view.index + 1).min(count.saturating_sub(1)),
+            _ => {},
+        }
+    }
+}
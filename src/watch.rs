@@ -0,0 +1,128 @@
+// Waiting for the files a render reads to change, for `render --watch` and
+// `serve`'s live reload: the task file and those it '#include's (see
+// `sources`), the config, and any template.
+//
+// The filesystem's own notifications (inotify, FSEvents, ReadDirectoryChangesW)
+// say when to look. The directories the files are in are watched rather than
+// the files themselves, so that a file an editor replaces on saving is still
+// followed. Modification times are compared as well, every few seconds or,
+// where there are no notifications (as on some network filesystems), twice a
+// second.
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+const POLL: Duration = Duration::from_millis(500);
+const POLL_NOTIFIED: Duration = Duration::from_secs(5);
+// A save is often several events; they're let settle before looking again.
+const SETTLE: Duration = Duration::from_millis(50);
+
+// The path events name the file by: its directory's canonical path, which is
+// what's watched, joined with its name.
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    return match (dir.canonicalize(), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    };
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    return std::fs::metadata(path).and_then(|m| m.modified()).ok();
+}
+
+// The directories of `files`, which are (canonical, as given) pairs, watched
+// from now on instead of those in `watched`. A notifier that can't watch one
+// is given up on, and the times polled instead.
+fn follow(notifier: &mut Option<RecommendedWatcher>, watched: &mut BTreeSet<PathBuf>, files: &[(PathBuf, PathBuf)]) {
+    let dirs: BTreeSet<PathBuf> = files.iter().filter_map(|(canonical, _)| canonical.parent().map(Path::to_path_buf)).collect();
+    let watcher = match notifier {
+        Some(watcher) => watcher,
+        None => return,
+    };
+    for dir in watched.difference(&dirs) {
+        let _ = watcher.unwatch(dir);
+    }
+    for dir in dirs.difference(watched) {
+        if let Err(why) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+            eprintln!("Checking for changes twice a second instead, as {} can't be watched: {}", dir.display(), why);
+            *notifier = None;
+            return;
+        }
+    }
+    *watched = dirs;
+}
+
+// Whether the event is of a change, rather than, say, wtd reading the file.
+fn changes(event: &notify::Event) -> bool {
+    return event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove();
+}
+
+// Calls `changed` with those of `files()` that changed, each time any do.
+// `files` is asked again after each change, as the task file may have come to
+// include others, or stopped. Never returns.
+pub fn watch(files: impl Fn() -> Vec<PathBuf>, mut changed: impl FnMut(&[PathBuf])) {
+    let (sender, events) = mpsc::channel();
+    let mut notifier = notify::recommended_watcher(sender).ok();
+    let mut watched = BTreeSet::new();
+    let mut read = |notifier: &mut Option<RecommendedWatcher>| {
+        let files: Vec<(PathBuf, PathBuf)> = files().into_iter().map(|file| (canonical(&file), file)).collect();
+        follow(notifier, &mut watched, &files);
+        let times: Vec<Option<SystemTime>> = files.iter().map(|(canonical, _)| modified(canonical)).collect();
+        return (files, times);
+    };
+    let (mut watching, mut times) = read(&mut notifier);
+    loop {
+        let mut touched: BTreeSet<PathBuf> = BTreeSet::new();
+        match notifier {
+            Some(_) => match events.recv_timeout(POLL_NOTIFIED) {
+                Ok(Ok(event)) if changes(&event) => touched.extend(event.paths),
+                Ok(_) | Err(mpsc::RecvTimeoutError::Timeout) => (),
+                Err(mpsc::RecvTimeoutError::Disconnected) => notifier = None,
+            },
+            None => thread::sleep(POLL),
+        }
+        if !touched.is_empty() {
+            thread::sleep(SETTLE);
+            for event in events.try_iter().flatten().filter(changes) {
+                touched.extend(event.paths);
+            }
+        }
+        let now: Vec<Option<SystemTime>> = watching.iter().map(|(canonical, _)| modified(canonical)).collect();
+        let different: Vec<PathBuf> = watching.iter().zip(times.iter().zip(&now))
+            .filter(|((canonical, _), (before, after))| touched.contains(canonical) || before != after)
+            .map(|((_, file), _)| file.clone())
+            .collect();
+        if different.is_empty() {
+            continue;
+        }
+        // Read before `changed` is, so that changes while it runs are caught
+        // next time round.
+        (watching, times) = read(&mut notifier);
+        changed(&different);
+    }
+}
+
+// Starts wtd over with the same arguments, for a change to the config, which
+// is only read on starting. Where a process can't replace itself with
+// another, the change is only reported.
+#[cfg(unix)]
+pub fn restart(why: &str) {
+    use std::os::unix::process::CommandExt;
+    let mut args = std::env::args_os();
+    let program = args.next().unwrap_or_else(|| "wtd".into());
+    eprintln!("{}; starting over", why);
+    let error = std::process::Command::new(program).args(args).exec();
+    eprintln!("Couldn't start over: {}", error);
+}
+
+#[cfg(not(unix))]
+pub fn restart(why: &str) {
+    eprintln!("{}; restart wtd to apply it", why);
+}
@@ -0,0 +1,37 @@
+// Embeds a compact list of upcoming events from wtd:
+//
+//   <script src="https://example.com/widget.js" data-src="https://example.com/widget.json"></script>
+//
+// data-src defaults to widget.json next to the script, which is what `wtd serve` provides.
+(function () {
+  var script = document.currentScript;
+  var src = script.getAttribute("data-src") || new URL("widget.json", script.src).href;
+  var container = document.createElement("div");
+  container.className = "wtd-widget";
+  script.parentNode.insertBefore(container, script.nextSibling);
+
+  function time(t) {
+    if (!t) { return ""; }
+    var parts = t.split(":");
+    var h = parseInt(parts[0], 10);
+    return ((h + 11) % 12 + 1) + ":" + parts[1] + (h < 12 ? "AM" : "PM");
+  }
+
+  fetch(src).then(function (r) { return r.json(); }).then(function (data) {
+    var list = document.createElement("ul");
+    data.events.forEach(function (e) {
+      var item = document.createElement("li");
+      var when = new Date(e.date + "T00:00:00").toLocaleDateString(undefined, { weekday: "short", month: "numeric", day: "numeric" });
+      if (e.start) { when += " " + time(e.start) + "--" + time(e.end); }
+      item.textContent = when + " " + e.title;
+      list.appendChild(item);
+    });
+    if (data.events.length === 0) {
+      container.textContent = "Nothing scheduled.";
+    } else {
+      container.appendChild(list);
+    }
+  }).catch(function () {
+    container.textContent = "Calendar unavailable.";
+  });
+})();
@@ -0,0 +1,34 @@
+// Data for the embeddable "next few events" widget (see widget.js).
+use std::collections::HashMap;
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use crate::{cmp_tasks, last_date, public_label, time, Task};
+
+pub const SCRIPT: &str = include_str!("widget.js");
+
+// The next `count` events that the public calendar would list, starting from
+// `now`, labelled the way the public calendar labels them.
+pub fn upcoming(tasks: &[Task], now: NaiveDateTime, count: usize, public_tags: &HashMap<String, String>) -> Value {
+    let mut upcoming: Vec<&Task> = tasks.iter()
+        .filter(|t| t.start_time.is_some() || t.all_day || t.tags.iter().any(|tag| tag == "public"))
+        .filter(|t| match t.span.map(|span| span.end).or(t.end_time) {
+            Some(end) => last_date(t).and_time(end) > now,
+            None => t.date >= now.date(),
+        })
+        .collect();
+    upcoming.sort_by(|a, b| cmp_tasks(a, b));
+
+    let mut events = Vec::new();
+    for task in upcoming.into_iter().take(count) {
+        let label = public_label(task, public_tags);
+        events.push(json!({
+            "date": time::format_iso_date(task.date),
+            "start": task.start_time.map(time::format_hhmm),
+            "end": task.span.map(|span| span.end).or(task.end_time).map(time::format_hhmm),
+            "endDate": time::format_iso_date(last_date(task)),
+            "allDay": task.all_day,
+            "title": if label.is_empty() { "busy".to_string() } else { label },
+        }));
+    }
+    return json!({ "events": events });
+}
@@ -0,0 +1,118 @@
+// Just enough of RFC 6455 to push text messages to browsers: the opening
+// handshake, unfragmented text frames out, and close/ping handling in.
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::sync::Mutex;
+use std::time::Duration;
+use base64::Engine;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// The largest frame read from a client. Only control frames are expected,
+// and their payloads are at most 125 bytes.
+const MAX_FRAME: u64 = 4 * 1024;
+
+// "Message Too Big", the close code for frames over MAX_FRAME.
+const TOO_BIG: u16 = 1009;
+
+// How long a client may hold up a broadcast before it's dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn handshake(stream: &mut TcpStream, key: &str) -> std::io::Result<()> {
+    let digest = sha1_smol::Sha1::from(format!("{}{}", key.trim(), GUID)).digest().bytes();
+    let accept = base64::engine::general_purpose::STANDARD.encode(digest);
+    let response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept);
+    return stream.write_all(response.as_bytes());
+}
+
+fn write_frame(stream: &mut TcpStream, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        },
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        },
+    }
+    frame.extend_from_slice(payload);
+    return stream.write_all(&frame);
+}
+
+pub fn send_text(stream: &mut TcpStream, text: &str) -> std::io::Result<()> {
+    return write_frame(stream, 0x1, text.as_bytes());
+}
+
+// Reads client frames until the connection closes, answering pings. Clients
+// aren't expected to send anything else, so data frames are discarded.
+pub fn drain(stream: &mut TcpStream) {
+    loop {
+        let mut header = [0u8; 2];
+        if stream.read_exact(&mut header).is_err() {
+            return;
+        }
+        let opcode = header[0] & 0x0f;
+        let masked = header[1] & 0x80 != 0;
+        let mut len = (header[1] & 0x7f) as u64;
+        if len == 126 {
+            let mut ext = [0u8; 2];
+            if stream.read_exact(&mut ext).is_err() { return; }
+            len = u16::from_be_bytes(ext) as u64;
+        } else if len == 127 {
+            let mut ext = [0u8; 8];
+            if stream.read_exact(&mut ext).is_err() { return; }
+            len = u64::from_be_bytes(ext);
+        }
+        if len > MAX_FRAME {
+            let _ = write_frame(stream, 0x8, &TOO_BIG.to_be_bytes());
+            return;
+        }
+        let mut mask = [0u8; 4];
+        if masked && stream.read_exact(&mut mask).is_err() {
+            return;
+        }
+        let mut payload = Vec::new();
+        if Read::by_ref(stream).take(len).read_to_end(&mut payload).is_err() {
+            return;
+        }
+        if masked {
+            for (i, b) in payload.iter_mut().enumerate() {
+                *b ^= mask[i % 4];
+            }
+        }
+        match opcode {
+            0x8 => {
+                let _ = write_frame(stream, 0x8, &payload);
+                return;
+            },
+            0x9 if write_frame(stream, 0xA, &payload).is_err() => return,
+            _ => (),
+        }
+    }
+}
+
+// The set of connected clients that updates are broadcast to.
+#[derive(Default)]
+pub struct Hub {
+    clients: Mutex<Vec<TcpStream>>,
+}
+
+impl Hub {
+    pub fn add(&self, stream: TcpStream) {
+        if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok() {
+            self.clients.lock().unwrap().push(stream);
+        }
+    }
+
+    // Sends `text` to every client, forgetting the ones that have gone away or
+    // stopped reading. The clients are written to outside the lock, so that
+    // new ones can still be added while a slow one is waited on.
+    pub fn broadcast(&self, text: &str) {
+        let mut clients = std::mem::take(&mut *self.clients.lock().unwrap());
+        clients.retain_mut(|client| send_text(client, text).is_ok());
+        self.clients.lock().unwrap().append(&mut clients);
+    }
+}
@@ -0,0 +1,84 @@
+// "Year at a glance" poster: one thin column per day of the year, with each
+// timed task drawn as a block at its time of day so busy stretches show up as
+// dense columns. Publicly described tasks are marked below the columns.
+use std::collections::HashMap;
+use chrono::{Datelike, Timelike};
+use crate::{escape_html, time, Task};
+
+const DAY_WIDTH: i64 = 3;
+const HOUR_HEIGHT: i64 = 8;
+const LEFT: i64 = 40;
+const TOP: i64 = 20;
+const MARKER_ROW: i64 = 16;
+
+fn tag_color(task: &Task) -> &'static str {
+    for (tag, color) in [("busy", "#d22"), ("join-me", "#2a2"), ("self", "#22aa9d"), ("tentative", "#999")] {
+        if task.tags.iter().any(|t| t == tag) {
+            return color;
+        }
+    }
+    return "#666";
+}
+
+fn minutes(time: chrono::NaiveTime) -> i64 {
+    return (time.hour() * 60 + time.minute()) as i64;
+}
+
+pub fn year_to_svg(tasks: &[Task], year: i32, public_tags: &HashMap<String, String>) -> Result<String, String> {
+    let (first_day, next_year) = match (time::ymd(year, 1, 1), time::ymd(year + 1, 1, 1)) {
+        (Some(first_day), Some(next_year)) => (first_day, next_year),
+        _ => return Err(format!("{} is outside the supported range of years", year)),
+    };
+    let n_days = next_year.signed_duration_since(first_day).num_days();
+    let plot_height = 24 * HOUR_HEIGHT;
+    let width = LEFT + n_days * DAY_WIDTH + 10;
+    let height = TOP + plot_height + MARKER_ROW + 10;
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"sans-serif\" font-size=\"10\">", width, height);
+    svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#f4f4f4\"/>",
+                          LEFT, TOP, n_days * DAY_WIDTH, plot_height));
+
+    // Hour labels and guide lines every six hours.
+    for hour in (0..=24).step_by(6) {
+        let y = TOP + hour * HOUR_HEIGHT;
+        svg.push_str(&format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#ccc\"/>",
+                              LEFT, y, LEFT + n_days * DAY_WIDTH, y));
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}:00</text>", LEFT - 4, y + 3, hour));
+    }
+
+    // Month labels and separators.
+    for month in 1..=12 {
+        let month_start = time::ymd(year, month, 1).unwrap_or(first_day);
+        let offset = month_start.signed_duration_since(first_day).num_days();
+        let x = LEFT + offset * DAY_WIDTH;
+        svg.push_str(&format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#888\"/>",
+                              x, TOP, x, TOP + plot_height));
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\">{}</text>",
+                              x + 2, TOP - 6, time::format_month(month_start)));
+    }
+
+    for task in tasks.iter().filter(|t| t.date.year() == year) {
+        let x = LEFT + task.date.ordinal0() as i64 * DAY_WIDTH;
+        if let [Some(start), Some(end)] = [task.start_time, task.end_time] {
+            let y = TOP + minutes(start) * HOUR_HEIGHT / 60;
+            let h = ((minutes(end) - minutes(start)) * HOUR_HEIGHT / 60).max(1);
+            svg.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"0.8\"><title>{} {} -- {}</title></rect>",
+                                  x, y, DAY_WIDTH, h, tag_color(task), time::format_date(task.date),
+                                  time::format_time(start), time::format_time(end)));
+        }
+        if task.tags.iter().any(|t| t == "public") {
+            let mut title = task.details.clone();
+            for tag in &task.tags {
+                if public_tags.contains_key(tag.as_str()) {
+                    title.push_str(" +");
+                    title.push_str(tag);
+                }
+            }
+            svg.push_str(&format!("<circle cx=\"{}\" cy=\"{}\" r=\"2\" fill=\"{}\"><title>{}: {}</title></circle>",
+                                  x + DAY_WIDTH / 2, TOP + plot_height + MARKER_ROW / 2, tag_color(task),
+                                  time::format_date(task.date), escape_html(&title)));
+        }
+    }
+    svg.push_str("</svg>");
+    return Ok(svg);
+}
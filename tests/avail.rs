@@ -0,0 +1,65 @@
+// The free time `wtd avail` offers.
+#![allow(clippy::needless_return)]
+use chrono::Duration;
+use chrono_tz::America::New_York;
+use chrono_tz::Asia::Tokyo;
+use wtd::avail::{availability, format_text, to_json, AvailOptions, Availability, Zones};
+use wtd::{parse_notes, parse_tasks, time};
+
+const WEEK: &str = "# 10/12/26
+## Monday
+- [ ] Review @10AM+1h
+- [ ] Workshop @11AM--1:45PM
+- [ ] Planning @2PM--4PM
+## OOO 10/13
+## Wednesday
+- [ ] Standup @9AM+20m
+";
+
+// Three working days from 9:05AM on Monday, 10/12/26.
+fn days() -> Vec<Availability> {
+    let tasks = parse_tasks(WEEK).unwrap_or_else(|errors| panic!("{:?}", errors));
+    let now = time::ymd(2026, 10, 12).expect("a date").and_time(time::hms(9, 5, 0).expect("a time"));
+    let options = AvailOptions { days: 3, work_hours: (9, 17), min_free: Duration::minutes(30) };
+    return availability(&tasks, &parse_notes(WEEK).out_of_office, now, &options);
+}
+
+// The rest of today from the next quarter hour, skipping days out of office
+// and stretches too short to offer.
+#[test]
+fn free_time_as_text() {
+    assert_eq!(format_text(&days(), (9, 17), Zones::Mine).lines().collect::<Vec<&str>>(), [
+        "Mon 10/12: free 9:15–10, after 4",
+        "Wed 10/14: free after 9:20",
+        "Thu 10/15: free all day",
+    ]);
+}
+
+// On someone else's clock, with the stretches grouped by their days. Mine is
+// the configured timezone, not the system's. No other test here reads the
+// system's timezone, so setting it can't race.
+#[test]
+fn their_timezone() {
+    std::env::set_var("TZ", "UTC");
+    let zones = |both| match both {
+        false => Zones::Theirs { mine: New_York, theirs: Tokyo },
+        true => Zones::Both { mine: New_York, theirs: Tokyo },
+    };
+    assert_eq!(format_text(&days(), (9, 17), zones(false)).lines().collect::<Vec<&str>>(), [
+        "Mon 10/12: free 10:15PM–11PM JST",
+        "Tue 10/13: free 5AM–6AM JST",
+        "Wed 10/14: free 10:20PM–6AM JST",
+        "Thu 10/15: free 10PM–6AM JST",
+    ]);
+    let both = format_text(&days(), (9, 17), zones(true));
+    assert_eq!(both.lines().next(), Some("Mon 10/12: free 9:15–10, after 4 (your Mon 10/12: free 10:15PM–11PM JST; your Tue 10/13: free 5AM–6AM JST)"));
+    let json = to_json(&days(), zones(false));
+    assert_eq!(json[2]["free"][0]["their_end"], "2026-10-16T06:00");
+}
+
+#[test]
+fn free_time_as_json() {
+    let json = to_json(&days(), Zones::Mine);
+    assert_eq!(json[0]["free"], serde_json::json!([{"start": "09:15", "end": "10:00"}, {"start": "16:00", "end": "17:00"}]));
+    assert_eq!(json[2]["date"], "2026-10-15");
+}
@@ -0,0 +1,25 @@
+// The generated schedule `wtd bench` times.
+#![allow(clippy::needless_return)]
+use wtd::bench::{run, synthetic_schedule, BenchOptions};
+use wtd::parse_tasks;
+
+// The schedule parses to the number of tasks asked for, and is the same on
+// every run.
+#[test]
+fn schedule() {
+    for count in [1, 55, 400].iter() {
+        let source = synthetic_schedule(*count);
+        let tasks = parse_tasks(&source).unwrap_or_else(|errors| panic!("{:?}", errors));
+        assert_eq!(tasks.len(), *count);
+        assert_eq!(source, synthetic_schedule(*count));
+    }
+}
+
+#[test]
+fn phases() {
+    let report = run(&BenchOptions { tasks: 100, runs: 1 }).expect("the bench runs");
+    let lines: Vec<&str> = report.lines().collect();
+    assert!(lines[0].starts_with("100 tasks (") && lines[0].ends_with("), one run:"), "{}", report);
+    assert_eq!(lines.len(), 9, "{}", report);
+    assert!(lines[1..].iter().all(|line| line.ends_with(" ms")), "{}", report);
+}
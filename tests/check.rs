@@ -0,0 +1,62 @@
+// `wtd check --published` on what wtd itself renders.
+#![allow(clippy::needless_return)]
+use wtd::check::check_published;
+use wtd::{init, parse_tasks, render_html, time, CalendarPrivacy, Notes, RenderOptions, Section, View};
+
+const SOURCE: &str = "# 10/12/26
+## Monday
+- [ ] Standup @9AM+15m +busy
+  > Room 4
+## Tuesday
+- [ ] Focus @1PM--3PM +self
+- [ ] Dentist @4PM+1h
+## Friday
+- [ ] Holiday @allday +busy
+";
+
+fn check(options: &RenderOptions) -> Vec<String> {
+    let tasks = parse_tasks(SOURCE).unwrap_or_else(|errors| panic!("{:?}", errors));
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, options);
+    return check_published(&html, &tasks, &options.public_tags);
+}
+
+#[test]
+fn default_page_passes() {
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), ..RenderOptions::default() };
+    assert_eq!(check(&options), Vec::<String>::new());
+}
+
+// The month view and legend write <div>s, and tag colors and an embedded
+// stylesheet write <style>.
+#[test]
+fn month_view_colors_and_embedded_css_pass() {
+    let options = RenderOptions {
+        start: time::ymd(2026, 10, 12),
+        sections: vec![
+            Section { view: View::Grid, days: Some(7), weeks: None },
+            Section { view: View::Month, days: None, weeks: Some(2) },
+        ],
+        tag_colors: vec![("busy".to_string(), "crimson".to_string())],
+        embedded_css: Some(init::STYLESHEET.to_string()),
+        ..RenderOptions::default()
+    };
+    assert_eq!(check(&options), Vec::<String>::new());
+}
+
+// A page that leaks private details, names private tags, or carries markup
+// of its own is flagged.
+#[test]
+fn problems_are_found() {
+    let tasks = parse_tasks(SOURCE).unwrap_or_else(|errors| panic!("{:?}", errors));
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), ..RenderOptions::default() };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options)
+        .replace("</body>", "<p>Dentist</p><td class=\"has-task tag-doctor\"></td><script>x()</script><a href=\"javascript:x()\"></a><img src=x onerror=x()></body>");
+    assert_eq!(check_published(&html, &tasks, &options.public_tags), [
+        "private text from 2026-10-13 appears: \"Dentist\"",
+        "unknown tag: \"doctor\"",
+        "unexpected markup: <script>",
+        "unexpected markup: </script>",
+        "script in markup: <a href=\"javascript:x()\">",
+        "script in markup: <img src=x onerror=x()>",
+    ]);
+}
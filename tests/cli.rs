@@ -0,0 +1,338 @@
+// Errors from the wtd binary are reported, not panics.
+#![allow(clippy::needless_return)]
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn wtd(args: &[&str]) -> Output {
+    return Command::new(env!("CARGO_BIN_EXE_wtd")).args(args).output().expect("wtd runs");
+}
+
+#[test]
+fn missing_task_file() {
+    let output = wtd(&["--config", "/dev/null", "render", "-i", "missing.md"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Couldn't read missing.md: "));
+}
+
+#[test]
+fn invalid_config() {
+    let config = std::env::temp_dir().join(format!("wtd-invalid-config-{}.toml", std::process::id()));
+    std::fs::write(&config, "[render]\ndays = 0\n").expect("the config is written");
+    let output = wtd(&["--config", config.to_str().expect("a UTF-8 path"), "render"]);
+    std::fs::remove_file(&config).ok();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Couldn't load config: days must be at least 1\n");
+}
+
+#[test]
+fn date_out_of_range() {
+    let output = wtd(&["--config", "/dev/null", "recall", "--on", "in 99999999999 days"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("expected e.g. 2024-03-14"));
+}
+
+#[test]
+fn address_in_use() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("a free port");
+    let address = listener.local_addr().expect("the port's address").to_string();
+    let output = wtd(&["--config", "/dev/null", "serve", "--bind", &address]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with(&format!("Couldn't listen on {}: ", address)));
+}
+
+#[test]
+fn unwritable_output() {
+    let output = wtd(&["--config", "/dev/null", "/dev/null", "year", "--output", "/nonexistent/year.svg"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Couldn't write /nonexistent/year.svg: "));
+}
+
+#[test]
+fn unwritable_chart() {
+    let output = wtd(&["--config", "/dev/null", "/dev/null", "stats", "meetings", "--svg", "/nonexistent/meetings.svg"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Couldn't write /nonexistent/meetings.svg: "));
+}
+
+// A color that could break out of the page's <style> is refused.
+#[test]
+fn invalid_tag_color() {
+    let config = std::env::temp_dir().join(format!("wtd-invalid-color-{}.toml", std::process::id()));
+    std::fs::write(&config, "[render.colors]\nbusy = \"red; } body { display: none\"\n").expect("the config is written");
+    let output = wtd(&["--config", config.to_str().expect("a UTF-8 path"), "/dev/null", "json"]);
+    std::fs::remove_file(&config).ok();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Couldn't load config: \"red; } body { display: none\" for busy under [render.colors] isn't a CSS color\n");
+}
+
+// Warnings go to standard error unless --quiet, and stop the run with --strict.
+#[test]
+fn quiet_and_strict() {
+    let path = std::env::temp_dir().join(format!("wtd-warnings-{}.md", std::process::id()));
+    std::fs::write(&path, "# 10/12/26\n## Monday\n- [] Standup @9AM+15m\n").expect("the task file is written");
+    let path = path.to_str().expect("a UTF-8 path");
+    let warned = wtd(&["--config", "/dev/null", path, "json"]);
+    let quiet = wtd(&["--config", "/dev/null", "--quiet", path, "json"]);
+    let strict = wtd(&["--config", "/dev/null", "--strict", path, "json"]);
+    std::fs::remove_file(path).ok();
+    assert_eq!(warned.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&warned.stderr),
+               "Warning: line 3: '- [] Standup @9AM+15m' looks like a task but isn't one; tasks start with '- [ ]' or '- [X]'\n");
+    assert_eq!((quiet.status.code(), quiet.stderr.len()), (Some(0), 0));
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&strict.stderr).contains("looks like a task but isn't one"));
+}
+
+// `recall` lists what was scheduled on the day, or says there was nothing.
+#[test]
+fn recall() {
+    let path = std::env::temp_dir().join(format!("wtd-recall-{}.md", std::process::id()));
+    std::fs::write(&path, "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n- [ ] Groceries\n").expect("the task file is written");
+    let path = path.to_str().expect("a UTF-8 path");
+    let found = wtd(&["--config", "/dev/null", path, "recall", "--on", "2026-10-12"]);
+    let nothing = wtd(&["--config", "/dev/null", path, "recall", "--on", "10/13/26"]);
+    std::fs::remove_file(path).ok();
+    assert_eq!(String::from_utf8_lossy(&found.stdout), "Mon 10/12/26\n   9:00AM --  9:15AM  Standup +busy\n                      Groceries\n");
+    assert_eq!(String::from_utf8_lossy(&nothing.stdout), "Nothing recorded for Tue 10/13/26.\n");
+}
+
+// `list` prints the tasks matching every filter, and fails when none do.
+#[test]
+fn list() {
+    let path = std::env::temp_dir().join(format!("wtd-list-{}.md", std::process::id()));
+    std::fs::write(&path, "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy +meetings\n- [ ] Dentist @2PM+1h +busy\n  > Ask about the CROWN\n## Wednesday\n- [ ] Review @1PM+1h +busy +meetings\n")
+        .expect("the task file is written");
+    let path = path.to_str().expect("a UTF-8 path");
+    let list = |filters: &[&str]| wtd(&[&["--config", "/dev/null", path, "list"], filters].concat());
+    let meetings = list(&["--tag", "busy", "--tag", "+meetings", "--from", "10/13/26"]);
+    let crown = list(&["--grep", "crown"]);
+    let nothing = list(&["--tag", "busy", "--to", "2026-10-11"]);
+    let bad = list(&["--grep", "("]);
+    std::fs::remove_file(path).ok();
+    assert_eq!(String::from_utf8_lossy(&meetings.stdout), "Wed 10/14/26\n   1:00PM --  2:00PM  Review +busy +meetings\n");
+    assert!(String::from_utf8_lossy(&crown.stdout).contains("Dentist") && !String::from_utf8_lossy(&crown.stdout).contains("Standup"));
+    assert_eq!((nothing.status.code(), nothing.stdout.len()), (Some(1), 0));
+    assert!(String::from_utf8_lossy(&bad.stderr).starts_with("--grep \"(\": "), "{}", String::from_utf8_lossy(&bad.stderr));
+}
+
+// The widget's data is written to a file, which is left alone when it
+// wouldn't change.
+#[test]
+fn widget_json_export() {
+    let dir = std::env::temp_dir().join(format!("wtd-widget-json-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    std::fs::write(dir.join("wtd.md"), "# 1/5/2099\n## Monday\n- [ ] Launch @10AM+1h +public\n").expect("the task file is written");
+    let (input, output) = (dir.join("wtd.md"), dir.join("widget.json"));
+    let export = || wtd(&["--config", "/dev/null", input.to_str().expect("a UTF-8 path"), "export", "--format", "widget-json",
+                          "--output", output.to_str().expect("a UTF-8 path")]);
+    assert_eq!(export().status.code(), Some(0));
+    let written = std::fs::metadata(&output).and_then(|metadata| metadata.modified()).expect("the file is written");
+    let json = std::fs::read_to_string(&output).expect("the file is read");
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    assert_eq!(export().status.code(), Some(0));
+    let rewritten = std::fs::metadata(&output).and_then(|metadata| metadata.modified()).expect("the file is there");
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(json.contains("\"date\": \"2099-01-05\"") && json.contains("\"title\": \"\\\"Launch\\\"\""), "{}", json);
+    assert_eq!(written, rewritten);
+}
+
+// Without a wtd.toml in the current directory, the user-wide config is read
+// from $XDG_CONFIG_HOME/wtd, or else ~/.config/wtd.
+#[test]
+fn user_config() {
+    let dir = std::env::temp_dir().join(format!("wtd-user-config-{}", std::process::id()));
+    for (config, contents) in [("xdg/wtd/config.toml", "[render]\ndays = 0\n"), ("home/.config/wtd/config.toml", "[render]\nweeks = 0\n")] {
+        std::fs::create_dir_all(dir.join(config).parent().expect("a directory")).expect("the directory is made");
+        std::fs::write(dir.join(config), contents).expect("the config is written");
+    }
+    let run = |xdg: Option<&str>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_wtd"));
+        command.args(["/dev/null", "json"]).current_dir(&dir).env("HOME", dir.join("home"));
+        match xdg {
+            Some(xdg) => command.env("XDG_CONFIG_HOME", dir.join(xdg)),
+            None => command.env_remove("XDG_CONFIG_HOME"),
+        };
+        return command.output().expect("wtd runs");
+    };
+    let from_xdg = run(Some("xdg"));
+    let from_home = run(None);
+    let none = run(Some("nowhere"));
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(String::from_utf8_lossy(&from_xdg.stderr), "Couldn't load config: days must be at least 1\n");
+    assert!(String::from_utf8_lossy(&from_home.stderr).contains("/home/.config/wtd/config.toml: unknown field `weeks`"));
+    assert_eq!(none.status.code(), Some(0), "{}", String::from_utf8_lossy(&none.stderr));
+}
+
+// `render` prints the public calendar over the window asked for, or the
+// private one with --private.
+#[test]
+fn render_to_standard_output() {
+    let path = std::env::temp_dir().join(format!("wtd-render-{}.md", std::process::id()));
+    std::fs::write(&path, "# 10/12/26\n## Monday\n- [ ] Dentist @2PM+1h\n").expect("the task file is written");
+    let path = path.to_str().expect("a UTF-8 path");
+    let public = wtd(&["--config", "/dev/null", "render", "--input", path, "--days", "2", "--start", "2026-10-12"]);
+    let private = wtd(&["--config", "/dev/null", "render", "--input", path, "--days", "2", "--start", "2026-10-12", "--private"]);
+    std::fs::remove_file(path).ok();
+    let public = String::from_utf8_lossy(&public.stdout);
+    assert!(public.contains("<th>Mon 10/12/26</th><th>Tue 10/13/26</th></tr>") && !public.contains("Dentist"), "{}", public);
+    assert!(String::from_utf8_lossy(&private.stdout).contains("Dentist"));
+}
+
+// `init` writes a week to fill in and the answers given, and leaves files
+// that are already there alone.
+#[test]
+fn init() {
+    let dir = std::env::temp_dir().join(format!("wtd-init-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    std::fs::write(dir.join("calendar_style.css"), "td {}\n").expect("the stylesheet is written");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wtd"))
+        .args(["--config", "/dev/null", "init"]).current_dir(&dir).env("TZ", "Europe/Paris")
+        .stdin(Stdio::piped()).stdout(Stdio::piped())
+        .spawn().expect("wtd runs");
+    child.stdin.take().expect("standard input").write_all(b"Someday\nsunday\n8-16\n").expect("the answers are sent");
+    let output = child.wait_with_output().expect("wtd finishes");
+    let tasks = std::fs::read_to_string(dir.join("wtd.md"));
+    let config = std::fs::read_to_string(dir.join("wtd.toml"));
+    let css = std::fs::read_to_string(dir.join("calendar_style.css"));
+    std::fs::remove_dir_all(&dir).ok();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("'Someday' is not a day of the week") && stdout.contains("calendar_style.css already exists"), "{}", stdout);
+    let tasks = tasks.expect("wtd.md is written");
+    assert!(tasks.starts_with("# ") && tasks.lines().nth(1) == Some("## Sunday"), "{}", tasks);
+    assert_eq!(tasks.matches("## ").count(), 7);
+    let config = config.expect("wtd.toml is written");
+    assert!(config.ends_with("[parse]\ntimezone = \"Europe/Paris\"\n\n[render]\nweek_start = \"sunday\"\n\n[stats]\nwork_hours = \"8-16\"\n"), "{}", config);
+    assert_eq!(css.expect("the stylesheet is kept"), "td {}\n");
+}
+
+// The grid's rows, window and stylesheet, and the public tags, come from the
+// config.
+#[test]
+fn configured_grid_and_tags() {
+    let dir = std::env::temp_dir().join(format!("wtd-grid-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    std::fs::write(dir.join("wtd.md"), "# 10/12/26\n## Monday\n- [ ] Writing @9AM+1h +focus\n- [ ] Standup @11AM+15m +busy\n").expect("wtd.md is written");
+    let render = |config: &str| {
+        std::fs::write(dir.join("wtd.toml"), config).expect("wtd.toml is written");
+        return Command::new(env!("CARGO_BIN_EXE_wtd")).args(["render", "--start", "2026-10-12"]).current_dir(&dir).output().expect("wtd runs");
+    };
+    let configured = render("[render]\nincrement = 30\ndays = 3\nstylesheet = \"/static/cal.css\"\n\n[tags]\nfocus = \"Heads down\"\n");
+    let uneven = render("[render]\nincrement = 7\n");
+    std::fs::remove_dir_all(&dir).ok();
+    let html = String::from_utf8_lossy(&configured.stdout);
+    assert!(html.contains("href=\"/static/cal.css\""), "{}", html);
+    assert!(html.contains("<th>Wed 10/14/26</th></tr>") && !html.contains("Thu 10/15/26"), "{}", html);
+    assert!(html.contains("<b> 9:30 AM</b>") && !html.contains("<b> 9:15 AM</b>"), "{}", html);
+    assert!(html.contains("tag-focus") && !html.contains("tag-busy"), "{}", html);
+    assert_eq!(String::from_utf8_lossy(&uneven.stderr), "Couldn't load config: increment must be a number of minutes that divides an hour, not 7\n");
+}
+
+// Every command `--capabilities` lists can be run, and every table the
+// starter config shows is among the keys it lists.
+#[test]
+fn capabilities() {
+    let output = wtd(&["--capabilities"]);
+    let capabilities: serde_json::Value = serde_json::from_slice(&output.stdout).expect("JSON");
+    let commands = capabilities["commands"].as_array().expect("a list of commands");
+    assert!(commands.contains(&"stats tags".into()) && commands.contains(&"config init".into()), "{:?}", commands);
+    for command in commands {
+        let mut args: Vec<&str> = command.as_str().expect("a command").split(' ').collect();
+        args.push("--help");
+        assert_eq!(wtd(&args).status.code(), Some(0), "{:?}", args);
+    }
+    let keys = capabilities["config"]["keys"].as_array().expect("a list of keys");
+    for line in wtd::config::STARTER.lines() {
+        if let Some(table) = line.trim_start_matches("# ").strip_prefix('[') {
+            let key = table.trim_matches(['[', ']']).split('.').next().unwrap_or("");
+            assert!(keys.contains(&key.into()), "{}", key);
+        }
+    }
+}
+
+// --embed-css writes the stylesheet beside the task file into the page, or
+// the built-in one when there isn't one.
+#[test]
+fn embedded_stylesheet() {
+    let dir = std::env::temp_dir().join(format!("wtd-embed-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    std::fs::write(dir.join("wtd.md"), "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n").expect("wtd.md is written");
+    std::fs::write(dir.join("wtd.toml"), "").expect("wtd.toml is written");
+    let render = || Command::new(env!("CARGO_BIN_EXE_wtd")).args(["--embed-css", "render"]).current_dir(&dir).output().expect("wtd runs");
+    let built_in = render();
+    std::fs::write(dir.join("calendar_style.css"), "td { color: red; } </style>\n").expect("the stylesheet is written");
+    let own = render();
+    std::fs::remove_dir_all(&dir).ok();
+    let built_in = String::from_utf8_lossy(&built_in.stdout);
+    assert!(built_in.contains(&format!("<style>{}</style>", wtd::init::STYLESHEET)) && !built_in.contains("<link"), "{}", built_in);
+    let own = String::from_utf8_lossy(&own.stdout);
+    assert!(own.contains("<style>td { color: red; } <\\/style>\n</style></head>"), "{}", own);
+}
+
+// Reading a long archive reports its progress a line each tenth of the way
+// when standard error isn't a terminal, and not at all with --quiet.
+#[test]
+fn archive_progress() {
+    let dir = std::env::temp_dir().join(format!("wtd-progress-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("archive")).expect("the directory is made");
+    std::fs::write(dir.join("wtd.md"), "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n").expect("the task file is written");
+    for week in 1..=20 {
+        std::fs::write(dir.join("archive").join(format!("{:02}.md", week)), format!("# 1/{}/26\n## Monday\n- [ ] Review +work\n", week))
+            .expect("the week is written");
+    }
+    let (archive, path) = (dir.join("archive"), dir.join("wtd.md"));
+    let tags = |quiet: &[&str]| wtd(&[&["--config", "/dev/null", "--archive", archive.to_str().expect("a UTF-8 path")], quiet,
+                                      &[path.to_str().expect("a UTF-8 path"), "tags"]].concat());
+    let (reported, quiet) = (tags(&[]), tags(&["--quiet"]));
+    std::fs::remove_dir_all(&dir).ok();
+    let lines: Vec<String> = (1..=10).map(|tenth| format!("Reading the archive: {}/20\n", 2 * tenth)).collect();
+    assert_eq!(String::from_utf8_lossy(&reported.stderr), lines.concat());
+    assert_eq!((quiet.status.code(), quiet.stderr.len()), (Some(0), 0));
+    assert_eq!(reported.stdout, quiet.stdout);
+}
+
+// The stats reports look back over at least a week and at most ten years.
+#[test]
+fn stats_weeks_are_bounded() {
+    for report in ["habits", "idle", "meetings", "tags", "checklists", "done"] {
+        for weeks in ["0", "-1", "521", "-100000000000"] {
+            let output = wtd(&["--config", "/dev/null", "missing.md", "stats", report, &format!("--weeks={}", weeks)]);
+            assert_eq!(output.status.code(), Some(2), "{} --weeks={}", report, weeks);
+        }
+    }
+}
+
+// `render --watch` renders again when a file the task file includes is saved,
+// and starts over when the config is.
+#[test]
+fn render_watch() {
+    let dir = std::env::temp_dir().join(format!("wtd-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    std::fs::write(dir.join("wtd.md"), "#include work.md\n# 10/12/26\n## Monday\n- [ ] Run @7AM+1h\n").expect("wtd.md is written");
+    std::fs::write(dir.join("work.md"), "").expect("work.md is written");
+    std::fs::write(dir.join("wtd.toml"), "").expect("wtd.toml is written");
+    let output = dir.join("out.html");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wtd"))
+        .arg("--config").arg(dir.join("wtd.toml")).arg(dir.join("wtd.md"))
+        .args(["render", "--private", "--start", "2026-10-12", "--watch", "--output"]).arg(&output)
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("wtd render --watch starts");
+    let rendered = |what: &str| {
+        for _ in 0..200 {
+            if std::fs::read_to_string(&output).is_ok_and(|html| html.contains(what)) {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        return false;
+    };
+    let first = rendered("Run");
+    std::fs::write(dir.join("work.md"), "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m\n").expect("work.md is written");
+    let included = rendered("Standup");
+    std::fs::write(dir.join("wtd.toml"), "[render]\ndays = 2\n").expect("wtd.toml is written");
+    let restarted = rendered("<th>Tue 10/13/26</th></tr>");
+    child.kill().ok();
+    child.wait().ok();
+    std::fs::remove_dir_all(&dir).ok();
+    assert!(first && included && restarted, "{} {} {}", first, included, restarted);
+}
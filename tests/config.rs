@@ -0,0 +1,83 @@
+// Loading wtd.toml: includes, environment variables, and profiles.
+#![allow(clippy::needless_return)]
+use std::path::PathBuf;
+use wtd::config::{self, Config};
+
+// A directory of config files, removed when dropped.
+struct Dir(PathBuf);
+
+impl Dir {
+    fn new(name: &str, files: &[(&str, &str)]) -> Dir {
+        let dir = std::env::temp_dir().join(format!("wtd-config-{}-{}", name, std::process::id()));
+        for (file, contents) in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().expect("a directory")).expect("the directory is made");
+            std::fs::write(&path, contents).expect("the file is written");
+        }
+        return Dir(dir);
+    }
+
+    fn load(&self, profile: Option<&str>) -> Result<Config, String> {
+        return config::load(&self.0.join("wtd.toml"), profile);
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+// Included files are merged in underneath the including one, and ${NAME}
+// is replaced by the environment variable.
+#[test]
+fn includes_and_environment_variables() {
+    std::env::set_var("WTD_TEST_PORT", "9090");
+    let dir = Dir::new("include", &[
+        ("wtd.toml", "include = [\"secrets/serve.toml\"]\n[render]\ncell_max_chars = 20\n[serve]\nbind = \"127.0.0.1:${WTD_TEST_PORT}\"\n"),
+        ("secrets/serve.toml", "[render]\ncell_max_chars = 10\ndays = 7\n[serve]\nmeeting_tag = \"$$meetings\"\n"),
+    ]);
+    let config = dir.load(None).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!((config.render.cell_max_chars, config.render.days), (Some(20), Some(7)));
+    assert_eq!(config.serve.bind.as_deref(), Some("127.0.0.1:9090"));
+    assert_eq!(config.serve.meeting_tag.as_deref(), Some("$meetings"));
+}
+
+#[test]
+fn bad_includes_and_variables() {
+    let error = |name: &str, files: &[(&str, &str)]| -> String {
+        let dir = Dir::new(name, files);
+        let why = dir.load(None).err().expect("an error");
+        return why.replace(&dir.0.canonicalize().expect("the directory").display().to_string(), "DIR")
+            .replace(&dir.0.display().to_string(), "DIR");
+    };
+    assert_eq!(error("outside", &[("wtd.toml", "include = \"../../etc/passwd\"\n")]),
+               "DIR/../../etc/passwd is outside of the config directory DIR");
+    assert_eq!(error("cycle", &[("wtd.toml", "include = \"wtd.toml\"\n")]), "DIR/wtd.toml includes itself");
+    assert_eq!(error("unset", &[("wtd.toml", "input = \"${WTD_TEST_UNSET}\"\n")]),
+               "DIR/wtd.toml: environment variable WTD_TEST_UNSET is not set");
+}
+
+// A profile's settings are laid over the rest of the config.
+#[test]
+fn profiles() {
+    let dir = Dir::new("profiles", &[(
+        "wtd.toml",
+        "[render]\ndays = 14\ncell_max_chars = 20\n[profiles.work.render]\ndays = 5\n[profiles.work.serve]\nbind = \"0.0.0.0:80\"\n",
+    )]);
+    let config = dir.load(None).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!((config.render.days, config.serve.bind), (Some(14), None));
+    let work = dir.load(Some("work")).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!((work.render.days, work.render.cell_max_chars), (Some(5), Some(20)));
+    assert_eq!(work.serve.bind.as_deref(), Some("0.0.0.0:80"));
+    let why = dir.load(Some("home")).err().expect("an error");
+    assert!(why.ends_with("wtd.toml: no profile named home"), "{}", why);
+}
+
+// A config saved with Windows line endings reads the same.
+#[test]
+fn windows_line_endings() {
+    let dir = Dir::new("crlf", &[("wtd.toml", "include = \"more.toml\"\r\n[render]\r\ndays = 5\r\n"), ("more.toml", "[serve]\r\nbind = \"127.0.0.1:9090\"\r\n")]);
+    let config = dir.load(None).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!((config.render.days, config.serve.bind.as_deref()), (Some(5), Some("127.0.0.1:9090")));
+}
@@ -0,0 +1,12 @@
+// Dates given on the command line.
+#![allow(clippy::needless_return)]
+use wtd::parse_cli_date;
+
+// Offsets too large for a date are reported like any other unreadable date.
+#[test]
+fn offsets_past_the_calendar() {
+    for s in ["in 99999999999 days", "99999999999 days ago", "+99999999999d", "in 2000000000000000000 weeks", "-2000000000000000000w"] {
+        let error = parse_cli_date(s).expect_err(s);
+        assert!(error.contains("expected e.g. 2024-03-14"), "{}", error);
+    }
+}
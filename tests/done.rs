@@ -0,0 +1,53 @@
+// Checking tasks off with `wtd done`.
+#![allow(clippy::needless_return)]
+use wtd::done::{done, done_at, DoneOptions};
+use wtd::{time, ParseOptions};
+
+// Runs `done` on a file of `source`, returning the task or why not, and the
+// file afterwards.
+fn check_off(name: &str, source: &str, query: &str, options: &DoneOptions) -> (Result<wtd::Task, String>, String) {
+    let dir = std::env::temp_dir().join(format!("wtd-done-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    let path = dir.join("wtd.md");
+    std::fs::write(&path, source).expect("the task file is written");
+    let result = done(&path, query, options);
+    let after = std::fs::read_to_string(&path).expect("the task file is read");
+    std::fs::remove_dir_all(&dir).ok();
+    return (result, after);
+}
+
+const WEEK: &str = "# 10/12/26
+## Monday
+- [ ] Write report @2PM+1h
+## Wednesday
+- [ ] Write report @2PM+1h #id:report
+- [x] Standup @9AM+15m
+";
+
+// The task is found by its id or description, on the day asked for, and
+// --time records when it was done.
+#[test]
+fn checks_off_the_task() {
+    let options = DoneOptions { on: time::ymd(2026, 10, 12), at: time::hms(15, 42, 0), parse: ParseOptions::default() };
+    let (task, after) = check_off("description", WEEK, "write REPORT", &options);
+    let task = task.unwrap_or_else(|why| panic!("{}", why));
+    assert!(task.done);
+    assert_eq!(done_at(&task), time::hms(15, 42, 0));
+    assert_eq!(after, WEEK.replacen("- [ ] Write report @2PM+1h\n", "- [X] Write report @2PM+1h +done:15:42\n", 1));
+
+    let options = DoneOptions { on: None, at: None, parse: ParseOptions::default() };
+    let (task, after) = check_off("id", WEEK, "#id:report", &options);
+    assert_eq!(task.map(|task| task.date), Ok(time::ymd(2026, 10, 14).expect("a date")));
+    assert_eq!(after, WEEK.replace("- [ ] Write report @2PM+1h #id:report", "- [X] Write report @2PM+1h #id:report"));
+}
+
+#[test]
+fn nothing_to_check_off() {
+    let options = DoneOptions { on: None, at: None, parse: ParseOptions::default() };
+    assert_eq!(check_off("none", WEEK, "lunch", &options), (Err("No task's id or description matches \"lunch\"".to_string()), WEEK.to_string()));
+    assert_eq!(check_off("already", WEEK, "standup", &options).0.map(|task| task.details),
+               Err("\"Standup\" on Wed 10/14/26 is already done".to_string()));
+    let options = DoneOptions { on: time::ymd(2026, 10, 13), ..options };
+    assert_eq!(check_off("day", WEEK, "write report", &options).0.map(|task| task.details),
+               Err("Nothing \"write report\" names is left to do on Tue 10/13/26; there is on Mon 10/12/26, Wed 10/14/26".to_string()));
+}
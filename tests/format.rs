@@ -0,0 +1,143 @@
+// Round-trips through `wtd fmt` and format_tasks, on randomly generated task
+// files and schedules: formatting never changes what a file means.
+#![allow(clippy::needless_return)]
+use chrono::{Duration, NaiveTime, Weekday};
+use wtd::format::{format_source, format_tasks, normalize, normalize_tasks};
+use wtd::{parse_tasks, time, ParseOptions, Span, Subtask, Task};
+
+// A small seeded generator, so any failure reproduces.
+struct Random(u64);
+
+impl Random {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        return ((self.0 >> 33) % n as u64) as usize;
+    }
+
+    fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+        return choices[self.below(choices.len())];
+    }
+}
+
+const WORDS: [&str; 12] = ["Call", "mom", "b;c", "x//y", "`code`", "&", "!", "+", "@", "-->", "x\\", "<!--"];
+const TIMES: [&str; 7] = ["", "@9AM+1h", "@9:30AM--11AM", "@10PM+90m", "@allday", "@6PM--+2d10AM", "@12PM+15m"];
+const TOKENS: [&str; 12] = ["@loc:Cafe", "@loc:\"The Cafe\"", "&3", "!2", "+busy", "+self", "+file:notes.pdf", "Lunch", "with", "Sam", "b;c", "x//y"];
+const DAYS: [&str; 7] = ["Monday", "tuesday", "WEDNESDAY", "Thursday ", "Friday", "Saturday", "Sunday"];
+
+// A task file as someone might write it by hand: loose spacing, times in
+// any form, tokens in any order, comments, and indented lines of all kinds.
+fn source(random: &mut Random) -> String {
+    let mut out = String::from("# 3/2/26\n");
+    for _ in 0..1 + random.below(3) {
+        out.push_str(&format!("##  {}\n", random.pick(&DAYS)));
+        for _ in 0..random.below(4) {
+            let mut tokens: Vec<&str> = (0..1 + random.below(4)).map(|_| random.pick(&TOKENS)).collect();
+            let at = random.below(tokens.len() + 1);
+            tokens.insert(at, random.pick(&TIMES));
+            let spacing = random.pick(&[" ", "  "]);
+            out.push_str(&format!("{} {}", random.pick(&["- [ ]", "- [x]", "- [X]"]), tokens.join(spacing)));
+            out.push_str(random.pick(&["", "", " // a comment", " <!-- hidden -->", " \\", "   "]));
+            out.push('\n');
+            for _ in 0..random.below(3) {
+                out.push_str(random.pick(&["  - [ ] Agenda\n", "    - [x] Slides\n", "  > Room 4\n", "   more +busy\n", "\n"]));
+            }
+        }
+    }
+    return out;
+}
+
+fn time_of_day(random: &mut Random) -> NaiveTime {
+    return time::hms(random.below(24) as u32, random.below(60) as u32, random.below(60) as u32).expect("a valid time");
+}
+
+fn text(random: &mut Random) -> String {
+    let words: Vec<&str> = (0..random.below(5)).map(|_| random.pick(&WORDS)).collect();
+    return words.join(random.pick(&[" ", "", "\n"]));
+}
+
+fn maybe<T>(random: &mut Random, value: impl FnOnce(&mut Random) -> T) -> Option<T> {
+    return if random.below(2) == 0 { Some(value(random)) } else { None };
+}
+
+// A schedule as another program might build it, with fields the file can't
+// say exactly as given.
+fn schedule(random: &mut Random) -> Vec<Task> {
+    return (0..1 + random.below(5)).map(|_| {
+        let date = time::ymd(2024 + random.below(3) as i32, 1 + random.below(12) as u32, 1 + random.below(28) as u32).expect("a valid date");
+        let end = time_of_day(random);
+        return Task {
+            date,
+            start_time: maybe(random, time_of_day),
+            end_time: maybe(random, |_| end),
+            all_day: random.below(4) == 0,
+            span: maybe(random, |random| Span { last: date + Duration::days(random.below(4) as i64 - 1), end }),
+            details: text(random),
+            tags: (0..random.below(3)).map(|_| text(random)).collect(),
+            done: random.below(2) == 0,
+            subtasks: (0..random.below(3)).map(|_| Subtask { text: text(random), done: random.below(2) == 0, level: random.below(3) }).collect(),
+            notes: (0..random.below(3)).map(|_| text(random)).collect(),
+            attachments: (0..random.below(2)).map(|_| text(random)).collect(),
+            attendees: maybe(random, |random| random.below(20) as u32),
+            priority: maybe(random, |random| random.below(9) as u8),
+            location: maybe(random, text),
+            id: maybe(random, text),
+            source: None,
+        };
+    }).collect();
+}
+
+// parse(format(s)) == parse(s), and formatting twice changes nothing more.
+#[test]
+fn formatting_keeps_the_tasks() {
+    let mut random = Random(1);
+    let mut parsed = 0;
+    for round in 0..2000 {
+        let file = source(&mut random);
+        let before = match parse_tasks(&file) {
+            Ok(tasks) => tasks,
+            Err(_) => continue,
+        };
+        parsed += 1;
+        let formatted = format_source(&file, &ParseOptions::default())
+            .unwrap_or_else(|why| panic!("round {}: {}\n{}", round, why, file));
+        assert_eq!(parse_tasks(&formatted).ok(), Some(before), "round {}:\n{}\n{}", round, file, formatted);
+        assert_eq!(format_source(&formatted, &ParseOptions::default()).as_ref(), Ok(&formatted), "round {}", round);
+    }
+    assert!(parsed > 1000, "only {} of the generated files parsed", parsed);
+}
+
+// parse(format_tasks(tasks)) == normalize_tasks(tasks), which is already
+// normal and which `wtd fmt` leaves alone.
+#[test]
+fn written_schedules_parse_back() {
+    let mut random = Random(2);
+    for round in 0..2000 {
+        let tasks = schedule(&mut random);
+        let first_day = if random.below(2) == 0 { Weekday::Mon } else { Weekday::Sun };
+        let file = format_tasks(&tasks, first_day);
+        let normal = normalize_tasks(&tasks);
+        assert_eq!(parse_tasks(&file).ok(), Some(normal.clone()), "round {}:\n{}", round, file);
+        for task in &normal {
+            assert_eq!(&normalize(task), task, "round {}", round);
+        }
+        assert_eq!(format_source(&file, &ParseOptions::default()).as_ref(), Ok(&file), "round {}", round);
+    }
+}
+
+// Day names are capitalized, times and durations written shortest, tags and
+// the time moved after the description, and indents made two spaces.
+#[test]
+fn canonical_form() {
+    let file = "# 10/12/26\n## monday\n- [ ] +busy Standup @9:00AM+0h15m with the team\n    bring notes\n\n\n";
+    let formatted = format_source(file, &ParseOptions::default()).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(formatted, "# 10/12/26\n## Monday\n- [ ] Standup with the team @9AM+15m +busy\n  bring notes\n");
+    assert_eq!(format_source(&formatted, &ParseOptions::default()), Ok(formatted.clone()));
+}
+
+// Comments stay, at the end of their line.
+#[test]
+fn comments_are_kept() {
+    let file = "# 10/12/26\n## Monday\n- [ ]  Dentist +busy @9AM+1h   // ask about the crown\n<!-- nothing on Tuesday -->\n";
+    let formatted = format_source(file, &ParseOptions::default()).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(formatted, "# 10/12/26\n## Monday\n- [ ] Dentist @9AM+1h +busy // ask about the crown\n<!-- nothing on Tuesday -->\n");
+}
@@ -0,0 +1,20 @@
+// The events `wtd push-gcal` sends to Google Calendar.
+#![allow(clippy::needless_return)]
+use regex::Regex;
+use wtd::gcal::event_ids;
+use wtd::{parse_tasks, Task};
+
+// IDs are made only of the characters Google accepts, and identical tasks on
+// a day get IDs of their own.
+#[test]
+fn event_ids_are_valid() {
+    let tasks = parse_tasks("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n- [ ] Standup @9AM+15m +busy\n- [ ] Groceries\n## Tuesday\n- [ ] Holiday @allday\n")
+        .unwrap_or_else(|errors| panic!("{:?}", errors));
+    let tasks: Vec<&Task> = tasks.iter().collect();
+    let ids = event_ids(&tasks);
+    let valid = Regex::new("^[0-9a-v]{5,1024}$").expect("a valid pattern");
+    assert!(ids.iter().all(|id| valid.is_match(id)), "{:?}", ids);
+    assert_eq!(ids.len(), 4);
+    assert!(ids[0] != ids[1]);
+    assert_eq!(ids, event_ids(&tasks));
+}
@@ -0,0 +1,64 @@
+// The hooks wtd runs around reading and publishing task files.
+#![allow(clippy::needless_return)]
+#![cfg(unix)]
+use std::fs;
+use std::path::Path;
+use wtd::{hooks, load, load_archive, load_archive_out_of_office, load_notes, public_tags, CalendarPrivacy, ParseOptions};
+
+// Each file is passed through the pre_parse hooks once, however many times
+// it's read, until it changes.
+#[test]
+fn pre_parse_runs_once_per_source() {
+    let dir = std::env::temp_dir().join(format!("wtd-pre-parse-{}", std::process::id()));
+    let archive = dir.join("archive");
+    fs::create_dir_all(&archive).expect("the directories are made");
+    let path = dir.join("wtd.md");
+    fs::write(&path, "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m\n#include work.md\n").expect("wtd.md is written");
+    fs::write(dir.join("work.md"), "# 10/12/26\n## Tuesday\n- [ ] Review @1PM+1h\n").expect("work.md is written");
+    fs::write(archive.join("2026-10-05.md"), "# 10/5/26\n## Monday\n- [ ] Standup @9AM+15m\n").expect("the archive is written");
+    let runs = dir.join("runs");
+    let options = ParseOptions {
+        pre_parse: vec![format!("cat >/dev/null; echo run >> '{}'", runs.display())],
+        ..ParseOptions::default()
+    };
+    let count = || fs::read_to_string(&runs).unwrap_or_default().lines().count();
+
+    assert_eq!(load(&path, &options).map(|tasks| tasks.len()), Ok(2));
+    load_notes(&path, &options);
+    assert_eq!(load_archive(&path, &archive, &options, true).map(|tasks| tasks.len()), Ok(3));
+    load_archive_out_of_office(&path, &archive, &options);
+    assert_eq!(count(), 3);
+
+    fs::write(dir.join("work.md"), "# 10/12/26\n## Wednesday\n- [ ] Review @1PM+1h\n").expect("work.md is rewritten");
+    load(&path, &options).expect("the files load");
+    assert_eq!(count(), 4);
+    fs::remove_dir_all(&dir).ok();
+}
+
+// A pre_parse hook can rewrite the source, and each hook is given what the
+// one before it printed.
+#[test]
+fn hooks_run_in_turn() {
+    let hooks = [
+        r#"sed 's/@standup/@9AM+15m/'"#.to_string(),
+        "cat".to_string(),
+        "true".to_string(),
+    ];
+    let source = "# 10/12/26\n## Monday\n- [ ] Standup @standup\n".to_string();
+    let expanded = hooks::pre_parse(&hooks, Path::new("wtd.md"), source);
+    assert_eq!(expanded, Ok("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m\n".to_string()));
+    let html = hooks::post_render(&[r#"echo '{"html": "<p>replaced</p>"}'"#.to_string()], CalendarPrivacy::Public, &[], &public_tags(), "<p>page</p>".to_string());
+    assert_eq!(html, Ok("<p>replaced</p>".to_string()));
+}
+
+// A hook exiting nonzero stops wtd with its reason, which before publishing
+// vetoes it.
+#[test]
+fn failing_hooks() {
+    let veto = hooks::pre_publish(&["echo 'has a secret' >&2; exit 1".to_string()], &[Path::new("public.html")], &[], &public_tags());
+    assert_eq!(veto, Err("The pre_publish hook `echo 'has a secret' >&2; exit 1` stopped wtd: has a secret".to_string()));
+    let garbled = hooks::pre_parse(&["echo '[1]'".to_string()], Path::new("wtd.md"), String::new());
+    assert_eq!(garbled, Err("The pre_parse hook `echo '[1]'` printed something other than a JSON object".to_string()));
+    let missing = hooks::post_render(&["echo '{}'".to_string()], CalendarPrivacy::Private, &[], &public_tags(), String::new());
+    assert_eq!(missing, Err("A post_render hook printed an object without a \"html\" string".to_string()));
+}
@@ -0,0 +1,104 @@
+// Calendars exported as iCalendar.
+#![allow(clippy::needless_return)]
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use chrono::NaiveTime;
+use wtd::ics::{self, ShowAs};
+use wtd::{parse_notes, parse_tasks, time, Task};
+
+fn parse(source: &str) -> Vec<Task> {
+    return parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+}
+
+// The text of `name` lines in `ics`, with folded lines joined back up.
+fn property(ics: &str, name: &str) -> Vec<String> {
+    let unfolded = ics.replace("\r\n ", "");
+    return unfolded.split("\r\n")
+        .filter_map(|line| line.strip_prefix(name).and_then(|rest| rest.strip_prefix(':')))
+        .map(|value| value.to_string())
+        .collect();
+}
+
+// A public event's checklist, with its progress, goes in its description.
+#[test]
+fn checklist_in_description() {
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Talk @9AM+1h +public\n  - [x] Slides\n  - [ ] Handouts\n    - [ ] Print\n");
+    let calendar = ics::tasks_to_ics(&tasks, &[], ShowAs::Busy, &HashMap::new());
+    assert_eq!(property(&calendar, "DESCRIPTION"), ["Talk\\n\\nChecklist (1/3 done):\\n[x] Slides\\n[ ] Handouts\\n  [ ] Print"]);
+    // A private one's stays off the public calendar.
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Talk @9AM+1h\n  - [x] Slides\n");
+    assert!(property(&ics::tasks_to_ics(&tasks, &[], ShowAs::Busy, &HashMap::new()), "DESCRIPTION").is_empty());
+}
+
+// '## OOO' lines are read as whole days away, with the year from the week's
+// header unless written out, and exported as all-day events.
+#[test]
+fn out_of_office() {
+    let source = "# 12/28/26\n## Monday\n- [ ] Pack @9AM+1h\n## OOO 12/30--1/2/27\n## OOO 12/29\n";
+    assert_eq!(parse(source).len(), 1);
+    let away: Vec<(String, String)> = parse_notes(source).out_of_office.iter()
+        .map(|range| (range.first.to_string(), range.last.to_string()))
+        .collect();
+    assert_eq!(away, [("2026-12-30".to_string(), "2027-01-02".to_string()), ("2026-12-29".to_string(), "2026-12-29".to_string())]);
+
+    let out_of_office = parse_notes(source).out_of_office;
+    let calendar = ics::tasks_to_ics(&[], &out_of_office, ShowAs::Busy, &HashMap::new());
+    assert_eq!(property(&calendar, "DTSTART;VALUE=DATE"), ["20261230", "20261229"]);
+    assert_eq!(property(&calendar, "DTEND;VALUE=DATE"), ["20270103", "20261230"]);
+    assert_eq!(property(&calendar, "TRANSP"), ["OPAQUE", "OPAQUE"]);
+    let calendar = ics::tasks_to_ics(&[], &out_of_office, ShowAs::Free, &HashMap::new());
+    assert_eq!(property(&calendar, "TRANSP"), ["TRANSPARENT", "TRANSPARENT"]);
+}
+
+// A public event's location is exported, and read back from imported events.
+#[test]
+fn locations() {
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Coffee @10AM+1h @loc:\"Coffee Bar, 5th Ave\" +public\n- [ ] Dentist @2PM+1h @loc:Clinic\n");
+    let calendar = ics::tasks_to_ics(&tasks, &[], ShowAs::Busy, &HashMap::new());
+    assert_eq!(property(&calendar, "LOCATION"), ["Coffee Bar\\, 5th Ave"]);
+    let events = ics::read_events(&calendar, "shared", None).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(events.iter().map(|event| event.location.as_deref()).collect::<Vec<_>>(), [Some("Coffee Bar, 5th Ave"), None]);
+}
+
+// Events from another calendar overlaid on this one, tagged with its name.
+#[test]
+fn overlaid_events() {
+    let calendar = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Sync\\, weekly\r\nDTSTART:20261012T090000\r\nDURATION:PT1H30M\r\nEND:VEVENT\r\n\
+                    BEGIN:VEVENT\r\nSUMMARY:Offsite\r\nDTSTART;VALUE=DATE:20261014\r\nDTEND;VALUE=DATE:20261016\r\nEND:VEVENT\r\n\
+                    BEGIN:VEVENT\r\nSUMMARY:Broken\r\nDTSTART:tomorrow\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let tasks = ics::read_events(calendar, "work", None).unwrap_or_else(|why| panic!("{}", why));
+    let summary: Vec<(String, &str, Option<NaiveTime>, Option<NaiveTime>)> = tasks.iter()
+        .map(|t| (t.date.to_string(), t.details.as_str(), t.start_time, t.end_time)).collect();
+    assert_eq!(summary, [
+        ("2026-10-12".to_string(), "Sync, weekly", time::hms(9, 0, 0), time::hms(10, 30, 0)),
+        ("2026-10-14".to_string(), "Offsite", None, None),
+        ("2026-10-15".to_string(), "Offsite", None, None),
+    ]);
+    assert!(tasks.iter().all(|t| t.tags == ["work"]));
+}
+
+// Calendars given as URLs are downloaded, and a failed download names the URL.
+#[test]
+fn calendars_at_urls() {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("a free port");
+    let address = listener.local_addr().expect("the address").to_string();
+    let server = std::thread::spawn(move || {
+        for (status, body) in [("200 OK", "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Sync\r\nDTSTART:20261012T090000\r\nDURATION:PT1H\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n"),
+                               ("404 Not Found", "")].iter() {
+            let (mut stream, _) = listener.accept().expect("a request");
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            write!(stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).expect("the response is sent");
+        }
+    });
+    let url = format!("http://{}/team.ics", address);
+    let fetched = ics::load(Path::new(&url), "external", None).unwrap_or_else(|why| panic!("{}", why));
+    let missing = ics::load(Path::new(&url), "external", None);
+    server.join().expect("the server stops");
+
+    assert_eq!(fetched.len(), 1);
+    assert_eq!((fetched[0].details.as_str(), fetched[0].start_time, &fetched[0].tags), ("Sync", time::hms(9, 0, 0), &vec!["external".to_string()]));
+    assert!(missing.as_ref().is_err_and(|why| why.starts_with(&format!("{}: ", url))), "{:?}", missing);
+}
@@ -0,0 +1,54 @@
+// Accepting an emailed invite with `wtd accept`.
+#![allow(clippy::needless_return)]
+use chrono::Weekday;
+use wtd::invite::{accept, read_invite, reply, AcceptOptions};
+use wtd::ParseOptions;
+
+const INVITE: &str = "BEGIN:VCALENDAR\r
+METHOD:REQUEST\r
+BEGIN:VEVENT\r
+UID:42@example.com\r
+SUMMARY:Design review\\, round 2\r
+ORGANIZER;CN=Bo:mailto:bo@example.com\r
+DTSTART:20261014T100000\r
+DTEND:20261014T110000\r
+CATEGORIES:theirs\r
+ATTENDEE;CN=Ann;RSVP=TRUE;PARTSTAT=NEEDS-ACTION:mailto:Ann@Example.com\r
+ATTENDEE;CN=Cy:mailto:cy@example.com\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+// The event goes under its day (a new one at the end of its week), once however often it's accepted, without
+// the organizer's tags.
+#[test]
+fn accepting_adds_the_event_once() {
+    let invite = read_invite(INVITE, None).unwrap_or_else(|why| panic!("{}", why));
+    let path = std::env::temp_dir().join(format!("wtd-accept-{}.md", std::process::id()));
+    std::fs::write(&path, "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m\n## Thursday\n- [ ] Run @7AM+1h\n").expect("wtd.md is written");
+    let options = AcceptOptions { tentative: true, parse: ParseOptions::default(), week_start: Weekday::Mon };
+    let first = accept(&path, &invite, &options).unwrap_or_else(|why| panic!("{}", why));
+    let second = accept(&path, &invite, &options).unwrap_or_else(|why| panic!("{}", why));
+    let written = std::fs::read_to_string(&path);
+    std::fs::remove_file(&path).ok();
+    assert_eq!(first.len(), 1);
+    assert!(second.is_empty());
+    assert_eq!(written.expect("wtd.md is there"),
+               "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m\n## Thursday\n- [ ] Run @7AM+1h\n\n## Wednesday\n- [ ] Design review, round 2 @10AM--11AM +tentative\n");
+}
+
+// The reply repeats the event and says who's coming, and how surely.
+#[test]
+fn replies() {
+    let invite = read_invite(INVITE, None).unwrap_or_else(|why| panic!("{}", why));
+    let accepted = reply(&invite, Some("ann@example.com"), false).unwrap_or_else(|why| panic!("{}", why));
+    assert!(accepted.contains("METHOD:REPLY\r\n"), "{}", accepted);
+    assert!(accepted.contains("UID:42@example.com\r\n") && accepted.contains("ORGANIZER;CN=Bo:mailto:bo@example.com\r\n"), "{}", accepted);
+    assert!(accepted.contains("ATTENDEE;PARTSTAT=ACCEPTED;CN=Ann:mailto:Ann@Example.com\r\n"), "{}", accepted);
+    assert!(!accepted.contains("cy@example.com"), "{}", accepted);
+    let tentative = reply(&invite, Some("mailto:CY@example.com"), true).unwrap_or_else(|why| panic!("{}", why));
+    assert!(tentative.contains("ATTENDEE;PARTSTAT=TENTATIVE;CN=Cy:mailto:cy@example.com\r\n"), "{}", tentative);
+    assert_eq!(reply(&invite, None, false), Err("pass --me with your address to reply; the invite is to ann@example.com, cy@example.com".to_string()));
+    assert_eq!(reply(&invite, Some("dee@example.com"), false),
+               Err("dee@example.com isn't among the invite's attendees (ann@example.com, cy@example.com)".to_string()));
+}
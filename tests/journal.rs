@@ -0,0 +1,56 @@
+// The journal kept beside the task file for +journal tasks.
+#![allow(clippy::needless_return)]
+use std::path::Path;
+use wtd::journal::{self, OpenOptions};
+use wtd::{parse_tasks, public_tags, render_html, time, CalendarPrivacy, Notes, RenderOptions, Task};
+
+const SOURCE: &str = "# 10/12/26
+## Monday
+- [ ] 1:1 with Ann @10AM+30m +journal #id:ann
+- [ ] Standup @9AM+15m
+## Thursday
+- [ ] 1:1 with Ann @10AM+30m +journal
+";
+
+fn parse() -> Vec<Task> {
+    return parse_tasks(SOURCE).unwrap_or_else(|errors| panic!("{:?}", errors));
+}
+
+// A new entry is written from the template, and an existing one left alone.
+#[test]
+fn entries() {
+    let dir = std::env::temp_dir().join(format!("wtd-journal-{}", std::process::id()));
+    let tags = public_tags();
+    let on = |date| OpenOptions { dir: Path::new("notes"), template: None, on: time::ymd(2026, 10, date), public_tags: &tags };
+    let monday = journal::entry(&parse(), "ann", &dir, &on(12));
+    let written = std::fs::read_to_string(dir.join("notes/2026-10-12.md"));
+    std::fs::write(dir.join("notes/2026-10-12.md"), "Talked about [[ann]].\n").expect("the entry is written");
+    let again = journal::entry(&parse(), "1:1 with ann", &dir, &on(12));
+    let kept = std::fs::read_to_string(dir.join("notes/2026-10-12.md"));
+    let untagged = journal::entry(&parse(), "standup", &dir, &on(12));
+    let other_day = journal::entry(&parse(), "1:1 with ann", &dir, &on(13));
+    let loaded = journal::load(&dir, Path::new("notes"));
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(monday, Ok(dir.join("notes/2026-10-12.md")));
+    assert_eq!(written.expect("the entry is there"), "# Mon 10/12/26\n\n## 1:1 with Ann\n");
+    assert_eq!(again, Ok(dir.join("notes/2026-10-12.md")));
+    assert_eq!(kept.expect("the entry is there"), "Talked about [[ann]].\n");
+    assert_eq!(untagged, Err("\"Standup\" on Mon 10/12/26 isn't tagged +journal; tag it to keep a journal of it".to_string()));
+    assert_eq!(other_day, Err("\"1:1 with ann\" isn't on Tue 10/13/26; it's on Mon 10/12/26, Thu 10/15/26".to_string()));
+    assert_eq!(loaded.dates.into_iter().collect::<Vec<_>>(), [time::ymd(2026, 10, 12).expect("a date")]);
+    assert_eq!(loaded.mentions["ann"], [time::ymd(2026, 10, 12).expect("a date")]);
+}
+
+// Only the private calendar links to the journal.
+#[test]
+fn calendar_links() {
+    let mut notes = Notes::default();
+    notes.journal.dir = "notes".into();
+    notes.journal.dates.insert(time::ymd(2026, 10, 12).expect("a date"));
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 7, ..RenderOptions::default() };
+    let private = render_html(&parse(), &notes, CalendarPrivacy::Private, &options);
+    assert_eq!(private.matches("href=\"notes/2026-10-12.md\"").count(), 1, "{}", private);
+    let public = render_html(&parse(), &notes, CalendarPrivacy::Public, &options);
+    assert!(!public.contains("notes/"), "{}", public);
+}
@@ -0,0 +1,62 @@
+// What `wtd lint` reports about a task file.
+#![allow(clippy::needless_return)]
+use chrono::Weekday;
+use wtd::lint::{lint, LintOptions};
+use wtd::policy::{Enforce, Policy};
+use wtd::{public_tags, ParseOptions};
+
+fn problems(name: &str, source: &str) -> Vec<String> {
+    return problems_with(name, source, &[]);
+}
+
+fn problems_with(name: &str, source: &str, policies: &[Policy]) -> Vec<String> {
+    let path = std::env::temp_dir().join(format!("wtd-lint-{}-{}.md", name, std::process::id()));
+    std::fs::write(&path, source).expect("the task file is written");
+    let options = LintOptions { parse: ParseOptions::default(), week_start: Weekday::Mon, public_tags: &public_tags(), policies, plugins: &[] };
+    let problems = lint(&path, &options);
+    std::fs::remove_file(&path).ok();
+    let prefix = format!("{}:", path.display());
+    return problems.unwrap_or_else(|why| panic!("{}", why)).iter()
+        .map(|problem| problem.strip_prefix(&prefix).unwrap_or(problem).to_string())
+        .collect();
+}
+
+#[test]
+fn overlapping_tasks() {
+    let source = "# 10/12/26\n## Monday\n- [ ] Standup @9AM+30m\n- [ ] Dentist @9:15AM+1h; Lunch @10:15AM+1h\n- [ ] Trip @8PM--+1d10AM\n## Tuesday\n- [ ] Breakfast @9AM+1h\n";
+    assert_eq!(problems("overlaps", source), [
+        "4: 9:15AM--10:15AM overlaps 9:00AM--9:30AM on line 3",
+        "7: 9:00AM--10:00AM overlaps 8:00PM--+1d10:00AM on line 5",
+    ]);
+}
+
+#[test]
+fn misplaced_day_headers() {
+    let source = "# 10/15/26\n## Thursday\n## Sunday\n## Monday\n## Thursday\n";
+    assert_eq!(problems("days", source), [
+        "4: '## Monday' under '# 10/15/26' falls on Mon 10/19/26, in the following week; start a new '# ' block for it",
+        "5: '## Thursday' is already under '# 10/15/26'",
+    ]);
+}
+
+#[test]
+fn untimed_public_tasks() {
+    let source = "# 10/12/26\n## Monday\n- [ ] Hike +rough\n- [ ] Holiday @allday +busy\n- [ ] Groceries\n";
+    assert_eq!(problems("untimed", source), ["3: tagged +rough but has no time, so the public calendar can't show it"]);
+}
+
+#[test]
+fn repeated_ids() {
+    let source = "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m #id:standup\n## Tuesday\n- [ ] Standup @9AM+15m #id:standup\n";
+    assert_eq!(problems("ids", source), ["5: #id:standup is already the id of the task on line 3"]);
+}
+
+#[test]
+fn flagged_policies() {
+    let policies = [
+        Policy { tag: "work".to_string(), hours: (9, 18), enforce: Enforce::Flag },
+        Policy { tag: "personal".to_string(), hours: (18, 24), enforce: Enforce::Busy },
+    ];
+    let source = "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +work\n- [ ] Deploy @8PM+1h +work\n- [ ] Gym @7AM+1h +personal\n";
+    assert_eq!(problems_with("policies", source, &policies), ["4: +work task outside 9:00--18:00"]);
+}
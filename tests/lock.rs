@@ -0,0 +1,54 @@
+// Editing the task file while something else does.
+#![allow(clippy::needless_return)]
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use wtd::lock::{lock, write_checked};
+
+fn dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("wtd-lock-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    return dir;
+}
+
+// A second editor waits for the first to finish.
+#[test]
+fn edits_take_turns() {
+    let dir = dir("turns");
+    let path = dir.join("wtd.md");
+    let first = lock(&path).unwrap_or_else(|why| panic!("{}", why));
+    let (sender, receiver) = mpsc::channel();
+    let waiting = path.clone();
+    let second = thread::spawn(move || {
+        let _lock = lock(&waiting).unwrap_or_else(|why| panic!("{}", why));
+        sender.send(()).expect("the test is listening");
+    });
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    drop(first);
+    assert!(receiver.recv_timeout(Duration::from_secs(10)).is_ok());
+    second.join().expect("the second editor finishes");
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+// Changes made since the file was read aren't written over, and a symlinked
+// task file stays a symlink.
+#[cfg(unix)]
+#[test]
+fn concurrent_changes_are_kept() {
+    let dir = dir("changes");
+    let path = dir.join("wtd.md");
+    std::fs::write(&path, "edited elsewhere\n").expect("wtd.md is written");
+    let refused = write_checked(&path, "as read\n", "as edited\n");
+    let kept = std::fs::read_to_string(&path);
+    std::os::unix::fs::symlink(&path, dir.join("link.md")).expect("the link is made");
+    let written = write_checked(&dir.join("link.md"), "edited elsewhere\n", "as edited\n");
+    let is_link = std::fs::symlink_metadata(dir.join("link.md")).map(|metadata| metadata.file_type().is_symlink());
+    let contents = std::fs::read_to_string(&path);
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert!(refused.expect_err("the file changed").contains("was changed by another program"));
+    assert_eq!(kept.expect("wtd.md is there"), "edited elsewhere\n");
+    assert_eq!(written, Ok(()));
+    assert!(is_link.expect("the link is there"));
+    assert_eq!(contents.expect("wtd.md is there"), "as edited\n");
+}
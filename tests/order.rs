@@ -0,0 +1,51 @@
+// The order cmp_tasks puts tasks in.
+#![allow(clippy::needless_return)]
+use wtd::{cmp_tasks, parse_tasks};
+
+// Tasks differing only in their '#id:' still have an order, so which comes
+// first doesn't depend on the file.
+#[test]
+fn ids_break_ties() {
+    let tasks = parse_tasks("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m #id:b\n- [ ] Standup @9AM+15m #id:a\n")
+        .unwrap_or_else(|errors| panic!("{:?}", errors));
+    assert!(cmp_tasks(&tasks[0], &tasks[1]).is_gt());
+    assert!(cmp_tasks(&tasks[1], &tasks[0]).is_lt());
+}
+
+// Dates, then all-day tasks, then start times with untimed tasks last, then
+// shorter first, then the rest; and the same tasks come out the same way
+// whatever order they went in.
+#[test]
+fn total_order() {
+    let source = "# 10/12/26
+## Tuesday
+- [ ] Lunch @12PM+1h
+## Monday
+- [ ] Groceries
+- [ ] Review @9AM+1h +busy
+- [ ] Review @9AM+1h
+- [x] Review @9AM+1h
+- [ ] Standup @9AM+15m
+- [ ] Holiday @allday
+";
+    let mut tasks = parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+    tasks.sort_by(cmp_tasks);
+    let order: Vec<(&str, &[String], bool)> = tasks.iter().map(|t| (t.details.as_str(), t.tags.as_slice(), t.done)).collect();
+    assert_eq!(order, [
+        ("Holiday", &[][..], false),
+        ("Standup", &[][..], false),
+        ("Review", &[][..], false),
+        ("Review", &[][..], true),
+        ("Review", &["busy".to_string()][..], false),
+        ("Groceries", &[][..], false),
+        ("Lunch", &[][..], false),
+    ]);
+    let mut reversed: Vec<_> = tasks.iter().rev().cloned().collect();
+    reversed.sort_by(cmp_tasks);
+    assert_eq!(reversed, tasks);
+    for a in &tasks {
+        for b in &tasks {
+            assert_eq!(cmp_tasks(a, b), cmp_tasks(b, a).reverse());
+        }
+    }
+}
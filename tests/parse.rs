@@ -0,0 +1,302 @@
+// Reading task files with parse_tasks.
+#![allow(clippy::needless_return)]
+use chrono::Datelike;
+use wtd::{cmp_tasks, last_date, load, load_notes, parse_notes_with, parse_tasks, parse_tasks_with, sources, split_days, time, ParseOptions, Span, Task};
+
+fn parse(source: &str) -> Vec<Task> {
+    return parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+}
+
+fn at(hour: u32, minute: u32) -> Option<chrono::NaiveTime> {
+    return time::hms(hour, minute, 0);
+}
+
+// Malformed times are errors, not panics.
+#[test]
+fn malformed_times() {
+    for time in ["M", "éM", "PM", "9XM", "é"] {
+        let source = format!("# 10/12/26\n## Monday\n- [ ] Standup @{}+1h\n", time);
+        assert!(parse_tasks(&source).is_err(), "{}", time);
+    }
+    let tasks = parse_tasks("# 10/12/26\n## Monday\n- [ ] Standup @9AM+1h\n- [ ] Lunch @12:30PM+1h\n")
+        .unwrap_or_else(|errors| panic!("{:?}", errors));
+    assert_eq!(tasks.len(), 2);
+}
+
+// Each task on a line has its own time and tags.
+#[test]
+fn semicolon_separated_tasks() {
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Bank @10AM+15m; Post office @11AM+15m +errand; Call mom\n");
+    let summary: Vec<(&str, Option<chrono::NaiveTime>, &[String])> = tasks.iter()
+        .map(|t| (t.details.as_str(), t.start_time, t.tags.as_slice()))
+        .collect();
+    assert_eq!(summary, [
+        ("Bank", at(10, 0), &[][..]),
+        ("Post office", at(11, 0), &["errand".to_string()][..]),
+        ("Call mom", None, &[][..]),
+    ]);
+    assert!(tasks.iter().all(|t| t.date == time::ymd(2026, 10, 12).expect("a valid date")));
+
+    // Continuation lines add to the line's last task.
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Bank @10AM+15m; Post office\n  @11AM+15m +errand\n");
+    assert_eq!((tasks[0].start_time, tasks[0].tags.len()), (at(10, 0), 0));
+    assert_eq!((tasks[1].start_time, tasks[1].tags.as_slice()), (at(11, 0), &["errand".to_string()][..]));
+}
+
+#[test]
+fn all_day_tasks() {
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Holiday @allday +busy\n");
+    assert!(tasks[0].all_day);
+    assert_eq!((tasks[0].start_time, tasks[0].end_time, tasks[0].details.as_str()), (None, None, "Holiday"));
+    for source in ["- [ ] Holiday @allday @9AM+1h", "- [ ] Holiday @9AM+1h @ALLDAY"] {
+        let errors = parse_tasks(&format!("# 10/12/26\n## Monday\n{}\n", source)).expect_err(source);
+        assert_eq!(errors[0].message, "An all-day task can't also have a time");
+    }
+}
+
+// A span runs from its start on the first day to its end on the last, and is
+// cut into a task per day for anything looking at one day at a time.
+#[test]
+fn spans_of_days() {
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Conference @6PM--+2d10AM +busy\n");
+    let (monday, wednesday) = (time::ymd(2026, 10, 12).expect("a valid date"), time::ymd(2026, 10, 14).expect("a valid date"));
+    assert_eq!((tasks[0].date, tasks[0].start_time, tasks[0].end_time), (monday, at(18, 0), Some(time::end_of_day())));
+    assert_eq!(tasks[0].span, Some(Span { last: wednesday, end: at(10, 0).expect("a valid time") }));
+    assert_eq!(last_date(&tasks[0]), wednesday);
+    let days: Vec<_> = split_days(&tasks).iter().map(|t| (t.date.day(), t.start_time, t.end_time, t.span)).collect();
+    assert_eq!(days, [
+        (12, at(18, 0), Some(time::end_of_day()), None),
+        (13, Some(time::midnight()), Some(time::end_of_day()), None),
+        (14, Some(time::midnight()), at(10, 0), None),
+    ]);
+    for token in ["@6PM--+0d10AM", "@6PM--+2x10AM", "@6PM--+99999999999d10AM", "@6PM--+2d"] {
+        let source = format!("# 10/12/26\n## Monday\n- [ ] Conference {}\n", token);
+        assert!(parse_tasks(&source).is_err(), "{}", token);
+    }
+}
+
+#[test]
+fn week_header_dates() {
+    let monday = time::ymd(2024, 3, 4).expect("a valid date");
+    for header in ["3/4/24", "03/04/24", "3/4/2024", "2024-03-04", "Week of 2024-03-04"] {
+        let tasks = parse(&format!("# {}\n## Tuesday\n- [ ] Standup @9AM+15m\n", header));
+        assert_eq!(tasks[0].date, monday.succ_opt().expect("a valid date"), "{}", header);
+    }
+    // Configured formats are tried first.
+    let options = ParseOptions { date_formats: vec!["%d.%m.%Y".to_string(), "%d/%m/%y".to_string()], ..ParseOptions::default() };
+    for header in ["4.3.2024", "4/3/24"] {
+        let tasks = parse_tasks_with(&format!("# {}\n## Monday\n- [ ] Standup @9AM+15m\n", header), &options)
+            .unwrap_or_else(|errors| panic!("{:?}", errors));
+        assert_eq!(tasks[0].date, monday, "{}", header);
+    }
+    // And apply to OOO lines too.
+    let notes = parse_notes_with("# 4.3.2024\n## OOO 6.3.2024--8.3.2024\n", &options);
+    assert_eq!(notes.out_of_office.len(), 1);
+    assert_eq!(notes.out_of_office[0].first, time::ymd(2024, 3, 6).expect("a valid date"));
+}
+
+// Files saved on Windows, or pasted from elsewhere, read the same.
+#[test]
+fn line_endings_and_unusual_whitespace() {
+    let plain = parse("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n");
+    for source in [
+        "\u{feff}# 10/12/26\r\n## Monday\r\n- [ ] Standup @9AM+15m +busy\r\n",
+        "# 10/12/26\r## Monday\r- [ ] Standup @9AM+15m +busy\r",
+        "# 10/12/26\n## Monday\n- [ ] Standup\u{a0}@9AM+15m\u{2003}+busy\u{200b}\n",
+        "# 10/12/26\n## Monday\n-\u{a0}[\u{a0}] Standup\t@9AM+15m +busy\n",
+    ] {
+        assert_eq!(parse(source), plain, "{:?}", source);
+    }
+}
+
+// Days resolve forward from the week's date, into the next month or year.
+#[test]
+fn weeks_crossing_leap_days_and_years() {
+    let tasks = parse("# 2/27/24\n## Thursday\n- [ ] Leap @9AM+1h\n## Friday\n- [ ] March @9AM+1h\n");
+    assert_eq!(tasks[0].date, time::ymd(2024, 2, 29).expect("a valid date"));
+    assert_eq!(tasks[1].date, time::ymd(2024, 3, 1).expect("a valid date"));
+    let source = "# 12/31/26\n## Thursday\n- [ ] Eve @9AM+1h\n## Monday\n- [ ] Back @9AM+1h\n";
+    let tasks = parse(source);
+    assert_eq!(tasks[0].date, time::ymd(2026, 12, 31).expect("a valid date"));
+    assert_eq!(tasks[1].date, time::ymd(2027, 1, 4).expect("a valid date"));
+    // Crossing into the new year is only warned about, unless parsing strictly.
+    let strict = ParseOptions { strict: true, quiet: true, ..ParseOptions::default() };
+    let errors = parse_tasks_with(source, &strict).expect_err("the block crosses a year");
+    assert_eq!((errors.len(), errors[0].line), (1, 4));
+}
+
+// Misspelled days stop parsing, unless it's lenient and one day is closest.
+#[test]
+fn misspelled_day_headers() {
+    let source = |day: &str| format!("# 10/12/26\n## {}\n- [ ] Standup @9AM+15m\n", day);
+    let errors = parse_tasks(&source("Tuessday")).expect_err("Tuessday isn't a day");
+    assert_eq!(errors[0].message, "'Tuessday' is not a day of the week");
+    let lenient = ParseOptions { lenient: true, quiet: true, ..ParseOptions::default() };
+    for (day, expected) in [("Tuessday", 13), ("wednsday", 14), ("Fri", 16), ("Mnoday", 12)] {
+        let tasks = parse_tasks_with(&source(day), &lenient).unwrap_or_else(|errors| panic!("{}: {:?}", day, errors));
+        assert_eq!(tasks[0].date.day(), expected, "{}", day);
+    }
+    // Nothing is guessed when no day is within two edits.
+    for day in ["Weekend", "Tusday Thursday", "Sunday!!!"] {
+        assert!(parse_tasks_with(&source(day), &lenient).is_err(), "{}", day);
+    }
+}
+
+// A line ending in '\' keeps its line break; lines indented deeper than a
+// checklist item go on with the item, and shallower ones with the task.
+#[test]
+fn hard_breaks_and_continuation_lines() {
+    let tasks = parse("# 10/12/26
+## Monday
+- [ ] Trip to the lake\\
+  pack the night before
+  - [ ] Bring the tent\\
+    and the poles
+    and stakes
+  in the blue bag @9AM+1h
+");
+    assert_eq!(tasks[0].details, "Trip to the lake\npack the night before in the blue bag");
+    assert_eq!(tasks[0].start_time, at(9, 0));
+    assert_eq!(tasks[0].subtasks.iter().map(|item| item.text.as_str()).collect::<Vec<&str>>(), ["Bring the tent\nand the poles and stakes"]);
+}
+
+// Comments never reach the tasks, but '//' inside a word, as in a URL, isn't
+// one.
+#[test]
+fn comments_are_ignored() {
+    let tasks = parse("<!-- plans for the week -->
+# 10/12/26
+## Monday
+- [ ] Dentist @9AM+1h +busy // ask about the crown
+- [ ] Read https://example.com <!-- @2PM+1h -->
+// - [ ] Not a task
+");
+    let summary: Vec<(&str, Option<chrono::NaiveTime>)> = tasks.iter().map(|t| (t.details.as_str(), t.start_time)).collect();
+    assert_eq!(summary, [("Dentist", at(9, 0)), ("Read https://example.com", None)]);
+}
+
+// Included files' tasks and notes are read along with the including file's,
+// labelled with the file they came from; a file including itself is an error.
+#[test]
+fn includes() {
+    let dir = std::env::temp_dir().join(format!("wtd-include-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("work")).expect("the directory is made");
+    std::fs::write(dir.join("wtd.md"), "#include work/work.md\n# 10/12/26\n## Monday\n- [ ] Run @7AM+1h\n").expect("wtd.md is written");
+    std::fs::write(dir.join("work/work.md"), "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +file:agenda.md\n## OOO 10/16\n").expect("work.md is written");
+    let tasks = load(&dir.join("wtd.md"), &ParseOptions::default());
+    let notes = load_notes(&dir.join("wtd.md"), &ParseOptions::default());
+    let read = sources(&dir.join("wtd.md"), &ParseOptions::default());
+    std::fs::write(dir.join("work/work.md"), "#include ../wtd.md\n").expect("work.md is written");
+    let cycle = load(&dir.join("wtd.md"), &ParseOptions::default());
+    std::fs::remove_dir_all(&dir).ok();
+
+    let tasks = tasks.unwrap_or_else(|why| panic!("{}", why));
+    let summary: Vec<(&str, Option<&str>)> = tasks.iter().map(|t| (t.details.as_str(), t.source.as_deref())).collect();
+    assert_eq!(summary, [("Run", None), ("Standup", Some("work"))]);
+    assert!(tasks[1].attachments[0].ends_with("work/agenda.md"), "{:?}", tasks[1].attachments);
+    assert_eq!(notes.out_of_office.len(), 1);
+    assert_eq!(read, [dir.join("wtd.md"), dir.join("work/work.md")]);
+    let why = cycle.expect_err("an error");
+    assert!(why.contains("includes itself"), "{}", why);
+}
+
+// '!1' to '!5' are priorities, and the day's untimed tasks sort most
+// important first; other '!' words are just words.
+#[test]
+fn priorities() {
+    let mut tasks = parse("# 10/12/26\n## Monday\n- [ ] Groceries\n- [ ] Laundry !3\n- [ ] File taxes !1 !!\n- [ ] Standup @9AM+15m !5\n");
+    tasks.sort_by(cmp_tasks);
+    let summary: Vec<(&str, Option<u8>)> = tasks.iter().map(|t| (t.details.as_str(), t.priority)).collect();
+    assert_eq!(summary, [("Standup", Some(5)), ("File taxes !!", Some(1)), ("Laundry", Some(3)), ("Groceries", None)]);
+    for level in ["!0", "!6", "!10"] {
+        assert!(parse_tasks(&format!("# 10/12/26\n## Monday\n- [ ] Taxes {}\n", level)).is_err(), "{}", level);
+    }
+}
+
+// '>' lines are prose: run together into paragraphs, split by a bare '>', and
+// not read for times and tags.
+#[test]
+fn note_lines() {
+    let tasks = parse("# 10/12/26
+## Monday
+- [ ] Offsite @9AM--5PM +public
+  > Meet at the north entrance @ 8:45;
+  > bring +1s if you like.
+  >
+  > Lunch is provided.
+  +busy
+");
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].notes, ["Meet at the north entrance @ 8:45; bring +1s if you like.", "Lunch is provided."]);
+    assert_eq!(tasks[0].tags, ["public", "busy"]);
+}
+
+#[test]
+fn locations() {
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Coffee with Sam @10AM+1h @loc:\"Coffee Bar, 5th Ave\" +public\n- [ ] Run @loc:Park\n");
+    let places: Vec<(&str, Option<&str>)> = tasks.iter().map(|t| (t.details.as_str(), t.location.as_deref())).collect();
+    assert_eq!(places, [("Coffee with Sam", Some("Coffee Bar, 5th Ave")), ("Run", Some("Park"))]);
+}
+
+// A checklist item indented deeper than the one above it is nested under it.
+#[test]
+fn nested_checklists() {
+    let tasks = parse("# 10/12/26
+## Monday
+- [ ] Launch @10AM+1h
+  - [X] Write the post
+    - [x] Draft
+      - [ ] Outline
+    - [ ] Edit
+  - [ ] Announce
+");
+    let items: Vec<(&str, bool, usize)> = tasks[0].subtasks.iter().map(|item| (item.text.as_str(), item.done, item.level)).collect();
+    assert_eq!(items, [("Write the post", true, 0), ("Draft", true, 1), ("Outline", false, 2), ("Edit", false, 1), ("Announce", false, 0)]);
+}
+
+// What's otherwise only warned about stops strict parsing, as do tags the
+// config doesn't know.
+#[test]
+fn strict_parsing() {
+    let strict = ParseOptions { strict: true, quiet: true, tags: vec!["busy".to_string()], ..ParseOptions::default() };
+    let lenient = ParseOptions { quiet: true, ..ParseOptions::default() };
+    for source in [
+        "# 10/12/26\n## Monday\n- [] Standup @9AM+15m\n",
+        "# 10/12/26\n## Monday\n* [ ] Standup @9AM+15m\n",
+        "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m <!-- moved?\n",
+        "# 10/12/26\n## Monday\nStandup at nine\n",
+        "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +focus\n",
+    ] {
+        assert!(parse_tasks_with(source, &lenient).is_ok(), "{}", source);
+        assert!(parse_tasks_with(source, &strict).is_err(), "{}", source);
+    }
+    let known = "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy +habit:run +done:9:15 +meetings\n";
+    assert!(parse_tasks_with(known, &strict).is_ok());
+    let errors = parse_tasks_with("# 10/12/26\n## Monday\n- [ ] Standup +focus\n", &strict).expect_err("an error");
+    assert_eq!(errors.iter().map(|error| error.to_string()).collect::<Vec<String>>(),
+               ["3:15: +focus isn't a tag the config knows; list it under tags in [parse] to use it with --strict"]);
+}
+
+// Every line of a file saved on Windows ends in "\r\n", the last one too.
+#[test]
+fn windows_line_endings() {
+    let source = "# 10/12/26\n## Monday\n- [ ] Offsite @9AM--5PM +public\n  > North entrance\n  - [ ] Pack\n## OOO 10/14\n";
+    assert_eq!(parse(&source.replace('\n', "\r\n")), parse(source));
+    assert_eq!(parse_notes_with(&source.replace('\n', "\r\n"), &ParseOptions::default()).out_of_office.len(), 1);
+}
+
+// The whole file is read past an error, and each one is placed at the token
+// it's about.
+#[test]
+fn every_error_with_its_column() {
+    let source = "# 10/12/26\n## Monday\n- [ ] Standup @9AM+1x\n## Funday\n- [ ] Lunch @noon+1h\n## Tuesday\n- [ ] Dentist @3PM--2PM\n";
+    let errors = parse_tasks(source).expect_err("errors");
+    let errors: Vec<(usize, usize, &str)> = errors.iter().map(|error| (error.line, error.column, error.token.as_str())).collect();
+    assert_eq!(errors, [(3, 15, "@9AM+1x"), (4, 4, "Funday"), (7, 15, "@3PM--2PM")]);
+
+    let path = std::env::temp_dir().join(format!("wtd-errors-{}.md", std::process::id()));
+    std::fs::write(&path, "# 10/12/26\n- [ ] Standup\n").expect("the task file is written");
+    let why = load(&path, &ParseOptions::default());
+    std::fs::remove_file(&path).ok();
+    assert_eq!(why.expect_err("an error"), format!("{}:2:1: Task before any '## ' day header", path.display()));
+}
@@ -0,0 +1,65 @@
+// WebAssembly plugins that ask too much of wtd are stopped.
+#![allow(clippy::needless_return)]
+use std::collections::HashMap;
+use wtd::plugin;
+
+// A WebAssembly section: its id, then its contents' length (all under 128
+// bytes here, so one byte) and the contents.
+fn section(id: u8, contents: &[u8]) -> Vec<u8> {
+    return [&[id, contents.len() as u8][..], contents].concat();
+}
+
+// A plugin with one page of memory, whose wtd_alloc returns 0 and whose
+// wtd_render runs `body`, the code of an () -> i64 function without its
+// trailing `end`.
+fn plugin_with_render(name: &str, body: &[u8]) -> plugin::Plugin {
+    let mut exports = vec![3];
+    for (export, kind, index) in [("memory", 2, 0), ("wtd_alloc", 0, 0), ("wtd_render", 0, 1)].iter().copied() {
+        exports.push(export.len() as u8);
+        exports.extend(export.bytes());
+        exports.extend([kind, index].iter());
+    }
+    let alloc = [0x00, 0x41, 0x00, 0x0b]; // no locals; i32.const 0; end
+    let render = [&[0x00][..], body, &[0x0b]].concat();
+    let code = [&[2, alloc.len() as u8][..], &alloc, &[render.len() as u8], &render].concat();
+    let wasm = [
+        &b"\0asm\x01\0\0\0"[..],
+        // (i32) -> i32, and (i32, i32) -> i64.
+        &section(1, &[2, 0x60, 1, 0x7f, 1, 0x7f, 0x60, 2, 0x7f, 0x7f, 1, 0x7e]),
+        &section(3, &[2, 0, 1]),
+        &section(5, &[1, 0, 1]),
+        &section(7, &exports),
+        &section(10, &code),
+    ].concat();
+    let path = std::env::temp_dir().join(format!("{}-{}.wasm", name, std::process::id()));
+    std::fs::write(&path, wasm).expect("the plugin is written");
+    let plugin = plugin::load(&path);
+    std::fs::remove_file(&path).ok();
+    return plugin.unwrap_or_else(|why| panic!("{}", why));
+}
+
+fn render(plugin: &plugin::Plugin) -> Result<Vec<u8>, String> {
+    return plugin.render(&[], &HashMap::new());
+}
+
+#[test]
+fn plugins_run() {
+    // i64.const 0: nothing, at address 0.
+    assert_eq!(render(&plugin_with_render("empty", &[0x42, 0x00])), Ok(Vec::new()));
+}
+
+#[test]
+fn output_is_capped() {
+    // i64.const 0xffffffff: 4GiB at address 0.
+    let why = render(&plugin_with_render("huge", &[0x42, 0xff, 0xff, 0xff, 0xff, 0x0f])).expect_err("too much output");
+    assert!(why.contains("more than the 67108864 allowed"), "{}", why);
+}
+
+#[test]
+fn memory_is_limited() {
+    // i32.const 16384; memory.grow; drop; i64.const 0: growing by 1GiB.
+    let why = render(&plugin_with_render("greedy", &[0x41, 0x80, 0x80, 0x01, 0x40, 0x00, 0x1a, 0x42, 0x00])).expect_err("too much memory");
+    assert!(why.contains("Plugin greedy"), "{}", why);
+    // i32.const 1024; memory.grow; drop; i64.const 0: growing by 64MiB is fine.
+    assert_eq!(render(&plugin_with_render("modest", &[0x41, 0x80, 0x08, 0x40, 0x00, 0x1a, 0x42, 0x00])), Ok(Vec::new()));
+}
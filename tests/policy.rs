@@ -0,0 +1,43 @@
+// Per-tag working-hours policies from [[policies]] in the config.
+#![allow(clippy::needless_return)]
+use wtd::config::PolicyConfig;
+use wtd::policy::{apply_public, from_config, violations, Enforce, Policy};
+use wtd::{parse_tasks, Task};
+
+const SOURCE: &str = "# 10/12/26
+## Monday
+- [ ] Climbing @7PM+2h +personal
+- [ ] Call mom @10AM+30m +personal
+- [ ] Review @8PM+1h +work
+- [ ] Standup @9AM+15m +work
+";
+
+fn parse() -> Vec<Task> {
+    return parse_tasks(SOURCE).unwrap_or_else(|errors| panic!("{:?}", errors));
+}
+
+fn policy(tag: &str, hours: &str, enforce: Enforce) -> Result<Vec<Policy>, String> {
+    return from_config(&[PolicyConfig { tag: tag.to_string(), hours: hours.to_string(), enforce }]);
+}
+
+// A "busy" tag's tasks show publicly as busy blocks within its hours, and not
+// at all outside them.
+#[test]
+fn busy_policies() {
+    let policies = policy("+personal", "18-24", Enforce::Busy).unwrap_or_else(|why| panic!("{}", why));
+    let public = apply_public(&parse(), &policies);
+    let public: Vec<(&str, Vec<String>)> = public.iter()
+        .map(|task| (task.details.as_str(), task.tags.clone())).collect();
+    assert_eq!(public, [
+        ("", vec!["busy".to_string()]),
+        ("Review", vec!["work".to_string()]),
+        ("Standup", vec!["work".to_string()]),
+    ]);
+}
+
+#[test]
+fn flag_policies() {
+    let policies = policy("work", "9-18", Enforce::Flag).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(violations(&parse(), &policies), ["Mon 10/12/26 8:00PM--9:00PM: +work task outside 9:00--18:00: Review"]);
+    assert_eq!(policy("work", "18-9", Enforce::Flag).err(), Some("policy for +work: expected hours like 9-17, got '18-9'".to_string()));
+}
@@ -0,0 +1,86 @@
+// Booking focus time into free working hours with `wtd protect`.
+#![allow(clippy::needless_return)]
+use std::process::Command;
+use chrono::{Datelike, Duration, NaiveTime, Weekday};
+use wtd::protect::{plan, ProtectOptions};
+use wtd::{parse_tasks, time, ParseOptions};
+
+const WEEK: &str = "# 10/19/26
+## Monday
+- [ ] Standup @10AM+1h
+## Tuesday
+- [ ] Holiday @allday
+## OOO 10/21
+";
+
+fn options(hours: f64) -> ProtectOptions<'static> {
+    return ProtectOptions {
+        label: "Deep work",
+        hours,
+        work_hours: (9, 17),
+        min_block: Duration::hours(1),
+        max_block: Duration::hours(2),
+        parse: ParseOptions::default(),
+        week_start: Weekday::Mon,
+    };
+}
+
+// The day, start and end of each block `plan` would add.
+fn blocks(source: &str, hours: f64) -> (Vec<(u32, NaiveTime, NaiveTime)>, f64) {
+    return blocks_with(source, &options(hours));
+}
+
+fn blocks_with(source: &str, options: &ProtectOptions) -> (Vec<(u32, NaiveTime, NaiveTime)>, f64) {
+    let tasks = parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+    let protected = plan(&tasks, source, time::ymd(2026, 10, 19).expect("a date"), options);
+    assert!(protected.added.iter().all(|task| task.details == "Deep work" && task.tags == ["self"]));
+    let blocks = protected.added.iter()
+        .map(|task| (task.date.day(), task.start_time.expect("a start"), task.end_time.expect("an end")))
+        .collect();
+    return (blocks, protected.missing);
+}
+
+fn at(hour: u32) -> NaiveTime {
+    return time::hms(hour, 0, 0).expect("a valid time");
+}
+
+// Blocks are spread over the free weekdays, skipping all-day tasks and days
+// out of office.
+#[test]
+fn blocks_are_spread_over_the_week() {
+    assert_eq!(blocks(WEEK, 5.0), (vec![(19, at(9), at(10)), (22, at(9), at(11)), (23, at(9), at(11))], 0.0));
+    let (all, missing) = blocks(WEEK, 40.0);
+    assert_eq!(all.len(), 12);
+    assert_eq!(missing, 40.0 - 7.0 - 8.0 - 8.0);
+}
+
+// Blocks already booked count towards the hours.
+#[test]
+fn booked_blocks_count() {
+    let booked = format!("{}## Thursday\n- [ ] Deep work @1PM+3h +self\n", WEEK);
+    assert_eq!(blocks(&booked, 5.0), (vec![(19, at(9), at(10)), (22, at(9), at(10))], 0.0));
+    assert_eq!(blocks(&booked, 3.0), (vec![], 0.0));
+}
+
+// Blocks of no length book nothing rather than going round forever, and
+// hours past the week's are as many as the week has.
+#[test]
+fn degenerate_requests_end() {
+    let zero = |min_block, max_block| ProtectOptions { min_block, max_block, ..options(3.0) };
+    assert_eq!(blocks_with(WEEK, &zero(Duration::zero(), Duration::zero())), (vec![], 3.0));
+    assert_eq!(blocks_with(WEEK, &zero(Duration::zero(), Duration::hours(2))).0[0], (19, at(9), at(10)));
+    let (all, missing) = blocks(WEEK, 1e300);
+    assert_eq!((all.len(), missing), (12, 168.0 - 7.0 - 8.0 - 8.0));
+    assert_eq!(blocks(WEEK, f64::NAN), (vec![], 0.0));
+}
+
+#[test]
+fn degenerate_arguments_are_refused() {
+    for args in [["--hours", "3", "--min-block", "0"], ["--hours", "3", "--max-block", "0"], ["--hours", "1e300", "--label", "Focus"], ["--hours", "NaN", "--label", "Focus"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_wtd"))
+            .args(["--config", "/dev/null", "missing.md", "protect"]).args(args)
+            .output()
+            .expect("wtd runs");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+    }
+}
@@ -0,0 +1,73 @@
+// The default run, which publishes the calendars and the other formats
+// configured under [output].
+#![allow(clippy::needless_return)]
+use std::fs;
+use std::process::Command;
+use chrono::Weekday;
+use wtd::time;
+
+#[test]
+fn other_formats_are_published_with_the_pages() {
+    let dir = std::env::temp_dir().join(format!("wtd-publish-{}", std::process::id()));
+    fs::create_dir_all(&dir).expect("the directory is made");
+    // Today, so the pages' grids show it.
+    let today = time::today();
+    let week = time::format_header_date(time::week_start(today, Weekday::Mon));
+    fs::write(dir.join("wtd.md"), format!("# {}\n## {}\n- [ ] Launch party @5PM+2h +public\n- [ ] Dentist @9AM+1h +busy\n",
+                                          week, time::format_weekday(today)))
+        .expect("wtd.md is written");
+    fs::write(dir.join("wtd.toml"), "[output]\nics = \"out/calendar.ics\"\njson = \"out/calendar.json\"\nyear = \"out/year.svg\"\n")
+        .expect("the config is written");
+    let status = Command::new(env!("CARGO_BIN_EXE_wtd"))
+        .args(["--config", "wtd.toml", "--quiet"])
+        .current_dir(&dir)
+        .status()
+        .expect("wtd runs");
+    let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap_or_else(|why| panic!("{}: {}", name, why));
+    let (public, private, ics, json, year) =
+        (read("public.html"), read("private.html"), read("out/calendar.ics"), read("out/calendar.json"), read("out/year.svg"));
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(status.success());
+    assert!(private.contains("Dentist"));
+    for file in [&public, &ics, &json, &year].iter() {
+        assert!(!file.contains("Dentist"), "{}", file);
+    }
+    assert!(ics.starts_with("BEGIN:VCALENDAR") && ics.contains("Launch party"), "{}", ics);
+    assert!(json.contains("\"Launch party\""), "{}", json);
+    assert!(year.starts_with("<svg"), "{}", year);
+}
+
+// Attachments are copied beside the pages that show them, and only files
+// inside the task file's directory can be attached.
+#[test]
+fn attachments_are_published() {
+    let dir = std::env::temp_dir().join(format!("wtd-attachments-{}", std::process::id()));
+    fs::create_dir_all(dir.join("docs")).expect("the directory is made");
+    let today = time::today();
+    let week = time::format_header_date(time::week_start(today, Weekday::Mon));
+    fs::write(dir.join("wtd.md"), format!("# {}\n## {}\n- [ ] Talk @5PM+1h +public +file:docs/slides.pdf +file:../secret.txt +file:missing.pdf\n\
+                                           - [ ] Review @9AM+1h +busy +file:docs/notes.txt\n",
+                                          week, time::format_weekday(today)))
+        .expect("wtd.md is written");
+    fs::write(dir.join("docs/slides.pdf"), "slides").expect("the slides are written");
+    fs::write(dir.join("docs/notes.txt"), "notes").expect("the notes are written");
+    fs::write(dir.join("wtd.toml"), "[output]\npublic = \"out/public.html\"\nprivate = \"private/private.html\"\n")
+        .expect("the config is written");
+    let output = Command::new(env!("CARGO_BIN_EXE_wtd"))
+        .args(["--config", "wtd.toml"])
+        .current_dir(&dir)
+        .output()
+        .expect("wtd runs");
+    let public = fs::read_to_string(dir.join("out/public.html")).unwrap_or_default();
+    let copied = |path: &str| dir.join(path).is_file();
+    let (slides, public_notes, private_notes) = (copied("out/docs/slides.pdf"), copied("out/docs/notes.txt"), copied("private/docs/notes.txt"));
+    fs::remove_dir_all(&dir).ok();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Ignoring attachment ../secret.txt"), "{}", stderr);
+    assert!(stderr.contains("Ignoring attachment missing.pdf"), "{}", stderr);
+    assert!(public.contains("<li><b>Files:</b> <a href=\"docs/slides.pdf\">slides.pdf</a></li>"), "{}", public);
+    assert!(slides && private_notes && !public_notes);
+}
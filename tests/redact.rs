@@ -0,0 +1,130 @@
+// What the redaction rules leave of tasks for the public.
+#![allow(clippy::needless_return)]
+use wtd::config::RedactConfig;
+use chrono::NaiveTime;
+use wtd::{parse_notes, parse_tasks, time};
+use wtd::redact::{Privacy, Redactor};
+
+const SOURCE: &str = "# 10/12/26
+## Monday
+- [ ] Call Alice on 555-0134 @9AM+15m +public
+  - [ ] Ask alice about the ALICE project
+  > Her number is 555-0199
+## Tuesday
+- [ ] Malice aforethought @1PM+1h +public
+";
+
+fn redact(config: &RedactConfig) -> Vec<(String, Vec<String>, Vec<String>)> {
+    let tasks = parse_tasks(SOURCE).unwrap_or_else(|errors| panic!("{:?}", errors));
+    let redactor = Redactor::new(config, &[]).unwrap_or_else(|why| panic!("{}", why));
+    return redactor.redact(&tasks).into_iter()
+        .map(|task| (task.details, task.subtasks.into_iter().map(|subtask| subtask.text).collect(), task.notes))
+        .collect();
+}
+
+// Keywords match whole words in any case; patterns match anywhere, and both
+// reach the checklist and notes.
+#[test]
+fn keywords_and_patterns() {
+    let config = RedactConfig {
+        keywords: vec!["alice".to_string()],
+        patterns: vec![r"\d{3}-\d{4}".to_string()],
+        ..RedactConfig::default()
+    };
+    assert_eq!(redact(&config), [
+        ("Call [redacted] on [redacted]".to_string(),
+         vec!["Ask [redacted] about the [redacted] project".to_string()],
+         vec!["Her number is [redacted]".to_string()]),
+        ("Malice aforethought".to_string(), vec![], vec![]),
+    ]);
+
+    let config = RedactConfig { replacement: Some("$1 ***".to_string()), ..config };
+    assert_eq!(redact(&config)[0].0, "Call $1 *** on $1 ***");
+}
+
+// Attachments named with a redacted word are dropped rather than published.
+#[test]
+fn attachment_names() {
+    let tasks = parse_tasks("# 10/12/26\n## Monday\n- [ ] Talk @5PM+1h +public +file:docs/slides.pdf +file:docs/alice-review.pdf\n")
+        .unwrap_or_else(|errors| panic!("{:?}", errors));
+    let config = RedactConfig { keywords: vec!["alice".to_string()], ..RedactConfig::default() };
+    let redactor = Redactor::new(&config, &[]).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(redactor.redact(&tasks)[0].attachments, ["docs/slides.pdf"]);
+}
+
+#[test]
+fn bad_patterns_are_errors() {
+    let config = RedactConfig { patterns: vec!["(".to_string()], ..RedactConfig::default() };
+    let why = Redactor::new(&config, &[]).err().expect("an error");
+    assert!(why.starts_with("redaction pattern \"(\": "), "{}", why);
+}
+
+// The start, end, and tags of each task, as the public sees them.
+fn times(source: &str, config: &RedactConfig) -> Vec<(Option<NaiveTime>, Option<NaiveTime>, Vec<String>)> {
+    let tasks = parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+    let redactor = Redactor::new(config, &[]).unwrap_or_else(|why| panic!("{}", why));
+    return redactor.redact(&tasks).into_iter().map(|task| (task.start_time, task.end_time, task.tags)).collect();
+}
+
+fn at(hour: u32, minute: u32) -> NaiveTime {
+    return time::time_of_day((hour * 60 + minute) as i64);
+}
+
+const BACK_TO_BACK: &str = "# 10/12/26
+## Monday
+- [ ] Dentist @9AM+30m
+- [ ] Therapy @9:30AM+1h
+- [ ] Lunch @11AM+1h
+- [ ] Standup @9AM+15m +public
+";
+
+// Touching and overlapping private tasks become one busy block.
+#[test]
+fn private_tasks_merge() {
+    let config = RedactConfig { merge_private: true, ..RedactConfig::default() };
+    assert_eq!(times(BACK_TO_BACK, &config), [
+        (Some(at(9, 0)), Some(at(9, 15)), vec!["public".to_string()]),
+        (Some(at(9, 0)), Some(at(10, 30)), vec!["busy".to_string()]),
+        (Some(at(11, 0)), Some(at(12, 0)), vec!["busy".to_string()]),
+    ]);
+}
+
+// Private times move out to the half hour, and an end rounded up past the
+// last half hour is the end of the day; public ones are left as they are.
+#[test]
+fn private_times_round_out() {
+    let source = "# 10/12/26\n## Monday\n- [ ] Dentist @9:10AM+20m\n- [ ] Standup @9:10AM+20m +public\n- [ ] Late @11:40PM--11:50PM\n";
+    let config = RedactConfig { round_minutes: Some(30), ..RedactConfig::default() };
+    assert_eq!(times(source, &config), [
+        (Some(at(9, 0)), Some(at(9, 30)), vec![]),
+        (Some(at(9, 10)), Some(at(9, 30)), vec!["public".to_string()]),
+        (Some(at(23, 30)), Some(time::end_of_day()), vec![]),
+    ]);
+    for minutes in [0, 7, 90] {
+        let config = RedactConfig { round_minutes: Some(minutes), ..RedactConfig::default() };
+        assert!(Redactor::new(&config, &[]).is_err(), "{}", minutes);
+    }
+}
+
+// `--privacy full` leaves only busy blocks, public tasks' included, and no
+// notes but the days away; `busy-only` is the same as merge_private.
+#[test]
+fn privacy_levels() {
+    let source = "Moving house this month.\n# 10/12/26\nPacking week.\n## Monday\n- [ ] Dentist @9AM+30m\n- [ ] Standup @9AM+15m +public\n- [ ] Pack books\n## OOO 10/15--10/16\n";
+    let full = RedactConfig { privacy: Some(Privacy::Full), ..RedactConfig::default() };
+    assert_eq!(times(source, &full), [(Some(at(9, 0)), Some(at(9, 30)), vec!["busy".to_string()])]);
+    let busy_only = RedactConfig { privacy: Some(Privacy::BusyOnly), ..RedactConfig::default() };
+    let merged = RedactConfig { merge_private: true, ..RedactConfig::default() };
+    assert_eq!(times(source, &busy_only), times(source, &merged));
+    assert_eq!(times(source, &busy_only).len(), 3);
+
+    let notes = parse_notes(source);
+    let redactor = Redactor::new(&RedactConfig::default(), &[]).unwrap_or_else(|why| panic!("{}", why));
+    assert_eq!(redactor.redact_notes(&notes).preamble, ["Moving house this month."]);
+    let redactor = redactor.with_privacy(Some(Privacy::Full));
+    let public = redactor.redact_notes(&notes);
+    assert!(public.preamble.is_empty() && public.weeks.is_empty());
+    assert_eq!(public.out_of_office.len(), 1);
+    let tasks = parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+    assert_eq!(redactor.redact(&tasks).len(), 1);
+}
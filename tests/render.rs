@@ -0,0 +1,381 @@
+// Rendering the calendars from task files.
+#![allow(clippy::needless_return)]
+use chrono::Weekday;
+use wtd::{parse_notes, parse_tasks, render_fragment, render_html, render_into_template, time, CalendarPrivacy, CellOverflow, MapLinks, Notes, RenderOptions, Section, TimeFormat, View};
+
+fn tasks(source: &str) -> Vec<wtd::Task> {
+    return parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+}
+
+// An all-day task in a later section's days but outside the grid's.
+#[test]
+fn sections_with_all_day_tasks_outside_the_grid() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Meet @9AM+1h +busy\n## Friday\n- [ ] Holiday @allday +busy\n");
+    let options = RenderOptions {
+        start: time::ymd(2026, 10, 12),
+        sections: vec![
+            Section { view: View::Grid, days: Some(3), weeks: None },
+            Section { view: View::Month, days: None, weeks: Some(2) },
+        ],
+        ..RenderOptions::default()
+    };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains("<table class=\"month\">"));
+}
+
+// The month view is a box per day, weeks starting on the week start, with
+// each task on every day it covers and its time on the first.
+#[test]
+fn month_view() {
+    let source = "# 10/12/26\n## Wednesday\n- [ ] Trip @6PM--+1d10AM +busy\n- [ ] Groceries\n## OOO 10/23\n";
+    let options = RenderOptions {
+        start: time::ymd(2026, 10, 14),
+        sections: vec![Section { view: View::Month, days: None, weeks: Some(2) }],
+        week_start: Weekday::Sun,
+        line_breaks: true,
+        ..RenderOptions::default()
+    };
+    let html = render_fragment(&tasks(source), &parse_notes(source), CalendarPrivacy::Public, &options);
+    let month: Vec<&str> = html.lines().take_while(|line| *line != "</table>").collect();
+    assert_eq!(month, [
+        "<table class=\"month\">",
+        "<tr><th>Sunday</th><th>Monday</th><th>Tuesday</th><th>Wednesday</th><th>Thursday</th><th>Friday</th><th>Saturday</th></tr>",
+        "<tr><td><b>10/11</b></td><td><b>10/12</b></td><td><b>10/13</b></td>\
+         <td><b>10/14</b><div class=\"has-task tag-busy\"><a href=\"#task-a55620b6cf\">6:00PM busy</a></div></td>\
+         <td><b>10/15</b><div class=\"has-task tag-busy\"><a href=\"#task-a55620b6cf\">busy</a></div></td>\
+         <td><b>10/16</b></td><td><b>10/17</b></td></tr>",
+        "<tr><td><b>10/18</b></td><td><b>10/19</b></td><td><b>10/20</b></td><td><b>10/21</b></td>\
+         <td><b>10/22</b></td><td class=\"ooo\"><b>10/23</b></td><td><b>10/24</b></td></tr>",
+    ]);
+}
+
+// Details, tags, and their descriptions are escaped wherever they're shown.
+#[test]
+fn user_text_is_escaped() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] <b>Launch</b> & party @9AM+1h +public +busy\n");
+    let options = RenderOptions {
+        start: time::ymd(2026, 10, 12),
+        public_tags: vec![("busy".to_string(), "<i>Away</i>".to_string())].into_iter().collect(),
+        ..RenderOptions::default()
+    };
+    for privacy in [CalendarPrivacy::Public, CalendarPrivacy::Private].iter().copied() {
+        let html = render_html(&tasks, &Notes::default(), privacy, &options);
+        assert!(!html.contains("<b>Launch") && !html.contains("<i>"), "{}", html);
+        assert!(html.contains("&lt;b&gt;Launch&lt;/b&gt; &amp; party"));
+    }
+}
+
+// All-day tasks take a banner row under the day headers, not the whole column.
+#[test]
+fn all_day_banner() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Holiday @allday +busy\n## Tuesday\n- [ ] Meet @9AM+1h +busy\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 2, ..RenderOptions::default() };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    let banner = html.split("<tr class=\"all-day\">").nth(1).expect("a banner row");
+    let banner = &banner[..banner.find("</tr>").expect("the row ends")];
+    assert!(banner.starts_with("<td><b>All day</b></td>"), "{}", banner);
+    assert_eq!(banner.matches("<td").count(), 3, "{}", banner);
+    assert_eq!(html.matches("<tr class=\"all-day\">").count(), 1);
+}
+
+// The fragment is written exactly the same way every time; see render_fragment
+// for the rules.
+#[test]
+fn fragment_golden_output() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n- [x] Launch @9:30AM+30m +public +busy !1\n- [ ] Plan\n");
+    let options = RenderOptions {
+        start: time::ymd(2026, 10, 12),
+        days: 1,
+        hours: Some((9, 10)),
+        line_breaks: true,
+        ..RenderOptions::default()
+    };
+    let fragment = render_fragment(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert_eq!(fragment, r##"<table class="cells-wrap partial-day">
+<tr><th>Time</th><th>Mon 10/12/26</th></tr>
+<tr><td><b>9AM</b></td><td class="has-task tag-busy" rowspan="1"><a href="#task-fb89cf5d02">busy</a></td></tr>
+<tr><td></td><td></td></tr>
+<tr><td></td><td class="has-task done tag-busy" rowspan="2"><a href="#task-9279a29071">busy: &quot;Launch&quot;</a></td></tr>
+<tr><td></td></tr>
+</table>
+<ul class="legend"><li><div class="has-task tag-busy">busy</div> I will be genuinely busy, e.g., a meeting with others.</li></ul>
+<ul>
+<li id="task-fb89cf5d02">Mon 10/12/26  9:00AM --  9:15AM<ul><li>Tagged <b>busy:</b> I will be genuinely busy, e.g., a meeting with others.</li></ul></li>
+<li id="task-9279a29071" class="done priority-1">Mon 10/12/26  9:30AM -- 10:00AM<ul><li><b>Description:</b> Launch</li><li>Tagged <b>busy:</b> I will be genuinely busy, e.g., a meeting with others.</li></ul></li>
+</ul>
+"##);
+    let unbroken = RenderOptions { line_breaks: false, ..options };
+    assert_eq!(render_fragment(&tasks, &Notes::default(), CalendarPrivacy::Public, &unbroken), fragment.replace('\n', ""));
+}
+
+// A page of your own has its first marker replaced by the calendar.
+#[test]
+fn templates() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let fragment = render_fragment(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    let page = render_into_template("<h1>Me</h1><!-- wtd:calendar --><p>Bye</p><!-- wtd:calendar -->", &tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert_eq!(page, Ok(format!("<h1>Me</h1>{}<p>Bye</p><!-- wtd:calendar -->", fragment)));
+}
+
+// Templates can lay out the calendar's pieces and tasks themselves, with
+// everything but the pieces escaped.
+#[test]
+fn template_variables() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n- [ ] <b>Launch</b> @2PM+1h +public\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let template = "{% for task in tasks %}<a href=\"#{{ task.id }}\">{{ task.start }} {{ task.details or 'busy' }}</a>{% endfor %}{{ legend }}";
+    let page = render_into_template(template, &tasks, &Notes::default(), CalendarPrivacy::Public, &options)
+        .unwrap_or_else(|why| panic!("{}", why));
+    let fragment = render_fragment(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    let anchors = anchors(&fragment);
+    assert!(page.starts_with(&format!("<a href=\"#{}\">09:00 busy</a><a href=\"#{}\">14:00 &lt;b&gt;Launch&lt;/b&gt;</a><ul class=\"legend\">", anchors[0], anchors[1])), "{}", page);
+    let broken = render_into_template("{% for task in tasks %}", &tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(broken.is_err());
+}
+
+// A span is one entry in the list, drawn in each of its days' columns.
+#[test]
+fn spans_across_columns() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Conference @6PM--+2d10AM +busy\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 4, ..RenderOptions::default() };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert_eq!(html.matches("<td class=\"has-task tag-busy\"").count(), 3, "{}", html);
+    assert_eq!(html.matches("<li id=\"task-").count(), 1);
+    assert!(html.contains("Mon 10/12/26  6:00PM -- Wed 10/14/26 10:00AM"), "{}", html);
+}
+
+// Public tasks show their images, attached or linked, as thumbnails.
+#[test]
+fn thumbnails() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Hike https://example.com/trail.JPG?size=2 https://example.com/map @9AM+3h +public +file:./photos/peak.png +file:route.gpx\n\
+                       - [ ] Dentist @1PM+1h +busy +file:xray.png\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), ..RenderOptions::default() };
+    for privacy in [CalendarPrivacy::Public, CalendarPrivacy::Private].iter().copied() {
+        let html = render_html(&tasks, &Notes::default(), privacy, &options);
+        assert_eq!(html.matches("<li class=\"thumbnails\">").count(), 1, "{}", html);
+        assert!(html.contains("<li class=\"thumbnails\"><a href=\"photos/peak.png\"><img src=\"photos/peak.png\" alt=\"\" loading=\"lazy\"></a>\
+                               <a href=\"https://example.com/trail.JPG?size=2\"><img src=\"https://example.com/trail.JPG?size=2\" alt=\"\" loading=\"lazy\"></a></li>"),
+                "{}", html);
+    }
+}
+
+// The preamble is always shown, and a week's note only when the calendar
+// shows some of that week.
+#[test]
+fn preamble_and_week_notes() {
+    let source = "Back on the <b>20th</b>.\n\n# 10/12/26\nTraveling this week.\n## Monday\n- [ ] Meet @9AM+1h\n# 10/26/26\nQuiet week.\n## Monday\n- [ ] Meet @9AM+1h\n";
+    let notes = parse_notes(source);
+    assert_eq!(notes.preamble, ["Back on the <b>20th</b>."]);
+    assert_eq!(notes.weeks.iter().map(|(start, _)| *start).collect::<Vec<_>>(), [time::ymd(2026, 10, 12).expect("a date"), time::ymd(2026, 10, 26).expect("a date")]);
+    let options = RenderOptions { start: time::ymd(2026, 10, 14), days: 7, ..RenderOptions::default() };
+    let html = render_html(&tasks(source), &notes, CalendarPrivacy::Private, &options);
+    assert!(html.contains("Back on the &lt;b&gt;20th&lt;/b&gt;."), "{}", html);
+    assert!(html.contains("Traveling this week."), "{}", html);
+    assert!(!html.contains("Quiet week."), "{}", html);
+}
+
+// '- [x]' and '- [X]' are both done, struck through on both calendars and
+// left off the public one with hide_done.
+#[test]
+fn done_tasks() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [x] Standup @9AM+15m +public\n- [X] Review @10AM+1h +public\n- [ ] Retro @2PM+1h +public\n");
+    assert_eq!(tasks.iter().map(|task| task.done).collect::<Vec<bool>>(), [true, true, false]);
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert_eq!(html.matches("class=\"done\"").count(), 2, "{}", html);
+    let hidden = RenderOptions { hide_done: true, ..options };
+    let public = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &hidden);
+    assert!(!public.contains("Standup") && !public.contains("Review") && public.contains("Retro"), "{}", public);
+    let private = render_html(&tasks, &Notes::default(), CalendarPrivacy::Private, &hidden);
+    assert!(private.contains("Standup") && private.contains("Review"), "{}", private);
+}
+
+// List entries carry their priority, which the public only sees on public
+// tasks.
+#[test]
+fn priority_classes() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Talk @9AM+1h !1 +public\n- [ ] Dentist @2PM+1h !2\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let public = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(public.contains("class=\"priority-1\"") && !public.contains("priority-2"), "{}", public);
+    let private = render_html(&tasks, &Notes::default(), CalendarPrivacy::Private, &options);
+    assert!(private.contains("class=\"priority-1\"") && private.contains("class=\"priority-2\""), "{}", private);
+}
+
+// A cell's tags are listed by tag_priority, and the first of several leads.
+#[test]
+fn tag_priority() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Call @9AM+1h +tentative +busy\n- [ ] Hike @2PM+1h +rough\n");
+    let written = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let html = render_fragment(&tasks, &Notes::default(), CalendarPrivacy::Public, &written);
+    assert!(html.contains("class=\"has-task tag-tentative tag-busy lead-tentative\" rowspan=\"4\"><a href=\"#task-"), "{}", html);
+    assert!(html.contains("\">tentative, busy</a>"), "{}", html);
+    assert!(html.contains("class=\"has-task tag-rough\" "), "{}", html);
+    let ranked = RenderOptions { tag_priority: vec!["busy".to_string(), "tentative".to_string()], ..written };
+    let html = render_fragment(&tasks, &Notes::default(), CalendarPrivacy::Public, &ranked);
+    assert!(html.contains("class=\"has-task tag-busy tag-tentative lead-busy\""), "{}", html);
+    assert!(html.contains("\">busy, tentative</a>"), "{}", html);
+}
+
+// Notes are listed with the details, so only public tasks' reach the public.
+#[test]
+fn notes_beside_the_details() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Offsite @9AM+1h +public\n  > North <entrance>\n- [ ] Dentist @2PM+1h\n  > Bring x-rays\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let public = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(public.contains("North &lt;entrance&gt;") && !public.contains("x-rays"), "{}", public);
+    let private = render_html(&tasks, &Notes::default(), CalendarPrivacy::Private, &options);
+    assert!(private.contains("Bring x-rays"), "{}", private);
+}
+
+// A location is listed as a link to search the configured map for it.
+#[test]
+fn map_links() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Coffee @10AM+1h @loc:\"Café & Bar\" +public\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains("https://www.openstreetmap.org/search?query=Caf%C3%A9%20%26%20Bar"), "{}", html);
+    assert!(html.contains("Café &amp; Bar"), "{}", html);
+    let options = RenderOptions { map_links: MapLinks::Google, ..options };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains("https://www.google.com/maps/search/?api=1&amp;query=Caf%C3%A9%20%26%20Bar"), "{}", html);
+}
+
+// The legend describes just the public tags on the tasks shown, ranked as
+// tag_priority says.
+#[test]
+fn legend() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy +self\n- [ ] Talk @2PM+1h +public\n## Friday\n- [ ] Holiday @allday +ooo\n");
+    let options = RenderOptions {
+        start: time::ymd(2026, 10, 12),
+        days: 1,
+        public_tags: vec![("busy".to_string(), "Busy".to_string()), ("self".to_string(), "Me time".to_string()), ("ooo".to_string(), "Away".to_string())].into_iter().collect(),
+        tag_priority: vec!["self".to_string()],
+        ..RenderOptions::default()
+    };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    let legend = &html[html.find("<ul class=\"legend\">").expect("a legend")..];
+    let legend = &legend[..legend.find("</ul>").expect("the legend's end")];
+    assert_eq!(legend, "<ul class=\"legend\"><li><div class=\"has-task tag-self\">self</div> Me time</li><li><div class=\"has-task tag-busy\">busy</div> Busy</li>");
+    let options = RenderOptions { start: time::ymd(2026, 10, 13), ..options };
+    assert!(!render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options).contains("legend"));
+}
+
+// Tag colors are written into the page for cells and month entries alike.
+#[test]
+fn tag_colors() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n");
+    let options = RenderOptions {
+        start: time::ymd(2026, 10, 12),
+        tag_colors: vec![("busy".to_string(), "#d33".to_string())],
+        ..RenderOptions::default()
+    };
+    let html = render_fragment(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.starts_with("<style>td.tag-busy, div.tag-busy { background-color: #d33; }td.lead-busy, div.lead-busy { background-color: #d33; background-image: none; }</style>"), "{}", html);
+}
+
+// The dates heading the grid's columns.
+fn columns(html: &str) -> Vec<&str> {
+    let header = &html[html.find("<tr><th>Time</th>").expect("a grid")..];
+    let header = &header[..header.find("</tr>").expect("the header's end")];
+    return header.split("<th").skip(2).map(|cell| &cell[cell.find('>').expect("the tag's end") + 1..cell.len() - "</th>".len()]).collect();
+}
+
+// With full_weeks the grid snaps back to the start of the week and on to the
+// end of the week of its last day.
+#[test]
+fn full_weeks() {
+    let options = RenderOptions { start: time::ymd(2026, 10, 14), days: 3, ..RenderOptions::default() };
+    let html = render_html(&[], &Notes::default(), CalendarPrivacy::Public, &options);
+    assert_eq!(columns(&html), ["Wed 10/14/26", "Thu 10/15/26", "Fri 10/16/26"]);
+    let options = RenderOptions { full_weeks: true, week_start: Weekday::Sun, days: 5, ..options };
+    let html = render_html(&[], &Notes::default(), CalendarPrivacy::Public, &options);
+    assert_eq!(columns(&html), ["Sun 10/11/26", "Mon 10/12/26", "Tue 10/13/26", "Wed 10/14/26", "Thu 10/15/26", "Fri 10/16/26", "Sat 10/17/26",
+                                "Sun 10/18/26", "Mon 10/19/26", "Tue 10/20/26", "Wed 10/21/26", "Thu 10/22/26", "Fri 10/23/26", "Sat 10/24/26"]);
+}
+
+// On the 24-hour clock both the grid's rows and the list's times read 14:30.
+#[test]
+fn time_format_24h() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Retro @2:30PM+1h +public\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains(" 2:30 PM") && html.contains(" 2:30PM") && !html.contains("14:30"), "{}", html);
+    let options = RenderOptions { time_format: TimeFormat::TwentyFourHour, ..options };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains("14:30") && !html.contains("PM"), "{}", html);
+}
+
+// The anchors of the list's entries, in order.
+fn anchors(html: &str) -> Vec<&str> {
+    return html.split("<li id=\"").skip(1).map(|entry| &entry[..entry.find('"').expect("the id's end")]).collect();
+}
+
+// Anchors don't move when other tasks are added, duplicates are numbered, and
+// an '#id:' names one, except a private task's on the public calendar.
+#[test]
+fn list_anchors() {
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 2, ..RenderOptions::default() };
+    let render = |source: &str, privacy| render_html(&tasks(source), &Notes::default(), privacy, &options);
+    let html = render("# 10/12/26\n## Monday\n- [ ] Run @7AM+1h +public\n- [ ] Run @6PM+1h +public\n## Tuesday\n- [ ] Standup @9AM+15m #id:standup\n", CalendarPrivacy::Private);
+    let before = anchors(&html);
+    assert_eq!(before.len(), 3);
+    assert!(before[0].starts_with("task-") && before[0].len() == 15, "{:?}", before);
+    assert_eq!(before[1], format!("{}-2", before[0]));
+    assert_eq!(before[2], "task-standup");
+    let html = render("# 10/12/26\n## Monday\n- [ ] Coffee @6AM+1h\n- [ ] Run @7AM+1h +public\n- [ ] Run @6PM+1h +public\n## Tuesday\n- [ ] Standup @9AM+15m #id:standup\n", CalendarPrivacy::Private);
+    assert_eq!(anchors(&html)[1..], before[..]);
+    let html = render("# 10/12/26\n## Tuesday\n- [ ] Standup @9AM+15m #id:standup\n", CalendarPrivacy::Public);
+    assert!(!html.contains("standup"), "{}", html);
+}
+
+// Long cell text is cut short with an ellipsis, while the list keeps all of it.
+#[test]
+fn cell_truncation() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Quarterly planning review @9AM+1h +public\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, ..RenderOptions::default() };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains("<table class=\"cells-wrap\">") && !html.contains('\u{2026}'), "{}", html);
+    let options = RenderOptions { cell_max_chars: Some(10), cell_overflow: CellOverflow::Ellipsis, ..options };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains("<table class=\"cells-ellipsis\">"), "{}", html);
+    assert!(html.contains(">&quot;Quarterl\u{2026}</a>"), "{}", html);
+    assert!(html.contains("Quarterly planning review"), "{}", html);
+}
+
+// The compare view puts the same weekday of each week side by side, ending
+// with the current week.
+#[test]
+fn compare_view() {
+    let options = RenderOptions { start: time::ymd(2026, 10, 14), view: View::Compare, weeks: 2, ..RenderOptions::default() };
+    let html = render_html(&[], &Notes::default(), CalendarPrivacy::Public, &options);
+    assert_eq!(columns(&html), ["Mon 10/5/26", "Mon 10/12/26", "Tue 10/6/26", "Tue 10/13/26", "Wed 10/7/26", "Wed 10/14/26",
+                                "Thu 10/8/26", "Thu 10/15/26", "Fri 10/9/26", "Fri 10/16/26", "Sat 10/10/26", "Sat 10/17/26",
+                                "Sun 10/11/26", "Sun 10/18/26"]);
+    assert_eq!(html.matches("<th class=\"group-start\">").count(), 7);
+}
+
+// Cell text is cut by the columns it takes up, and never inside a character
+// made of several code points.
+#[test]
+fn cell_truncation_by_width() {
+    let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+    let tasks = tasks(&format!("# 10/12/26\n## Monday\n- [ ] {}{} dinner @6PM+1h +public\n- [ ] 会議の準備 @9AM+1h +public\n", family, family));
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, cell_max_chars: Some(6), ..RenderOptions::default() };
+    let html = render_html(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains(&format!(">&quot;{}{}\u{2026}</a>", family, family)), "{}", html);
+    assert!(html.contains(">&quot;会議\u{2026}</a>"), "{}", html);
+}
+
+// A task already under way when the shown hours start begins in their first
+// row, and is still listed when it's wholly outside them.
+#[test]
+fn partial_days() {
+    let tasks = tasks("# 10/12/26\n## Monday\n- [ ] Run @7AM+2h +busy\n- [ ] Late call @9PM+1h +busy\n");
+    let options = RenderOptions { start: time::ymd(2026, 10, 12), days: 1, hours: Some((8, 20)), ..RenderOptions::default() };
+    let html = render_fragment(&tasks, &Notes::default(), CalendarPrivacy::Public, &options);
+    assert!(html.contains("<tr><td><b>8AM</b></td><td class=\"has-task tag-busy\" rowspan=\"4\">"), "{}", html);
+    assert_eq!(html.matches("<td class=\"has-task").count(), 1, "{}", html);
+    assert_eq!(anchors(&html).len(), 2);
+}
@@ -0,0 +1,326 @@
+// Requests to `wtd serve` that ask too much of it.
+#![allow(clippy::needless_return)]
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use chrono::Weekday;
+use wtd::time;
+
+// `wtd serve` on a free port, stopped when dropped.
+struct Server {
+    child: Child,
+    address: String,
+    dir: std::path::PathBuf,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+const STANDUP: &str = "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n";
+
+fn serve(name: &str, contents: &str) -> Server {
+    return serve_with_config(name, contents, "");
+}
+
+fn serve_with_config(name: &str, contents: &str, config: &str) -> Server {
+    return start(name, contents, config, &["--privacy-mode"]);
+}
+
+// Serves `contents` as wtd.md with `config` as wtd.toml, from another
+// directory than theirs, with its standard error captured.
+fn start(name: &str, contents: &str, config: &str, flags: &[&str]) -> Server {
+    let dir = std::env::temp_dir().join(format!("wtd-serve-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    std::fs::write(dir.join("wtd.md"), contents).expect("wtd.md is written");
+    std::fs::write(dir.join("wtd.toml"), config).expect("wtd.toml is written");
+    let address = TcpListener::bind("127.0.0.1:0").and_then(|listener| listener.local_addr()).expect("a free port").to_string();
+    let child = Command::new(env!("CARGO_BIN_EXE_wtd"))
+        .arg("--config").arg(dir.join("wtd.toml")).arg(dir.join("wtd.md"))
+        .args(["serve", "--bind", &address]).args(flags)
+        .current_dir(std::env::temp_dir())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("wtd serve starts");
+    for _ in 0..100 {
+        if TcpStream::connect(&address).is_ok() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    return Server { child, address, dir };
+}
+
+// The status line and body of the response to `request`.
+fn send(server: &Server, request: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(&server.address).expect("the server is listening");
+    stream.write_all(request.as_bytes()).expect("the request is sent");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).expect("a response");
+    let (head, body) = response.split_once("\r\n\r\n").expect("a complete response");
+    return (head.lines().next().unwrap_or("").to_string(), body.to_string());
+}
+
+// A WebSocket connection to /ws, past the handshake.
+fn subscribe(server: &Server) -> TcpStream {
+    let mut stream = TcpStream::connect(&server.address).expect("the server is listening");
+    stream.write_all(b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n")
+        .expect("the upgrade is sent");
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).expect("the handshake");
+        head.push(byte[0]);
+    }
+    assert!(head.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+    return stream;
+}
+
+// The payload of the next frame the server sends.
+fn read_frame(stream: &mut TcpStream) -> String {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).expect("a frame");
+    let len = match header[1] {
+        126 => {
+            let mut ext = [0u8; 2];
+            stream.read_exact(&mut ext).expect("the frame's length");
+            u16::from_be_bytes(ext) as usize
+        },
+        len => len as usize,
+    };
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).expect("the frame's payload");
+    return String::from_utf8_lossy(&payload).to_string();
+}
+
+fn graphql(server: &Server, query: &str) -> String {
+    let request = format!("POST /graphql HTTP/1.1\r\nContent-Type: application/graphql\r\nContent-Length: {}\r\n\r\n{}", query.len(), query);
+    return send(server, &request).1;
+}
+
+#[test]
+fn oversized_bodies_are_refused() {
+    let server = serve("body", STANDUP);
+    let (status, _) = send(&server, "POST /graphql HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 413 Payload Too Large");
+    let (status, _) = send(&server, "POST /graphql HTTP/1.1\r\nContent-Length: 65537\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 413 Payload Too Large");
+}
+
+#[test]
+fn oversized_headers_are_refused() {
+    let server = serve("headers", STANDUP);
+    let (status, _) = send(&server, &format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10000)));
+    assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+    let (status, _) = send(&server, &format!("GET / HTTP/1.1\r\n{}\r\n", "X-Header: 1\r\n".repeat(101)));
+    assert_eq!(status, "HTTP/1.1 431 Request Header Fields Too Large");
+    let (status, _) = send(&server, &format!("GET /metrics HTTP/1.1\r\n{}\r\n", "X-Header: 1\r\n".repeat(100)));
+    assert_eq!(status, "HTTP/1.1 200 OK");
+}
+
+#[test]
+fn graphql_limits() {
+    let server = serve("graphql", STANDUP);
+    let within = graphql(&server, r#"{ days(from: "2026-10-12", to: "2026-10-13") { date tasks { start } } }"#);
+    assert!(within.starts_with("{\"data\""), "{}", within);
+    let long = graphql(&server, r#"{ days(from: "2000-01-01", to: "2099-12-31") { date } }"#);
+    assert!(long.contains("'days' can cover at most 366 days at once"), "{}", long);
+    let year = graphql(&server, r#"{ freebusy(from: "2026-01-01", to: "2027-01-01") { date } }"#);
+    assert!(year.starts_with("{\"data\""), "{}", year);
+    let longer = graphql(&server, r#"{ freebusy(from: "2026-01-01", to: "2027-01-02") { date } }"#);
+    assert!(longer.contains("'freebusy' can cover at most 366 days at once"), "{}", longer);
+    let deep = format!("{{ tasks {}{}", "{ a ".repeat(5000), "}".repeat(5001));
+    let deep = graphql(&server, &deep);
+    assert!(deep.contains("selections can only nest 8 deep"), "{}", deep);
+}
+
+#[test]
+fn graphql_dates_past_the_calendar() {
+    let server = serve("graphql-dates", STANDUP);
+    let far = graphql(&server, r#"{ days(from: "in 99999999999 days") { date } }"#);
+    assert!(far.contains("couldn't read 'in 99999999999 days' as a date"), "{}", far);
+    let last = graphql(&server, r#"{ days(from: "+262142-12-25") { date } }"#);
+    assert!(last.contains("'days' needs a 'to' date"), "{}", last);
+    let end = graphql(&server, r#"{ days(from: "+262142-12-30", to: "+262142-12-31") { date } }"#);
+    assert!(end.starts_with("{\"data\""), "{}", end);
+}
+
+// Filtering on a private tag would give away the times of the tasks it's on.
+#[test]
+fn graphql_filters_only_on_public_tags() {
+    let server = serve("graphql-tags", "# 10/12/26\n## Monday\n- [ ] Therapy +doctor @2PM+1h\n- [ ] Standup @9AM+15m +busy\n");
+    let private = graphql(&server, r#"{ tasks(tag: "doctor") { date start end } }"#);
+    assert!(private.contains("'doctor' is not a public tag"), "{}", private);
+    let missing = graphql(&server, r#"{ tasks(tag: "nothere") { date start end } }"#);
+    assert_eq!(private.replace("doctor", "nothere"), missing);
+    let public = graphql(&server, r#"{ tasks(tag: "busy") { start } }"#);
+    assert_eq!(public, r#"{"data":{"tasks":[{"start":"09:00"}]}}"#);
+}
+
+// A client claiming an enormous frame is closed with "Message Too Big" rather
+// than read into memory.
+#[test]
+fn oversized_websocket_frames_close_the_connection() {
+    let server = serve("ws", STANDUP);
+    let mut stream = subscribe(&server);
+    read_frame(&mut stream);
+
+    stream.write_all(&[0x82, 0xff, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).expect("the frame header is sent");
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).expect("the connection closes");
+    assert_eq!(response, [0x88, 0x02, 0x03, 0xf1]);
+}
+
+// The stylesheet is the one the config names, beside the task file.
+#[test]
+fn configured_stylesheet() {
+    let server = serve_with_config("css", STANDUP, "[render]\nstylesheet = \"style/cal.css\"\n");
+    std::fs::create_dir_all(server.dir.join("style")).expect("the directory is made");
+    std::fs::write(server.dir.join("style/cal.css"), "body { color: red; }\n").expect("the stylesheet is written");
+    let (_, page) = send(&server, "GET / HTTP/1.1\r\n\r\n");
+    assert!(page.contains("href=\"style/cal.css\""), "{}", page);
+    assert_eq!(send(&server, "GET /style/cal.css HTTP/1.1\r\n\r\n"), ("HTTP/1.1 200 OK".to_string(), "body { color: red; }\n".to_string()));
+    assert_eq!(send(&server, "GET /calendar_style.css HTTP/1.1\r\n\r\n").0, "HTTP/1.1 404 Not Found");
+
+    // Without one written out, the built-in stylesheet is served.
+    let server = serve("css-built-in", STANDUP);
+    let (status, css) = send(&server, "GET /calendar_style.css HTTP/1.1\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(css.contains("table"), "{}", css);
+}
+
+#[test]
+fn metrics() {
+    let server = serve("metrics", "# 10/12/26\n## Monday\n- [X] Standup @9AM+15m +busy\n- [ ] Groceries\n- [ ] Dentist @2PM+1h\n");
+    let (status, body) = send(&server, "GET /metrics HTTP/1.1\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert!(body.contains("# HELP wtd_tasks_open Tasks not yet checked off.\n# TYPE wtd_tasks_open gauge\nwtd_tasks_open 2\n"), "{}", body);
+    assert!(body.contains("\nwtd_tasks_total 3\n"), "{}", body);
+    assert!(body.contains("\nwtd_scheduled_hours_today ") && body.contains("\nwtd_meetings_this_week "), "{}", body);
+}
+
+// The gauges count what the public page shows, so they give away no more.
+#[test]
+fn metrics_are_redacted() {
+    let today = time::today();
+    let week = time::format_header_date(time::week_start(today, Weekday::Mon));
+    let source = format!("# {}\n## {}\n- [ ] Interview @9AM+1h +meetings\n- [ ] Review @10AM+1h +meetings\n- [ ] Groceries\n",
+                         week, time::format_weekday(today));
+    let server = serve_with_config("metrics-redacted", &source, "[redact]\nprivacy = \"full\"\n");
+    let (_, body) = send(&server, "GET /metrics HTTP/1.1\r\n\r\n");
+    assert!(body.contains("\nwtd_meetings_this_week 0\n") && body.contains("\nwtd_tasks_total 1\n"), "{}", body);
+    assert!(body.contains("\nwtd_scheduled_hours_today 2\n"), "{}", body);
+}
+
+// The head of the response to a GET of `target`, and what the server logged
+// up to then.
+fn get_and_stop(mut server: Server, target: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(&server.address).expect("the server is listening");
+    stream.write_all(format!("GET {} HTTP/1.1\r\n\r\n", target).as_bytes()).expect("the request is sent");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).expect("a response");
+    server.child.kill().ok();
+    let mut log = String::new();
+    server.child.stderr.take().expect("standard error").read_to_string(&mut log).expect("the log");
+    let head = response.split("\r\n\r\n").next().unwrap_or("").to_string();
+    return (head, log);
+}
+
+// Requests are logged, and the pages may be cached and linked from, unless
+// in privacy mode.
+#[test]
+fn privacy_mode() {
+    let (head, log) = get_and_stop(start("logged", STANDUP, "", &[]), "/metrics");
+    assert!(!head.contains("Referrer-Policy") && !head.contains("Cache-Control"), "{}", head);
+    assert!(log.ends_with("\nGET /metrics\n"), "{}", log);
+    let (head, log) = get_and_stop(serve("private", STANDUP), "/metrics");
+    assert!(head.ends_with("\r\nReferrer-Policy: no-referrer\r\nCache-Control: no-store"), "{}", head);
+    assert!(!log.contains("GET"), "{}", log);
+}
+
+// A compact dashboard of a few days, keeping to some hours.
+#[test]
+fn dashboard() {
+    let server = start("dashboard", "# 10/12/26\n## Monday\n- [ ] Run @7AM+3h +busy\n", "", &["--days", "3", "--hours", "8-20"]);
+    let (status, page) = send(&server, "GET / HTTP/1.1\r\n\r\n");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    let header = page.split("</tr>").next().unwrap_or("");
+    assert_eq!(header.matches("<th>").count(), 4, "{}", header);
+    assert!(page.contains("<table class=\"cells-wrap partial-day\">"), "{}", page);
+    assert!(page.contains("<tr><td><b>8AM</b></td>") && page.contains("<b>7PM</b>") && !page.contains("<b>8PM</b>"), "{}", page);
+}
+
+// The page reloads itself when /ws says the task file changed, which it does
+// on the next save.
+#[test]
+fn live_reload() {
+    let server = serve("reload", STANDUP);
+    let (_, page) = send(&server, "GET / HTTP/1.1\r\n\r\n");
+    assert!(page.contains("location.reload();") && page.contains("</script></body>"), "{}", page);
+
+    let mut stream = subscribe(&server);
+    assert!(read_frame(&mut stream).contains("\"tasks\""));
+    std::fs::write(server.dir.join("wtd.md"), format!("{}- [ ] Retro @4PM+1h +busy\n", STANDUP)).expect("wtd.md is saved");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).expect("the timeout is set");
+    let pushed = read_frame(&mut stream);
+    assert_eq!(pushed.matches("\"start\"").count(), 2, "{}", pushed);
+}
+
+// Saving a file the task file includes reloads the page too, even one that
+// wasn't there to begin with.
+#[test]
+fn live_reload_follows_includes() {
+    let server = serve("reload-includes", &format!("#include work.md\n{}", STANDUP));
+    let mut stream = subscribe(&server);
+    assert!(read_frame(&mut stream).contains("\"error\""));
+    std::fs::write(server.dir.join("work.md"), "# 10/12/26\n## Monday\n- [ ] Retro @4PM+1h +busy\n").expect("work.md is saved");
+    stream.set_read_timeout(Some(Duration::from_secs(5))).expect("the timeout is set");
+    let pushed = read_frame(&mut stream);
+    assert_eq!(pushed.matches("\"start\"").count(), 2, "{}", pushed);
+}
+
+// Attachments a rendered page would refuse aren't linked from the served one
+// either.
+#[test]
+fn attachments_are_checked() {
+    let today = time::today();
+    let week = time::format_header_date(time::week_start(today, Weekday::Mon));
+    let server = serve("attachments", &format!("# {}\n## {}\n- [ ] Talk @5PM+1h +public +file:docs/slides.pdf +file:../secret.txt +file:missing.pdf\n",
+                                               week, time::format_weekday(today)));
+    std::fs::create_dir_all(server.dir.join("docs")).expect("the directory is made");
+    std::fs::write(server.dir.join("docs/slides.pdf"), "slides").expect("the slides are written");
+    let (_, page) = send(&server, "GET / HTTP/1.1\r\n\r\n");
+    assert!(page.contains("<a href=\"docs/slides.pdf\">slides.pdf</a>"), "{}", page);
+    assert!(!page.contains("secret.txt") && !page.contains("missing.pdf"), "{}", page);
+}
+
+// The merged calendars are drawn from memory on every route, so one that never
+// answers holds nothing up.
+#[test]
+fn merged_calendars_on_every_route() {
+    let hanging = TcpListener::bind("127.0.0.1:0").expect("a free port");
+    let dir = std::env::temp_dir().join(format!("wtd-serve-merged-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    let meetings = dir.join("meetings.ics");
+    std::fs::write(&meetings, "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Sync\r\nDTSTART:20261012T140000\r\nDURATION:PT1H\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n")
+        .expect("the calendar is written");
+    let config = format!("merge_ics = [{:?}, \"http://{}/calendar.ics\"]\n", meetings, hanging.local_addr().expect("an address"));
+    let server = serve_with_config("merged", STANDUP, &config);
+    let query = r#"{ tasks(from: "2026-10-12", to: "2026-10-12") { start } }"#;
+    for _ in 0..100 {
+        if graphql(&server, query).matches("\"start\"").count() == 2 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    assert_eq!(graphql(&server, query).matches("\"start\"").count(), 2, "{}", graphql(&server, query));
+    let mut stream = subscribe(&server);
+    let sent = read_frame(&mut stream);
+    assert_eq!(sent.matches("\"start\"").count(), 2, "{}", sent);
+}
@@ -0,0 +1,58 @@
+// Files written together or not at all.
+#![allow(clippy::needless_return)]
+use std::fs;
+use std::path::PathBuf;
+use wtd::staged::Staged;
+
+fn directory(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wtd-staged-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).expect("the directory is made");
+    return dir;
+}
+
+fn listing(dir: &PathBuf) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir).expect("the directory is read")
+        .map(|entry| entry.expect("the entry is read").file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    return names;
+}
+
+#[test]
+fn all_files_are_written() {
+    let dir = directory("written");
+    fs::write(dir.join("public.html"), "old").expect("the page is written");
+    let mut staged = Staged::default();
+    staged.write(&dir.join("public.html"), "new");
+    staged.write(&dir.join("docs/slides.pdf"), "slides");
+    staged.write(&dir.join("public.html"), "newer");
+    let result = staged.commit();
+    let (page, slides, names) = (fs::read_to_string(dir.join("public.html")), fs::read_to_string(dir.join("docs/slides.pdf")), listing(&dir));
+    fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(page.expect("the page is read"), "newer");
+    assert_eq!(slides.expect("the slides are read"), "slides");
+    assert_eq!(names, ["docs", "public.html"]);
+}
+
+// A file that can't be written leaves the others as they were, with no
+// staged copies behind.
+#[test]
+fn nothing_is_written_on_failure() {
+    let dir = directory("failed");
+    fs::write(dir.join("public.html"), "old").expect("the page is written");
+    fs::write(dir.join("docs"), "a file, not a directory").expect("the blocker is written");
+    let mut staged = Staged::default();
+    staged.write(&dir.join("public.html"), "new");
+    staged.write(&dir.join("docs/slides.pdf"), "slides");
+    let result = staged.commit();
+    let (page, names) = (fs::read_to_string(dir.join("public.html")), listing(&dir));
+    fs::remove_dir_all(&dir).ok();
+
+    let why = result.expect_err("the slides can't be written");
+    assert!(why.ends_with("; nothing was changed"), "{}", why);
+    assert_eq!(page.expect("the page is read"), "old");
+    assert_eq!(names, ["docs", "public.html"]);
+}
@@ -0,0 +1,214 @@
+// The reports from `wtd stats`.
+#![allow(clippy::needless_return)]
+use chrono::Weekday;
+use wtd::stats::{self, checklists_report, cost_report, done_report, habits_report, idle_report, meeting_cost, sparkline_report, weekly_tag_hours};
+use wtd::{parse_notes, parse_tasks, public_tags, time, Task};
+
+fn parse(source: &str) -> Vec<Task> {
+    return parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+}
+
+// A meeting costs its hours times its attendees times the rate; ones without
+// an '&N' count are listed as left out.
+#[test]
+fn meeting_costs() {
+    let tasks = parse("# 10/5/26
+## Monday
+- [ ] Review @10AM+1h &4 +meetings
+# 10/12/26
+## Monday
+- [ ] Planning @10AM+90m &8 +meetings
+- [ ] Sync @2PM+30m +meetings
+- [ ] Lunch @12PM+1h &3
+");
+    assert_eq!(meeting_cost(&tasks[1], 100.0), Some(1200.0));
+    assert_eq!(meeting_cost(&tasks[2], 100.0), None);
+    let today = time::ymd(2026, 10, 14).expect("a date");
+    assert_eq!(cost_report(&tasks, today, 2, "meetings", 100.0, Weekday::Mon), "\
+Estimated cost of +meetings at 100 per person per hour:
+  10/5/26       400
+  10/12/26     1200
+This week:
+      1200  Mon 10/12/26  Planning &8
+  (1 without an &N attendee count left out)
+");
+}
+
+// Nested items count along with the rest.
+#[test]
+fn checklists() {
+    let tasks = parse("# 10/5/26
+## Monday
+- [ ] Launch @10AM+1h
+  - [X] Write the post
+    - [X] Draft
+  - [X] Announce
+# 10/12/26
+## Monday
+- [ ] Move @9AM+1h
+  - [X] Pack
+    - [ ] Books
+## Tuesday
+- [ ] Call @2PM+1h
+  - [X] Agenda
+");
+    let report = checklists_report(&tasks, time::ymd(2026, 10, 14).expect("a date"), 3, Weekday::Mon);
+    assert_eq!(report.lines().collect::<Vec<&str>>(), [
+        " 9/28  no checklists",
+        " 10/5  3/3 items done (100%), 1/1 checklists finished",
+        "10/12  2/3 items done (67%), 1/2 checklists finished",
+    ]);
+}
+
+// Recorded finishing times against the planned ends.
+#[test]
+fn finishing_times() {
+    let tasks = parse("# 10/12/26
+## Monday
+- [X] Report @2PM+1h +done:14:50
+- [X] Review @4PM+1h +done:17:30
+- [X] Inbox +done:9:00
+- [ ] Retro @5PM+1h
+");
+    let report = done_report(&tasks, time::ymd(2026, 10, 14).expect("a date"), 2, Weekday::Mon);
+    assert_eq!(report.lines().collect::<Vec<&str>>(), [
+        " 10/5  no finishing times recorded",
+        "10/12  1/2 finished by their planned end, +10m past it on average",
+    ]);
+}
+
+// Streaks count done occurrences in a row, and today's not being done yet
+// doesn't break the current one.
+#[test]
+fn habit_streaks() {
+    let tasks = parse("# 10/5/26
+## Monday
+- [X] Run @7AM+30m +habit:run
+## Wednesday
+- [X] Run @7AM+30m +habit:run
+## Friday
+- [ ] Run @7AM+30m +habit:run
+# 10/12/26
+## Monday
+- [X] Run @7AM+30m +habit:run
+  - [X] Stretch
+  - [ ] Log it
+## Tuesday
+- [X] Run @7AM+30m +habit:run
+## Wednesday
+- [ ] Run @7AM+30m +habit:run
+## Friday
+- [ ] Run @7AM+30m +habit:run
+");
+    let report = habits_report(&tasks, time::ymd(2026, 10, 14).expect("a date"), 2, Weekday::Mon);
+    assert_eq!(report.lines().collect::<Vec<&str>>(), [
+        "run",
+        "  done 4 of 6 (67%)",
+        "  current streak: 2, longest: 2",
+        "  checklist items done 1 of 2 (50%)",
+        "  weekly: 10/5 2/3 10/12 2/3",
+    ]);
+    assert_eq!(habits_report(&[], time::ymd(2026, 10, 14).expect("a date"), 2, Weekday::Mon), "No +habit:NAME tasks found.\n");
+}
+
+// An hour counts as free in a week if no task overlaps it and the day isn't
+// spent out of office.
+#[test]
+fn idle_hours() {
+    let source = "# 9/28/26
+## Monday
+- [ ] Standup @9AM+1h
+# 10/5/26
+## Monday
+- [ ] Standup @9AM+1h
+- [ ] Review @11AM+30m
+## OOO 10/9
+";
+    let report = idle_report(&parse(source), &parse_notes(source).out_of_office, time::ymd(2026, 10, 14).expect("a date"), 2, (9, 12), Weekday::Mon);
+    assert_eq!(report.lines().collect::<Vec<&str>>(), [
+        "Weeks free during working hours, 2 weeks from 9/28/26:",
+        "       9  10  11",
+        "Mon    0   2   1",
+        "Tue    2   2   2",
+        "Wed    2   2   2",
+        "Thu    2   2   2",
+        "Fri    1   1   1",
+        "Free every week:",
+        "  Mon 10:00--11:00",
+        "  Tue 9:00--12:00",
+        "  Wed 9:00--12:00",
+        "  Thu 9:00--12:00",
+    ]);
+}
+
+// Hours of meetings each week, drawn as a sparkline.
+#[test]
+fn meeting_load() {
+    let tasks = parse("# 9/28/26
+## Monday
+- [ ] Planning @10AM+2h +meetings
+# 10/12/26
+## Monday
+- [ ] Planning @10AM+90m +meetings
+- [ ] Lunch @12PM+1h
+## Thursday
+- [ ] Retro @2PM+2h30m +meetings
+");
+    let hours = weekly_tag_hours(&tasks, time::ymd(2026, 10, 14).expect("a date"), 3, "meetings", Weekday::Mon);
+    assert_eq!(hours, [(time::ymd(2026, 9, 28).expect("a date"), 2.0), (time::ymd(2026, 10, 5).expect("a date"), 0.0),
+                       (time::ymd(2026, 10, 12).expect("a date"), 4.0)]);
+    assert_eq!(sparkline_report(&hours, "meetings").lines().collect::<Vec<&str>>(), [
+        "Hours tagged +meetings per week: \u{2585}\u{2581}\u{2588}",
+        "  9/28/26    2.0",
+        "  10/5/26    0.0",
+        "  10/12/26   4.0",
+    ]);
+}
+
+// Hours per tag per week, a task with several tags counting towards each.
+#[test]
+fn tag_hours() {
+    let tasks = parse("# 10/5/26
+## Monday
+- [ ] Standup @9AM+30m +busy +meetings
+- [ ] Writing @10AM+2h +self
+# 10/12/26
+## Tuesday
+- [ ] Run @7AM+1h
+- [ ] Groceries +errand
+");
+    let table = stats::tag_hours(&tasks, time::ymd(2026, 10, 14).expect("a date"), 2, false, Weekday::Mon);
+    assert_eq!(table.columns, ["self", "busy", "meetings", "untagged", "total"]);
+    assert_eq!(stats::tag_hours_report(&table, false).lines().collect::<Vec<&str>>(), [
+        "Hours per tag per week:",
+        "             self   busy  meetings  untagged  total",
+        "  10/5/26     2.0    0.5       0.5       0.0    2.5",
+        "  10/12/26    0.0    0.0       0.0       1.0    1.0",
+    ]);
+    let daily = stats::tag_hours(&tasks, time::ymd(2026, 10, 14).expect("a date"), 1, true, Weekday::Mon);
+    assert_eq!(daily.columns, ["untagged", "total"]);
+    assert_eq!(daily.rows.len(), 7);
+    assert_eq!(daily.rows[1].1, [1.0, 1.0]);
+}
+
+// `wtd tags` lists each tag by how often it's used, with public ones starred.
+#[test]
+fn tag_usage() {
+    let tasks = parse("# 10/5/26
+## Monday
+- [ ] Standup @9AM+30m +busy +meetings
+## Friday
+- [ ] Standup @9AM+30m +busy +meetings
+# 10/12/26
+## Tuesday
+- [ ] Run @7AM+1h +slef
+");
+    assert_eq!(stats::tag_usage_report(&tasks, &public_tags()).lines().collect::<Vec<&str>>(), [
+        "  tag       tasks  first       last",
+        "  busy*         2  2026-10-05  2026-10-09",
+        "  meetings      2  2026-10-05  2026-10-09",
+        "  slef          1  2026-10-13  2026-10-13",
+        "(* described on the public calendar)",
+    ]);
+    assert_eq!(stats::tag_usage_report(&[], &public_tags()), "No tags found.\n");
+}
@@ -0,0 +1,116 @@
+// Task files written as TOML, JSON or iCalendar rather than markdown.
+#![allow(clippy::needless_return)]
+use std::path::PathBuf;
+use serde_json::json;
+use wtd::{load, load_notes, parse_tasks, public_tags, ParseOptions, Task};
+
+// A task file in the temporary directory, removed when dropped.
+struct File(PathBuf);
+
+impl File {
+    fn new(name: &str, contents: &str) -> File {
+        let path = std::env::temp_dir().join(format!("wtd-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).expect("the task file is written");
+        return File(path);
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+fn parse(source: &str) -> Vec<Task> {
+    return parse_tasks(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+}
+
+const TOML: &str = r#"notes = ["Back on the 26th."]
+
+[[out_of_office]]
+first = 2026-10-20
+last = 2026-10-23
+
+[[tasks]]
+date = 2026-10-16
+start = 09:00:00
+end = 10:00:00
+details = "Standup"
+tags = ["busy"]
+attendees = 4
+subtasks = [{ text = "Share the agenda", done = true }]
+
+[[tasks]]
+date = "2026-10-16"
+start = "22:00"
+end = "24:00"
+details = "Late shift"
+"#;
+
+// A TOML file reads into the same tasks and notes as the markdown saying the
+// same thing.
+#[test]
+fn toml_task_files() {
+    let file = File::new("tasks.toml", TOML);
+    let tasks = load(&file.0, &ParseOptions::default()).unwrap_or_else(|why| panic!("{}", why));
+    let notes = load_notes(&file.0, &ParseOptions::default());
+    assert_eq!(tasks, parse("# 10/12/26\n## Friday\n- [ ] Standup @9AM--10AM +busy &4\n  - [X] Share the agenda\n- [ ] Late shift @10PM+2h\n"));
+    assert_eq!(notes.preamble, ["Back on the 26th."]);
+    assert_eq!(notes.out_of_office.len(), 1);
+
+    let bad = File::new("bad.toml", "[[tasks]]\ndate = 2026-10-16\n");
+    let why = load(&bad.0, &ParseOptions::default()).expect_err("details are required");
+    assert!(why.starts_with(&format!("{}: ", bad.0.display())) && why.contains("missing field `details`"), "{}", why);
+}
+
+// `wtd json` shows what the public calendar would, unless asked for more.
+#[test]
+fn json_output() {
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Therapy @2PM+1h +doctor +busy\n- [X] Launch party @6PM+2h +public !2\n");
+    let public = wtd::json::tasks_to_json(&tasks, false, &public_tags());
+    assert_eq!(public, json!([
+        {"date": "2026-10-12", "start": "14:00", "end": "15:00", "end_date": null, "all_day": false, "details": null, "notes": null,
+         "location": null, "tags": ["busy"], "done": false, "priority": null, "id": null, "source": null},
+        {"date": "2026-10-12", "start": "18:00", "end": "20:00", "end_date": null, "all_day": false, "details": "Launch party", "notes": [],
+         "location": null, "tags": [], "done": true, "priority": 2, "id": null, "source": null},
+    ]));
+    let private = wtd::json::tasks_to_json(&tasks, true, &public_tags());
+    assert_eq!(private[0]["details"], "Therapy");
+    assert_eq!(private[0]["tags"], json!(["doctor", "busy"]));
+}
+
+// Without a telling extension, the format is worked out from the contents, so
+// what `wtd json --private` prints reads back as the same tasks.
+#[test]
+fn formats_told_by_contents() {
+    let tasks = parse("# 10/12/26\n## Monday\n- [ ] Therapy @2PM+1h +doctor\n- [X] Launch party @6PM+2h +public\n## Tuesday\n- [ ] Holiday @allday\n- [ ] Late shift @10PM+2h\n");
+    let json = wtd::json::tasks_to_json(&tasks, true, &public_tags()).to_string();
+    let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Sync\r\nDTSTART:20261012T090000\r\nDTEND:20261012T100000\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    for (name, contents, expected) in [
+        ("tasks.txt", json.as_str(), tasks.len()),
+        ("tasks", TOML, 2),
+        ("calendar", ics, 1),
+        ("wtd.txt", "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m\n", 1),
+    ] {
+        let file = File::new(name, contents);
+        let read = load(&file.0, &ParseOptions::default()).unwrap_or_else(|why| panic!("{}: {}", name, why));
+        assert_eq!(read.len(), expected, "{}", name);
+    }
+    let file = File::new("tasks.text", &json);
+    assert_eq!(load(&file.0, &ParseOptions::default()), Ok(tasks));
+}
+
+// A task file given as '-' is read from standard input.
+#[test]
+fn standard_input() {
+    use std::io::Write;
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_wtd"))
+        .args(["--config", "/dev/null", "-", "json", "--private"])
+        .stdin(std::process::Stdio::piped()).stdout(std::process::Stdio::piped())
+        .spawn().expect("wtd runs");
+    child.stdin.take().expect("standard input").write_all(TOML.as_bytes()).expect("the tasks are sent");
+    let output = child.wait_with_output().expect("wtd finishes");
+    let printed: serde_json::Value = serde_json::from_slice(&output.stdout).expect("JSON");
+    assert_eq!(printed[0]["details"], "Standup");
+    assert_eq!(printed[1]["end"], "24:00");
+}
@@ -0,0 +1,213 @@
+// `wtd sync` against a CalDAV calendar kept in memory.
+#![allow(clippy::needless_return)]
+use std::collections::BTreeMap;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use chrono::Weekday;
+use wtd::sync::{sync, Summary, SyncOptions};
+use wtd::ParseOptions;
+
+// Each event by href, with its ETag and body.
+type Events = Arc<Mutex<BTreeMap<String, (String, String)>>>;
+
+// A calendar collection at /cal/ answering PROPFIND, GET, PUT and DELETE, with
+// a task file and sync state beside it.
+struct Calendar {
+    url: String,
+    events: Events,
+    dir: PathBuf,
+}
+
+impl Drop for Calendar {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, headers: &str, body: &str) {
+    write!(stream, "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}", status, headers, body.len(), body)
+        .expect("the response is sent");
+}
+
+fn answer(mut stream: TcpStream, events: &Events, count: &mut usize) {
+    let mut reader = BufReader::new(stream.try_clone().expect("the stream is cloned"));
+    let mut line = String::new();
+    reader.read_line(&mut line).expect("a request line");
+    let mut parts = line.split_whitespace();
+    let (method, href) = (parts.next().unwrap_or("").to_string(), parts.next().unwrap_or("").to_string());
+    let mut headers = BTreeMap::new();
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).expect("a header");
+        match header.trim_end().split_once(':') {
+            Some((name, value)) => { headers.insert(name.to_ascii_lowercase(), value.trim().to_string()); },
+            None => break,
+        }
+    }
+    let length = headers.get("content-length").and_then(|length| length.parse().ok()).unwrap_or(0);
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).expect("the body");
+    let body = String::from_utf8(body).expect("a UTF-8 body");
+
+    let mut events = events.lock().expect("the events");
+    let current = events.get(&href).map(|(etag, _)| etag.clone());
+    let matches = match (headers.get("if-match"), headers.get("if-none-match")) {
+        (Some(etag), _) => current.as_ref() == Some(etag),
+        (None, Some(_)) => current.is_none(),
+        (None, None) => true,
+    };
+    match method.as_str() {
+        "PROPFIND" => {
+            let responses: String = events.iter()
+                .map(|(href, (etag, _))| format!("<d:response><d:href>{}</d:href><d:propstat><d:prop><d:getetag>{}</d:getetag></d:prop></d:propstat></d:response>",
+                                                 href, etag.replace('"', "&quot;")))
+                .collect();
+            respond(&mut stream, "207 Multi-Status", "", &format!(
+                "<?xml version=\"1.0\"?><d:multistatus xmlns:d=\"DAV:\"><d:response><d:href>/cal/</d:href><d:propstat><d:prop><d:getetag/></d:prop></d:propstat></d:response>{}</d:multistatus>",
+                responses));
+        },
+        "GET" => match events.get(&href) {
+            Some((_, event)) => respond(&mut stream, "200 OK", "", event),
+            None => respond(&mut stream, "404 Not Found", "", ""),
+        },
+        "PUT" | "DELETE" if !matches => respond(&mut stream, "412 Precondition Failed", "", ""),
+        "PUT" => {
+            *count += 1;
+            let etag = format!("\"{}\"", count);
+            events.insert(href, (etag.clone(), body));
+            respond(&mut stream, "201 Created", &format!("ETag: {}\r\n", etag), "");
+        },
+        "DELETE" => match events.remove(&href) {
+            Some(_) => respond(&mut stream, "204 No Content", "", ""),
+            None => respond(&mut stream, "404 Not Found", "", ""),
+        },
+        _ => respond(&mut stream, "405 Method Not Allowed", "", ""),
+    }
+}
+
+fn calendar(name: &str, tasks: &str) -> Calendar {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("a free port");
+    let url = format!("http://{}/cal/", listener.local_addr().expect("the address"));
+    let events = Events::default();
+    let served = events.clone();
+    std::thread::spawn(move || {
+        let mut count = 0;
+        for stream in listener.incoming().flatten() {
+            answer(stream, &served, &mut count);
+        }
+    });
+    let dir = std::env::temp_dir().join(format!("wtd-sync-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).expect("the directory is made");
+    std::fs::write(dir.join("wtd.md"), tasks).expect("wtd.md is written");
+    return Calendar { url, events, dir };
+}
+
+impl Calendar {
+    fn sync(&self) -> Summary {
+        let options = SyncOptions {
+            url: &self.url,
+            username: None,
+            password: None,
+            state: Some(self.dir.join("state.json")),
+            parse: ParseOptions::default(),
+            week_start: Weekday::Mon,
+            quiet: true,
+        };
+        return sync(&self.dir.join("wtd.md"), &options).unwrap_or_else(|why| panic!("{}", why));
+    }
+
+    fn tasks(&self) -> String {
+        return std::fs::read_to_string(self.dir.join("wtd.md")).expect("wtd.md is read");
+    }
+
+    fn write(&self, tasks: &str) {
+        std::fs::write(self.dir.join("wtd.md"), tasks).expect("wtd.md is written");
+    }
+
+    // Adds or edits an event as another calendar app would, with a new ETag.
+    fn put(&self, href: &str, summary: &str, start: &str, end: &str) {
+        let event = format!("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:{}\r\nSUMMARY:{}\r\nDTSTART:{}\r\nDTEND:{}\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+                            href, summary, start, end);
+        let mut events = self.events.lock().expect("the events");
+        let etag = format!("\"{}-{}\"", href, events.len() + summary.len());
+        events.insert(href.to_string(), (etag, event));
+    }
+
+    // The summary of each event on the server.
+    fn summaries(&self) -> Vec<String> {
+        let events = self.events.lock().expect("the events");
+        let mut summaries: Vec<String> = events.values()
+            .filter_map(|(_, event)| event.lines().find_map(|l| l.strip_prefix("SUMMARY:")).map(|s| s.to_string()))
+            .collect();
+        summaries.sort();
+        return summaries;
+    }
+}
+
+fn counts(summary: &Summary) -> [usize; 4] {
+    return [summary.pulled, summary.deleted_here, summary.pushed, summary.deleted_there];
+}
+
+const STANDUP: &str = "# 10/12/26\n## Monday\n- [ ] Standup @9AM+15m +busy\n";
+
+// New events come into the file under their day and new tasks go to the
+// server, after which there's nothing left to do.
+#[test]
+fn new_events_and_tasks() {
+    let calendar = calendar("new", STANDUP);
+    calendar.put("/cal/dentist.ics", "Dentist", "20261013T140000", "20261013T150000");
+    assert_eq!(counts(&calendar.sync()), [1, 0, 1, 0]);
+    assert_eq!(calendar.tasks(), format!("{}\n## Tuesday\n- [ ] Dentist @2PM--3PM\n", STANDUP));
+    assert_eq!(calendar.summaries(), ["Dentist", "Standup"]);
+    assert_eq!(counts(&calendar.sync()), [0, 0, 0, 0]);
+}
+
+// An event deleted on the server is deleted from the file, and a task deleted
+// from the file is deleted on the server.
+#[test]
+fn deletions() {
+    let calendar = calendar("delete", &format!("{}- [ ] Retro @4PM+1h\n", STANDUP));
+    calendar.sync();
+    calendar.events.lock().expect("the events").retain(|_, (_, event)| !event.contains("SUMMARY:Standup"));
+    calendar.write(STANDUP);
+    assert_eq!(counts(&calendar.sync()), [0, 1, 0, 1]);
+    assert_eq!(calendar.tasks(), "# 10/12/26\n## Monday\n");
+    assert!(calendar.summaries().is_empty(), "{:?}", calendar.summaries());
+}
+
+// An event edited on the server replaces its task; a task edited here and on
+// the server keeps the edit made here, which replaces the event.
+#[test]
+fn edits() {
+    let calendar = calendar("edit", "");
+    calendar.put("/cal/dentist.ics", "Dentist", "20261013T140000", "20261013T150000");
+    calendar.put("/cal/standup.ics", "Standup", "20261012T090000", "20261012T091500");
+    calendar.sync();
+    calendar.put("/cal/dentist.ics", "Dentist (moved)", "20261013T160000", "20261013T170000");
+    assert_eq!(counts(&calendar.sync()), [1, 0, 0, 0]);
+    assert!(calendar.tasks().contains("- [ ] Dentist (moved) @4PM--5PM\n"), "{}", calendar.tasks());
+
+    calendar.write(&calendar.tasks().replace("Standup @9AM--9:15AM", "Standup @10AM--10:15AM"));
+    calendar.put("/cal/standup.ics", "Standup (remote)", "20261012T093000", "20261012T094500");
+    assert_eq!(counts(&calendar.sync()), [0, 0, 1, 1]);
+    assert!(calendar.tasks().contains("- [ ] Standup @10AM--10:15AM\n"), "{}", calendar.tasks());
+    assert_eq!(calendar.summaries(), ["Dentist (moved)", "Standup"]);
+    assert_eq!(counts(&calendar.sync()), [0, 0, 0, 0]);
+}
+
+// Once the state is lost, the events already on the server are matched up
+// with their tasks again rather than added to the file, or pushed, twice.
+#[test]
+fn lost_state() {
+    let calendar = calendar("lost", STANDUP);
+    calendar.put("/cal/dentist.ics", "Dentist", "20261013T140000", "20261013T150000");
+    calendar.sync();
+    let synced = calendar.tasks();
+    std::fs::remove_file(calendar.dir.join("state.json")).expect("the state is removed");
+    assert_eq!(counts(&calendar.sync()), [0, 0, 0, 0]);
+    assert_eq!(calendar.tasks(), synced);
+    assert_eq!(calendar.summaries(), ["Dentist", "Standup"]);
+}
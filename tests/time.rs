@@ -0,0 +1,137 @@
+// Durations and imported times across daylight saving changes.
+#![allow(clippy::needless_return)]
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::America::New_York;
+use chrono_tz::Europe::London;
+use wtd::{parse_tasks, parse_tasks_with, time, ParseOptions};
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    return time::ymd(year, month, day).expect("a valid date");
+}
+
+fn at(hour: u32, minute: u32) -> NaiveTime {
+    return time::hms(hour, minute, 0).expect("a valid time");
+}
+
+fn datetime(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    return date(year, month, day).and_time(at(hour, minute));
+}
+
+#[test]
+fn ordinary_days_add_on_the_wall_clock() {
+    let end = time::add_wall_clock_in(&New_York, date(2026, 3, 7), at(1, 0), Duration::hours(2));
+    assert_eq!(end, Some(at(3, 0)));
+    assert_eq!(time::add_wall_clock_in(&New_York, date(2026, 3, 7), at(23, 0), Duration::hours(2)), None);
+}
+
+// New York's clocks go from 2AM to 3AM on March 8th, 2026.
+#[test]
+fn spring_forward() {
+    let day = date(2026, 3, 8);
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(1, 0), Duration::hours(2)), Some(at(4, 0)));
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(1, 30), Duration::minutes(45)), Some(at(3, 15)));
+    // After the change, nothing's different.
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(9, 0), Duration::hours(2)), Some(at(11, 0)));
+    // 2:30AM never happens; it's read as if the clocks hadn't changed yet.
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(2, 30), Duration::hours(1)), Some(at(4, 30)));
+}
+
+// New York's clocks go from 2AM back to 1AM on November 1st, 2026.
+#[test]
+fn fall_back() {
+    let day = date(2026, 11, 1);
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(0, 0), Duration::hours(3)), Some(at(2, 0)));
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(0, 30), Duration::hours(2)), Some(at(1, 30)));
+    // The day is 25 hours long, so this still ends before midnight.
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(0, 0), Duration::hours(24)), Some(at(23, 0)));
+}
+
+// London's clocks go from 1AM to 2AM on March 29th, 2026.
+#[test]
+fn other_timezones_change_on_their_own_dates() {
+    assert_eq!(time::add_wall_clock_in(&London, date(2026, 3, 8), at(1, 0), Duration::hours(2)), Some(at(3, 0)));
+    assert_eq!(time::add_wall_clock_in(&London, date(2026, 3, 29), at(0, 30), Duration::hours(1)), Some(at(2, 30)));
+}
+
+// A multi-day block crossing the change, as an imported event's DURATION.
+#[test]
+fn elapsed_time_across_days() {
+    let start = datetime(2026, 3, 7, 22, 0);
+    assert_eq!(time::add_elapsed_in(&New_York, start, Duration::hours(12)), datetime(2026, 3, 8, 11, 0));
+    assert_eq!(time::add_elapsed_in(&New_York, start, Duration::days(2)), datetime(2026, 3, 9, 23, 0));
+}
+
+#[test]
+fn ics_times_with_a_tzid_are_converted() {
+    // 9AM in New York is 2PM UTC in winter and 1PM once the clocks change.
+    assert_eq!(time::parse_ics_datetime_in(&Utc, "20260307T090000", Some("America/New_York")), Some(datetime(2026, 3, 7, 14, 0)));
+    assert_eq!(time::parse_ics_datetime_in(&Utc, "20260308T090000", Some("America/New_York")), Some(datetime(2026, 3, 8, 13, 0)));
+    assert_eq!(time::parse_ics_datetime_in(&New_York, "20260329T090000", Some("Europe/London")), Some(datetime(2026, 3, 29, 4, 0)));
+    assert_eq!(time::parse_ics_datetime_in(&New_York, "20260308T120000Z", None), Some(datetime(2026, 3, 8, 8, 0)));
+    // Floating times, and TZIDs that aren't IANA names, are left as they are.
+    assert_eq!(time::parse_ics_datetime_in(&Utc, "20260308T090000", None), Some(datetime(2026, 3, 8, 9, 0)));
+    assert_eq!(time::parse_ics_datetime_in(&Utc, "20260308T090000", Some("Eastern Standard Time")), Some(datetime(2026, 3, 8, 9, 0)));
+    // Onto the task file's clocks, or unchanging ones without a timezone.
+    assert_eq!(time::parse_ics_datetime(Some(New_York), "20260308T120000Z", None), Some(datetime(2026, 3, 8, 8, 0)));
+    assert_eq!(time::parse_ics_datetime(None, "20260308T120000Z", None), Some(datetime(2026, 3, 8, 12, 0)));
+}
+
+// Times sent to Google carry the configured timezone's offset on their day.
+#[test]
+fn rfc3339_offsets() {
+    assert_eq!(time::format_rfc3339(New_York, date(2026, 3, 7), at(9, 30)), "2026-03-07T09:30:00-05:00");
+    assert_eq!(time::format_rfc3339(New_York, date(2026, 3, 8), at(9, 30)), "2026-03-08T09:30:00-04:00");
+    assert_eq!(time::format_rfc3339(London, date(2026, 3, 8), at(0, 0)), "2026-03-08T00:00:00+00:00");
+}
+
+// Blocks may end at midnight, but not after it.
+#[test]
+fn ending_at_midnight() {
+    let day = date(2026, 3, 7);
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(22, 0), Duration::hours(2)), Some(time::end_of_day()));
+    assert_eq!(time::add_wall_clock_in(&New_York, day, at(22, 0), Duration::minutes(121)), None);
+    let errors = parse_tasks("# 3/2/26\n## Saturday\n- [ ] Late @11PM+1h\n- [ ] Later @11PM+2h\n").expect_err("Later runs past midnight");
+    assert_eq!(errors.len(), 1);
+}
+
+// The file's times are read on the configured clocks, whatever the system's.
+#[test]
+fn durations_in_the_configured_timezone() {
+    let source = "# 3/2/26\n## Sunday\n- [ ] Drive @1AM+2h\n";
+    let options = |timezone| ParseOptions { timezone: Some(timezone), ..ParseOptions::default() };
+    let tasks = parse_tasks_with(source, &options(New_York)).unwrap_or_else(|errors| panic!("{:?}", errors));
+    assert_eq!(tasks[0].end_time, Some(at(4, 0)));
+    let tasks = parse_tasks_with(source, &options(London)).unwrap_or_else(|errors| panic!("{:?}", errors));
+    assert_eq!(tasks[0].end_time, Some(at(3, 0)));
+}
+
+// Without a configured timezone, clocks never change, whatever the system's
+// do. No other test here reads the system's timezone, so setting it can't
+// race with them.
+#[test]
+fn durations_without_a_timezone() {
+    std::env::set_var("TZ", "America/New_York");
+    let tasks = parse_tasks("# 3/2/26\n## Sunday\n- [ ] Drive @1AM+2h\n").unwrap_or_else(|errors| panic!("{:?}", errors));
+    assert_eq!(tasks[0].end_time, Some(at(3, 0)));
+    assert_eq!(time::add_elapsed(None, datetime(2026, 3, 7, 23, 0), Duration::hours(12)), datetime(2026, 3, 8, 11, 0));
+}
+
+// Weeks start on the day usual in the locale's country. No other test here
+// reads the locale, so setting it can't race with them.
+#[test]
+fn locale_week_start() {
+    let wednesday = date(2026, 10, 14);
+    assert_eq!(time::week_start(wednesday, Weekday::Mon), date(2026, 10, 12));
+    assert_eq!(time::week_start(wednesday, Weekday::Sun), date(2026, 10, 11));
+    assert_eq!(time::week_start(wednesday, Weekday::Wed), wednesday);
+    std::env::remove_var("LC_ALL");
+    std::env::remove_var("LC_TIME");
+    for (lang, first) in [("en_US.UTF-8", Weekday::Sun), ("de_DE.UTF-8", Weekday::Mon), ("ar_EG", Weekday::Sat), ("C", Weekday::Mon)] {
+        std::env::set_var("LANG", lang);
+        assert_eq!(time::locale_week_start(), first, "{}", lang);
+    }
+    // LC_TIME is for dates alone, so it wins over LANG.
+    std::env::set_var("LANG", "en_US.UTF-8");
+    std::env::set_var("LC_TIME", "en_GB.UTF-8");
+    assert_eq!(time::locale_week_start(), Weekday::Mon);
+}
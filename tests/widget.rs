@@ -0,0 +1,29 @@
+// The data behind the embeddable widget.
+#![allow(clippy::needless_return)]
+use std::collections::HashMap;
+use serde_json::json;
+use wtd::widget::upcoming;
+use wtd::{parse_tasks, time};
+
+// The next events from now that the public calendar would list, labelled as
+// it labels them; private untimed tasks and those already over are left out.
+#[test]
+fn next_events() {
+    let tasks = parse_tasks("# 10/12/26
+## Monday
+- [ ] Standup @9AM+15m +public
+- [ ] Dentist @2PM+1h
+- [ ] Groceries
+## Tuesday
+- [ ] Launch @10AM+1h +public
+## Friday
+- [ ] Holiday @allday
+").unwrap_or_else(|errors| panic!("{:?}", errors));
+    let now = time::ymd(2026, 10, 12).expect("a date").and_time(time::hms(10, 0, 0).expect("a time"));
+    assert_eq!(upcoming(&tasks, now, 3, &HashMap::new()), json!({ "events": [
+        { "date": "2026-10-12", "start": "14:00", "end": "15:00", "endDate": "2026-10-12", "allDay": false, "title": "busy" },
+        { "date": "2026-10-13", "start": "10:00", "end": "11:00", "endDate": "2026-10-13", "allDay": false, "title": "\"Launch\"" },
+        { "date": "2026-10-16", "start": null, "end": null, "endDate": "2026-10-16", "allDay": true, "title": "busy" },
+    ]}));
+    assert_eq!(upcoming(&tasks, now, 1, &HashMap::new())["events"].as_array().map(Vec::len), Some(1));
+}